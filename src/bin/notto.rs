@@ -1,20 +1,44 @@
 mod ui;
 
-use std::{fs, path::{Path, PathBuf}};
-use std::sync::{Arc, Mutex};
+use std::{cmp::Ordering, env, fs::File, io::Write, net::TcpListener, path::{Path, PathBuf}, process};
 
-use console::Term;
-use dialoguer::Select;
+use chrono::{DateTime, Local};
+use console::{Term, style};
+use dialoguer::{Confirm, Password, Select};
 use dialoguer::theme::ColorfulTheme;
 
-use clap::{App, Arg, ArgMatches};
-use notto::{Notto, io::browser::NottoPath};
-use notto::models::note::Note;
-use notto::errors::NottoError;
+use clap::{App, AppSettings, Arg, ArgMatches};
+use notto::{Notto, io::{browser::{ListOrder, NoteListing, NottoPath, PathEntry}, merge::ConflictChoice}};
+use notto::errors::{EXIT_FAILURE, NottoError};
 use notto::finder::FindCondition;
 use notto::finder::NoteFindMessage;
+use notto::import::ImportFormat;
+use notto::export::ExportSummary;
+use notto::io::terminal::render_markdown;
+use notto::lsp::LspServer;
+use notto::mcp::McpServer;
+use notto::rpc::RpcServer;
+use notto::server::NoteServer;
+use notto::models::note::Note;
+use serde::Serialize;
+use serde_json::json;
+use uuid::Uuid;
+use ui::{NottoUI, error::NottoViewError};
+
+/// The width of the notes shown when the terminal's is unknown
+const DEFAULT_WIDTH: usize = 80;
+const PAGER_VARIABLE: &str = "PAGER";
+const DEFAULT_PAGER: &str = "less";
+/// The start of the ids shown by `ls --long`, enough to find the notes by
+const LISTING_ID_LENGTH: usize = 8;
+const FORMAT_TEXT: &str = "text";
+const FORMAT_JSON: &str = "json";
 
 fn main() {
+    // Quits quietly when the output is piped to a command that stops reading, like `head`
+    #[cfg(unix)]
+    unsafe { libc::signal(libc::SIGPIPE, libc::SIG_DFL); }
+
     let matches = App::new("notto")
         .author("Nico")
         .subcommand(App::new("new")
@@ -30,46 +54,588 @@ fn main() {
                 .takes_value(false))
             )
         .subcommand(App::new("open")
-            .about("Opens a note"))
+            .about("Opens a note, chosen in the notes or by its name. Examples:\n`notto open`\n`notto open work/plan`\n`notto open 8f14`\n`notto open \"weekly plan\"`")
+            .arg(Arg::new("name")
+                .about("Path of the note, its id or the start of it, the end of its path or its title")
+                .index(1))
+            .arg(Arg::new("all_contexts")
+                .about("Chooses the context first, to browse the notes of any context")
+                .short('a')
+                .long("all-contexts")
+                .takes_value(false)))
+        .subcommand(App::new("show")
+            .about("Shows a note in the terminal, without opening the editor. Example:\n`notto show work/plan`")
+            .arg(Arg::new("name")
+                .about("Path of the note like `work/plan`, its id or the start of it, the end of its path or its title")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("raw")
+                .about("Shows the markdown as it is")
+                .long("raw")
+                .takes_value(false))
+            .arg(Arg::new("front_matter")
+                .about("Shows the front matter too")
+                .long("front-matter")
+                .takes_value(false)))
+        .subcommand(App::new("ls")
+            .about("Lists the notes and directories, without asking anything. Example:\n`notto ls work --long --sort updated`")
+            .arg(Arg::new("path")
+                .about("Directory to list, like `work/projects`")
+                .index(1))
+            .arg(Arg::new("tree")
+                .about("Lists the directories too, as a tree")
+                .short('t')
+                .long("tree")
+                .takes_value(false))
+            .arg(Arg::new("long")
+                .about("Shows the id, the date, when it was updated and the number of words of the notes, and their tags")
+                .short('l')
+                .long("long")
+                .takes_value(false))
+            .arg(Arg::new("sort")
+                .about("Order of the notes, by date and updated the latest first, the directories always come first")
+                .short('s')
+                .long("sort")
+                .takes_value(true)
+                .possible_values(&["name", "date", "updated"])
+                .default_value("name"))
+            .arg(Arg::new("json")
+                .about("Prints the notes as JSON, for scripts")
+                .long("json")
+                .takes_value(false)))
+        .subcommand(App::new("rpc")
+            .about("Answers JSON-RPC 2.0 requests on stdin, a JSON document per line or with `Content-Length` headers, for the editor plugins. Methods: `find`, `browse`, `create`, `resolve`, `backlinks`, `tasks` and `config.contexts`"))
+        .subcommand(App::new("lsp")
+            .about("Runs a language server for the notes on stdin and stdout: links and tags completion, go to the note linked, links to a note, hover, broken links and notes by title"))
+        .subcommand(App::new("mcp")
+            .about("Serves the notes of the context to the AI assistants speaking the Model Context Protocol on stdin and stdout: the notes as resources, and tools to search and read them. Creating notes and writing in the journal needs `notto config set mcp_write true`"))
+        .subcommand(App::new("serve")
+            .about("Serves the notes over HTTP: a REST API under `/api` and a page at `/` to read them from a browser. Example:\n`notto serve --port 8080 --read-only`")
+            .arg(Arg::new("port")
+                .about("Port to listen on")
+                .short('p')
+                .long("port")
+                .takes_value(true)
+                .default_value("8080"))
+            .arg(Arg::new("host")
+                .about("Address to listen on, `0.0.0.0` to read the notes from the other devices of the network, a token is then generated if none is given")
+                .long("host")
+                .takes_value(true)
+                .default_value("127.0.0.1"))
+            .arg(Arg::new("token")
+                .about("Token the API requires as `Authorization: Bearer <token>`, the page gets it in its address")
+                .long("token")
+                .takes_value(true))
+            .arg(Arg::new("read_only")
+                .about("Refuses to create and save notes")
+                .long("read-only")
+                .takes_value(false)))
+        .subcommand(App::new("tui")
+            .about("Browses, previews and searches the notes in a terminal UI"))
         .subcommand(App::new("find")
             .about("Finds a note")
             .arg(Arg::new("text")
                 .about("Text to find in the note")
                 .index(1)
+                .required(true))
+            .arg(Arg::new("all_contexts")
+                .about("Finds in the notes of every context")
+                .short('a')
+                .long("all-contexts")
+                .takes_value(false)))
+        .subcommand(App::new("mv")
+            .about("Moves or renames a note, with its sub notes. Example:\n`notto mv meeting_minutes work/meetings/kickoff`")
+            .arg(Arg::new("name")
+                .about("Name of the note to move, like `work/resources`, or its id")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("dest")
+                .about("New name of the note")
+                .index(2)
                 .required(true)))
+        .subcommand(App::new("rm")
+            .about("Deletes a note")
+            .arg(Arg::new("name")
+                .about("Name of the note to delete, like `work/resources`, or its id")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("yes")
+                .about("Deletes without asking")
+                .short('y')
+                .long("yes")
+                .takes_value(false)))
+        .subcommand(App::new("history")
+            .about("Lists the revisions of a note, the latest first, from git when `git` is on for the context or from the snapshots")
+            .arg(Arg::new("name")
+                .about("Name of the note, like `work/resources`")
+                .index(1)
+                .required(true)))
+        .subcommand(App::new("restore")
+            .about("Brings back a note as it was in a revision listed by `history`, with git a deleted note too")
+            .arg(Arg::new("name")
+                .about("Name of the note, like `work/resources`")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("rev")
+                .about("Number of the revision, 1 is the latest")
+                .short('r')
+                .long("rev")
+                .takes_value(true)
+                .required(true)))
+        .subcommand(App::new("diff")
+            .about("Shows the changes of a note since a revision listed by `history`")
+            .arg(Arg::new("name")
+                .about("Name of the note, like `work/resources`")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("rev")
+                .about("Number of the revision, 1 is the latest")
+                .index(2)
+                .default_value("1")))
+        .subcommand(App::new("remind")
+            .about("Shows the overdue tasks and the ones due in the next days, recurring tasks use `every: weekly`, `every: 2nd monday`... Example:\n`- [ ] Pay the rent due: 2021-05-01 every: monthly`")
+            .arg(Arg::new("days")
+                .about("Number of days ahead to look for tasks")
+                .short('d')
+                .long("days")
+                .takes_value(true)
+                .default_value("7"))
+            .arg(Arg::new("count")
+                .about("Only prints the number of overdue and upcoming tasks, useful for a shell prompt")
+                .short('c')
+                .long("count")
+                .takes_value(false)))
+        .subcommand(App::new("export")
+            .about("Exports the notes of the current context")
+            .subcommand(App::new("html")
+                .about("Exports the notes as a static HTML site, notes with `private: true` in the front matter are skipped")
+                .arg(Arg::new("out_dir")
+                    .about("Directory to write the site to")
+                    .index(1)
+                    .required(true)))
+            .subcommand(App::new("json")
+                .about("Exports every note as a line of JSON, with its front matter, content, links and tasks")
+                .arg(Arg::new("output")
+                    .about("File to write to, the standard output by default")
+                    .short('o')
+                    .long("output")
+                    .takes_value(true)))
+            .subcommand(App::new("archive")
+                .about("Bundles the notes directory in a `.tar.gz` archive with a manifest, to restore with `import archive`")
+                .arg(Arg::new("file")
+                    .about("Archive file to create")
+                    .index(1)
+                    .required(true))))
+        .subcommand(App::new("import")
+            .setting(AppSettings::SubcommandsNegateReqs)
+            .setting(AppSettings::ArgsNegateSubcommands)
+            .about("Imports notes from a markdown directory like an Obsidian vault, a Joplin export or an Evernote `.enex` file")
+            .arg(Arg::new("source")
+                .about("Directory or file to import")
+                .index(1)
+                .required(true))
+            .arg(Arg::new("from")
+                .about("Format of the source, detected by default")
+                .short('f')
+                .long("from")
+                .possible_values(&["markdown", "joplin", "evernote"])
+                .takes_value(true))
+            .arg(Arg::new("into")
+                .about("Path in the notes where to put the imported notes, the root by default")
+                .short('i')
+                .long("into")
+                .takes_value(true))
+            .subcommand(App::new("archive")
+                .about("Restores an archive made with `export archive`, existing notes are never overwritten")
+                .arg(Arg::new("file")
+                    .about("Archive file to restore")
+                    .index(1)
+                    .required(true))
+                .arg(Arg::new("into")
+                    .about("Path in the notes where to restore the archive, the root by default")
+                    .short('i')
+                    .long("into")
+                    .takes_value(true))))
+        .subcommand(App::new("context")
+            .about("Manages the contexts, every context has its own notes directory and editor")
+            .subcommand(App::new("list")
+                .about("Lists the contexts, the one in use is marked with `*`"))
+            .subcommand(App::new("add")
+                .about("Adds a context")
+                .arg(Arg::new("name")
+                    .about("Name of the context")
+                    .index(1)
+                    .required(true))
+                .arg(Arg::new("dir")
+                    .about("Notes directory of the context, it has to exist")
+                    .short('d')
+                    .long("dir")
+                    .takes_value(true)
+                    .required(true))
+                .arg(Arg::new("editor")
+                    .about("Editor command for the notes of the context, the one of the default context if not set. Can use `{file}`, `{line}` and `{column}`, like `code --wait --goto {file}:{line}`")
+                    .short('e')
+                    .long("editor")
+                    .takes_value(true)))
+            .subcommand(App::new("remove")
+                .about("Removes a context, its notes are not deleted")
+                .arg(Arg::new("name")
+                    .about("Name of the context")
+                    .index(1)
+                    .required(true)))
+            .subcommand(App::new("use")
+                .about("Switches to a context, `NOTTO_CONFIG` and `--context` still take precedence")
+                .arg(Arg::new("name")
+                    .about("Name of the context")
+                    .index(1)
+                    .required(true)))
+            .subcommand(App::new("show")
+                .about("Shows the settings of a context, the one in use by default")
+                .arg(Arg::new("name")
+                    .about("Name of the context")
+                    .index(1))))
+        .subcommand(App::new("config")
+            .about("Checks and changes the config")
+            .subcommand(App::new("dirs")
+                .about("Shows where the config, the notes of the default context and the temp files are.\nThey follow `XDG_CONFIG_HOME`, `XDG_DATA_HOME` and `XDG_STATE_HOME`, or go all in `NOTTO_HOME` if it's set"))
+            .subcommand(App::new("migrate")
+                .about("Copies the config and the notes of `~/.notto` to the XDG directories, `~/.notto` is left untouched"))
+            .subcommand(App::new("check")
+                .about("Checks every context: its directory is writable, its editor and hooks can be found and no other context uses its directory"))
+            .subcommand(App::new("get")
+                .about("Shows a config value")
+                .arg(Arg::new("key")
+                    .about("`context`, `editor`, `base_dir`, `git`, `encrypted`, `mcp_write`, `snapshots[.keep_last|.keep_daily]` or `hooks.<hook>` of the context in use, or the same under `contexts.<name>.`")
+                    .index(1)
+                    .required(true)))
+            .subcommand(App::new("unset")
+                .about("Unsets a value of a context, to use the default")
+                .arg(Arg::new("key")
+                    .about("`editor`, `base_dir`, `git`, `encrypted`, `mcp_write`, `snapshots[.keep_last|.keep_daily]` or `hooks.<hook>` of the context in use, or the same under `contexts.<name>.`")
+                    .index(1)
+                    .required(true)))
+            .subcommand(App::new("set")
+                .about("Changes a config value.\nWith `git` set to true every change of the notes is committed to the git repository of the notes directory, created if needed.\nOtherwise snapshots of the notes are kept, unless `snapshots` is false: the last `snapshots.keep_last` ones (10) and the last of each of the last `snapshots.keep_daily` days (30).\nWith `encrypted` set to true the content of every new or edited note is encrypted, like the notes with `encrypted: true` in their front matter. The passphrase is asked for when it's needed, or taken from `NOTTO_PASSPHRASE`.\nWith `mcp_write` set to true the assistants using `notto mcp` can create notes and write in the journal.\nThe hooks `pre_create`, `post_save`, `post_delete` and `post_move` are commands run in the notes directory with the note as JSON on the standard input, and the `NOTTO_NOTE_PATH`, `NOTTO_NOTE_ID` and `NOTTO_CONTEXT` variables. A failing `pre_create` hook cancels the new note")
+                .arg(Arg::new("key")
+                    .about("`context`, `editor`, `base_dir`, `git`, `encrypted`, `mcp_write`, `snapshots[.keep_last|.keep_daily]` or `hooks.<hook>` of the context in use, or the same under `contexts.<name>.`")
+                    .index(1)
+                    .required(true))
+                .arg(Arg::new("value")
+                    .about("The new value")
+                    .index(2)
+                    .required(true))))
+        .arg(Arg::new("context")
+            .about("Context to use, overrides the `NOTTO_CONFIG` variable and the config file")
+            .long("context")
+            .takes_value(true)
+            .global(true))
+        .arg(Arg::new("format")
+            .about("Output of `new`, `find`, `ls`, `show`, `mv`, `rm`, `restore` and of the errors, `json` for scripts. The exit code tells the error: 3 not found, 4 ambiguous name, 5 already exists, 6 note locked or in use, 7 config, 8 files, 9 editor, hook or git")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&[FORMAT_TEXT, FORMAT_JSON])
+            .default_value(FORMAT_TEXT)
+            .global(true))
         .get_matches();
 
-    match matches.subcommand() {
-        Some(("new", matches)) => new(matches),
-        Some(("open", matches)) => { open(matches); },
-        Some(("find", matches)) => { find(matches); },
-        Some(_) => {}
-        None => {}
+    let result = match matches.subcommand() {
+        Some(("new", matches)) => new(matches).map_err(|e| ("Error creating note", e)),
+        Some(("open", matches)) => open(matches).map_err(|e| ("Error opening note", e)),
+        Some(("show", matches)) => show(matches).map_err(|e| ("Error showing note", e)),
+        Some(("ls", matches)) => list(matches).map_err(|e| ("Error listing notes", e)),
+        Some(("rpc", matches)) => rpc(matches).map_err(|e| ("Error in the RPC server", e)),
+        Some(("lsp", matches)) => lsp(matches).map_err(|e| ("Error in the language server", e)),
+        Some(("mcp", matches)) => mcp(matches).map_err(|e| ("Error in the MCP server", e)),
+        Some(("serve", matches)) => serve(matches).map_err(|e| ("Error in the HTTP server", e)),
+        Some(("tui", matches)) => match tui(matches) {
+            Err(NottoViewError::NottoError { source }) => Err(("Error in the terminal UI", source)),
+            Err(e) => {
+                eprintln!("Error in the terminal UI: {}", e);
+                process::exit(EXIT_FAILURE);
+            }
+            Ok(()) => Ok(()),
+        },
+        Some(("find", matches)) => find(matches).map_err(|e| ("Error finding notes", e)),
+        Some(("mv", matches)) => move_note(matches).map_err(|e| ("Error moving note", e)),
+        Some(("rm", matches)) => delete_note(matches).map_err(|e| ("Error deleting note", e)),
+        Some(("history", matches)) => history(matches).map_err(|e| ("Error getting the history", e)),
+        Some(("restore", matches)) => restore(matches).map_err(|e| ("Error restoring note", e)),
+        Some(("diff", matches)) => diff(matches).map_err(|e| ("Error comparing note", e)),
+        Some(("remind", matches)) => remind(matches).map_err(|e| ("Error getting reminders", e)),
+        Some(("export", matches)) => export(matches).map_err(|e| ("Error exporting notes", e)),
+        Some(("import", matches)) => import(matches).map_err(|e| ("Error importing notes", e)),
+        Some(("context", matches)) => context(matches).map_err(|e| ("Error managing contexts", e)),
+        Some(("config", matches)) => config(matches).map_err(|e| ("Error with the config", e)),
+        Some(_) | None => Ok(()),
     };
+
+    if let Err((action, e)) = result {
+        print_error(&matches, action, &e);
+        process::exit(e.exit_code());
+    }
 }
 
-fn new(matches: &ArgMatches) {
-    let notto = Notto::new().unwrap();
+fn is_json(matches: &ArgMatches) -> bool {
+    matches.value_of("format") == Some(FORMAT_JSON)
+}
 
-    let note_name = matches.value_of("name");
+fn print_json<T: Serialize>(value: &T) -> Result<(), NottoError> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| NottoError::FileError { message: format!("can't write the JSON output: {}", e) })?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+/// Prints the path of the note saved, moved... or a message with it
+fn print_path<P: AsRef<Path>>(matches: &ArgMatches, message: &str, path: P) -> Result<(), NottoError> {
+    let path = path.as_ref().to_string_lossy();
+    if is_json(matches) {
+        print_json(&json!({ "path": path }))
+    } else {
+        println!("{} {}", message, path);
+        Ok(())
+    }
+}
+
+/// Prints the error on stderr, or as `{"error": {"code": ..., "message": ..., "exit_code": ...}}` on stdout with the JSON format
+fn print_error(matches: &ArgMatches, action: &str, e: &NottoError) {
+    if is_json(matches) {
+        println!("{}", json!({ "error": e.to_json() }));
+    } else {
+        eprintln!("{}: {}", action, e);
+    }
+}
+
+fn load_notto(matches: &ArgMatches) -> Result<Notto, NottoError> {
+    let mut notto = Notto::with_context(matches.value_of("context"))?;
+    notto.set_passphrase_prompt(|| {
+        let passphrase = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("Passphrase of the encrypted notes")
+            .interact_on(&Term::stderr())?;
+        Ok(passphrase)
+    });
+    notto.set_in_use_prompt(|note, pid, host| {
+        let open = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} is already open by process {} on {}, open it anyway?", note, pid, host))
+            .default(false)
+            .interact_on(&Term::stderr())?;
+        Ok(open)
+    });
+    notto.set_conflict_prompt(|note, conflicts| {
+        let choices = [ConflictChoice::Merge, ConflictChoice::KeepEdited, ConflictChoice::KeepSaved];
+        let items = ["Resolve the conflicts in the editor", "Keep my version", "Keep the version saved meanwhile"];
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} changed while you edited it, {} conflicts", note, conflicts))
+            .items(&items)
+            .default(0)
+            .interact_on(&Term::stderr())?;
+        Ok(choices[selection])
+    });
+
+    Ok(notto)
+}
+
+fn show(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+    let (path, note) = notto.get_note(matches.value_of("name").unwrap_or_default())?;
+    if is_json(matches) {
+        // A note without a saved id gets a new one every time it's read
+        let mut front_matter = json!(note.front_matter);
+        front_matter["id"] = json!(note.front_matter.stored_id());
+        return print_json(&json!({
+            "path": path.to_string_lossy(),
+            "id": note.front_matter.stored_id(),
+            "title": note.get_title(),
+            "front_matter": front_matter,
+            "content": note.content,
+        }));
+    }
+    let width = Term::stdout().size_checked().map(|(_, columns)| columns as usize).unwrap_or(DEFAULT_WIDTH);
+
+    let mut text = String::new();
+    if matches.is_present("front_matter") {
+        let (front_matter, _) = Note::split_front_matter(note.to_text());
+        let front_matter = format!("---\n{}\n---", front_matter.unwrap_or_default());
+        if matches.is_present("raw") {
+            text.push_str(&front_matter);
+        } else {
+            text.push_str(&style(front_matter).dim().to_string());
+        }
+        text.push('\n');
+    }
+    if matches.is_present("raw") {
+        text.push_str(&note.content);
+    } else {
+        text.push_str(&render_markdown(&note.content, width));
+    }
+    print_paged(&text);
+
+    Ok(())
+}
 
-    if matches.is_present("journal") {
-        match notto.create_journal_entry(note_name) {
-            Ok(path) => println!("Saved note at {}", path.to_string_lossy()),
-            Err(e) => println!("Error creating note: {}", e)
+/// Prints the text, through the pager when it doesn't fit in the terminal
+fn print_paged(text: &str) {
+    let term = Term::stdout();
+    let rows = term.size_checked().map(|(rows, _)| rows as usize).unwrap_or(usize::MAX);
+    if !term.is_term() || text.lines().count() < rows {
+        return println!("{}", text);
+    }
+
+    let pager = env::var(PAGER_VARIABLE).unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    let mut command = process::Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(process::Stdio::piped());
+    // Keeps the colors, and quits when the text fits after all
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let paged = command.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            // The pager may be quit before reading everything
+            let _ = writeln!(stdin, "{}", text);
         }
+        child.wait()
+    });
+    // 127 is the status of the shell when the pager isn't found
+    if paged.map(|status| status.code() == Some(127)).unwrap_or(true) {
+        println!("{}", text);
+    }
+}
+
+fn list(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    let order = matches.value_of_t_or_exit::<ListOrder>("sort");
+    let tree = matches.is_present("tree");
+    let listings = notto.list(matches.value_of("path"), tree, order)?;
+    if matches.is_present("json") || is_json(matches) {
+        print_json(&listings)?;
     } else {
-        match notto.create_or_open_note_at(note_name) {
-            Ok(path) => println!("Saved note at {}", path.to_string_lossy()),
-            Err(e) => println!("Error creating note: {}", e)
+        print_listings(&listings, "", matches.is_present("long"), tree);
+    }
+
+    Ok(())
+}
+
+/// Prints a line per note or directory, the entries of the directories below them with the branches of the tree
+fn print_listings(listings: &[NoteListing], prefix: &str, long: bool, tree: bool) {
+    for (index, listing) in listings.iter().enumerate() {
+        let (branch, indent) = match (tree, index + 1 == listings.len()) {
+            (false, _) => ("", ""),
+            (true, true) => ("└── ", "    "),
+            (true, false) => ("├── ", "│   "),
+        };
+        let details = if long {
+            let id = listing.id.as_deref().map(|id| id.chars().take(LISTING_ID_LENGTH).collect::<String>()).unwrap_or_default();
+            let words = match (listing.is_dir, listing.words) {
+                (true, _) => String::new(),
+                (false, Some(words)) => words.to_string(),
+                (false, None) => "-".to_string(),
+            };
+            let line = format!("{:8}  {:16}  {:16}  {:>6}  ", id, listing.date.as_deref().unwrap_or_default(), listing.updated.as_deref().unwrap_or_default(), words);
+            style(line).dim().to_string()
+        } else {
+            String::new()
+        };
+        let name = if listing.is_dir { style(format!("{}/", listing.name)).blue().bold() } else { style(listing.name.clone()) };
+        let title = listing.title.as_ref().map(|title| format!("  {}", title)).unwrap_or_default();
+        let tags = if long { listing.tags.iter().map(|tag| format!(" #{}", tag)).collect::<String>() } else { String::new() };
+        println!("{}{}{}{}{}", details, style(format!("{}{}", prefix, branch)).dim(), name, title, style(tags).yellow());
+        if tree {
+            print_listings(&listing.children, &format!("{}{}", prefix, indent), long, tree);
         }
     }
 }
 
+/// Serves the requests until stdin is closed, there's no prompt as stdin is taken by the requests
+fn rpc(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = Notto::with_context(matches.value_of("context"))?;
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    RpcServer::new(&notto).serve(stdin.lock(), stdout.lock())
+}
+
+fn lsp(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = Notto::with_context(matches.value_of("context"))?;
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    LspServer::new(&notto)?.serve(stdin.lock(), stdout.lock())
+}
+
+fn mcp(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = Notto::with_context(matches.value_of("context"))?;
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+
+    McpServer::new(&notto)?.serve(stdin.lock(), stdout.lock())
+}
+
+fn serve(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = Notto::with_context(matches.value_of("context"))?;
+    let port = matches.value_of_t_or_exit::<u16>("port");
+    let listener = TcpListener::bind((matches.value_of("host").unwrap_or_default(), port))?;
+    let address = listener.local_addr()?;
+    // Anyone on the network could read and change the notes otherwise
+    let token = matches.value_of("token").map(String::from)
+        .or_else(|| Some(Uuid::new_v4().to_simple().to_string()).filter(|_| !address.ip().is_loopback()));
+    match &token {
+        Some(token) => eprintln!("Serving the notes of {} on http://{}/?token={}", notto.config.get_context()?, address, token),
+        None => eprintln!("Serving the notes of {} on http://{}", notto.config.get_context()?, address),
+    }
+
+    NoteServer::new(&notto).read_only(matches.is_present("read_only")).token(token).serve(&listener)
+}
+
+fn tui(matches: &ArgMatches) -> Result<(), NottoViewError> {
+    let notto = load_notto(matches)?;
+    // The passphrase can't be asked for once the UI is on screen
+    notto.unlock_context()?;
+
+    NottoUI::new(notto).run()
+}
+
+fn new(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    let note_name = matches.value_of("name");
+    let path = if matches.is_present("journal") {
+        notto.create_journal_entry(note_name)?
+    } else {
+        notto.create_or_open_note_at(note_name)?
+    };
+
+    print_path(matches, "Saved note at", path)
+}
+
 fn open(matches: &ArgMatches) -> Result<(), NottoError> {
-    let notto = Notto::new()?;
+    let mut notto = load_notto(matches)?;
 
-    if let Some(note_path) = display_selection_for_path(&notto, &NottoPath::new())? {
+    // Nothing can be asked with the JSON format
+    if is_json(matches) && matches.is_present("all_contexts") {
+        return Err(NottoError::InvalidArgument { argument: "--all-contexts".to_string(), message: "the context can't be chosen with `--format json`".to_string() });
+    }
+    if is_json(matches) && !matches.is_present("name") {
+        return Err(NottoError::InvalidArgument { argument: "name".to_string(), message: "it's required with `--format json`".to_string() });
+    }
+
+    if matches.is_present("all_contexts") {
+        let contexts = notto.config.get_context_names();
+        let current = notto.config.get_context()?;
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Context")
+            .items(&contexts)
+            .default(contexts.iter().position(|context| context == &current).unwrap_or(0))
+            .interact_on_opt(&Term::stderr())?;
+        match selection {
+            Some(selection) => notto = notto.in_context(&contexts[selection])?,
+            None => return Ok(())
+        }
+    }
+
+    if let Some(name) = matches.value_of("name") {
+        notto.open_note(name)?;
+    } else if let Some(note_path) = display_selection_for_path(&notto, &NottoPath::new())? {
         notto.open_by_path(note_path)?;
     }
 
@@ -77,31 +643,391 @@ fn open(matches: &ArgMatches) -> Result<(), NottoError> {
 }
 
 fn find(matches: &ArgMatches) -> Result<(), NottoError> {
-    let notto = Notto::new()?;
+    let notto = load_notto(matches)?;
+
+    if let Some(text) = matches.value_of("text") {
+        let conditions = vec![FindCondition::Text(text.to_string())];
+        let rx = if matches.is_present("all_contexts") {
+            notto.find_in_all_contexts(conditions)?
+        } else {
+            notto.find(conditions)?
+        };
+
+        // The notto of the context of every result, with the path of the note in the context and where the text is
+        let mut results = vec![];
+        // The results printed as JSON instead of choosing one to open
+        let mut found = vec![];
+        for message in rx {
+            match message {
+                NoteFindMessage::Result(result) => {
+                    let result_notto = match &result.context {
+                        Some(context) => notto.in_context(context)?,
+                        None => notto.in_context(notto.config.get_context()?)?
+                    };
+                    let notes_dir = result_notto.config.get_notes_dir()?;
+                    let path = result.path.strip_prefix(&notes_dir).map(PathBuf::from).unwrap_or(result.path);
+                    if is_json(matches) {
+                        found.push(json!({
+                            "path": path.to_string_lossy(),
+                            "id": result.note.front_matter.stored_id(),
+                            "title": result.note.get_title(),
+                            "context": result.context,
+                            "line": result.position.map(|position| position.line),
+                            "column": result.position.map(|position| position.column),
+                        }));
+                        continue;
+                    }
+                    let item = match &result.context {
+                        Some(context) => format!("[{}] {} {}", context, result.note.get_title(), style(path.to_string_lossy()).dim()),
+                        None => format!("{} {}", result.note.get_title(), style(path.to_string_lossy()).dim())
+                    };
+                    results.push((item, result_notto, path, result.position));
+                }
+                NoteFindMessage::Finish { .. } => break
+            }
+        }
+
+        if is_json(matches) {
+            found.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+            return print_json(&found);
+        }
+        if results.is_empty() {
+            println!("No notes found with `{}`", text);
+            return Ok(());
+        }
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let items = results.iter().map(|(item, _, _, _)| item).collect::<Vec<_>>();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("{} notes found", results.len()))
+            .items(&items)
+            .default(0)
+            .interact_on_opt(&Term::stderr())?;
+        if let Some((_, result_notto, path, position)) = selection.and_then(|selection| results.get(selection)) {
+            result_notto.open_by_path_at(PathEntry::pathbuf_to_string(path), *position)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn move_note(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
 
-    let find_text = matches.value_of("text");
+    if let (Some(name), Some(dest)) = (matches.value_of("name"), matches.value_of("dest")) {
+        let path = notto.move_note(name, dest)?;
+        print_path(matches, "Moved note to", path)?;
+    }
+
+    Ok(())
+}
+
+fn delete_note(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    // Nothing can be asked with the JSON format
+    if is_json(matches) && !matches.is_present("yes") {
+        return Err(NottoError::InvalidArgument { argument: "--yes".to_string(), message: "it's required with `--format json`".to_string() });
+    }
+
+    if let Some(name) = matches.value_of("name") {
+        // The note found, by its path or its id which may not be the one named
+        let note_path = notto.resolve_exactly(name)?;
+        let confirmed = matches.is_present("yes") || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Delete note {}?", note_path.to_string_lossy()))
+            .default(false)
+            .interact_on(&Term::stderr())?;
+        if confirmed {
+            let path = notto.delete_note(note_path.to_string_lossy())?;
+            print_path(matches, "Deleted note at", path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn history(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    if let Some(name) = matches.value_of("name") {
+        let revisions = notto.history(name)?;
+        if revisions.is_empty() {
+            println!("No revisions of `{}`", name);
+        }
+        for revision in revisions {
+            let date = DateTime::parse_from_rfc3339(&revision.date)
+                .map(|date| date.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or(revision.date);
+            println!("{:>3}  {}  {}", revision.number, style(date).dim(), revision.message);
+        }
+    }
+
+    Ok(())
+}
+
+fn restore(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    if let (Some(name), Some(rev)) = (matches.value_of("name"), matches.value_of("rev")) {
+        let number = parse_revision(rev)?;
+        let path = notto.restore(name, number)?;
+        print_path(matches, &format!("Restored revision {} at", number), path)?;
+    }
+
+    Ok(())
+}
+
+fn diff(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    if let (Some(name), Some(rev)) = (matches.value_of("name"), matches.value_of("rev")) {
+        let number = parse_revision(rev)?;
+        let diff = notto.diff(name, Some(number))?;
+        if diff.is_empty() {
+            println!("No changes since revision {}", number);
+        }
+        for line in diff.lines() {
+            if line.starts_with("+++") || line.starts_with("---") {
+                println!("{}", style(line).bold());
+            } else if line.starts_with('+') {
+                println!("{}", style(line).green());
+            } else if line.starts_with('-') {
+                println!("{}", style(line).red());
+            } else if line.starts_with("@@") {
+                println!("{}", style(line).cyan());
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_revision(rev: &str) -> Result<usize, NottoError> {
+    rev.parse::<usize>().map_err(|_| NottoError::HistoryError { message: format!("`{}` is not a revision number", rev) })
+}
+
+fn remind(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    let days = matches.value_of_t_or_exit::<i64>("days");
+
+    let today = Notto::today();
+    let reminders = notto.remind(days)?;
+    let overdue = reminders.iter().filter(|reminder| reminder.date < today).count();
+
+    if matches.is_present("count") {
+        // Nothing to show in a prompt if there are no tasks
+        if !reminders.is_empty() {
+            println!("{} overdue, {} upcoming", overdue, reminders.len() - overdue);
+        }
+    } else {
+        for reminder in reminders {
+            let when = match reminder.date.cmp(&today) {
+                Ordering::Less => style("overdue ").red().bold(),
+                Ordering::Equal => style("today   ").yellow().bold(),
+                Ordering::Greater => style("upcoming").green(),
+            };
+            let recurring = if reminder.task.every.is_some() { " ↻" } else { "" };
+            println!("{} {}  {}{}  {}", when, reminder.date, reminder.task.text, recurring, style(format!("[{}]", reminder.path.to_string_lossy())).dim());
+        }
+    }
+
+    Ok(())
+}
+
+fn export(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
+
+    match matches.subcommand() {
+        Some(("html", matches)) => {
+            if let Some(out_dir) = matches.value_of("out_dir") {
+                let summary = notto.export_html(out_dir)?;
+                println!("Exported {} notes to {}, skipped {} private notes", summary.exported, out_dir, summary.skipped);
+                print_encrypted(&summary, "left out, notto is locked");
+            }
+        }
+        Some(("json", matches)) => {
+            match matches.value_of("output") {
+                Some(output) => {
+                    let summary = notto.export_json(File::create(output)?)?;
+                    println!("Exported {} notes to {}", summary.exported, output);
+                    print_encrypted(&summary, "left out, notto is locked");
+                }
+                None => {
+                    let summary = notto.export_json(std::io::stdout().lock())?;
+                    print_encrypted(&summary, "left out, notto is locked");
+                }
+            }
+        }
+        Some(("archive", matches)) => {
+            if let Some(file) = matches.value_of("file") {
+                let summary = notto.export_archive(file)?;
+                println!("Archived {} notes in {}", summary.exported, file);
+                print_encrypted(&summary, "archived encrypted");
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Lists the encrypted notes of an export on stderr, so they don't mix with a JSON export on stdout
+fn print_encrypted(summary: &ExportSummary, what: &str) {
+    if !summary.encrypted.is_empty() {
+        eprintln!("{} encrypted notes {}:", summary.encrypted.len(), what);
+        for path in &summary.encrypted {
+            eprintln!("  {}", path.to_string_lossy());
+        }
+    }
+}
+
+fn import(matches: &ArgMatches) -> Result<(), NottoError> {
+    let notto = load_notto(matches)?;
 
-    if let Some(text) = find_text {
-        let mut conditions = vec![];
-        conditions.push(FindCondition::Text(text.to_string()));
-        let rx = notto.find(conditions)?;
+    let report = match matches.subcommand() {
+        Some(("archive", matches)) => matches.value_of("file")
+            .map(|file| notto.import(file, Some(ImportFormat::Archive), matches.value_of("into")))
+            .transpose()?,
+        _ => {
+            let format = matches.value_of("from").map(str::parse::<ImportFormat>).transpose()?;
+            matches.value_of("source")
+                .map(|source| notto.import(source, format, matches.value_of("into")))
+                .transpose()?
+        }
+    };
+
+    if let Some(report) = report {
+        println!("Imported {} notes and {} attachments", report.notes.len(), report.attachments);
+        if !report.issues.is_empty() {
+            println!("{} issues found:", report.issues.len());
+            for issue in report.issues {
+                println!("  {}", issue);
+            }
+        }
+    }
+
+    Ok(())
+}
 
-        let theme = ColorfulTheme::default();
-        let mut selection = Select::with_theme(&theme);
+fn context(matches: &ArgMatches) -> Result<(), NottoError> {
+    let mut notto = load_notto(matches)?;
 
-        match rx.try_recv() {
-            Ok(msg) => {
-                if let NoteFindMessage::Result(note_result) = msg {
-                    /*selection.item(PathEntry {
-                        name: note_result.note.get_title(),
-                        path: note_result.path,
-                        is_dir: false});*/
+    match matches.subcommand() {
+        Some(("list", _)) | None => {
+            for name in notto.config.get_context_names() {
+                let info = notto.config.get_context_info(&name)?;
+                let marker = if info.active { "*" } else { " " };
+                println!("{} {} {}", marker, style(&info.name).bold(), style(info.base_dir.to_string_lossy()).dim());
+            }
+        }
+        Some(("add", matches)) => {
+            if let (Some(name), Some(dir)) = (matches.value_of("name"), matches.value_of("dir")) {
+                notto.config.add_context(name, PathBuf::from(dir), matches.value_of("editor").map(String::from))?;
+                notto.save_config()?;
+                println!("Added context {}", name);
+            }
+        }
+        Some(("remove", matches)) => {
+            if let Some(name) = matches.value_of("name") {
+                notto.config.remove_context(name)?;
+                notto.save_config()?;
+                println!("Removed context {}, its notes are still in place", name);
+            }
+        }
+        Some(("use", matches)) => {
+            if let Some(name) = matches.value_of("name") {
+                notto.config.use_context(name)?;
+                notto.save_config()?;
+                println!("Using context {}", name);
+                if let Ok(env_context) = env::var("NOTTO_CONFIG") {
+                    println!("{} NOTTO_CONFIG is set, context {} is used while it's set", style("Warning:").yellow(), env_context);
                 }
-            },
-            Err(e) => {}
+            }
+        }
+        Some(("show", matches)) => {
+            let name = match matches.value_of("name") {
+                Some(name) => name.to_string(),
+                None => notto.config.get_context()?
+            };
+            let info = notto.config.get_context_info(&name)?;
+            println!("context: {}{}", info.name, if info.active { " (in use)" } else { "" });
+            println!("base_dir: {}", info.base_dir.to_string_lossy());
+            println!("editor: {}", info.editor.as_deref().unwrap_or("not set"));
+            println!("git: {}", info.git);
+            println!("encrypted: {}", info.encrypted);
+        }
+        Some(_) => {}
+    }
+
+    Ok(())
+}
+
+fn config(matches: &ArgMatches) -> Result<(), NottoError> {
+    // These don't need a valid config
+    match matches.subcommand() {
+        Some(("dirs", _)) => {
+            let home = Notto::get_home()?;
+            println!("layout: {:?}", home.layout);
+            println!("config: {}", home.config_file().to_string_lossy());
+            println!("notes: {}", home.notes_dir.to_string_lossy());
+            println!("temp: {}", home.temp_dir.to_string_lossy());
+            println!("snapshots: {}", home.snapshots_dir.to_string_lossy());
+            return Ok(());
+        }
+        Some(("migrate", _)) => {
+            let report = Notto::get_home()?.migrate_legacy()?;
+            if let Some(to) = report.to {
+                println!("Copied {} files from {} to {} and {}", report.files, report.from.to_string_lossy(), to.config_dir.to_string_lossy(), to.notes_dir.to_string_lossy());
+                println!("{} is still there, remove it once you've checked your notes", report.from.to_string_lossy());
+            }
+            return Ok(());
         }
+        _ => {}
+    }
+
+    let mut notto = load_notto(matches)?;
 
-        selection.interact_on_opt(&Term::stderr())?; 
+    match matches.subcommand() {
+        Some(("check", _)) => {
+            let issues = notto.config.check();
+            if issues.is_empty() && is_json(matches) {
+                print_json(&json!({ "issues": [] }))?;
+            } else if issues.is_empty() {
+                println!("{} the config is fine", style("Ok:").green());
+            } else if is_json(matches) {
+                let messages = issues.iter().map(|issue| format!("context {}: {}", issue.context, issue.message)).collect::<Vec<_>>();
+                return Err(NottoError::LoadConfigError { message: format!("{} issues found, {}", issues.len(), messages.join(", ")) });
+            } else {
+                for issue in &issues {
+                    eprintln!("{} context {}: {}", style("Error:").red(), style(&issue.context).bold(), issue.message);
+                }
+                return Err(NottoError::LoadConfigError { message: format!("{} issues found", issues.len()) });
+            }
+        }
+        Some(("get", matches)) => {
+            if let Some(key) = matches.value_of("key") {
+                match notto.config.get_value(key)? {
+                    Some(value) => println!("{}", value),
+                    None => println!("{}", style("not set").dim())
+                }
+            }
+        }
+        Some(("set", matches)) => {
+            if let (Some(key), Some(value)) = (matches.value_of("key"), matches.value_of("value")) {
+                notto.config.set_value(key, value)?;
+                notto.save_config()?;
+            }
+        }
+        Some(("unset", matches)) => {
+            if let Some(key) = matches.value_of("key") {
+                notto.config.set_value(key, "")?;
+                notto.save_config()?;
+            }
+        }
+        _ => {}
     }
 
     Ok(())
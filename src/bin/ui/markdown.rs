@@ -0,0 +1,189 @@
+use cursive::{theme::{BaseColor, Color, Effect, Style}, utils::markup::StyledString};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+const QUOTE_BAR: &str = "│ ";
+const BULLET: &str = "• ";
+const RULE: &str = "────────────────────";
+
+/// Renders the markdown of a note for the preview, keeping the list markers, the quotes and the code blocks
+pub fn render(markdown: &str) -> StyledString {
+    let mut renderer = Renderer::default();
+    for event in Parser::new_ext(markdown, Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH) {
+        renderer.event(event);
+    }
+
+    renderer.text
+}
+
+#[derive(Default)]
+struct Renderer {
+    text: StyledString,
+    styles: Vec<Style>,
+    /// The next number of the ordered lists, `None` for the bullet lists
+    lists: Vec<Option<u64>>,
+    quotes: usize,
+    /// Whether the quote bars and the indentation are still to write in the line
+    line_start: bool,
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.write(&text),
+            Event::Code(code) => {
+                self.styles.push(code_style());
+                self.write(&code);
+                self.styles.pop();
+            }
+            Event::Html(html) => self.write(&html),
+            Event::FootnoteReference(name) => self.write(&format!("[^{}]", name)),
+            Event::SoftBreak | Event::HardBreak => self.new_line(),
+            Event::Rule => {
+                self.block_break();
+                self.styles.push(dim_style());
+                self.write(RULE);
+                self.styles.pop();
+            }
+            Event::TaskListMarker(done) => self.write(if done { "[x] " } else { "[ ] " }),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block_break(),
+            Tag::Heading(level) => {
+                self.block_break();
+                let color = if level == 1 { BaseColor::Yellow } else { BaseColor::Cyan };
+                self.styles.push(Style::from(Effect::Bold).combine(Color::Light(color)));
+                self.write(&format!("{} ", "#".repeat(level as usize)));
+            }
+            Tag::BlockQuote => {
+                self.block_break();
+                self.quotes += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.block_break();
+                self.styles.push(code_style());
+            }
+            Tag::List(first) => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+                self.lists.push(first);
+            }
+            Tag::Item => {
+                self.line_break();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => BULLET.to_string(),
+                };
+                self.start_line(self.lists.len().saturating_sub(1));
+                self.text.append_styled(marker, Color::Light(BaseColor::Blue));
+            }
+            Tag::Emphasis => self.styles.push(Style::from(Effect::Italic)),
+            Tag::Strong => self.styles.push(Style::from(Effect::Bold)),
+            Tag::Strikethrough => self.styles.push(Style::from(Effect::Strikethrough)),
+            Tag::Link(..) | Tag::Image(..) => self.styles.push(Style::from(Effect::Underline).combine(Color::Light(BaseColor::Blue))),
+            Tag::FootnoteDefinition(name) => {
+                self.block_break();
+                self.write(&format!("[^{}]: ", name));
+            }
+            Tag::Table(_) | Tag::TableHead | Tag::TableRow => self.line_break(),
+            Tag::TableCell => self.write("| "),
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(_) | Tag::CodeBlock(_) | Tag::Emphasis | Tag::Strong | Tag::Strikethrough => {
+                self.styles.pop();
+            }
+            Tag::Link(_, url, _) | Tag::Image(_, url, _) => {
+                self.styles.pop();
+                if !url.is_empty() {
+                    self.styles.push(dim_style());
+                    self.write(&format!(" <{}>", url));
+                    self.styles.pop();
+                }
+            }
+            Tag::BlockQuote => self.quotes = self.quotes.saturating_sub(1),
+            Tag::List(_) => {
+                self.lists.pop();
+            }
+            Tag::TableCell => self.write(" "),
+            _ => {}
+        }
+    }
+
+    /// Writes text in the current style, the lines after the first one get the quote bars and the indentation
+    fn write(&mut self, text: &str) {
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                self.new_line();
+            }
+            if !line.is_empty() {
+                self.start_line(self.lists.len());
+                self.text.append_styled(line, Style::merge(&self.styles));
+            }
+        }
+    }
+
+    fn start_line(&mut self, indent: usize) {
+        if self.line_start {
+            self.line_start = false;
+            if self.quotes > 0 {
+                self.text.append_styled(QUOTE_BAR.repeat(self.quotes), dim_style());
+            }
+            self.text.append_plain("  ".repeat(indent));
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.text.append_plain("\n");
+        self.line_start = true;
+    }
+
+    /// Goes to a new line unless the text is already at the start of one
+    fn line_break(&mut self) {
+        let source = self.text.source();
+        if !source.is_empty() && !source.ends_with('\n') {
+            self.new_line();
+        }
+    }
+
+    /// Separates a block from the one before with an empty line, only a new line in the lists
+    fn block_break(&mut self) {
+        self.line_break();
+        let source = self.text.source();
+        if self.lists.is_empty() && !source.is_empty() && !source.ends_with("\n\n") {
+            self.new_line();
+        }
+    }
+}
+
+fn code_style() -> Style {
+    Style::from(Color::Dark(BaseColor::Green))
+}
+
+fn dim_style() -> Style {
+    Style::from(Color::Light(BaseColor::Black))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn renders_markdown() {
+        let text = render("# Plan\n\nSome *text* and `code`.\n\n- one\n- [x] two\n  1. first\n  2. second\n\n> quoted\n> twice\n\n```\nlet a = 1;\nlet b = 2;\n```\n");
+        assert_eq!(
+            "# Plan\n\nSome text and code.\n\n• one\n• [x] two\n  1. first\n  2. second\n\n│ quoted\n│ twice\n\nlet a = 1;\nlet b = 2;\n",
+            text.source()
+        );
+    }
+}
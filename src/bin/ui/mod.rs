@@ -1,52 +1,84 @@
+mod markdown;
 mod note_browser;
 pub mod error;
 
 use crossbeam_channel::{Receiver, Sender};
-use cursive::{Cursive, CursiveExt, View, event::{Event, EventResult}, views::{Dialog, LinearLayout}};
+use cursive::{Cursive, CursiveExt, View};
+use notto::{Notto, finder::NoteFindResult, io::browser::{NottoPath, PathEntry}};
 
-use self::error::NottoViewError;
+use self::{error::NottoViewError, note_browser::NoteBrowser};
 
+/// The results of the work done out of the event loop, like the searches
 pub enum UIMessage {
+    /// A note found by the search `search`
+    Found { search: usize, result: NoteFindResult },
+    /// The search `search` is over, with the number of notes read and found
+    SearchFinished { search: usize, scanned: usize, matched: usize },
+    Error(String),
+}
 
+/// What to do once the terminal is given back by the UI, like running the editor
+pub enum UIAction {
+    Open(NottoPath),
+    Create(String),
 }
 
 pub trait NottoScreen {
     fn load_view(&self) -> Result<Box<dyn View>, NottoViewError>;
 }
 
+/// The terminal UI, kept as the user data of cursive while it runs
 pub struct NottoUI {
-    siv: Cursive,
+    notto: Notto,
+    browser: NoteBrowser,
     rx: Receiver<UIMessage>,
-    tx: Sender<UIMessage>
+    tx: Sender<UIMessage>,
+    /// Set to leave the event loop and run the editor
+    action: Option<UIAction>,
 }
 
 impl NottoUI {
-    pub fn new() -> Self {
+    pub fn new(notto: Notto) -> Self {
         let (tx, rx) = crossbeam_channel::unbounded();
-        let cursive = Cursive::default();
-        Self { siv: cursive, rx, tx }
+        Self { notto, browser: NoteBrowser::default(), rx, tx, action: None }
     }
 
-    fn run(&mut self) {
-        self.build_view();
-        self.siv.run();
-    }
+    /// Runs the UI until it's quit, it's left while the editor runs and built again after
+    pub fn run(mut self) -> Result<(), NottoViewError> {
+        loop {
+            let mut siv = Cursive::default();
+            siv.add_fullscreen_layer(self.browser.load_view()?);
+            siv.set_user_data(self);
+            note_browser::select_current(&mut siv);
+            siv.run();
 
-    fn build_view(&mut self) {
-        self.siv.add_layer(
-            Dialog::around(
-                LinearLayout::horizontal()
-            )
-        )
+            self = siv.take_user_data().ok_or_else(|| NottoViewError::LoadViewError { message: "the UI state is gone".to_string() })?;
+            let result = match self.action.take() {
+                Some(UIAction::Open(path)) => self.notto.open_by_path(path),
+                Some(UIAction::Create(name)) => self.notto.create_or_open_note_at(Some(&name)).map(|path| {
+                    self.browser.select(&PathEntry::pathbuf_to_string(path));
+                }),
+                None => return Ok(()),
+            };
+            self.browser.status = match result {
+                Ok(()) => String::new(),
+                Err(e) => e.to_string(),
+            };
+        }
     }
-}
 
-impl cursive::view::View for NottoUI {
-    fn draw(&self, _printer: &cursive::Printer) {
-        // do nothing
+    /// Sends a message to the event loop from another thread, it's handled once the loop wakes up
+    fn send(siv_sink: &cursive::CbSink, tx: &Sender<UIMessage>, message: UIMessage) {
+        if tx.send(message).is_ok() {
+            let _ = siv_sink.send(Box::new(NottoUI::receive));
+        }
     }
 
-    fn on_event(&mut self, event: Event) -> EventResult {
-        EventResult::Ignored
+    /// Handles the messages sent to the event loop
+    fn receive(siv: &mut Cursive) {
+        let messages = siv.with_user_data(|ui: &mut NottoUI| ui.rx.try_iter().collect::<Vec<_>>()).unwrap_or_default();
+        for message in messages {
+            note_browser::receive(siv, message);
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,20 +1,402 @@
-use notto::Notto;
+use std::{collections::BTreeSet, path::PathBuf, rc::Rc, thread};
 
-use super::NottoScreen;
+use cursive::{Cursive, View, event::Key, theme::{BaseColor, Color, Effect, Style}, traits::{Nameable, Resizable, Scrollable}, utils::markup::StyledString, views::{Dialog, EditView, LinearLayout, OnEventView, Panel, SelectView, TextView}};
+use notto::{Notto, errors::NottoError, finder::{FindCancel, FindCondition, NoteFindMessage}, io::{browser::{NottoPath, PathEntry}, crypto::is_encrypted}};
 
+use super::{NottoScreen, NottoUI, UIAction, UIMessage, error::NottoViewError, markdown};
+
+const TREE: &str = "tree";
+const PREVIEW: &str = "preview";
+const QUERY: &str = "query";
+const RESULTS: &str = "results";
+const STATUS: &str = "status";
+const NAME: &str = "name";
+/// The characters shown around a match in the search results
+const SNIPPET_CONTEXT: usize = 16;
+const HELP: &str = "Enter open · n new · m move · d delete · / search · Esc notes · r refresh · q quit";
+
+/// A note or a directory of the tree, or a note found
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    path: NottoPath,
+    is_dir: bool,
+}
+
+/// The screen with the tree of the notes, the preview of the selected one and the search
+#[derive(Default)]
 pub struct NoteBrowser {
-    notto: Notto
+    /// The directories open in the tree
+    expanded: BTreeSet<NottoPath>,
+    /// The note or directory the actions apply to
+    selected: Option<Entry>,
+    query: String,
+    /// The number of the latest search, the results of the ones before are dropped
+    search: usize,
+    /// Stops the latest search when the query changes
+    cancel: FindCancel,
+    /// The last error or outcome, the keys are shown when it's empty
+    pub status: String,
 }
 
 impl NoteBrowser {
+    /// Selects a note, opening the directories it's in
+    pub fn select(&mut self, path: &NottoPath) {
+        let segments = String::from(path).split('/').map(str::to_string).collect::<Vec<_>>();
+        let mut dir = NottoPath::new();
+        for segment in &segments[..segments.len().saturating_sub(1)] {
+            dir.push(segment);
+            self.expanded.insert(dir.clone());
+        }
+        self.selected = Some(Entry { path: path.clone(), is_dir: false });
+    }
+
+    fn tree_items(&self, notto: &Notto, path: &NottoPath, depth: usize, items: &mut Vec<(String, Entry)>) -> Result<(), NottoError> {
+        for entry in notto.browse(path)? {
+            let indent = "  ".repeat(depth);
+            if entry.is_dir() {
+                let expanded = self.expanded.contains(&entry.path);
+                items.push((format!("{}{} {}", indent, if expanded { "▾" } else { "▸" }, entry), Entry { path: entry.path.clone(), is_dir: true }));
+                if expanded {
+                    self.tree_items(notto, &entry.path, depth + 1, items)?;
+                }
+            } else {
+                items.push((format!("{}  {}", indent, entry), Entry { path: entry.path.clone(), is_dir: false }));
+            }
+        }
 
+        Ok(())
+    }
 }
 
-/*
 impl NottoScreen for NoteBrowser {
-    fn load_view(&self) -> Result<Box<dyn cursive::View>, super::error::NottoViewError> {
-        let notto = Notto::new()?;
-            
+    fn load_view(&self) -> Result<Box<dyn View>, NottoViewError> {
+        let tree = SelectView::<Entry>::new()
+            .on_select(show_preview)
+            .on_submit(|siv, entry| open(siv, entry.clone()));
+        let results = SelectView::<Entry>::new()
+            .on_select(show_preview)
+            .on_submit(|siv, entry| open(siv, entry.clone()));
+        let query = EditView::new()
+            .content(self.query.clone())
+            .on_edit(|siv, query, _| search(siv, query))
+            .on_submit(|siv, _| { let _ = siv.focus_name(RESULTS); });
+
+        let notes = LinearLayout::vertical()
+            .child(Panel::new(tree.with_name(TREE).scrollable()).title("Notes").full_height())
+            .child(Panel::new(LinearLayout::vertical()
+                .child(query.with_name(QUERY))
+                .child(results.with_name(RESULTS).scrollable()))
+                .title("Search")
+                .min_height(10))
+            .min_width(30)
+            .max_width(50);
+        let preview = Panel::new(TextView::new("").with_name(PREVIEW).scrollable()).title("Preview").full_screen();
+        let status = if self.status.is_empty() { HELP } else { &self.status };
+        let layout = LinearLayout::vertical()
+            .child(LinearLayout::horizontal().child(notes).child(preview))
+            .child(TextView::new(status).with_name(STATUS));
+
+        let view = OnEventView::new(layout)
+            .on_event('q', Cursive::quit)
+            .on_event('n', new_note)
+            .on_event('o', |siv| if let Some(entry) = selected(siv) { open(siv, entry) })
+            .on_event('m', move_note)
+            .on_event('d', delete_note)
+            .on_event('r', refresh_tree)
+            .on_event('/', |siv| { let _ = siv.focus_name(QUERY); })
+            .on_event(Key::Esc, |siv| { let _ = siv.focus_name(TREE); });
+
+        Ok(Box::new(view))
+    }
+}
+
+/// Fills the tree with the selected entry in view, and runs the search again after the editor
+pub fn select_current(siv: &mut Cursive) {
+    refresh_tree(siv);
+    let query = siv.with_user_data(|ui: &mut NottoUI| ui.browser.query.clone()).unwrap_or_default();
+    if !query.is_empty() {
+        search(siv, &query);
+    }
+}
+
+/// Handles a message of a search
+pub fn receive(siv: &mut Cursive, message: UIMessage) {
+    match message {
+        UIMessage::Found { search, result } => {
+            let item = siv.with_user_data(|ui: &mut NottoUI| {
+                if search != ui.browser.search {
+                    return None;
+                }
+                let notes_dir = ui.notto.config.get_notes_dir().ok()?;
+                let path = PathEntry::pathbuf_to_string(result.path.strip_prefix(&notes_dir).unwrap_or(&result.path));
+                let mut label = StyledString::styled(format!("{} ", result.note.get_title()), Effect::Bold);
+                label.append(snippet(&result.note.content, &ui.browser.query));
+                label.append_styled(format!(" {}", String::from(&path)), Color::Light(BaseColor::Black));
+                Some((label, Entry { path, is_dir: false }))
+            }).flatten();
+            if let Some((label, entry)) = item {
+                siv.call_on_name(RESULTS, |results: &mut SelectView<Entry>| results.add_item(label, entry));
+            }
+        }
+        UIMessage::SearchFinished { search, scanned, matched } => {
+            let latest = siv.with_user_data(|ui: &mut NottoUI| search == ui.browser.search).unwrap_or(false);
+            if latest {
+                set_status(siv, format!("{} of {} notes match", matched, scanned));
+            }
+        }
+        UIMessage::Error(message) => set_status(siv, message),
+    }
+}
+
+/// Builds the tree again from the notes directory, keeping the selected entry
+fn refresh_tree(siv: &mut Cursive) {
+    let items = siv.with_user_data(|ui: &mut NottoUI| {
+        let mut items = vec![];
+        ui.browser.tree_items(&ui.notto, &NottoPath::new(), 0, &mut items).map(|_| (items, ui.browser.selected.clone()))
+    });
+    match items {
+        Some(Ok((items, selected))) => {
+            let index = selected.and_then(|selected| items.iter().position(|(_, entry)| entry == &selected)).unwrap_or(0);
+            let callback = siv.call_on_name(TREE, |tree: &mut SelectView<Entry>| {
+                tree.clear();
+                tree.add_all(items);
+                tree.set_selection(index)
+            });
+            if let Some(callback) = callback {
+                callback(siv);
+            }
+        }
+        Some(Err(e)) => set_status(siv, e.to_string()),
+        None => {}
+    }
+}
+
+fn show_preview(siv: &mut Cursive, entry: &Entry) {
+    let content = siv.with_user_data(|ui: &mut NottoUI| {
+        ui.browser.selected = Some(entry.clone());
+        if entry.is_dir {
+            return StyledString::new();
+        }
+        match ui.notto.read_note(&entry.path) {
+            Ok(note) if is_encrypted(&note.content) => StyledString::plain("This note is encrypted, open it to unlock it"),
+            Ok(note) => markdown::render(&note.content),
+            Err(e) => StyledString::plain(e.to_string()),
+        }
+    });
+    if let Some(content) = content {
+        siv.call_on_name(PREVIEW, |preview: &mut TextView| preview.set_content(content));
+    }
+}
+
+/// Opens a note in the editor, or a directory in the tree
+fn open(siv: &mut Cursive, entry: Entry) {
+    if entry.is_dir {
+        siv.with_user_data(|ui: &mut NottoUI| {
+            if !ui.browser.expanded.remove(&entry.path) {
+                ui.browser.expanded.insert(entry.path.clone());
+            }
+            ui.browser.selected = Some(entry);
+        });
+        refresh_tree(siv);
+    } else {
+        siv.with_user_data(|ui: &mut NottoUI| ui.action = Some(UIAction::Open(entry.path)));
+        siv.quit();
+    }
+}
+
+/// Searches the text of the notes out of the event loop, the results are sent back as they're found
+///
+/// The search before is cancelled, it's run at every change of the query
+fn search(siv: &mut Cursive, query: &str) {
+    siv.call_on_name(RESULTS, |results: &mut SelectView<Entry>| results.clear());
+    let started = siv.with_user_data(|ui: &mut NottoUI| {
+        ui.browser.cancel.cancel();
+        ui.browser.cancel = FindCancel::default();
+        ui.browser.query = query.to_string();
+        ui.browser.search += 1;
+        let cancel = ui.browser.cancel.clone();
+        ui.notto.finder().map(|finder| (finder.with_cancel(cancel.clone()), cancel, ui.browser.search, ui.tx.clone()))
+    });
+    if query.is_empty() {
+        return set_status(siv, String::new());
+    }
+
+    match started {
+        Some(Ok((finder, cancel, search, tx))) => {
+            set_status(siv, format!("Searching `{}`…", query));
+            let sink = siv.cb_sink().clone();
+            let conditions = vec![FindCondition::Text(query.to_string())];
+            thread::spawn(move || {
+                let rx = match finder.find(PathBuf::new(), conditions) {
+                    Ok(rx) => rx,
+                    Err(e) => return NottoUI::send(&sink, &tx, UIMessage::Error(e.to_string())),
+                };
+                for message in rx {
+                    if cancel.is_cancelled() {
+                        break;
+                    }
+                    match message {
+                        NoteFindMessage::Result(result) => NottoUI::send(&sink, &tx, UIMessage::Found { search, result }),
+                        NoteFindMessage::Finish { scanned, matched } => {
+                            NottoUI::send(&sink, &tx, UIMessage::SearchFinished { search, scanned, matched });
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+        Some(Err(e)) => set_status(siv, e.to_string()),
+        None => {}
+    }
+}
+
+/// The first line of the content with `query`, around its first match, the matches highlighted
+fn snippet(content: &str, query: &str) -> StyledString {
+    let lower = |text: &str| text.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect::<Vec<_>>();
+    let query = lower(query);
+    let matches = |line: &[char]| (0..line.len()).filter(|start| line[*start..].starts_with(&query)).collect::<Vec<_>>();
+    if query.is_empty() {
+        return StyledString::new();
+    }
+
+    let mut snippet = StyledString::new();
+    for line in content.lines() {
+        let chars = line.trim().chars().collect::<Vec<_>>();
+        let starts = matches(&lower(line.trim()));
+        let first = match starts.first() {
+            Some(first) => *first,
+            None => continue,
+        };
+
+        let from = first.saturating_sub(SNIPPET_CONTEXT);
+        let to = chars.len().min(first + query.len() + SNIPPET_CONTEXT);
+        if from > 0 {
+            snippet.append_plain("…");
+        }
+        let mut position = from;
+        for start in starts {
+            if start < position || start + query.len() > to {
+                continue;
+            }
+            snippet.append_plain(chars[position..start].iter().collect::<String>());
+            snippet.append_styled(chars[start..start + query.len()].iter().collect::<String>(), Style::from(Effect::Bold).combine(Color::Light(BaseColor::Yellow)));
+            position = start + query.len();
+        }
+        snippet.append_plain(chars[position..to].iter().collect::<String>());
+        if to < chars.len() {
+            snippet.append_plain("…");
+        }
+        break;
+    }
+
+    snippet
+}
+
+fn new_note(siv: &mut Cursive) {
+    // In the directory of the selected entry
+    let dir = match selected(siv) {
+        Some(Entry { path, is_dir: true }) => format!("{}/", String::from(path)),
+        Some(Entry { path, is_dir: false }) => String::from(path).rsplit_once('/').map(|(dir, _)| format!("{}/", dir)).unwrap_or_default(),
+        None => String::new(),
+    };
+    ask_name(siv, "New note", dir, |siv, name| {
+        siv.with_user_data(|ui: &mut NottoUI| ui.action = Some(UIAction::Create(name.to_string())));
+        siv.quit();
+    });
+}
+
+fn move_note(siv: &mut Cursive) {
+    let entry = match selected(siv) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let name = note_name(&entry.path);
+    ask_name(siv, &format!("Move {}", name), name.clone(), move |siv, dest| {
+        let moved = siv.with_user_data(|ui: &mut NottoUI| ui.notto.move_note(&name, dest));
+        match moved {
+            Some(Ok(path)) => {
+                siv.with_user_data(|ui: &mut NottoUI| ui.browser.select(&PathEntry::pathbuf_to_string(path)));
+                set_status(siv, format!("Moved {} to {}", name, dest));
+                refresh_tree(siv);
+            }
+            Some(Err(e)) => set_status(siv, e.to_string()),
+            None => {}
+        }
+    });
+}
+
+fn delete_note(siv: &mut Cursive) {
+    let entry = match selected(siv) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let name = note_name(&entry.path);
+    let dialog = Dialog::text(format!("Delete {}?", name))
+        .title("Delete note")
+        .button("Delete", move |siv| {
+            siv.pop_layer();
+            let deleted = siv.with_user_data(|ui: &mut NottoUI| ui.notto.delete_note(&name));
+            match deleted {
+                Some(Ok(_)) => {
+                    siv.with_user_data(|ui: &mut NottoUI| ui.browser.selected = None);
+                    siv.call_on_name(PREVIEW, |preview: &mut TextView| preview.set_content(""));
+                    set_status(siv, format!("Deleted {}", name));
+                    refresh_tree(siv);
+                }
+                Some(Err(e)) => set_status(siv, e.to_string()),
+                None => {}
+            }
+        })
+        .dismiss_button("Cancel");
+    siv.add_layer(OnEventView::new(dialog).on_event(Key::Esc, |siv| { siv.pop_layer(); }));
+}
+
+/// Asks for the name of a note in a dialog, `on_name` gets it once the dialog is closed
+fn ask_name<F>(siv: &mut Cursive, title: &str, name: String, on_name: F) where F: Fn(&mut Cursive, &str) + 'static {
+    let submit = Rc::new(move |siv: &mut Cursive, name: &str| {
+        let name = name.trim();
+        if !name.is_empty() {
+            siv.pop_layer();
+            on_name(siv, name);
+        }
+    });
+    let on_submit = Rc::clone(&submit);
+    let edit = EditView::new().content(name).on_submit(move |siv, name| on_submit(siv, name));
+    let dialog = Dialog::around(edit.with_name(NAME).fixed_width(40))
+        .title(title)
+        .button("Ok", move |siv| {
+            let name = siv.call_on_name(NAME, |edit: &mut EditView| edit.get_content()).unwrap_or_default();
+            submit(siv, &name);
+        })
+        .dismiss_button("Cancel");
+    siv.add_layer(OnEventView::new(dialog).on_event(Key::Esc, |siv| { siv.pop_layer(); }));
+}
+
+fn selected(siv: &mut Cursive) -> Option<Entry> {
+    siv.with_user_data(|ui: &mut NottoUI| ui.browser.selected.clone()).flatten()
+}
+
+/// The name of a note for the commands, like `work/plan`
+fn note_name(path: &NottoPath) -> String {
+    String::from(path).trim_end_matches(".md").to_string()
+}
+
+fn set_status(siv: &mut Cursive, status: String) {
+    let text = if status.is_empty() { HELP.to_string() } else { status.clone() };
+    siv.with_user_data(|ui: &mut NottoUI| ui.browser.status = status);
+    siv.call_on_name(STATUS, |status: &mut TextView| status.set_content(text));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::snippet;
+
+    #[test]
+    fn highlights_matches() {
+        let text = snippet("# Errands\n\nCall the bank, then the BANK again before the bank closes at five\n", "bank");
+        assert_eq!("Call the bank, then the BANK …", text.source());
+        let highlighted = text.spans().filter(|span| !span.attr.effects.is_empty()).map(|span| span.content).collect::<Vec<_>>();
+        assert_eq!(vec!["bank", "BANK"], highlighted);
+        assert!(snippet("Nothing here", "bank").is_empty());
     }
 }
-*/
\ No newline at end of file
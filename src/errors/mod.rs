@@ -1,6 +1,7 @@
 use std::{env, io};
 
 use crossbeam_channel::SendError;
+use serde_json::{Value, json};
 use thiserror::Error;
 
 use crate::finder::NoteFindMessage;
@@ -8,6 +9,10 @@ use crate::finder::NoteFindMessage;
 pub enum NottoError {
     #[error("context {context} not found")]
     ContextNotFound { context: String },
+    #[error("context {context} already exists")]
+    ContextExists { context: String },
+    #[error("invalid context {context}: {message}")]
+    InvalidContext { context: String, message: String },
     #[error("context error from environment variable - {source}")]
     ContextError { 
         #[from]
@@ -27,11 +32,42 @@ pub enum NottoError {
     HomeDirectoryNotFound,
     #[error("{message}")]
     LoadConfigError { message: String },
+    /// The location is empty or like ` at line 3, column 5`
+    #[error("invalid config file {path}{location}: {message}")]
+    InvalidConfig { path: String, location: String, message: String },
+    #[error("unknown config key `{key}`, expected `context`, `editor`, `base_dir`, `git`, `encrypted`, `mcp_write`, `snapshots[.keep_last|.keep_daily]`, `hooks.<hook>` or the same under `contexts.<name>.`")]
+    UnknownConfigKey { key: String },
+    #[error("no editor for context {context}, set it with `notto config set editor <editor>` or the EDITOR variable")]
+    EditorNotSet { context: String },
+    #[error("can't run editor `{editor}`: {message}")]
+    EditorError { editor: String, message: String },
+    #[error("{hook} hook failed: {message}")]
+    HookFailed { hook: String, message: String },
+    #[error("git error: {message}")]
+    GitError { message: String },
+    #[error("{message}")]
+    HistoryError { message: String },
+    #[error("encryption error: {message}")]
+    EncryptionError { message: String },
+    #[error("{note} is encrypted, a passphrase is needed to unlock it")]
+    NoteLocked { note: String },
+    #[error("{note} is already open by process {pid} on {host}")]
+    NoteInUse { note: String, pid: u32, host: String },
+    #[error("{note} changed while it was edited, {conflicts} conflicts are left to resolve in it")]
+    EditConflict { note: String, conflicts: usize },
+    /// `hash` is the hash of the text saved meanwhile
+    #[error("{note} changed since it was read")]
+    NoteChanged { note: String, hash: String },
+    #[error("invalid note name `{note}`, it must be a path in the notes directory without `.`, `..` or a root")]
+    InvalidNoteName { note: String },
+    #[error("note `{note}` not found")]
+    NoteNotFound { note: String },
+    #[error("`{note}` matches several notes: {}", .candidates.join(", "))]
+    AmbiguousNote { note: String, candidates: Vec<String> },
 
     #[error("error sending an asynchronous request")]
     SendError {
-        #[from]
-        source: SendError<NoteFindMessage>
+        source: Box<SendError<NoteFindMessage>>
     },
     
     #[error("Note {note_name} alerady exists.")]
@@ -42,4 +78,108 @@ pub enum NottoError {
 
     #[error("Create Note Error: {message}")]
     CreateNoteError { message: String },
-}
\ No newline at end of file
+
+    #[error("invalid recurrence `{value}`")]
+    InvalidRecurrence { value: String },
+
+    #[error("invalid argument {argument}: {message}")]
+    InvalidArgument { argument: String, message: String },
+
+    /// A message of a client that can't be read, the server answers it and goes on
+    #[error("invalid message: {message}")]
+    ProtocolError { message: String },
+    /// A request the HTTP server can't read, answered with `status`
+    #[error("invalid request: {message}")]
+    HttpError { status: u16, message: String },
+}
+
+/// The exit codes of notto
+pub const EXIT_FAILURE: i32 = 1;
+/// Invalid arguments, like the ones rejected by clap
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_AMBIGUOUS: i32 = 4;
+pub const EXIT_EXISTS: i32 = 5;
+/// The note is encrypted, open elsewhere or was changed while edited
+pub const EXIT_LOCKED: i32 = 6;
+pub const EXIT_CONFIG: i32 = 7;
+pub const EXIT_IO: i32 = 8;
+/// The editor, a hook or git failed
+pub const EXIT_EXTERNAL: i32 = 9;
+
+impl NottoError {
+    /// A name of the error that doesn't change with its message, for the scripts reading the JSON output
+    pub fn code(&self) -> &'static str {
+        match self {
+            NottoError::ContextNotFound { .. } => "context_not_found",
+            NottoError::ContextExists { .. } => "context_exists",
+            NottoError::InvalidContext { .. } => "invalid_context",
+            NottoError::ContextError { .. } => "context_variable",
+            NottoError::ConfigDirectory { .. } => "io",
+            NottoError::ReadingFile { .. } => "invalid_yaml",
+            NottoError::HomeDirectoryNotFound => "home_not_found",
+            NottoError::LoadConfigError { .. } => "config",
+            NottoError::InvalidConfig { .. } => "invalid_config",
+            NottoError::UnknownConfigKey { .. } => "unknown_config_key",
+            NottoError::EditorNotSet { .. } => "editor_not_set",
+            NottoError::EditorError { .. } => "editor",
+            NottoError::HookFailed { .. } => "hook_failed",
+            NottoError::GitError { .. } => "git",
+            NottoError::HistoryError { .. } => "history",
+            NottoError::EncryptionError { .. } => "encryption",
+            NottoError::NoteLocked { .. } => "note_locked",
+            NottoError::NoteInUse { .. } => "note_in_use",
+            NottoError::EditConflict { .. } => "edit_conflict",
+            NottoError::NoteChanged { .. } => "note_changed",
+            NottoError::InvalidNoteName { .. } => "invalid_note_name",
+            NottoError::NoteNotFound { .. } => "note_not_found",
+            NottoError::AmbiguousNote { .. } => "ambiguous_note",
+            NottoError::SendError { .. } => "internal",
+            NottoError::NoteExists { .. } => "note_exists",
+            NottoError::FileError { .. } => "file",
+            NottoError::CreateNoteError { .. } => "create_note",
+            NottoError::InvalidRecurrence { .. } => "invalid_recurrence",
+            NottoError::InvalidArgument { .. } => "invalid_argument",
+            NottoError::ProtocolError { .. } => "protocol",
+            NottoError::HttpError { .. } => "http",
+        }
+    }
+
+    /// The error as `{"code": ..., "message": ..., "exit_code": ...}`, with the `candidates` of an ambiguous note
+    /// and the `hash` of a changed note
+    pub fn to_json(&self) -> Value {
+        let mut error = json!({ "code": self.code(), "message": self.to_string(), "exit_code": self.exit_code() });
+        if let NottoError::AmbiguousNote { candidates, .. } = self {
+            error["candidates"] = json!(candidates);
+        }
+        if let NottoError::NoteChanged { hash, .. } = self {
+            error["hash"] = json!(hash);
+        }
+
+        error
+    }
+
+    /// The code notto exits with when a command fails with the error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NottoError::ContextNotFound { .. } | NottoError::NoteNotFound { .. } | NottoError::HomeDirectoryNotFound => EXIT_NOT_FOUND,
+            NottoError::AmbiguousNote { .. } => EXIT_AMBIGUOUS,
+            NottoError::ContextExists { .. } | NottoError::NoteExists { .. } => EXIT_EXISTS,
+            NottoError::NoteLocked { .. } | NottoError::NoteInUse { .. } | NottoError::EditConflict { .. } | NottoError::NoteChanged { .. }
+                | NottoError::EncryptionError { .. } => EXIT_LOCKED,
+            NottoError::InvalidContext { .. } | NottoError::ContextError { .. } | NottoError::LoadConfigError { .. } | NottoError::InvalidConfig { .. }
+                | NottoError::UnknownConfigKey { .. } | NottoError::EditorNotSet { .. } => EXIT_CONFIG,
+            NottoError::ConfigDirectory { .. } | NottoError::ReadingFile { .. } | NottoError::FileError { .. } => EXIT_IO,
+            NottoError::EditorError { .. } | NottoError::HookFailed { .. } | NottoError::GitError { .. } => EXIT_EXTERNAL,
+            NottoError::HistoryError { .. } | NottoError::SendError { .. } | NottoError::CreateNoteError { .. } | NottoError::InvalidRecurrence { .. }
+                | NottoError::ProtocolError { .. } | NottoError::HttpError { .. } => EXIT_FAILURE,
+            NottoError::InvalidArgument { .. } | NottoError::InvalidNoteName { .. } => EXIT_USAGE,
+        }
+    }
+}
+
+impl From<SendError<NoteFindMessage>> for NottoError {
+    fn from(source: SendError<NoteFindMessage>) -> Self {
+        NottoError::SendError { source: Box::new(source) }
+    }
+}
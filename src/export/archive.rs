@@ -0,0 +1,101 @@
+use std::{ffi::OsStr, fs::{self, File}, path::Path};
+
+use chrono::Local;
+use flate2::{Compression, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use tar::{Builder, Header};
+
+use crate::{errors::NottoError, io::{ReaderWriter, crypto::is_encrypted, links::to_link}, models::note::Note};
+
+use super::ExportSummary;
+
+/// The first file of the archive, describing its contents
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// The directory of the archive with the notes
+pub const NOTES_DIR: &str = "notes";
+pub const ARCHIVE_FORMAT: &str = "notto-archive";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const NOTE_EXTENSION: &str = "md";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    /// When the archive was created, in RFC 3339
+    pub created: String,
+    /// The context the notes were exported from
+    pub context: String,
+    pub files: Vec<ArchiveFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveFile {
+    /// Path of the file, relative to the notes directory
+    pub path: String,
+    pub size: u64,
+    /// The id of the note, `None` for the other files
+    pub id: Option<String>,
+}
+
+/// Bundles the whole notes directory in a `.tar.gz` file, with a manifest
+///
+/// Private notes are included, it's meant for backups
+pub struct ArchiveExporter {
+    reader: ReaderWriter
+}
+
+impl ArchiveExporter {
+    /// Archives the files read by `reader` as they are, the encrypted notes stay encrypted
+    pub fn new(reader: ReaderWriter) -> Self { Self { reader } }
+
+    /// Writes the archive at `archive_path`, which can't exist yet
+    pub fn export<P, S>(&self, archive_path: P, context: S) -> Result<ExportSummary, NottoError> where P: AsRef<Path>, S: AsRef<str> {
+        let archive_path = archive_path.as_ref();
+        if archive_path.exists() {
+            return Err(NottoError::FileError { message: format!("`{}` already exists", archive_path.to_string_lossy()) });
+        }
+
+        let reader = &self.reader;
+        let mut summary = ExportSummary::default();
+        let mut files = vec![];
+        let paths = reader.list_files("")?;
+        for path in &paths {
+            let id = if path.extension() == Some(OsStr::new(NOTE_EXTENSION)) {
+                summary.exported += 1;
+                let note = Note::from_text(reader.read_text(path)?);
+                if is_encrypted(&note.content) {
+                    summary.encrypted.push(path.clone());
+                }
+                Some(note.front_matter.id)
+            } else {
+                None
+            };
+            let size = fs::metadata(reader.get_full_path(path))?.len();
+            files.push(ArchiveFile { path: to_link(path), size, id });
+        }
+
+        let manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            created: Local::now().to_rfc3339(),
+            context: context.as_ref().to_string(),
+            files,
+        };
+        let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| NottoError::FileError { message: format!("can't write the manifest: {}", e) })?;
+
+        let mut builder = Builder::new(GzEncoder::new(File::create(archive_path)?, Compression::default()));
+        let mut header = Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Local::now().timestamp() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_FILE, manifest.as_slice())?;
+        for path in &paths {
+            builder.append_path_with_name(reader.get_full_path(path), Path::new(NOTES_DIR).join(path))?;
+        }
+        builder.into_inner()?.finish()?;
+
+        Ok(summary)
+    }
+}
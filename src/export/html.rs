@@ -0,0 +1,504 @@
+use std::{collections::{BTreeMap, HashMap}, ffi::OsStr, fs, path::{Path, PathBuf}};
+
+use pulldown_cmark::{Event, Options, Parser, Tag, escape::escape_html, html::push_html};
+use serde::Serialize;
+
+use crate::{errors::NottoError, io::{DIR_ROOT_NOTE_NAME, ReaderWriter, browser::{NoteBrowser, NottoPath, PathEntry}, crypto::is_encrypted, links::{link_candidates, percent_decode, relative_link, resolve_link, split_fragment, to_link}}, finder::resolver::NoteResolver, models::note::Note};
+
+use super::ExportSummary;
+
+const NOTE_EXTENSION: &str = "md";
+const PAGE_EXTENSION: &str = "html";
+const INDEX_PAGE: &str = "index.html";
+const TAGS_DIR: &str = "tags";
+const SEARCH_PAGE: &str = "search.html";
+const SEARCH_INDEX_FILE: &str = "search-index.js";
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; line-height: 1.6; max-width: 48em; margin: 0 auto; padding: 1em; color: #222; }
+nav { font-size: 0.9em; margin-bottom: 0.5em; }
+nav.breadcrumbs { color: #666; }
+a { color: #0366d6; text-decoration: none; }
+a:hover { text-decoration: underline; }
+pre { background: #f6f8fa; padding: 0.8em; overflow: auto; }
+code { background: #f6f8fa; padding: 0.1em 0.3em; }
+.meta { color: #666; font-size: 0.9em; }
+.tag { display: inline-block; background: #eef; border-radius: 0.3em; padding: 0 0.4em; margin-right: 0.3em; }
+input#search { width: 100%; font-size: 1.1em; padding: 0.4em; }
+"#;
+
+const SEARCH_SCRIPT: &str = r#"
+const input = document.getElementById('search');
+const results = document.getElementById('results');
+input.addEventListener('input', () => {
+    const terms = input.value.toLowerCase().split(/\s+/).filter(term => term.length > 0);
+    results.innerHTML = '';
+    if (terms.length === 0) {
+        return;
+    }
+    for (const note of window.NOTTO_SEARCH_INDEX) {
+        const text = (note.title + ' ' + note.tags.join(' ') + ' ' + note.text).toLowerCase();
+        if (terms.every(term => text.includes(term))) {
+            const item = document.createElement('li');
+            const link = document.createElement('a');
+            link.href = note.url;
+            link.textContent = note.title;
+            item.appendChild(link);
+            results.appendChild(item);
+        }
+    }
+});
+"#;
+
+/// A note exported as a page
+struct Page {
+    note: Note,
+    /// Path of the note file, relative to the notes directory
+    source: PathBuf,
+}
+
+impl Page {
+    fn page_path(&self) -> PathBuf {
+        self.source.with_extension(PAGE_EXTENSION)
+    }
+}
+
+/// A directory of the notes, exported as an index page
+#[derive(Default)]
+struct Section {
+    /// The `index.md` note of the directory
+    index: Option<usize>,
+    notes: Vec<usize>,
+    sections: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct Site {
+    pages: Vec<Page>,
+    sections: BTreeMap<PathBuf, Section>,
+    /// All the note files by their path, `None` for the private ones
+    notes: HashMap<PathBuf, Option<usize>>,
+    /// Finds the notes linked by their id or title
+    resolver: NoteResolver,
+    skipped: usize,
+    encrypted: Vec<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct SearchEntry<'a> {
+    title: String,
+    url: String,
+    tags: &'a [String],
+    text: String,
+}
+
+/// Exports the notes as a static HTML site
+///
+/// Directories become sections, with their `index.md` note as the section page.
+/// Notes marked as `private` are left out, and so are the links to them
+pub struct HtmlExporter {
+    reader: ReaderWriter
+}
+
+impl HtmlExporter {
+    /// Reads the notes with `reader`, the encrypted ones are left out if it can't decrypt them
+    pub fn new(reader: ReaderWriter) -> Self { Self { reader } }
+
+    pub fn export<P>(&self, out_dir: P) -> Result<ExportSummary, NottoError> where P: AsRef<Path> {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir)?;
+
+        let mut site = Site::default();
+        self.collect(&NottoPath::new(), &mut site, out_dir)?;
+
+        for page in site.pages.iter().filter(|page| page.source.file_name() != Some(OsStr::new(DIR_ROOT_NOTE_NAME))) {
+            let page_path = page.page_path();
+            let body = format!("{}{}", self.render_meta(&page.note, &page_path), self.render_markdown(&site, &page.source, &page.note.content));
+            write_page(out_dir, &page_path, &layout(&page_path, &page.note.get_title(), &body))?;
+        }
+
+        for (dir, section) in &site.sections {
+            let page_path = dir.join(INDEX_PAGE);
+            let (title, mut body) = match section.index.map(|index| &site.pages[index]) {
+                Some(page) => {
+                    let body = format!("{}{}", self.render_meta(&page.note, &page_path), self.render_markdown(&site, &page.source, &page.note.content));
+                    (page.note.get_title(), body)
+                }
+                None => {
+                    let title = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "Notes".to_string());
+                    (title.clone(), format!("<h1>{}</h1>\n", escape(&title)))
+                }
+            };
+            body.push_str(&render_section(&site, section, &page_path));
+            write_page(out_dir, &page_path, &layout(&page_path, &title, &body))?;
+        }
+
+        self.write_tags(&site, out_dir)?;
+        self.write_search(&site, out_dir)?;
+
+        Ok(ExportSummary { exported: site.pages.len(), skipped: site.skipped, encrypted: site.encrypted })
+    }
+
+    /// Walks the notes with the `NoteBrowser`, copying the attachments on the way
+    fn collect(&self, path: &NottoPath, site: &mut Site, out_dir: &Path) -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(self.reader.get_full_path(""));
+        let dir = PathEntry::string_to_pathbuf(path);
+
+        let mut section = Section::default();
+        for entry in browser.get_selections_for_path(path)? {
+            let entry_path = PathEntry::string_to_pathbuf(&entry.path);
+            if entry.is_dir() {
+                self.collect(&entry.path, site, out_dir)?;
+                section.sections.push(entry_path);
+            } else if entry_path.extension() == Some(OsStr::new(NOTE_EXTENSION)) {
+                let note = self.reader.read_note(&entry_path)?;
+                site.resolver.add(entry_path.clone(), &note.front_matter.id, note.get_title());
+                if is_encrypted(&note.content) {
+                    site.notes.insert(entry_path.clone(), None);
+                    site.encrypted.push(entry_path);
+                    continue;
+                }
+                if note.front_matter.private {
+                    site.notes.insert(entry_path, None);
+                    site.skipped += 1;
+                    continue;
+                }
+
+                let index = site.pages.len();
+                if entry_path.file_name() == Some(OsStr::new(DIR_ROOT_NOTE_NAME)) {
+                    section.index = Some(index);
+                } else {
+                    section.notes.push(index);
+                }
+                site.notes.insert(entry_path.clone(), Some(index));
+                site.pages.push(Page { note, source: entry_path });
+            }
+        }
+        site.sections.insert(dir.clone(), section);
+
+        // Images and other files the notes may link to
+        for dir_entry in fs::read_dir(self.reader.get_full_path(&dir))?.flatten() {
+            let file_path = dir_entry.path();
+            let hidden = dir_entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && file_path.is_file() && file_path.extension() != Some(OsStr::new(NOTE_EXTENSION)) {
+                let dest_dir = out_dir.join(&dir);
+                fs::create_dir_all(&dest_dir)?;
+                fs::copy(&file_path, dest_dir.join(dir_entry.file_name()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render_meta(&self, note: &Note, page_path: &Path) -> String {
+        let mut meta = format!("<p class=\"meta\">{}", note.front_matter.date.format("%Y-%m-%d"));
+        for tag in &note.front_matter.tags {
+            let url = relative_link(page_path, &tag_page_path(tag));
+            meta.push_str(&format!(" <a class=\"tag\" href=\"{}\">{}</a>", escape(&url), escape(tag)));
+        }
+        meta.push_str("</p>\n");
+
+        meta
+    }
+
+    /// Renders the markdown of a note, pointing the links to other notes to their pages
+    fn render_markdown(&self, site: &Site, source: &Path, content: &str) -> String {
+        let page_path = source.with_extension(PAGE_EXTENSION);
+        let mut removed_links = vec![];
+        let events = Parser::new_ext(content, markdown_options()).filter_map(|event| match event {
+            Event::Start(Tag::Link(link_type, dest, title)) => {
+                match self.resolve_link(site, source, &dest) {
+                    Some((Some(target), fragment)) => {
+                        removed_links.push(false);
+                        let url = format!("{}{}", relative_link(&page_path, &site.pages[target].page_path()), fragment);
+                        Some(Event::Start(Tag::Link(link_type, url.into(), title)))
+                    }
+                    // Links to private notes are left as plain text
+                    Some((None, _)) => {
+                        removed_links.push(true);
+                        None
+                    }
+                    None => {
+                        removed_links.push(false);
+                        Some(Event::Start(Tag::Link(link_type, dest, title)))
+                    }
+                }
+            }
+            Event::End(Tag::Link(link_type, dest, title)) => {
+                if removed_links.pop().unwrap_or(false) {
+                    None
+                } else {
+                    Some(Event::End(Tag::Link(link_type, dest, title)))
+                }
+            }
+            event => Some(event)
+        });
+
+        let mut html = String::new();
+        push_html(&mut html, events);
+
+        html
+    }
+
+    /// Finds the note a link points to, returns the page of the note (`None` if it's private) and the link fragment
+    fn resolve_link(&self, site: &Site, source: &Path, dest: &str) -> Option<(Option<usize>, String)> {
+        let path = resolve_link(source, dest)?;
+        let fragment = split_fragment(dest).1.to_string();
+
+        link_candidates(path).iter()
+            .find_map(|candidate| site.notes.get(candidate))
+            .or_else(|| {
+                // Like `[plan](8f14)` or `[plan](Weekly plan)`
+                let path = site.resolver.resolve(&percent_decode(split_fragment(dest).0)).ok()?;
+                site.notes.get(&path)
+            })
+            .map(|page| (*page, fragment))
+    }
+
+    fn write_tags(&self, site: &Site, out_dir: &Path) -> Result<(), NottoError> {
+        let mut tags: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, page) in site.pages.iter().enumerate() {
+            for tag in &page.note.front_matter.tags {
+                tags.entry(tag.as_str()).or_default().push(index);
+            }
+        }
+
+        let tags_index_path = Path::new(TAGS_DIR).join(INDEX_PAGE);
+        let mut tags_index = String::from("<h1>Tags</h1>\n<ul>\n");
+        for (tag, pages) in &tags {
+            let tag_path = tag_page_path(tag);
+            let url = relative_link(&tags_index_path, &tag_path);
+            tags_index.push_str(&format!("<li><a href=\"{}\">{}</a> ({})</li>\n", escape(&url), escape(tag), pages.len()));
+
+            let mut body = format!("<h1>#{}</h1>\n<ul>\n", escape(tag));
+            for index in pages {
+                body.push_str(&note_list_item(&site.pages[*index], &tag_path));
+            }
+            body.push_str("</ul>\n");
+            write_page(out_dir, &tag_path, &layout(&tag_path, tag, &body))?;
+        }
+        tags_index.push_str("</ul>\n");
+        write_page(out_dir, &tags_index_path, &layout(&tags_index_path, "Tags", &tags_index))?;
+
+        Ok(())
+    }
+
+    fn write_search(&self, site: &Site, out_dir: &Path) -> Result<(), NottoError> {
+        let entries = site.pages.iter().map(|page| SearchEntry {
+            title: page.note.get_title(),
+            url: to_link(&page.page_path()),
+            tags: &page.note.front_matter.tags,
+            text: plain_text(&page.note.content),
+        }).collect::<Vec<_>>();
+        let index = serde_json::to_string(&entries).map_err(|e| NottoError::FileError { message: format!("Error building the search index: {}", e) })?;
+        fs::write(out_dir.join(SEARCH_INDEX_FILE), format!("window.NOTTO_SEARCH_INDEX = {};\n", index))?;
+
+        let search_path = Path::new(SEARCH_PAGE);
+        let body = format!(
+            "<h1>Search</h1>\n<input id=\"search\" type=\"search\" placeholder=\"Search notes\" autofocus>\n<ul id=\"results\"></ul>\n<script src=\"{}\"></script>\n<script>{}</script>\n",
+            SEARCH_INDEX_FILE, SEARCH_SCRIPT);
+        write_page(out_dir, search_path, &layout(search_path, "Search", &body))
+    }
+}
+
+fn render_section(site: &Site, section: &Section, page_path: &Path) -> String {
+    let mut html = String::new();
+    if !section.sections.is_empty() {
+        html.push_str("<h2>Sections</h2>\n<ul>\n");
+        for dir in &section.sections {
+            let name = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let url = relative_link(page_path, &dir.join(INDEX_PAGE));
+            html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape(&url), escape(&name)));
+        }
+        html.push_str("</ul>\n");
+    }
+    if !section.notes.is_empty() {
+        html.push_str("<h2>Notes</h2>\n<ul>\n");
+        for index in &section.notes {
+            html.push_str(&note_list_item(&site.pages[*index], page_path));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html
+}
+
+fn note_list_item(page: &Page, from: &Path) -> String {
+    let url = relative_link(from, &page.page_path());
+    format!("<li><a href=\"{}\">{}</a> <span class=\"meta\">{}</span></li>\n", escape(&url), escape(&page.note.get_title()), page.note.front_matter.date.format("%Y-%m-%d"))
+}
+
+fn layout(page_path: &Path, title: &str, body: &str) -> String {
+    let root = relative_link(page_path, Path::new(""));
+    let mut breadcrumbs = format!("<a href=\"{}{}\">Home</a>", root, INDEX_PAGE);
+    if let Some(parent) = page_path.parent() {
+        let mut dir = PathBuf::new();
+        for component in parent.components() {
+            dir.push(component);
+            if dir.as_path() != parent || page_path.file_name() != Some(OsStr::new(INDEX_PAGE)) {
+                let url = relative_link(page_path, &dir.join(INDEX_PAGE));
+                breadcrumbs.push_str(&format!(" / <a href=\"{}\">{}</a>", escape(&url), escape(&component.as_os_str().to_string_lossy())));
+            }
+        }
+    }
+
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>{style}</style>
+</head>
+<body>
+<nav><a href="{root}{index}">Home</a> · <a href="{root}{tags}/{index}">Tags</a> · <a href="{root}{search}">Search</a></nav>
+<nav class="breadcrumbs">{breadcrumbs}</nav>
+<main>
+{body}</main>
+</body>
+</html>
+"#, title = escape(title), style = STYLE, root = root, index = INDEX_PAGE, tags = TAGS_DIR, search = SEARCH_PAGE, breadcrumbs = breadcrumbs, body = body)
+}
+
+fn write_page(out_dir: &Path, page_path: &Path, html: &str) -> Result<(), NottoError> {
+    let path = out_dir.join(page_path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, html)?;
+
+    Ok(())
+}
+
+fn tag_page_path(tag: &str) -> PathBuf {
+    let slug = tag.to_lowercase().chars().map(|c| if c.is_alphanumeric() { c } else { '-' }).collect::<String>();
+    Path::new(TAGS_DIR).join(format!("{}.{}", slug, PAGE_EXTENSION))
+}
+
+pub(crate) fn markdown_options() -> Options {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options
+}
+
+fn plain_text(content: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(content, markdown_options()) {
+        match event {
+            Event::Text(t) | Event::Code(t) => {
+                text.push_str(&t);
+                text.push(' ');
+            }
+            _ => {}
+        }
+    }
+
+    text.trim_end().to_string()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    // Writing to a String doesn't fail
+    let _ = escape_html(&mut escaped, text);
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, export::ExportSummary, io::ReaderWriter, test_utils::test_dir};
+
+    use super::HtmlExporter;
+
+    /// Exports a home note, a `work` section with a plan and a diagram, and a private note, returns the directory of the site
+    fn export_test_site(name: &str) -> Result<(ExportSummary, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let notes_path = base_path.join("notes");
+        let out_path = base_path.join("site");
+        fs::create_dir_all(notes_path.join("work"))?;
+
+        fs::write(notes_path.join("index.md"), "# Home\nSee [work](work/) and [the plan](work/plan.md#goals), [by title](The%20plan).")?;
+        fs::write(notes_path.join("work").join("index.md"), "---\ntags: [job, ../../escape]\n---\n# Work\nAll about work.")?;
+        fs::write(notes_path.join("work").join("plan.md"), "# The plan\nBack [home](../index.md), [secret](/secret.md), [out](../../../etc/passwd)")?;
+        fs::write(notes_path.join("secret.md"), "---\nprivate: true\n---\n# Secret")?;
+        fs::write(notes_path.join("work").join("diagram.png"), [0u8, 159, 146, 150])?;
+
+        let summary = HtmlExporter::new(ReaderWriter::new(notes_path)).export(&out_path)?;
+
+        Ok((summary, out_path))
+    }
+
+    #[test]
+    fn writes_a_page_per_note() -> Result<(), NottoError> {
+        let (summary, out_path) = export_test_site("html_pages")?;
+
+        assert_eq!(3, summary.exported);
+        assert!(out_path.join("index.html").exists());
+        assert!(out_path.join("work").join("index.html").exists());
+        assert!(out_path.join("work").join("plan.html").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn points_the_links_to_the_pages() -> Result<(), NottoError> {
+        let (_, out_path) = export_test_site("html_links")?;
+
+        let home = fs::read_to_string(out_path.join("index.html"))?;
+        assert!(home.contains("href=\"work/index.html\""));
+        assert!(home.contains("href=\"work/plan.html#goals\""));
+        assert!(home.contains("href=\"work/plan.html\">by title"));
+        let plan = fs::read_to_string(out_path.join("work").join("plan.html"))?;
+        assert!(plan.contains("href=\"../index.html\""));
+        assert!(plan.contains("href=\"../../../etc/passwd\""));
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_out_the_private_notes() -> Result<(), NottoError> {
+        let (summary, out_path) = export_test_site("html_private")?;
+
+        assert_eq!(1, summary.skipped);
+        assert!(!out_path.join("secret.html").exists());
+        let plan = fs::read_to_string(out_path.join("work").join("plan.html"))?;
+        assert!(!plan.contains("secret.md"));
+        assert!(plan.contains("secret"));
+        assert!(!fs::read_to_string(out_path.join("search-index.js"))?.contains("Secret"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn copies_the_attachments() -> Result<(), NottoError> {
+        let (_, out_path) = export_test_site("html_attachments")?;
+
+        assert_eq!(vec![0u8, 159, 146, 150], fs::read(out_path.join("work").join("diagram.png"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_the_tags_and_search_index() -> Result<(), NottoError> {
+        let (_, out_path) = export_test_site("html_tags")?;
+
+        assert!(out_path.join("tags").join("job.html").exists());
+        assert!(fs::read_to_string(out_path.join("tags").join("index.html"))?.contains("href=\"job.html\""));
+        assert!(fs::read_to_string(out_path.join("search-index.js"))?.contains("All about work"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_tag_pages_in_the_site() -> Result<(), NottoError> {
+        let (_, out_path) = export_test_site("html_tag_names")?;
+
+        assert!(out_path.join("tags").join("------escape.html").exists());
+        assert!(!out_path.join("..").join("escape.html").exists());
+
+        Ok(())
+    }
+}
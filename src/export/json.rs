@@ -0,0 +1,168 @@
+use std::{ffi::OsStr, io::Write, path::Path};
+
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::Serialize;
+
+use crate::{errors::NottoError, io::{ReaderWriter, crypto::is_encrypted, links::{resolve_link, to_link}}, models::{front_matter::FrontMatter, note::Note, task::Task}};
+
+use super::ExportSummary;
+
+const NOTE_EXTENSION: &str = "md";
+
+/// Exports every note as a JSON object per line (NDJSON)
+///
+/// Private notes are exported too, this is meant for backups and other tools
+pub struct JsonExporter {
+    reader: ReaderWriter
+}
+
+#[derive(Serialize)]
+struct NoteRecord<'a> {
+    /// Path of the note file, relative to the notes directory
+    path: String,
+    id: &'a str,
+    title: String,
+    front_matter: &'a FrontMatter,
+    content: &'a str,
+    links: Vec<LinkRecord>,
+    tasks: Vec<TaskRecord>,
+}
+
+#[derive(Serialize)]
+struct LinkRecord {
+    url: String,
+    /// The linked file, relative to the notes directory, `None` for external links
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TaskRecord {
+    line: usize,
+    done: bool,
+    text: String,
+    due: Option<String>,
+    every: Option<String>,
+}
+
+impl From<Task> for TaskRecord {
+    fn from(task: Task) -> Self {
+        Self {
+            line: task.line,
+            done: task.done,
+            text: task.text,
+            due: task.due.map(|due| due.format("%Y-%m-%d").to_string()),
+            every: task.every.map(|every| every.to_string()),
+        }
+    }
+}
+
+impl JsonExporter {
+    /// Reads the notes with `reader`, the encrypted ones are left out if it can't decrypt them
+    pub fn new(reader: ReaderWriter) -> Self { Self { reader } }
+
+    pub fn export<W>(&self, mut out: W) -> Result<ExportSummary, NottoError> where W: Write {
+        let mut summary = ExportSummary::default();
+
+        for path in self.reader.list_files("")? {
+            if path.extension() != Some(OsStr::new(NOTE_EXTENSION)) {
+                continue;
+            }
+            let note = self.reader.read_note(&path)?;
+            if is_encrypted(&note.content) {
+                summary.encrypted.push(path);
+                continue;
+            }
+            let record = NoteRecord {
+                path: to_link(&path),
+                id: &note.front_matter.id,
+                title: note.get_title(),
+                front_matter: &note.front_matter,
+                content: &note.content,
+                links: links(&note, &path),
+                tasks: note.get_tasks().into_iter().map(TaskRecord::from).collect(),
+            };
+            let line = serde_json::to_string(&record).map_err(|e| NottoError::FileError { message: format!("can't serialize `{}`: {}", record.path, e) })?;
+            writeln!(out, "{}", line)?;
+            summary.exported += 1;
+        }
+
+        Ok(summary)
+    }
+}
+
+/// The links and images in the content of the note
+fn links(note: &Note, path: &Path) -> Vec<LinkRecord> {
+    Parser::new(&note.content).filter_map(|event| match event {
+        Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _)) => Some(LinkRecord {
+            path: resolve_link(path, &url).map(|target| to_link(&target)),
+            url: url.to_string(),
+        }),
+        _ => None
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use crate::{errors::NottoError, io::ReaderWriter, test_utils::test_dir};
+
+    use super::JsonExporter;
+
+    /// The notes exported from a directory with `projects/index.md` linking to `projects/plan.md`, and an image, a JSON object per note
+    fn export_test_notes(name: &str) -> Result<Vec<Value>, NottoError> {
+        let base_path = test_dir(name)?;
+        fs::create_dir_all(base_path.join("projects"))?;
+        fs::write(base_path.join("projects").join("index.md"), "---\nid: abc\ntitle: Projects\ndate: 2021-03-04\ntime: \"10:11:12\"\ntags: [work]\nprivate: true\n---\nSee [the plan](plan.md) and [the web](https://example.com)\n- [ ] Call Ana due: 2021-03-05 every: weekly\n")?;
+        fs::write(base_path.join("projects").join("plan.md"), "Step 1 [out](../../../etc/passwd)")?;
+        fs::write(base_path.join("projects").join("image.png"), [0u8])?;
+
+        let mut out = vec![];
+        let summary = JsonExporter::new(ReaderWriter::new(base_path)).export(&mut out)?;
+        assert_eq!(2, summary.exported);
+
+        Ok(String::from_utf8_lossy(&out).lines().map(|line| serde_json::from_str::<Value>(line).unwrap()).collect())
+    }
+
+    #[test]
+    fn exports_a_line_per_note() -> Result<(), NottoError> {
+        let lines = export_test_notes("export_json_lines")?;
+
+        assert_eq!(vec!["projects/index.md", "projects/plan.md"], lines.iter().map(|line| line["path"].as_str().unwrap()).collect::<Vec<_>>());
+        assert_eq!("abc", lines[0]["id"]);
+        assert_eq!("Projects", lines[0]["title"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_the_front_matter() -> Result<(), NottoError> {
+        let lines = export_test_notes("export_json_front_matter")?;
+
+        let front_matter = &lines[0]["front_matter"];
+        assert_eq!("2021-03-04", front_matter["date"]);
+        assert_eq!(true, front_matter["private"]);
+        assert_eq!("work", front_matter["tags"][0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_the_links_and_tasks() -> Result<(), NottoError> {
+        let lines = export_test_notes("export_json_links")?;
+
+        let index = &lines[0];
+        assert_eq!("projects/plan.md", index["links"][0]["path"]);
+        assert_eq!(Value::Null, index["links"][1]["path"]);
+        assert_eq!("Call Ana", index["tasks"][0]["text"]);
+        assert_eq!("2021-03-05", index["tasks"][0]["due"]);
+        assert_eq!("weekly", index["tasks"][0]["every"]);
+        // A link out of the notes directory has no path
+        assert_eq!("../../../etc/passwd", lines[1]["links"][0]["url"]);
+        assert_eq!(Value::Null, lines[1]["links"][0]["path"]);
+
+        Ok(())
+    }
+}
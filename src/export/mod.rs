@@ -0,0 +1,15 @@
+pub mod html;
+pub mod json;
+pub mod archive;
+
+use std::path::PathBuf;
+
+/// The result of exporting the notes of a context
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub exported: usize,
+    /// Notes left out because they are marked as `private`
+    pub skipped: usize,
+    /// Encrypted notes, left out of the HTML and JSON exports when notto can't decrypt them, archived still encrypted
+    pub encrypted: Vec<PathBuf>,
+}
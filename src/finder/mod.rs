@@ -1,20 +1,27 @@
-use std::{fs, path::{Path, PathBuf}, sync::Arc, thread};
+pub mod resolver;
+
+use std::{fs, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}, thread};
 use chrono::{NaiveDate, NaiveTime};
 use crossbeam_utils::sync::WaitGroup;
 use log::{error, warn};
 use crossbeam_channel::{Receiver, Sender};
 
-use crate::{errors::NottoError, models::note::Note};
+use crate::{errors::NottoError, io::{crypto::{NoteCipher, is_encrypted}, editor::Position}, models::note::Note};
 
 pub enum NoteFindMessage {
     Result(NoteFindResult),
-    Finish
+    /// The search is over, with the number of notes read and found
+    Finish { scanned: usize, matched: usize }
 }
 
 #[derive(Debug)]
 pub struct NoteFindResult {
     pub note: Note,
-    pub path: PathBuf
+    pub path: PathBuf,
+    /// The context of the note when finding in several contexts
+    pub context: Option<String>,
+    /// Where the text searched first appears in the file
+    pub position: Option<Position>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,17 +39,77 @@ pub enum FindCondition {
     Time(TimeFind, NaiveTime),
 }
 
+/// Stops a search in progress, the notes not read yet are skipped
+#[derive(Debug, Clone, Default)]
+pub struct FindCancel(Arc<AtomicBool>);
+
+impl FindCancel {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 pub struct Finder {
-    base_path: PathBuf
+    /// The directories to look into, with the name of their context
+    base_paths: Vec<(Option<String>, PathBuf)>,
+    /// Decrypts the encrypted notes in memory, without it their content is left out of the search
+    cipher: Option<NoteCipher>,
+    cancel: FindCancel,
 }
 
 type Fc = Box<dyn Fn(&Note) -> bool + Send + Sync>;
 
+/// What the threads reading the notes of a search share
+struct Search {
+    text: Option<String>,
+    cipher: Option<NoteCipher>,
+    sender: Sender<NoteFindMessage>,
+    check_conds: Fc,
+    cancel: FindCancel,
+    scanned: AtomicUsize,
+    matched: AtomicUsize,
+}
+
 impl Finder {
-    pub fn new(base_path: PathBuf) -> Self { Self { base_path } }
+    pub fn new(base_path: PathBuf) -> Self { Self { base_paths: vec![(None, base_path)], cipher: None, cancel: FindCancel::default() } }
+
+    /// Finds in the notes of several contexts, the results are tagged with the context name
+    ///
+    /// Contexts sharing a directory are only looked into once, as the first of them
+    pub fn with_contexts(contexts: Vec<(String, PathBuf)>) -> Self {
+        let mut base_paths: Vec<(Option<String>, PathBuf)> = vec![];
+        for (context, base_path) in contexts {
+            if !base_paths.iter().any(|(_, path)| path == &base_path) {
+                base_paths.push((Some(context), base_path));
+            }
+        }
+
+        Self { base_paths, cipher: None, cancel: FindCancel::default() }
+    }
+
+    /// Searches the content of the encrypted notes too, decrypted in memory
+    pub fn with_cipher(mut self, cipher: Option<NoteCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
 
+    /// Stops the search once `cancel` is cancelled, the results found until then are sent
+    pub fn with_cancel(mut self, cancel: FindCancel) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Returns a receiver getting the results as the notes are read, then `Finish`
     pub fn find<P>(&self, path: P, conditions: Vec<FindCondition>) -> Result<Receiver<NoteFindMessage>, NottoError> where P: AsRef<Path> {
         let (tx, rx) = crossbeam_channel::unbounded();
+        let text = conditions.iter().find_map(|cond| match cond {
+            FindCondition::Text(text) => Some(text.clone()),
+            _ => None
+        });
 
         let check_conds = Box::new(move |note: &Note| {
             for cond in &conditions {
@@ -50,9 +117,11 @@ impl Finder {
                     FindCondition::Text(text) => {
                         note.content.to_uppercase().find(&text.to_uppercase()).is_some()
                     }
-                    FindCondition::Tag(tag) => { false }
-                    FindCondition::Date(when, date) => { false }
-                    FindCondition::Time(when, time) => { false }
+                    FindCondition::Tag(tag) => {
+                        note.front_matter.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+                    }
+                    FindCondition::Date(_when, _date) => { false }
+                    FindCondition::Time(_when, _time) => { false }
                 };
 
                 if !matched {
@@ -64,35 +133,50 @@ impl Finder {
         });
 
         let wg = WaitGroup::new();
-        Finder::read_dir(self.base_path.join(path), tx.clone(), wg.clone(), Arc::new(check_conds))?;
+        let search = Arc::new(Search {
+            text,
+            cipher: self.cipher.clone(),
+            sender: tx,
+            check_conds,
+            cancel: self.cancel.clone(),
+            scanned: AtomicUsize::new(0),
+            matched: AtomicUsize::new(0),
+        });
+        for (context, base_path) in &self.base_paths {
+            Finder::read_dir(base_path.join(&path), context.clone(), Arc::clone(&search), wg.clone())?;
+        }
 
-        wg.wait();
-        tx.send(NoteFindMessage::Finish)?;
+        // The notes are still read by their threads
+        thread::spawn(move || {
+            wg.wait();
+            let finish = NoteFindMessage::Finish { scanned: search.scanned.load(Ordering::Relaxed), matched: search.matched.load(Ordering::Relaxed) };
+            // The receiver may be gone with a cancelled search
+            let _ = search.sender.send(finish);
+        });
 
         Ok(rx)
     }
 
-    fn read_dir<P>(path: P, sender: Sender<NoteFindMessage>, wg: WaitGroup, f: Arc<Fc>) -> Result<(), NottoError> where P: AsRef<Path> {
+    fn read_dir<P>(path: P, context: Option<String>, search: Arc<Search>, wg: WaitGroup) -> Result<(), NottoError> where P: AsRef<Path> {
         for entry in fs::read_dir(path)? {
-            let p = entry?.path();
-            let f = Arc::clone(&f);
+            if search.cancel.is_cancelled() {
+                break;
+            }
+            let entry = entry?;
+            // Like the lock files and the git repository
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let p = entry.path();
             if p.is_dir() {
-                if let Err(e) = Finder::read_dir(p, sender.clone(), wg.clone(), f) { error!("{}", e); }
+                if let Err(e) = Finder::read_dir(p, context.clone(), Arc::clone(&search), wg.clone()) { error!("{}", e); }
             } else {
-                let tx = sender.clone();
+                let search = Arc::clone(&search);
                 let wg_cloned = wg.clone();
+                let context = context.clone();
                 thread::spawn(move || {
-                    match fs::read_to_string(&p) {
-                        Ok(note_content) => {
-                            let note = Note::from_text(note_content);
-                            if f(&note) {
-                                let note_find_result = NoteFindResult { note, path: p };
-                                if let Err(e) = tx.send(NoteFindMessage::Result(note_find_result)) { error!("{}", e); }
-                            };
-                        }
-                        Err(e) => {
-                            warn!("Error reading file at {}: {}", p.to_string_lossy(), e);
-                        }
+                    if !search.cancel.is_cancelled() {
+                        Finder::read_note(p, context, &search);
                     }
 
                     drop(wg_cloned);
@@ -104,4 +188,173 @@ impl Finder {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn read_note(p: PathBuf, context: Option<String>, search: &Search) {
+        match fs::read_to_string(&p) {
+            Ok(note_content) => {
+                search.scanned.fetch_add(1, Ordering::Relaxed);
+                let mut note = Note::from_text(&note_content);
+                let text = search.text.as_deref();
+                let position = if is_encrypted(&note.content) {
+                    // Only the decrypted content is searched, the position is in the plaintext the editor gets
+                    note.content = search.cipher.as_ref().and_then(|cipher| cipher.decrypt(&note.content)
+                        .map_err(|e| warn!("Can't decrypt {}: {}", p.to_string_lossy(), e))
+                        .ok()).unwrap_or_default();
+                    text.and_then(|text| find_position(&note.to_text(), text))
+                } else {
+                    text.and_then(|text| find_position(&note_content, text))
+                };
+                if (search.check_conds)(&note) {
+                    search.matched.fetch_add(1, Ordering::Relaxed);
+                    let note_find_result = NoteFindResult { note, path: p, context, position };
+                    if let Err(e) = search.sender.send(NoteFindMessage::Result(note_find_result)) { error!("{}", e); }
+                };
+            }
+            Err(e) => {
+                warn!("Error reading file at {}: {}", p.to_string_lossy(), e);
+            }
+        }
+    }
+}
+/// Where `text` first appears in the content of a note, ignoring the case, the front matter is skipped
+pub fn find_position(note_text: &str, text: &str) -> Option<Position> {
+    let text = text.to_lowercase();
+    let (_, content) = Note::split_front_matter(note_text);
+    let front_matter_lines = note_text.lines().count().saturating_sub(content.split('\n').count());
+
+    content.lines().enumerate().find_map(|(index, line)| {
+        let line = line.to_lowercase();
+        line.find(&text).map(|start| Position { line: front_matter_lines + index + 1, column: line[..start].chars().count() + 1 })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, io::editor::Position, test_utils::test_dir};
+
+    use super::{FindCancel, FindCondition, Finder, NoteFindMessage, find_position};
+
+    /// The contexts `work` and `personal` in `test_notes_dir_<name>`, two of their notes mention a call
+    fn test_contexts(name: &str) -> Result<PathBuf, NottoError> {
+        let base_path = test_dir(name)?;
+        fs::create_dir_all(base_path.join("work").join("projects"))?;
+        fs::create_dir_all(base_path.join("personal"))?;
+        fs::write(base_path.join("work").join("projects").join("plan.md"), "---\ntags: [bank]\n---\nCall the bank")?;
+        fs::write(base_path.join("work").join("todo.md"), "Nothing here")?;
+        fs::write(base_path.join("personal").join("home.md"), "Call the plumber")?;
+
+        Ok(base_path)
+    }
+
+    /// The context and path of each result
+    type Found = Vec<(String, PathBuf)>;
+
+    /// The results, sorted, with the number of notes scanned and matched
+    fn collect(finder: &Finder, conditions: Vec<FindCondition>) -> Result<(Found, (usize, usize)), NottoError> {
+        let mut results = vec![];
+        for message in finder.find(PathBuf::new(), conditions)? {
+            match message {
+                NoteFindMessage::Result(result) => results.push((result.context.unwrap_or_default(), result.path)),
+                NoteFindMessage::Finish { scanned, matched } => {
+                    results.sort();
+                    return Ok((results, (scanned, matched)));
+                }
+            }
+        }
+
+        Err(NottoError::FileError { message: "the search didn't finish".to_string() })
+    }
+
+    #[test]
+    fn finds_in_several_contexts() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_contexts")?;
+
+        let finder = Finder::with_contexts(vec![
+            ("work".to_string(), base_path.join("work")),
+            ("personal".to_string(), base_path.join("personal")),
+        ]);
+        let (results, counts) = collect(&finder, vec![FindCondition::Text("call".to_string())])?;
+        assert_eq!((3, 2), counts);
+        assert_eq!(vec![
+            ("personal".to_string(), base_path.join("personal").join("home.md")),
+            ("work".to_string(), base_path.join("work").join("projects").join("plan.md")),
+        ], results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn looks_into_the_shared_directories_once() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_shared")?;
+
+        let finder = Finder::with_contexts(vec![
+            ("work".to_string(), base_path.join("work")),
+            ("other".to_string(), base_path.join("work")),
+        ]);
+        let (results, counts) = collect(&finder, vec![FindCondition::Text("call".to_string())])?;
+        assert_eq!((2, 1), counts);
+        assert_eq!(vec![("work".to_string(), base_path.join("work").join("projects").join("plan.md"))], results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_by_tags() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_tags")?;
+
+        let (results, _) = collect(&Finder::new(base_path.clone()), vec![FindCondition::Tag("BANK".to_string())])?;
+        assert_eq!(vec![(String::new(), base_path.join("work").join("projects").join("plan.md"))], results);
+        let (results, _) = collect(&Finder::new(base_path), vec![FindCondition::Tag("bank".to_string()), FindCondition::Text("plumber".to_string())])?;
+        assert!(results.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_the_hidden_files() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_hidden")?;
+        fs::write(base_path.join("work").join(".todo.md.lock"), "Call the bank")?;
+        fs::create_dir_all(base_path.join("work").join(".git"))?;
+        fs::write(base_path.join("work").join(".git").join("COMMIT_EDITMSG"), "Call the bank")?;
+
+        let (results, counts) = collect(&Finder::new(base_path.join("work")), vec![FindCondition::Text("call".to_string())])?;
+        assert_eq!((2, 1), counts);
+        assert_eq!(1, results.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn stops_when_cancelled() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_cancelled")?;
+
+        // Cancelled before it starts, nothing is read
+        let cancel = FindCancel::default();
+        cancel.cancel();
+        let rx = Finder::new(base_path).with_cancel(cancel).find(PathBuf::new(), vec![])?;
+        assert!(matches!(rx.recv(), Ok(NoteFindMessage::Finish { scanned: 0, matched: 0 })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_the_missing_directories() -> Result<(), NottoError> {
+        let base_path = test_contexts("finder_missing")?;
+
+        assert!(Finder::new(base_path.clone()).find("nowhere", vec![]).is_err());
+        assert!(Finder::new(base_path.join("nowhere")).find(PathBuf::new(), vec![]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_text_position() {
+        let text = "---\ntitle: Bank\n---\n# Errands\n\nCall the BANK today\n";
+        assert_eq!(Some(Position { line: 6, column: 10 }), find_position(text, "bank"));
+        assert_eq!(Some(Position { line: 2, column: 5 }), find_position("Café\nÉté à la bank", "à"));
+        assert_eq!(None, find_position(text, "title"));
+        assert_eq!(None, find_position("", "bank"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::{ffi::OsStr, path::{Path, PathBuf}};
+
+use crate::{errors::NottoError, io::{DIR_ROOT_NOTE_NAME, ReaderWriter, links::to_link}, models::note::Note};
+
+const NOTE_EXTENSION: &str = "md";
+/// The shortest start of an id a note is found by, shorter ones would match too many notes
+const MIN_ID_PREFIX: usize = 4;
+
+/// A note with what it can be found by
+#[derive(Debug, Clone)]
+struct NoteRef {
+    path: PathBuf,
+    /// The path as a link without the extension, and without `index` for a parent note
+    name: String,
+    id: String,
+    title: String,
+}
+
+/// Finds a note by its path, its id or the start of it, the end of its path or its title
+///
+/// The ways are tried in that order, the first one matching notes decides: a single note is found,
+/// several notes are ambiguous and the error lists them
+#[derive(Debug, Default)]
+pub struct NoteResolver {
+    notes: Vec<NoteRef>,
+}
+
+impl NoteResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the notes under the notes directory, the title of an encrypted note is only in its front matter
+    pub fn load(reader: &ReaderWriter) -> Result<Self, NottoError> {
+        let mut resolver = NoteResolver::new();
+        for path in reader.list_files("")? {
+            if path.extension() == Some(OsStr::new(NOTE_EXTENSION)) {
+                let note = Note::from_text(reader.read_text(&path)?);
+                resolver.add(path, &note.front_matter.id, note.get_title());
+            }
+        }
+
+        Ok(resolver)
+    }
+
+    /// Adds the note at `path`, relative to the notes directory
+    pub fn add<P: Into<PathBuf>, S: Into<String>>(&mut self, path: P, id: &str, title: S) {
+        let path = path.into();
+        let name = if path.file_name() == Some(OsStr::new(DIR_ROOT_NOTE_NAME)) {
+            to_link(path.parent().unwrap_or_else(|| Path::new("")))
+        } else {
+            to_link(&path.with_extension(""))
+        };
+        self.notes.push(NoteRef { path, name, id: id.to_lowercase(), title: title.into().to_lowercase() });
+    }
+
+    /// The path of the note `note_name`
+    pub fn resolve(&self, note_name: &str) -> Result<PathBuf, NottoError> {
+        self.resolve_with(note_name, true)
+    }
+
+    /// The path of the note `note_name` by its path, its id or the start of it, never by the end of its path or its title
+    ///
+    /// For the commands changing a note, a name close to another note's mustn't pick it
+    pub fn resolve_exactly(&self, note_name: &str) -> Result<PathBuf, NottoError> {
+        self.resolve_with(note_name, false)
+    }
+
+    fn resolve_with(&self, note_name: &str, fuzzy: bool) -> Result<PathBuf, NottoError> {
+        let name = note_name.trim().trim_matches('/');
+        // Every title contains an empty name
+        if name.is_empty() {
+            return Err(NottoError::NoteNotFound { note: note_name.to_string() });
+        }
+        let lowercase = name.to_lowercase();
+        let without_extension = name.strip_suffix(&format!(".{}", NOTE_EXTENSION)).unwrap_or(name);
+        let suffix = format!("/{}", without_extension);
+        let is_id_prefix = lowercase.len() >= MIN_ID_PREFIX && lowercase.chars().all(|c| c.is_ascii_hexdigit());
+
+        let ways: [&dyn Fn(&NoteRef) -> bool; 6] = [
+            &|note| note.name == without_extension || to_link(&note.path) == name,
+            &|note| note.id == lowercase,
+            &|note| is_id_prefix && note.id.starts_with(&lowercase),
+            &|note| note.name.ends_with(&suffix),
+            &|note| note.title == lowercase,
+            &|note| note.title.contains(&lowercase),
+        ];
+        // The first three find a note exactly
+        let ways = if fuzzy { &ways[..] } else { &ways[..3] };
+        for found in ways.iter() {
+            if let Some(path) = self.one_of(note_name, found)? {
+                return Ok(path);
+            }
+        }
+        if !fuzzy {
+            return Err(NottoError::NoteNotFound { note: note_name.to_string() });
+        }
+
+        // The letters of the name in the same order in the title, like `wkpln` for `Weekly plan`
+        let letters = lowercase.chars().filter(|c| !c.is_whitespace()).collect::<Vec<_>>();
+        match self.one_of(note_name, |note| !letters.is_empty() && is_subsequence(&letters, &note.title))? {
+            Some(path) => Ok(path),
+            None => Err(NottoError::NoteNotFound { note: note_name.to_string() })
+        }
+    }
+
+    /// The note matching, `None` if none does, an error if several do
+    fn one_of<F>(&self, note_name: &str, matches: F) -> Result<Option<PathBuf>, NottoError> where F: Fn(&NoteRef) -> bool {
+        let found = self.notes.iter().filter(|note| matches(note)).collect::<Vec<_>>();
+        match found.as_slice() {
+            [] => Ok(None),
+            [note] => Ok(Some(note.path.clone())),
+            notes => {
+                let mut candidates = notes.iter().map(|note| to_link(&note.path)).collect::<Vec<_>>();
+                candidates.sort();
+                Err(NottoError::AmbiguousNote { note: note_name.to_string(), candidates })
+            }
+        }
+    }
+}
+
+fn is_subsequence(letters: &[char], text: &str) -> bool {
+    let mut letters = letters.iter().peekable();
+    for c in text.chars() {
+        if letters.peek() == Some(&&c) {
+            letters.next();
+        }
+    }
+
+    letters.peek().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::errors::NottoError;
+
+    use super::NoteResolver;
+
+    /// Two plans, one in `work` and one in `home`, the projects of `work` and the errands of `home`
+    fn test_resolver() -> NoteResolver {
+        let mut resolver = NoteResolver::new();
+        resolver.add("work/plan.md", "8f14e45fceea167a5a36dedd4bea2543", "Weekly plan");
+        resolver.add("work/projects/index.md", "c9f0f895fb98ab9159f51fd0297e236d", "Projects");
+        resolver.add("home/plan.md", "c9f0f895aaaaaaaaaaaaaaaaaaaaaaaa", "Garden plan");
+        resolver.add("home/todo.md", "45c48cce2e2d7fbdea1afc51c7c6ad26", "Errands");
+
+        resolver
+    }
+
+    #[test]
+    fn resolves_the_paths() -> Result<(), NottoError> {
+        let resolver = test_resolver();
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve("work/plan")?);
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve("work/plan.md")?);
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve(" /work/plan/ ")?);
+        assert_eq!(PathBuf::from("work/projects/index.md"), resolver.resolve("work/projects")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_the_ids() -> Result<(), NottoError> {
+        let resolver = test_resolver();
+        assert_eq!(PathBuf::from("home/todo.md"), resolver.resolve("45c48cce2e2d7fbdea1afc51c7c6ad26")?);
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve("8F14")?);
+        // Too short for an id, and nothing else matches
+        assert!(matches!(resolver.resolve("8f1"), Err(NottoError::NoteNotFound { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_the_ends_of_the_paths() -> Result<(), NottoError> {
+        let resolver = test_resolver();
+        assert_eq!(PathBuf::from("home/todo.md"), resolver.resolve("todo")?);
+        assert_eq!(PathBuf::from("work/projects/index.md"), resolver.resolve("projects")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_the_titles() -> Result<(), NottoError> {
+        let resolver = test_resolver();
+        assert_eq!(PathBuf::from("home/todo.md"), resolver.resolve("errands")?);
+        assert_eq!(PathBuf::from("home/plan.md"), resolver.resolve("garden")?);
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve("wkly pln")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_exactly_the_paths_and_ids() -> Result<(), NottoError> {
+        let resolver = test_resolver();
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve_exactly("work/plan")?);
+        assert_eq!(PathBuf::from("home/todo.md"), resolver.resolve_exactly("45c48cce2e2d7fbdea1afc51c7c6ad26")?);
+        assert_eq!(PathBuf::from("work/plan.md"), resolver.resolve_exactly("8f14")?);
+        assert!(matches!(resolver.resolve_exactly("c9f0f895"), Err(NottoError::AmbiguousNote { .. })));
+        for name in &["todo", "errands", "garden", "wkly pln", "err"] {
+            assert!(matches!(resolver.resolve_exactly(name), Err(NottoError::NoteNotFound { .. })), "{}", name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_ambiguous_notes() {
+        let resolver = test_resolver();
+        assert!(matches!(
+            resolver.resolve("c9f0f895"),
+            Err(NottoError::AmbiguousNote { candidates, .. }) if candidates == vec!["home/plan.md", "work/projects/index.md"]
+        ));
+        assert!(matches!(
+            resolver.resolve("plan"),
+            Err(NottoError::AmbiguousNote { candidates, .. }) if candidates == vec!["home/plan.md", "work/plan.md"]
+        ));
+    }
+
+    #[test]
+    fn only_resolves_the_known_notes() {
+        let resolver = test_resolver();
+        assert!(matches!(resolver.resolve("nothing like it"), Err(NottoError::NoteNotFound { .. })));
+        assert!(matches!(resolver.resolve("../work/plan"), Err(NottoError::NoteNotFound { .. })));
+        assert!(matches!(resolver.resolve(""), Err(NottoError::NoteNotFound { .. })));
+        assert!(matches!(NoteResolver::new().resolve("plan"), Err(NottoError::NoteNotFound { .. })));
+    }
+}
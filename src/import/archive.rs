@@ -0,0 +1,255 @@
+use std::{collections::HashSet, ffi::OsStr, fs::File, io::Read, path::Path};
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+
+use crate::{errors::NottoError, export::archive::{ARCHIVE_FORMAT, ARCHIVE_VERSION, ArchiveManifest, MANIFEST_FILE, NOTES_DIR}, io::{ReaderWriter, links::{normalize, to_link}}};
+
+use super::{ImportReport, write_new_file};
+
+const NOTE_EXTENSION: &str = "md";
+
+/// Restores an archive made by `notto export archive`
+///
+/// The files are written as they are in the archive, the notes encrypted in an encrypted context.
+/// The ones that already exist are left untouched
+pub struct ArchiveImporter {
+    writer: ReaderWriter
+}
+
+impl ArchiveImporter {
+    /// Restores the files with `writer`, the restored notes go through its encryption, hooks and git like the new notes
+    pub fn new(writer: ReaderWriter) -> Self { Self { writer } }
+
+    /// Restores the notes of the archive under the `into` path of the notes directory
+    pub fn import<P, Q>(&self, archive_path: P, into: Q) -> Result<ImportReport, NottoError> where P: AsRef<Path>, Q: AsRef<Path> {
+        let archive_path = archive_path.as_ref();
+        let not_an_archive = |message: String| NottoError::FileError { message: format!("`{}` is not a notto archive, {}", archive_path.to_string_lossy(), message) };
+
+        let mut archive = Archive::new(GzDecoder::new(File::open(archive_path)?));
+        let mut entries = archive.entries().map_err(|e| not_an_archive(e.to_string()))?;
+
+        // The manifest is always the first entry
+        let manifest: ArchiveManifest = match entries.next() {
+            Some(Ok(mut entry)) if entry.path().map(|path| path == Path::new(MANIFEST_FILE)).unwrap_or(false) => {
+                let mut manifest = String::new();
+                entry.read_to_string(&mut manifest).map_err(|e| not_an_archive(e.to_string()))?;
+                serde_json::from_str(&manifest).map_err(|e| not_an_archive(format!("invalid manifest: {}", e)))?
+            }
+            Some(Err(e)) => return Err(not_an_archive(e.to_string())),
+            _ => return Err(not_an_archive("the manifest is missing".to_string())),
+        };
+        if manifest.format != ARCHIVE_FORMAT || manifest.version > ARCHIVE_VERSION {
+            return Err(not_an_archive(format!("unsupported format `{}` version {}", manifest.format, manifest.version)));
+        }
+
+        let writer = &self.writer;
+        let mut report = ImportReport::default();
+        let mut restored = HashSet::new();
+        for entry in entries {
+            let mut entry = entry.map_err(|e| not_an_archive(e.to_string()))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let entry_path = entry.path()?.to_path_buf();
+            let source = match entry_path.strip_prefix(NOTES_DIR).ok().and_then(normalize) {
+                Some(source) => source,
+                None => {
+                    report.add_issue(&entry_path, "not a file of the notes, skipped");
+                    continue;
+                }
+            };
+
+            let mut contents = vec![];
+            entry.read_to_end(&mut contents)?;
+            restored.insert(to_link(&source));
+            let dest = into.as_ref().join(&source);
+            let is_note = source.extension() == Some(OsStr::new(NOTE_EXTENSION));
+
+            // A note may exist as a file or as a directory with an `index.md`
+            let existing_note = if is_note {
+                let parent = dest.parent().unwrap_or_else(|| Path::new(""));
+                dest.file_name().and_then(|file_name| writer.note_file_exists(parent, file_name.to_string_lossy()))
+            } else {
+                None
+            };
+            if existing_note.is_some() {
+                report.add_issue(&source, format!("a note already exists at `{}`, not restored", dest.to_string_lossy()));
+            } else if is_note {
+                match writer.save_note_text_at(&String::from_utf8_lossy(&contents), &dest) {
+                    Ok(path) => report.notes.push(path),
+                    Err(e) => report.add_issue(&source, format!("can't restore the note: {}", e)),
+                }
+            } else if write_new_file(writer, &contents, &dest, &source, &mut report)? {
+                report.attachments += 1;
+            }
+        }
+
+        for file in manifest.files.iter().filter(|file| !restored.contains(&file.path)) {
+            report.add_issue(&file.path, "listed in the manifest but missing in the archive");
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{self, File}, path::{Path, PathBuf}};
+
+    use flate2::{Compression, write::GzEncoder};
+    use tar::{Builder, Header};
+
+    use crate::{errors::NottoError, export::archive::ArchiveExporter, io::{ReaderWriter, hooks::{HookRunner, Hooks}}, test_utils::test_dir};
+
+    use super::ArchiveImporter;
+
+    const PLAN: &str = "---\nid: abc\ntitle: Plan\ndate: 2021-03-04\ntime: \"10:11:12\"\n---\nStep 1\n";
+
+    /// The archive of a notes directory with two notes and an image, and the empty directory to restore it
+    fn test_archive(name: &str) -> Result<(PathBuf, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let notes_path = base_path.join("notes");
+        let restore_path = base_path.join("restore");
+        fs::create_dir_all(notes_path.join("projects"))?;
+        fs::create_dir_all(&restore_path)?;
+
+        fs::write(notes_path.join("projects").join("plan.md"), PLAN)?;
+        fs::write(notes_path.join("projects").join("image.png"), [0u8, 1, 2])?;
+        fs::write(notes_path.join("todo.md"), "Buy milk")?;
+        let archive_path = base_path.join("backup.tar.gz");
+        let summary = ArchiveExporter::new(ReaderWriter::new(notes_path)).export(&archive_path, "work")?;
+        assert_eq!(2, summary.exported);
+
+        Ok((archive_path, restore_path))
+    }
+
+    /// Writes an archive with the files as they're given, even the paths that `tar` refuses to add
+    fn write_archive(archive_path: &Path, files: &[(&str, &[u8])]) -> Result<(), NottoError> {
+        let mut builder = Builder::new(GzEncoder::new(File::create(archive_path)?, Compression::default()));
+        for (path, contents) in files {
+            let mut header = Header::new_old();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *contents)?;
+        }
+        builder.into_inner()?.finish()?;
+
+        Ok(())
+    }
+
+    fn manifest(files: &str) -> String {
+        format!(r#"{{"format": "notto-archive", "version": 1, "created": "2021-03-04T10:11:12+00:00", "context": "work", "files": [{}]}}"#, files)
+    }
+
+    #[test]
+    fn restores_the_notes_and_attachments() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_restore")?;
+
+        let report = ArchiveImporter::new(ReaderWriter::new(restore_path.clone())).import(&archive_path, "old")?;
+        assert_eq!(2, report.notes.len());
+        assert_eq!(1, report.attachments);
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!(PLAN, fs::read_to_string(restore_path.join("old/projects/plan.md"))?);
+        assert!(restore_path.join("old/projects/image.png").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn runs_the_pre_create_hook() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_pre_create")?;
+        let pre_create = Some("sh -c 'test \"$NOTTO_NOTE_PATH\" != todo.md'".to_string());
+        let writer = ReaderWriter::new(restore_path.clone()).with_hooks(HookRunner::new("work", Hooks { pre_create, ..Hooks::default() }));
+
+        let report = ArchiveImporter::new(writer).import(&archive_path, "")?;
+        assert_eq!(vec![PathBuf::from("projects/plan.md")], report.notes);
+        assert_eq!(1, report.issues.len());
+        assert!(report.issues[0].message.contains("pre_create"), "{}", report.issues[0]);
+        assert!(!restore_path.join("todo.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_existing_notes() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_existing")?;
+        fs::write(restore_path.join("todo.md"), "Already here")?;
+
+        let report = ArchiveImporter::new(ReaderWriter::new(restore_path.clone())).import(&archive_path, "")?;
+        assert_eq!(vec![PathBuf::from("projects/plan.md")], report.notes);
+        assert_eq!(1, report.issues.len());
+        assert!(report.issues[0].message.starts_with("a note already exists"));
+        assert_eq!("Already here", fs::read_to_string(restore_path.join("todo.md"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn never_overwrites_an_archive() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_overwrite")?;
+
+        assert!(ArchiveExporter::new(ReaderWriter::new(restore_path)).export(&archive_path, "work").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_what_isnt_an_archive() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_invalid")?;
+        let importer = ArchiveImporter::new(ReaderWriter::new(restore_path.clone()));
+        let expect_invalid = |expected: &str| match importer.import(&archive_path, "") {
+            Err(NottoError::FileError { message }) => assert!(message.contains("is not a notto archive") && message.contains(expected), "{}", message),
+            result => panic!("expected an invalid archive, got {:?}", result),
+        };
+
+        fs::write(&archive_path, "# Not even gzip")?;
+        expect_invalid("");
+        write_archive(&archive_path, &[("notes/todo.md", b"Buy milk")])?;
+        expect_invalid("the manifest is missing");
+        write_archive(&archive_path, &[("manifest.json", b"{\"format\": ")])?;
+        expect_invalid("invalid manifest");
+        write_archive(&archive_path, &[("manifest.json", manifest("").replace("\"version\": 1", "\"version\": 99").as_bytes())])?;
+        expect_invalid("unsupported format `notto-archive` version 99");
+        assert!(fs::read_dir(&restore_path)?.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_the_files_out_of_the_notes() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_escape")?;
+        write_archive(&archive_path, &[
+            ("manifest.json", manifest("").as_bytes()),
+            ("notes/../../escape.md", b"Out"),
+            ("/etc/escape.md", b"Out"),
+            ("other/todo.md", b"Out"),
+            ("notes/todo.md", b"Buy milk"),
+        ])?;
+
+        let report = ArchiveImporter::new(ReaderWriter::new(restore_path.clone())).import(&archive_path, "")?;
+        assert_eq!(vec![PathBuf::from("todo.md")], report.notes);
+        assert_eq!(3, report.issues.iter().filter(|issue| issue.message == "not a file of the notes, skipped").count(), "{:?}", report.issues);
+        assert!(!restore_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_files_missing_from_the_archive() -> Result<(), NottoError> {
+        let (archive_path, restore_path) = test_archive("archive_missing")?;
+        write_archive(&archive_path, &[
+            ("manifest.json", manifest(r#"{"path": "todo.md", "size": 8, "id": null}, {"path": "gone.md", "size": 3, "id": null}"#).as_bytes()),
+            ("notes/todo.md", b"Buy milk"),
+        ])?;
+
+        let report = ArchiveImporter::new(ReaderWriter::new(restore_path)).import(&archive_path, "")?;
+        assert_eq!(1, report.notes.len());
+        assert_eq!(1, report.issues.len());
+        assert_eq!("gone.md: listed in the manifest but missing in the archive", report.issues[0].to_string());
+
+        Ok(())
+    }
+}
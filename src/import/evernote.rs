@@ -0,0 +1,653 @@
+use std::{borrow::Cow, collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use quick_xml::{Reader, events::{BytesStart, BytesText, Event}};
+
+use crate::{errors::NottoError, io::{ReaderWriter, links::{percent_encode, relative_link}}, models::{front_matter::FrontMatter, note::Note}};
+
+use super::{ImportReport, save_attachment, save_note, unique_path};
+
+const RESOURCES_DIR: &str = "resources";
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Imports an Evernote `.enex` export
+///
+/// The notes go into a directory named after the file, as every export is a notebook.
+/// The ENML content is converted to markdown and the resources are saved as attachments
+pub struct EvernoteImporter {
+    writer: ReaderWriter
+}
+
+#[derive(Debug, Default)]
+struct EnexNote {
+    title: String,
+    content: String,
+    created: Option<NaiveDateTime>,
+    tags: Vec<String>,
+    resources: Vec<EnexResource>,
+}
+
+#[derive(Debug, Default)]
+struct EnexResource {
+    data: String,
+    mime: String,
+    file_name: String,
+}
+
+impl EvernoteImporter {
+    /// Saves the notes with `writer`, running the hooks of the context and encrypting the notes like the ones created in it
+    pub fn new(writer: ReaderWriter) -> Self { Self { writer } }
+
+    /// Imports the notes of the `.enex` file under the `into` path of the notes directory
+    ///
+    /// Existing notes are never overwritten
+    pub fn import<P, Q>(&self, enex_file: P, into: Q) -> Result<ImportReport, NottoError> where P: AsRef<Path>, Q: AsRef<Path> {
+        let enex_file = enex_file.as_ref();
+        let text = fs::read_to_string(enex_file)?;
+        let notes = parse_enex(&text)?;
+        let source = PathBuf::from(enex_file.file_name().unwrap_or_default());
+
+        let notebook = ReaderWriter::normalize_file_name(enex_file.file_stem().unwrap_or_default().to_string_lossy());
+        let notebook_dir = into.as_ref().join(notebook);
+        let writer = &self.writer;
+        let mut report = ImportReport::default();
+        let mut taken = HashSet::new();
+
+        for note in notes {
+            let note_source = source.join(&note.title);
+            let mut name = ReaderWriter::normalize_file_name(&note.title);
+            let mut front_matter = FrontMatter { title: Some(note.title.clone()), tags: note.tags.clone(), ..FrontMatter::default() };
+            if name.is_empty() {
+                name = front_matter.id.clone();
+            }
+            if let Some(created) = note.created {
+                front_matter.date = created.date();
+                front_matter.time = created.time();
+            }
+            let dest = notebook_dir.join(format!("{}.md", name));
+            let dest = if taken.contains(&dest) {
+                let unique = unique_path(&mut taken, dest.clone());
+                report.add_issue(&note_source, format!("`{}` was already taken, imported as `{}`", dest.to_string_lossy(), unique.to_string_lossy()));
+                unique
+            } else {
+                taken.insert(dest.clone());
+                dest
+            };
+
+            // The resources are referenced in the content by the MD5 hash of their data
+            let mut resources = HashMap::new();
+            for resource in &note.resources {
+                let data = resource.data.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+                let contents = match base64::decode(&data) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        report.add_issue(&note_source, format!("can't decode the resource `{}`: {}", resource.file_name, e));
+                        continue;
+                    }
+                };
+                let hash = format!("{:x}", md5::compute(&contents));
+                let file_name = resource_file_name(resource, &hash);
+                let resource_dest = unique_path(&mut taken, notebook_dir.join(RESOURCES_DIR).join(&file_name));
+                save_attachment(writer, &contents, &resource_dest, &note_source, &mut report)?;
+                resources.insert(hash, (file_name, resource.mime.clone(), relative_link(&dest, &resource_dest)));
+            }
+
+            let content = EnmlConverter::new(&resources).convert(&note.content, &note_source, &mut report);
+            save_note(writer, Note::new(front_matter, content), &dest, &note_source, &mut report);
+        }
+
+        Ok(report)
+    }
+}
+
+/// The file name of a resource, from its original name or its hash and its type
+fn resource_file_name(resource: &EnexResource, hash: &str) -> String {
+    let original = Path::new(&resource.file_name);
+    let stem = ReaderWriter::normalize_file_name(original.file_stem().unwrap_or_default().to_string_lossy());
+    let stem = if stem.is_empty() { hash.to_string() } else { stem };
+    let extension = original.extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .or_else(|| resource.mime.split('/').nth(1).map(|subtype| subtype.split('+').next().unwrap_or(subtype).to_string()));
+
+    match extension {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem
+    }
+}
+
+fn parse_enex(text: &str) -> Result<Vec<EnexNote>, NottoError> {
+    let mut reader = Reader::from_str(text);
+    let mut notes = vec![];
+    let mut note: Option<EnexNote> = None;
+    let mut elements: Vec<String> = vec![];
+
+    loop {
+        let event = reader.read_event().map_err(|e| enex_error(&reader, e))?;
+        match event {
+            Event::Start(start) => {
+                let name = element_name(&start);
+                match name.as_str() {
+                    "note" => note = Some(EnexNote::default()),
+                    "resource" => if let Some(note) = note.as_mut() { note.resources.push(EnexResource::default()) },
+                    _ => {}
+                }
+                elements.push(name);
+            }
+            Event::End(_) if elements.pop().as_deref() == Some("note") => notes.extend(note.take()),
+            Event::Text(text) => {
+                let text = text.unescape().map_err(|e| enex_error(&reader, e))?;
+                add_note_text(note.as_mut(), &elements, &text);
+            }
+            Event::CData(data) => {
+                let text = String::from_utf8_lossy(&data.into_inner()).to_string();
+                add_note_text(note.as_mut(), &elements, &text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(notes)
+}
+
+fn enex_error(reader: &Reader<&[u8]>, error: quick_xml::Error) -> NottoError {
+    NottoError::FileError { message: format!("invalid enex file at position {}: {}", reader.buffer_position(), error) }
+}
+
+fn add_note_text(note: Option<&mut EnexNote>, elements: &[String], text: &str) {
+    let note = match note {
+        Some(note) => note,
+        None => return
+    };
+    let element = elements.last().map(String::as_str).unwrap_or_default();
+    let in_resource = elements.iter().any(|element| element == "resource");
+
+    match (element, in_resource) {
+        ("title", false) => note.title.push_str(text.trim()),
+        ("content", false) => note.content.push_str(text),
+        ("created", false) => note.created = NaiveDateTime::parse_from_str(text.trim(), DATE_FORMAT).ok()
+            .map(|created| Local.from_utc_datetime(&created).naive_local()),
+        ("tag", false) => note.tags.push(text.trim().to_string()),
+        ("data", true) => if let Some(resource) = note.resources.last_mut() { resource.data.push_str(text) },
+        ("mime", true) => if let Some(resource) = note.resources.last_mut() { resource.mime.push_str(text.trim()) },
+        ("file-name", true) => if let Some(resource) = note.resources.last_mut() { resource.file_name.push_str(text.trim()) },
+        _ => {}
+    }
+}
+
+fn element_name(start: &BytesStart) -> String {
+    String::from_utf8_lossy(start.local_name().as_ref()).to_lowercase()
+}
+
+fn attribute(start: &BytesStart, name: &str) -> Option<String> {
+    start.try_get_attribute(name).ok().flatten().and_then(|attribute| attribute.unescape_value().ok().map(Cow::into_owned))
+}
+
+/// HTML entities that can show up in ENML besides the XML ones
+fn html_entity(entity: &str) -> Option<&'static str> {
+    match entity {
+        "nbsp" | "ensp" | "emsp" | "thinsp" => Some(" "),
+        "ndash" => Some("–"),
+        "mdash" => Some("—"),
+        "hellip" => Some("…"),
+        "lsquo" | "rsquo" => Some("'"),
+        "ldquo" | "rdquo" => Some("\""),
+        "laquo" => Some("«"),
+        "raquo" => Some("»"),
+        "copy" => Some("©"),
+        "reg" => Some("®"),
+        "trade" => Some("™"),
+        "euro" => Some("€"),
+        "bull" | "middot" => Some("·"),
+        _ => None
+    }
+}
+
+fn unescape_text(text: &BytesText) -> String {
+    match text.unescape_with(html_entity) {
+        Ok(text) => text.into_owned(),
+        Err(_) => String::from_utf8_lossy(text).to_string()
+    }
+}
+
+/// Converts the ENML of a note, an XHTML subset, to markdown
+struct EnmlConverter<'a> {
+    /// File name, type and link of the resources by their hash
+    resources: &'a HashMap<String, (String, String, String)>,
+    lines: Vec<String>,
+    line: String,
+    /// Ordered flag and item count of the open lists
+    lists: Vec<(bool, usize)>,
+    /// Bullet to add before the text of a list item
+    marker: Option<String>,
+    line_has_marker: bool,
+    quote_depth: usize,
+    links: Vec<Option<String>>,
+    /// Whether every open `div` is a code block
+    divs: Vec<bool>,
+    in_code: bool,
+    table: Option<Vec<Vec<String>>>,
+    skip_depth: usize,
+}
+
+impl<'a> EnmlConverter<'a> {
+    fn new(resources: &'a HashMap<String, (String, String, String)>) -> Self {
+        Self {
+            resources,
+            lines: vec![],
+            line: String::new(),
+            lists: vec![],
+            marker: None,
+            line_has_marker: false,
+            quote_depth: 0,
+            links: vec![],
+            divs: vec![],
+            in_code: false,
+            table: None,
+            skip_depth: 0,
+        }
+    }
+
+    fn convert(mut self, enml: &str, source: &Path, report: &mut ImportReport) -> String {
+        let mut reader = Reader::from_str(enml);
+        reader.check_end_names(false);
+
+        loop {
+            let event = match reader.read_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    report.add_issue(source, format!("the content is not valid, converted up to position {}: {}", reader.buffer_position(), e));
+                    break;
+                }
+            };
+            // The content of the skipped elements is dropped
+            if self.skip_depth > 0 {
+                match event {
+                    Event::Start(_) => self.skip_depth += 1,
+                    Event::End(_) => self.skip_depth -= 1,
+                    Event::Eof => break,
+                    _ => {}
+                }
+                continue;
+            }
+            match event {
+                Event::Start(start) => self.start(&start, source, report),
+                Event::Empty(start) => {
+                    self.start(&start, source, report);
+                    self.end(&element_name(&start));
+                }
+                Event::End(end) => self.end(&String::from_utf8_lossy(end.local_name().as_ref()).to_lowercase()),
+                Event::Text(text) => self.text(&unescape_text(&text)),
+                Event::CData(data) => self.text(&String::from_utf8_lossy(&data.into_inner())),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        self.end_line();
+
+        while self.lines.last().map(|line| line.is_empty()).unwrap_or(false) {
+            self.lines.pop();
+        }
+        self.lines.join("\n")
+    }
+
+    fn start(&mut self, start: &BytesStart, source: &Path, report: &mut ImportReport) {
+        let name = element_name(start);
+        match name.as_str() {
+            "div" => {
+                let code_block = attribute(start, "style").map(|style| style.replace(' ', "").contains("-en-codeblock:true")).unwrap_or(false);
+                self.end_div_line();
+                if code_block && !self.in_code {
+                    self.lines.push(format!("{}```", self.prefix()));
+                    self.in_code = true;
+                    self.divs.push(true);
+                } else {
+                    self.divs.push(false);
+                }
+            }
+            "p" | "blockquote" | "pre" | "hr" => {
+                self.end_block();
+                match name.as_str() {
+                    "blockquote" => self.quote_depth += 1,
+                    "pre" => {
+                        self.lines.push(format!("{}```", self.prefix()));
+                        self.in_code = true;
+                    }
+                    "hr" => self.lines.push("---".to_string()),
+                    _ => {}
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.end_block();
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                self.push_text(&format!("{} ", "#".repeat(level)));
+            }
+            "br" => {
+                if self.in_code || !self.line.is_empty() {
+                    self.end_line();
+                } else if self.lines.last().map(|line| !line.is_empty()).unwrap_or(false) {
+                    // An empty line in Evernote is a `div` with only a `br`
+                    self.lines.push(String::new());
+                }
+            }
+            "b" | "strong" => self.push_text("**"),
+            "i" | "em" => self.push_text("*"),
+            "s" | "strike" | "del" => self.push_text("~~"),
+            "code" if !self.in_code => self.push_text("`"),
+            "a" => {
+                let href = attribute(start, "href");
+                if href.is_some() {
+                    self.push_text("[");
+                }
+                self.links.push(href);
+            }
+            "ul" | "ol" => {
+                self.end_line();
+                self.lists.push((name == "ol", 0));
+            }
+            "li" => {
+                self.end_line();
+                let indent = "    ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some((true, count)) => {
+                        *count += 1;
+                        format!("{}{}. ", indent, count)
+                    }
+                    _ => format!("{}- ", indent)
+                };
+                self.marker = Some(marker);
+            }
+            "en-todo" => {
+                let checked = attribute(start, "checked").map(|checked| checked == "true").unwrap_or(false);
+                let checkbox = if checked { "[x] " } else { "[ ] " };
+                if self.line.is_empty() && self.marker.is_none() {
+                    self.marker = Some("- ".to_string());
+                }
+                self.push_text(checkbox);
+            }
+            "en-media" => {
+                let hash = attribute(start, "hash").unwrap_or_default().to_lowercase();
+                match self.resources.get(&hash) {
+                    Some((file_name, mime, link)) => {
+                        let image = if mime.starts_with("image/") { "!" } else { "" };
+                        let media = format!("{}[{}]({})", image, file_name, percent_encode(link));
+                        self.push_text(&media);
+                    }
+                    None => report.add_issue(source, format!("resource `{}` not found in the note, dropped", hash)),
+                }
+            }
+            "img" => {
+                if let Some(src) = attribute(start, "src") {
+                    let alt = attribute(start, "alt").unwrap_or_default();
+                    self.push_text(&format!("![{}]({})", alt, src));
+                }
+            }
+            "table" => {
+                self.end_block();
+                if self.table.is_none() {
+                    self.table = Some(vec![]);
+                }
+            }
+            "tr" => if let Some(table) = self.table.as_mut() { table.push(vec![]) },
+            "td" | "th" => if let Some(row) = self.table.as_mut().and_then(|table| table.last_mut()) { row.push(String::new()) },
+            "en-crypt" => {
+                report.add_issue(source, "encrypted content can't be imported, dropped");
+                // Empty elements don't have an end
+                if !start.is_empty() {
+                    self.skip_depth = 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "div" => {
+                self.end_div_line();
+                if self.divs.pop() == Some(true) {
+                    self.in_code = false;
+                    self.lines.push(format!("{}```", self.prefix()));
+                    self.end_block();
+                }
+            }
+            "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => self.end_block(),
+            "blockquote" => {
+                self.end_line();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.end_block();
+            }
+            "pre" => {
+                self.end_line();
+                self.in_code = false;
+                self.lines.push(format!("{}```", self.prefix()));
+                self.end_block();
+            }
+            "b" | "strong" => self.push_text("**"),
+            "i" | "em" => self.push_text("*"),
+            "s" | "strike" | "del" => self.push_text("~~"),
+            "code" if !self.in_code => self.push_text("`"),
+            "a" => if let Some(Some(href)) = self.links.pop() {
+                self.push_text(&format!("]({})", href));
+            },
+            "ul" | "ol" => {
+                self.end_line();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.end_block();
+                }
+            }
+            "li" => {
+                self.end_line();
+                self.marker = None;
+            }
+            "table" => {
+                if let Some(table) = self.table.take() {
+                    self.add_table(table);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.in_code {
+            let mut parts = text.split('\n');
+            if let Some(first) = parts.next() {
+                self.push_text(first);
+            }
+            for part in parts {
+                self.end_line();
+                self.push_text(part);
+            }
+            return;
+        }
+
+        // Whitespace is collapsed like in HTML
+        let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.starts_with(char::is_whitespace) && !collapsed.is_empty() {
+            collapsed.insert(0, ' ');
+        }
+        if text.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+        if self.line.is_empty() && self.marker.is_none() {
+            collapsed = collapsed.trim_start().to_string();
+        }
+        if !collapsed.is_empty() {
+            self.push_text(&collapsed);
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if let Some(row) = self.table.as_mut().and_then(|table| table.last_mut()) {
+            if let Some(cell) = row.last_mut() {
+                cell.push_str(&text.replace('|', "\\|"));
+            }
+            return;
+        }
+        if self.line.is_empty() {
+            if let Some(marker) = self.marker.take() {
+                self.line.push_str(&marker);
+                self.line_has_marker = true;
+            }
+        }
+        self.line.push_str(text);
+    }
+
+    /// The quote marks and the indentation of the lines inside list items
+    fn prefix(&self) -> String {
+        format!("{}{}", "> ".repeat(self.quote_depth), "    ".repeat(self.lists.len()))
+    }
+
+    fn end_line(&mut self) {
+        if self.in_code || !self.line.trim().is_empty() {
+            let line = std::mem::take(&mut self.line);
+            let line = if self.in_code { line } else { line.trim_end().to_string() };
+            // The bullet of the list items has its own indentation
+            let prefix = if self.line_has_marker { "> ".repeat(self.quote_depth) } else { self.prefix() };
+            self.lines.push(format!("{}{}", prefix, line));
+        }
+        self.line.clear();
+        self.line_has_marker = false;
+    }
+
+    /// Every line of a code block is a `div`, the empty lines are `br`
+    fn end_div_line(&mut self) {
+        if !self.in_code || !self.line.is_empty() {
+            self.end_line();
+        }
+    }
+
+    /// Ends the current line and adds an empty one, if there's not one already
+    fn end_block(&mut self) {
+        self.end_line();
+        if self.lists.is_empty() && self.lines.last().map(|line| !line.is_empty()).unwrap_or(false) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn add_table(&mut self, table: Vec<Vec<String>>) {
+        let columns = table.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        for (index, row) in table.iter().enumerate() {
+            let cells = (0..columns).map(|column| row.get(column).map(|cell| cell.trim()).unwrap_or_default()).collect::<Vec<_>>();
+            self.lines.push(format!("| {} |", cells.join(" | ")));
+            if index == 0 {
+                self.lines.push(format!("|{}", " --- |".repeat(columns)));
+            }
+        }
+        self.lines.push(String::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, io::ReaderWriter, test_utils::test_dir};
+
+    use super::EvernoteImporter;
+
+    /// A note with a todo list, a link, a table, a code block and a resource, its data is `hello` whose MD5 hash is 5d41402abc4b2a76b9719d911017c592
+    const ENEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export3.dtd">
+<en-export application="Evernote" version="10">
+  <note>
+    <title>Trip &amp; plans</title>
+    <created>20210304T101112Z</created>
+    <tag>travel</tag>
+    <tag>todo</tag>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><h1>Packing</h1><div><en-todo checked="true"/>Passport</div><div><en-todo/>Tickets&nbsp;<b>now</b></div><div><br/></div>
+<ul><li>See <a href="https://example.com">the map</a></li><li><div>Pay</div></li></ul>
+<div>Attached: <en-media hash="5d41402abc4b2a76b9719d911017c592" type="text/plain"/></div>
+<table><tr><th>Day</th><th>City</th></tr><tr><td>1</td><td>Rome</td></tr></table>
+<div style="-en-codeblock: true;"><div>let a = 1;</div><div>let b = 2;</div></div></en-note>]]></content>
+    <resource>
+      <data encoding="base64">aGVs
+bG8=</data>
+      <mime>text/plain</mime>
+      <resource-attributes><file-name>greeting.txt</file-name></resource-attributes>
+    </resource>
+  </note>
+</en-export>"#;
+
+    /// The enex file `My Trips.enex` with `enex`, and the empty notes directory to import it
+    fn test_enex(name: &str, enex: &str) -> Result<(PathBuf, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let notes_path = base_path.join("notes");
+        fs::create_dir_all(&notes_path)?;
+        let enex_path = base_path.join("My Trips.enex");
+        fs::write(&enex_path, enex)?;
+
+        Ok((enex_path, notes_path))
+    }
+
+    #[test]
+    fn converts_the_enml_to_markdown() -> Result<(), NottoError> {
+        let (enex_path, notes_path) = test_enex("import_evernote_enml", ENEX)?;
+
+        let report = EvernoteImporter::new(ReaderWriter::new(notes_path.clone())).import(&enex_path, "")?;
+        assert_eq!(1, report.notes.len());
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        let note = ReaderWriter::new(notes_path).read_note("MyTrips/Trip&plans.md")?;
+        assert_eq!(
+            "# Packing\n\n- [x] Passport\n- [ ] Tickets **now**\n\n- See [the map](https://example.com)\n- Pay\n\nAttached: [greeting.txt](resources/greeting.txt)\n\n| Day | City |\n| --- | --- |\n| 1 | Rome |\n\n```\nlet a = 1;\nlet b = 2;\n```",
+            note.content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_title_tags_and_date() -> Result<(), NottoError> {
+        let (enex_path, notes_path) = test_enex("import_evernote_front_matter", ENEX)?;
+
+        EvernoteImporter::new(ReaderWriter::new(notes_path.clone())).import(&enex_path, "")?;
+        let note = ReaderWriter::new(notes_path).read_note("MyTrips/Trip&plans.md")?;
+        assert_eq!(Some("Trip & plans".to_string()), note.front_matter.title);
+        assert_eq!(vec!["travel".to_string(), "todo".to_string()], note.front_matter.tags);
+        assert_eq!("2021-03-04", note.front_matter.date.format("%Y-%m-%d").to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn saves_the_resources() -> Result<(), NottoError> {
+        let (enex_path, notes_path) = test_enex("import_evernote_resources", ENEX)?;
+
+        let report = EvernoteImporter::new(ReaderWriter::new(notes_path.clone())).import(&enex_path, "")?;
+        assert_eq!(1, report.attachments);
+        assert_eq!(b"hello".to_vec(), fs::read(notes_path.join("MyTrips/resources/greeting.txt"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_resources_that_cant_be_decoded() -> Result<(), NottoError> {
+        let (enex_path, notes_path) = test_enex("import_evernote_base64", &ENEX.replace("aGVs\nbG8=", "aGV*bG8"))?;
+
+        let report = EvernoteImporter::new(ReaderWriter::new(notes_path)).import(&enex_path, "")?;
+        assert_eq!(1, report.notes.len());
+        assert_eq!(0, report.attachments);
+        assert!(report.issues.iter().any(|issue| issue.message.starts_with("can't decode the resource `greeting.txt`")), "{:?}", report.issues);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_invalid_xml() -> Result<(), NottoError> {
+        for (name, enex) in &[("tags", "<en-export><note><title>Trip</tag></note></en-export>"), ("entity", "<en-export><note><title>Trip &bogus; plans</title></note></en-export>")] {
+            let (enex_path, notes_path) = test_enex(&format!("import_evernote_invalid_{}", name), enex)?;
+            match EvernoteImporter::new(ReaderWriter::new(notes_path)).import(&enex_path, "") {
+                Err(NottoError::FileError { message }) => assert!(message.starts_with("invalid enex file"), "{}", message),
+                result => panic!("expected an invalid enex file, got {:?}", result),
+            }
+        }
+
+        Ok(())
+    }
+}
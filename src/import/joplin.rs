@@ -0,0 +1,459 @@
+use std::{collections::{HashMap, HashSet}, ffi::OsStr, fs, path::{Path, PathBuf}};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde_json::Value;
+
+use crate::{errors::NottoError, io::{DIR_ROOT_NOTE_NAME, ReaderWriter, links::{percent_encode, relative_link}}, models::{front_matter::FrontMatter, note::Note}};
+
+use super::{ImportReport, save_attachment, save_note, unique_path};
+
+const ID_LENGTH: usize = 32;
+const RESOURCES_DIR: &str = "resources";
+
+const TYPE_NOTE: u64 = 1;
+const TYPE_FOLDER: u64 = 2;
+const TYPE_RESOURCE: u64 = 4;
+const TYPE_TAG: u64 = 5;
+const TYPE_NOTE_TAG: u64 = 6;
+
+/// Imports a Joplin export directory, in the RAW (`.md`) or JSON (`.json`) format
+///
+/// Every item of the export is a file named after its id. Notebooks become directories,
+/// resources are copied to a `resources` directory and the `:/id` links are converted to
+/// relative links
+pub struct JoplinImporter {
+    writer: ReaderWriter
+}
+
+/// Any object of the export: note, notebook, resource, tag or tag of a note
+#[derive(Debug, Default)]
+struct Item {
+    source: PathBuf,
+    title: String,
+    body: String,
+    fields: HashMap<String, String>,
+}
+
+impl Item {
+    fn id(&self) -> &str {
+        self.field("id").unwrap_or_default()
+    }
+
+    fn kind(&self) -> u64 {
+        self.field("type_").and_then(|kind| kind.parse().ok()).unwrap_or_default()
+    }
+
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str).filter(|value| !value.is_empty())
+    }
+
+    fn is_set(&self, name: &str) -> bool {
+        self.field(name).map(|value| value != "0").unwrap_or(false)
+    }
+
+    fn created(&self) -> Option<NaiveDateTime> {
+        self.field("user_created_time").or_else(|| self.field("created_time")).and_then(parse_time)
+    }
+}
+
+impl JoplinImporter {
+    /// Saves the notes with `writer`, running the hooks of the context and encrypting the notes like the ones created in it
+    pub fn new(writer: ReaderWriter) -> Self { Self { writer } }
+
+    /// Imports the Joplin export in `source_dir` under the `into` path of the notes directory
+    ///
+    /// Existing notes are never overwritten
+    pub fn import<P, Q>(&self, source_dir: P, into: Q) -> Result<ImportReport, NottoError> where P: AsRef<Path>, Q: AsRef<Path> {
+        let source_dir = source_dir.as_ref();
+        let into = into.as_ref();
+        if !source_dir.is_dir() {
+            return Err(NottoError::FileError { message: format!("`{}` is not a directory", source_dir.to_string_lossy()) });
+        }
+
+        let mut report = ImportReport::default();
+        let mut paths = fs::read_dir(source_dir)?.flatten().map(|entry| entry.path()).filter(|path| is_item_file(path)).collect::<Vec<_>>();
+        paths.sort();
+
+        let mut items = vec![];
+        for path in paths {
+            let source = PathBuf::from(path.file_name().unwrap_or_default());
+            match read_item(&path) {
+                Ok(mut item) => {
+                    item.source = source;
+                    if item.is_set("encryption_applied") {
+                        report.add_issue(&item.source, "the item is encrypted, not imported");
+                    } else {
+                        items.push(item);
+                    }
+                }
+                Err(message) => report.add_issue(source, message),
+            }
+        }
+
+        let folders = items.iter().filter(|item| item.kind() == TYPE_FOLDER).map(|item| (item.id(), item)).collect::<HashMap<_, _>>();
+        let tags = items.iter().filter(|item| item.kind() == TYPE_TAG).map(|item| (item.id(), item.title.clone())).collect::<HashMap<_, _>>();
+        let mut note_tags: HashMap<&str, Vec<String>> = HashMap::new();
+        for note_tag in items.iter().filter(|item| item.kind() == TYPE_NOTE_TAG) {
+            if let (Some(note_id), Some(tag)) = (note_tag.field("note_id"), note_tag.field("tag_id").and_then(|id| tags.get(id))) {
+                note_tags.entry(note_id).or_default().push(tag.clone());
+            }
+        }
+
+        let folder_paths = folders.keys().map(|id| into.join(folder_path(id, &folders))).collect::<HashSet<_>>();
+        let mut taken = HashSet::new();
+        // Destination of the notes and resources by their id, for the `:/id` links
+        let mut destinations = HashMap::new();
+
+        let mut resources = vec![];
+        for resource in items.iter().filter(|item| item.kind() == TYPE_RESOURCE) {
+            let file = match find_resource_file(source_dir, resource.id()) {
+                Some(file) => file,
+                None => {
+                    report.add_issue(&resource.source, format!("the file of the resource `{}` is missing, not imported", resource.title));
+                    continue;
+                }
+            };
+            let title = Path::new(&resource.title);
+            let mut name = ReaderWriter::normalize_file_name(title.file_stem().unwrap_or_default().to_string_lossy());
+            if name.is_empty() {
+                name = resource.id().to_string();
+            }
+            let extension = resource.field("file_extension").map(String::from)
+                .or_else(|| file.extension().map(|e| e.to_string_lossy().to_string()));
+            if let Some(extension) = extension {
+                name = format!("{}.{}", name, extension);
+            }
+            let dest = unique_path(&mut taken, into.join(RESOURCES_DIR).join(name));
+            destinations.insert(resource.id().to_string(), dest.clone());
+            resources.push((resource, file, dest));
+        }
+
+        let mut notes = vec![];
+        for note in items.iter().filter(|item| item.kind() == TYPE_NOTE) {
+            if note.is_set("is_conflict") {
+                report.add_issue(&note.source, format!("`{}` is a conflict copy, not imported", note.title));
+                continue;
+            }
+            let dir = note.field("parent_id").filter(|id| folders.contains_key(id)).map(|id| into.join(folder_path(id, &folders))).unwrap_or_else(|| into.to_path_buf());
+            let mut name = ReaderWriter::normalize_file_name(&note.title);
+            if name.is_empty() {
+                name = note.id().to_string();
+            }
+
+            // A note with the same name of a notebook next to it is the index of the notebook
+            let folder_index = dir.join(&name).join(DIR_ROOT_NOTE_NAME);
+            let dest = if folder_paths.contains(&dir.join(&name)) && !taken.contains(&folder_index) {
+                taken.insert(folder_index.clone());
+                folder_index
+            } else {
+                let dest = dir.join(format!("{}.md", name));
+                if taken.contains(&dest) {
+                    let original = dest.clone();
+                    let dest = unique_path(&mut taken, dest);
+                    report.add_issue(&note.source, format!("`{}` was already taken, imported as `{}`", original.to_string_lossy(), dest.to_string_lossy()));
+                    dest
+                } else {
+                    taken.insert(dest.clone());
+                    dest
+                }
+            };
+            destinations.insert(note.id().to_string(), dest.clone());
+            notes.push((note, dest));
+        }
+
+        let writer = &self.writer;
+        for (resource, file, dest) in resources {
+            match fs::read(&file) {
+                Ok(contents) => save_attachment(writer, &contents, &dest, &resource.source, &mut report)?,
+                Err(e) => report.add_issue(&resource.source, format!("can't read the resource file: {}", e)),
+            }
+        }
+
+        for (note, dest) in notes {
+            let mut front_matter = FrontMatter {
+                id: note.id().to_string(),
+                title: Some(note.title.clone()),
+                tags: note_tags.get(note.id()).cloned().unwrap_or_default(),
+                ..FrontMatter::default()
+            };
+            if let Some(created) = note.created() {
+                front_matter.date = created.date();
+                front_matter.time = created.time();
+            }
+            let content = convert_links(&note.body, &dest, &destinations, &note.source, &mut report);
+
+            save_note(writer, Note::new(front_matter, content), &dest, &note.source, &mut report);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Whether the file looks like an item of a Joplin export, `<id>.md` or `<id>.json`
+pub fn is_item_file(path: &Path) -> bool {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default();
+    path.is_file() && is_id(&stem) && (extension == "md" || extension == "json")
+}
+
+fn is_id(text: &str) -> bool {
+    text.len() == ID_LENGTH && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn read_item(path: &Path) -> Result<Item, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("can't read the item: {}", e))?;
+    if path.extension() == Some(OsStr::new("json")) {
+        parse_json_item(&text)
+    } else {
+        Ok(parse_raw_item(&text))
+    }
+}
+
+/// Parses an item of the RAW export: the title, an empty line, the body, an empty line and the metadata
+///
+/// The metadata are `key: value` lines, some items like the tags of the notes only have metadata
+fn parse_raw_item(text: &str) -> Item {
+    let lines = text.lines().collect::<Vec<_>>();
+    let metadata_start = lines.iter().rposition(|line| line.trim().is_empty()).map(|index| index + 1).unwrap_or(0);
+    let (content, metadata) = if lines[metadata_start..].iter().all(|line| is_metadata_line(line)) {
+        (&lines[..metadata_start], &lines[metadata_start..])
+    } else {
+        (&lines[..], &lines[lines.len()..])
+    };
+
+    let fields = metadata.iter()
+        .filter_map(|line| line.find(':').map(|index| (line[..index].to_string(), line[index + 1..].trim().to_string())))
+        .collect();
+    let title = content.first().map(|title| title.trim().to_string()).unwrap_or_default();
+    let body = content.iter().skip(2).cloned().collect::<Vec<_>>().join("\n").trim_end().to_string();
+
+    Item { title, body, fields, ..Item::default() }
+}
+
+fn is_metadata_line(line: &str) -> bool {
+    match line.find(':') {
+        Some(index) => index > 0 && line[..index].chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+        None => false
+    }
+}
+
+fn parse_json_item(text: &str) -> Result<Item, String> {
+    let values = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Object(values)) => values,
+        Ok(_) => return Err("the item is not a JSON object".to_string()),
+        Err(e) => return Err(format!("can't read the item: {}", e)),
+    };
+
+    let mut item = Item::default();
+    for (key, value) in values {
+        let value = match value {
+            Value::String(text) => text,
+            Value::Null => String::new(),
+            value => value.to_string(),
+        };
+        match key.as_str() {
+            "title" => item.title = value,
+            "body" => item.body = value,
+            _ => { item.fields.insert(key, value); }
+        }
+    }
+
+    Ok(item)
+}
+
+/// Times are ISO 8601 in the RAW export and milliseconds since the epoch in the JSON one
+fn parse_time(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(millis) = value.parse::<i64>() {
+        return Local.timestamp_millis_opt(millis).single().map(|time| time.naive_local());
+    }
+
+    DateTime::parse_from_rfc3339(value).ok().map(|time| time.with_timezone(&Local).naive_local())
+}
+
+/// The path of a notebook from its parents, every name normalized
+fn folder_path(id: &str, folders: &HashMap<&str, &Item>) -> PathBuf {
+    let mut names = vec![];
+    let mut visited = HashSet::new();
+    let mut current = folders.get(id);
+    while let Some(folder) = current {
+        // A broken export could have cycles
+        if !visited.insert(folder.id()) {
+            break;
+        }
+        let name = ReaderWriter::normalize_file_name(&folder.title);
+        names.push(if name.is_empty() { folder.id().to_string() } else { name });
+        current = folder.field("parent_id").and_then(|parent| folders.get(parent));
+    }
+
+    names.iter().rev().collect()
+}
+
+fn find_resource_file(source_dir: &Path, id: &str) -> Option<PathBuf> {
+    fs::read_dir(source_dir.join(RESOURCES_DIR)).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem() == Some(OsStr::new(id)))
+}
+
+/// Converts the `:/id` links to notes and resources to relative links
+fn convert_links(body: &str, dest: &Path, destinations: &HashMap<String, PathBuf>, source: &Path, report: &mut ImportReport) -> String {
+    let mut result = String::new();
+    let mut rest = body;
+    while let Some(start) = rest.find(":/") {
+        let id = rest.get(start + 2..start + 2 + ID_LENGTH).filter(|id| is_id(id));
+        result.push_str(&rest[..start]);
+        match id {
+            Some(id) => {
+                match destinations.get(id) {
+                    Some(target) => result.push_str(&percent_encode(&relative_link(dest, target))),
+                    None => {
+                        report.add_issue(source, format!("link to `:/{}` not converted, it's not in the export", id));
+                        result.push_str(&rest[start..start + 2 + ID_LENGTH]);
+                    }
+                }
+                rest = &rest[start + 2 + ID_LENGTH..];
+            }
+            None => {
+                result.push_str(":/");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, io::ReaderWriter, test_utils::test_dir};
+
+    use super::JoplinImporter;
+
+    const FOLDER_ID: &str = "0123456789abcdef0123456789abcdef";
+    const NOTE_ID: &str = "11111111111111111111111111111111";
+    const OTHER_NOTE_ID: &str = "22222222222222222222222222222222";
+    const RESOURCE_ID: &str = "33333333333333333333333333333333";
+    const TAG_ID: &str = "44444444444444444444444444444444";
+
+    /// A Joplin export with a notebook, a RAW note with a tag linking to a JSON note and to a resource, and the empty notes directory to import it
+    fn test_export(name: &str) -> Result<(PathBuf, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let export_path = base_path.join("export");
+        let notes_path = base_path.join("notes");
+        fs::create_dir_all(export_path.join("resources"))?;
+        fs::create_dir_all(&notes_path)?;
+
+        fs::write(export_path.join(format!("{}.md", FOLDER_ID)), format!("Work Stuff\n\nid: {}\nparent_id: \ncreated_time: 2021-01-01T10:00:00.000Z\ntype_: 2", FOLDER_ID))?;
+        fs::write(export_path.join(format!("{}.md", NOTE_ID)), format!(
+            "Meeting notes\n\nSee [the plan](:/{}) and ![chart](:/{})\n\nid: {}\nparent_id: {}\ncreated_time: 2021-03-04T10:11:12.000Z\nis_conflict: 0\ntype_: 1",
+            OTHER_NOTE_ID, RESOURCE_ID, NOTE_ID, FOLDER_ID))?;
+        fs::write(export_path.join(format!("{}.json", OTHER_NOTE_ID)), format!(
+            r#"{{"id": "{}", "parent_id": "", "title": "The plan", "body": "Step 1", "created_time": 1614852672000, "type_": 1}}"#, OTHER_NOTE_ID))?;
+        fs::write(export_path.join(format!("{}.md", RESOURCE_ID)), format!("chart.png\n\nid: {}\nmime: image/png\nfile_extension: png\ntype_: 4", RESOURCE_ID))?;
+        fs::write(export_path.join("resources").join(format!("{}.png", RESOURCE_ID)), [0u8, 159, 146, 150])?;
+        fs::write(export_path.join(format!("{}.md", TAG_ID)), format!("meetings\n\nid: {}\ntype_: 5", TAG_ID))?;
+        fs::write(export_path.join("55555555555555555555555555555555.md"), format!("id: 55555555555555555555555555555555\nnote_id: {}\ntag_id: {}\ntype_: 6", NOTE_ID, TAG_ID))?;
+
+        Ok((export_path, notes_path))
+    }
+
+    #[test]
+    fn imports_the_notebooks_and_tags() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_notebooks")?;
+
+        let report = JoplinImporter::new(ReaderWriter::new(notes_path.clone())).import(&export_path, "")?;
+        assert_eq!(2, report.notes.len());
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        let meeting = ReaderWriter::new(notes_path).read_note("WorkStuff/Meetingnotes.md")?;
+        assert_eq!(NOTE_ID, meeting.front_matter.id);
+        assert_eq!(Some("Meeting notes".to_string()), meeting.front_matter.title);
+        assert_eq!(vec!["meetings".to_string()], meeting.front_matter.tags);
+
+        Ok(())
+    }
+
+    #[test]
+    fn converts_the_links_to_notes_and_resources() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_links")?;
+
+        let report = JoplinImporter::new(ReaderWriter::new(notes_path.clone())).import(&export_path, "")?;
+        assert_eq!(1, report.attachments);
+        let meeting = ReaderWriter::new(notes_path.clone()).read_note("WorkStuff/Meetingnotes.md")?;
+        assert_eq!("See [the plan](../Theplan.md) and ![chart](../resources/chart.png)", meeting.content);
+        assert!(notes_path.join("resources/chart.png").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_json_items() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_json")?;
+
+        JoplinImporter::new(ReaderWriter::new(notes_path.clone())).import(&export_path, "")?;
+        let plan = ReaderWriter::new(notes_path).read_note("Theplan.md")?;
+        assert_eq!(Some("The plan".to_string()), plan.front_matter.title);
+        assert_eq!("Step 1", plan.content);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_the_conflicts_and_encrypted_items() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_skipped")?;
+        fs::write(export_path.join("66666666666666666666666666666666.md"), "Meeting notes\n\nOld text\n\nid: 66666666666666666666666666666666\nis_conflict: 1\ntype_: 1")?;
+        fs::write(export_path.join("77777777777777777777777777777777.md"), "\n\nid: 77777777777777777777777777777777\nencryption_applied: 1\ntype_: 1")?;
+
+        let report = JoplinImporter::new(ReaderWriter::new(notes_path)).import(&export_path, "")?;
+        assert_eq!(2, report.notes.len());
+        assert_eq!(2, report.issues.len());
+        assert!(report.issues.iter().any(|issue| issue.message.contains("conflict copy")));
+        assert!(report.issues.iter().any(|issue| issue.message.contains("encrypted")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_invalid_items() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_invalid")?;
+        fs::write(export_path.join("88888888888888888888888888888888.json"), r#"{"id": "8888"#)?;
+        fs::write(export_path.join("99999999999999999999999999999999.json"), "[1, 2]")?;
+        fs::write(export_path.join("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa.md"), "Lost\n\nSee :/bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb\n\nid: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\ntype_: 1")?;
+        fs::remove_file(export_path.join("resources").join(format!("{}.png", RESOURCE_ID)))?;
+
+        let report = JoplinImporter::new(ReaderWriter::new(notes_path)).import(&export_path, "")?;
+        assert_eq!(3, report.notes.len());
+        assert_eq!(0, report.attachments);
+        let messages = report.issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>();
+        assert!(messages.iter().any(|m| m.starts_with("88888888888888888888888888888888.json: can't read the item")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.ends_with("the item is not a JSON object")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("`:/bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb` not converted")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("the file of the resource `chart.png` is missing")), "{:?}", messages);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_resources_in_the_notes_dir() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_escape")?;
+        fs::write(export_path.join(format!("{}.md", RESOURCE_ID)), format!("chart.png\n\nid: {}\nfile_extension: png/../../../escape\ntype_: 4", RESOURCE_ID))?;
+
+        let report = JoplinImporter::new(ReaderWriter::new(notes_path.clone())).import(&export_path, "")?;
+        assert_eq!(0, report.attachments);
+        assert!(report.issues.iter().any(|issue| issue.message.contains("out of the notes directory")), "{:?}", report.issues);
+        assert!(!notes_path.join("..").join("escape").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_without_an_export() -> Result<(), NottoError> {
+        let (export_path, notes_path) = test_export("import_joplin_missing")?;
+
+        assert!(JoplinImporter::new(ReaderWriter::new(notes_path)).import(export_path.join("nothing"), "").is_err());
+
+        Ok(())
+    }
+}
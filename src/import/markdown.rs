@@ -0,0 +1,590 @@
+use std::{collections::{HashMap, HashSet}, ffi::OsStr, fs, path::{Path, PathBuf}};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde_yaml::Value;
+
+use crate::{errors::NottoError, io::{DIR_ROOT_NOTE_NAME, ReaderWriter, links::{percent_encode, relative_link, resolve_link, split_fragment}}, models::{front_matter::FrontMatter, note::Note}};
+
+use super::{ImportReport, save_attachment, save_note, unique_path};
+
+const NOTE_EXTENSION: &str = "md";
+
+/// Imports a tree of markdown files, like an Obsidian vault
+///
+/// File names are normalized like the ones of new notes, a note with the same name as a
+/// directory next to it (or inside it) becomes the `index.md` of the directory,
+/// and `[[wikilinks]]` are converted to markdown links
+pub struct MarkdownImporter {
+    writer: ReaderWriter
+}
+
+/// Where every file of the imported tree goes, all the paths are relative
+#[derive(Default)]
+struct ImportPlan {
+    notes: Vec<(PathBuf, PathBuf)>,
+    attachments: Vec<(PathBuf, PathBuf)>,
+    /// Destination of every file by its source path
+    by_source: HashMap<PathBuf, PathBuf>,
+    /// Destination of the notes by their lowercase source path, without extension
+    notes_by_path: HashMap<String, PathBuf>,
+    /// Destinations of the notes by their lowercase source file stem
+    notes_by_name: HashMap<String, Vec<PathBuf>>,
+    /// Destinations of the attachments by their lowercase source file name
+    attachments_by_name: HashMap<String, Vec<PathBuf>>,
+}
+
+impl MarkdownImporter {
+    /// Saves the notes with `writer`, running the hooks of the context and encrypting the notes like the ones created in it
+    pub fn new(writer: ReaderWriter) -> Self { Self { writer } }
+
+    /// Imports the markdown files in `source_dir` under the `into` path of the notes directory
+    ///
+    /// Existing notes are never overwritten
+    pub fn import<P, Q>(&self, source_dir: P, into: Q) -> Result<ImportReport, NottoError> where P: AsRef<Path>, Q: AsRef<Path> {
+        let source_dir = source_dir.as_ref();
+        if !source_dir.is_dir() {
+            return Err(NottoError::FileError { message: format!("`{}` is not a directory", source_dir.to_string_lossy()) });
+        }
+
+        let mut report = ImportReport::default();
+        let plan = self.plan(source_dir, into.as_ref(), &mut report)?;
+        let writer = &self.writer;
+
+        for (source, dest) in &plan.notes {
+            let text = match fs::read_to_string(source_dir.join(source)) {
+                Ok(text) => text,
+                Err(e) => {
+                    report.add_issue(source, format!("can't read the note: {}", e));
+                    continue;
+                }
+            };
+            let (front_matter, content) = Note::split_front_matter(text);
+            let modified = fs::metadata(source_dir.join(source)).and_then(|m| m.modified()).ok().map(|m| DateTime::<Local>::from(m).naive_local());
+            let title = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let front_matter = build_front_matter(front_matter.as_deref(), &title, modified, source, &mut report);
+            let content = convert_links(&content, source, dest, &plan, &mut report);
+
+            save_note(writer, Note::new(front_matter, content), dest, source, &mut report);
+        }
+
+        for (source, dest) in &plan.attachments {
+            match fs::read(source_dir.join(source)) {
+                Ok(contents) => save_attachment(writer, &contents, dest, source, &mut report)?,
+                Err(e) => report.add_issue(source, format!("can't read the file: {}", e)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Decides the destination of every file before importing, so links can be converted
+    fn plan(&self, source_dir: &Path, into: &Path, report: &mut ImportReport) -> Result<ImportPlan, NottoError> {
+        let mut notes = vec![];
+        let mut attachments = vec![];
+        let mut dirs = HashSet::new();
+        walk(source_dir, Path::new(""), &mut notes, &mut attachments, &mut dirs)?;
+        notes.sort();
+        attachments.sort();
+
+        let mut plan = ImportPlan::default();
+        let mut taken = HashSet::new();
+        let sources = notes.iter().cloned().collect::<HashSet<_>>();
+
+        for source in notes {
+            let parent = source.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+            let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+
+            // Notes with the same name of a directory are the index of the directory
+            let folder = if parent.file_name() == Some(OsStr::new(&stem)) {
+                Some(parent.clone())
+            } else if dirs.contains(&parent.join(&stem)) {
+                Some(parent.join(&stem))
+            } else {
+                None
+            };
+            let mut dest = match folder {
+                Some(folder) if !sources.contains(&folder.join(DIR_ROOT_NOTE_NAME)) => dest_dir(into, &folder).join(DIR_ROOT_NOTE_NAME),
+                _ => {
+                    let mut file_name = ReaderWriter::normalize_file_name(&stem);
+                    if file_name.is_empty() {
+                        file_name = FrontMatter::default().id;
+                    }
+                    dest_dir(into, &parent).join(format!("{}.{}", file_name, NOTE_EXTENSION))
+                }
+            };
+
+            if taken.contains(&dest) {
+                let original = dest.clone();
+                dest = unique_path(&mut taken, dest);
+                report.add_issue(&source, format!("`{}` was already taken, imported as `{}`", original.to_string_lossy(), dest.to_string_lossy()));
+            } else {
+                taken.insert(dest.clone());
+            }
+
+            plan.notes_by_path.insert(path_key(&source.with_extension("")), dest.clone());
+            plan.notes_by_name.entry(stem.to_lowercase()).or_default().push(dest.clone());
+            plan.by_source.insert(source.clone(), dest.clone());
+            plan.notes.push((source, dest));
+        }
+
+        for source in attachments {
+            let parent = source.parent().unwrap_or_else(|| Path::new(""));
+            let dest = match source.file_name() {
+                Some(file_name) => dest_dir(into, parent).join(file_name),
+                None => continue
+            };
+            if let Some(file_name) = source.file_name() {
+                plan.attachments_by_name.entry(file_name.to_string_lossy().to_lowercase()).or_default().push(dest.clone());
+            }
+            plan.by_source.insert(source.clone(), dest.clone());
+            plan.attachments.push((source, dest));
+        }
+
+        Ok(plan)
+    }
+}
+
+impl ImportPlan {
+    /// Finds the destination of the target of a wikilink, `current` is the source of the note with the link
+    fn resolve_wikilink(&self, target: &str, current: &Path) -> Result<PathBuf, String> {
+        let target_path = Path::new(target);
+        let is_note = match target_path.extension() {
+            Some(extension) => extension == NOTE_EXTENSION,
+            None => true
+        };
+
+        let candidates = if is_note {
+            let key = path_key(&target_path.with_extension(""));
+            match self.notes_by_path.get(&key) {
+                Some(dest) => vec![dest.clone()],
+                None => {
+                    let name = target_path.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+                    self.notes_by_name.get(&name).cloned().unwrap_or_default()
+                }
+            }
+        } else {
+            match self.by_source.get(target_path) {
+                Some(dest) => vec![dest.clone()],
+                None => {
+                    let name = target_path.file_name().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default();
+                    self.attachments_by_name.get(&name).cloned().unwrap_or_default()
+                }
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(format!("can't find `{}`", target)),
+            1 => Ok(candidates[0].clone()),
+            _ => {
+                // Several files with the same name, we take the one in the same directory
+                let current_dir = self.by_source.get(current).and_then(|dest| dest.parent());
+                candidates.iter()
+                    .find(|candidate| candidate.parent() == current_dir)
+                    .cloned()
+                    .ok_or_else(|| format!("`{}` matches several files", target))
+            }
+        }
+    }
+}
+
+/// Collects the notes, other files and directories in a tree, skipping the hidden ones
+fn walk(root: &Path, dir: &Path, notes: &mut Vec<PathBuf>, attachments: &mut Vec<PathBuf>, dirs: &mut HashSet<PathBuf>) -> Result<(), NottoError> {
+    for entry in fs::read_dir(root.join(dir))?.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = dir.join(&name);
+        let full_path = entry.path();
+        if full_path.is_dir() {
+            dirs.insert(path.clone());
+            walk(root, &path, notes, attachments, dirs)?;
+        } else if path.extension() == Some(OsStr::new(NOTE_EXTENSION)) {
+            notes.push(path);
+        } else {
+            attachments.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The destination of a source directory, with every directory name normalized
+fn dest_dir(into: &Path, source_dir: &Path) -> PathBuf {
+    let mut dest = into.to_path_buf();
+    for component in source_dir.components() {
+        let name = component.as_os_str().to_string_lossy();
+        let normalized = ReaderWriter::normalize_file_name(&name);
+        dest.push(if normalized.is_empty() { name.to_string() } else { normalized });
+    }
+
+    dest
+}
+
+fn path_key(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy().to_lowercase()).collect::<Vec<_>>().join("/")
+}
+
+/// Builds the front matter from the one in the imported note
+///
+/// The title defaults to the file name and the date and time to the modification time of the file
+fn build_front_matter(raw: Option<&str>, title: &str, modified: Option<NaiveDateTime>, source: &Path, report: &mut ImportReport) -> FrontMatter {
+    let mut front_matter = FrontMatter { title: Some(title.to_string()), ..FrontMatter::default() };
+    if let Some(modified) = modified {
+        front_matter.date = modified.date();
+        front_matter.time = modified.time();
+    }
+
+    let values = match raw.map(serde_yaml::from_str::<Value>) {
+        Some(Ok(Value::Mapping(values))) => values,
+        Some(Ok(Value::Null)) | None => return front_matter,
+        Some(_) => {
+            report.add_issue(source, "the front matter is not valid, ignored");
+            return front_matter;
+        }
+    };
+
+    for (key, value) in values {
+        let key = key.as_str().unwrap_or_default().to_string();
+        match (key.as_str(), &value) {
+            ("id", Value::String(id)) => front_matter.id = id.clone(),
+            ("title", Value::String(title)) => front_matter.title = Some(title.clone()),
+            ("date", Value::String(date)) | ("created", Value::String(date)) => match parse_date_time(date) {
+                Some((date, time)) => {
+                    front_matter.date = date;
+                    if let Some(time) = time {
+                        front_matter.time = time;
+                    }
+                }
+                None => report.add_issue(source, format!("can't read the date `{}`, using the file date", date))
+            },
+            ("time", Value::String(time)) => match NaiveTime::parse_from_str(time, "%H:%M:%S").or_else(|_| NaiveTime::parse_from_str(time, "%H:%M")) {
+                Ok(time) => front_matter.time = time,
+                Err(_) => report.add_issue(source, format!("can't read the time `{}`, using the file time", time))
+            },
+            ("tags", _) | ("tag", _) => front_matter.tags.extend(read_tags(&value)),
+            ("private", Value::Bool(private)) => front_matter.private = *private,
+            _ => report.add_issue(source, format!("front matter field `{}` not supported, dropped", key)),
+        }
+    }
+
+    front_matter
+}
+
+fn parse_date_time(text: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let text = text.trim();
+    for format in [ "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M" ].iter() {
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(text, format) {
+            return Some((date_time.date(), Some(date_time.time())));
+        }
+    }
+
+    // Dates with a time zone or other details after the day
+    text.get(..10).and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()).map(|date| (date, None))
+}
+
+fn read_tags(value: &Value) -> Vec<String> {
+    let tags = match value {
+        Value::Sequence(values) => values.iter().filter_map(|v| v.as_str()).map(String::from).collect(),
+        Value::String(text) => text.split(|c: char| c == ',' || c.is_whitespace()).map(String::from).collect(),
+        _ => vec![]
+    };
+
+    tags.into_iter()
+        .map(|tag| tag.trim().trim_start_matches('#').to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Converts the wikilinks to markdown links and points the existing markdown links to the new paths
+///
+/// Code blocks and code spans are left untouched
+fn convert_links(content: &str, source: &Path, dest: &Path, plan: &ImportPlan, report: &mut ImportReport) -> String {
+    let mut lines = vec![];
+    let mut in_code_block = false;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        // Odd segments are inside code spans
+        let converted = line.split('`').enumerate().map(|(index, segment)| {
+            if index % 2 == 0 {
+                // Markdown links first, so the links coming from wikilinks aren't converted again
+                let segment = convert_markdown_links(segment, source, dest, plan, report);
+                convert_wikilinks(&segment, source, dest, plan, report)
+            } else {
+                segment.to_string()
+            }
+        }).collect::<Vec<_>>();
+        lines.push(converted.join("`"));
+    }
+
+    let mut converted = lines.join("\n");
+    if content.ends_with('\n') {
+        converted.push('\n');
+    }
+
+    converted
+}
+
+fn convert_wikilinks(text: &str, source: &Path, dest: &Path, plan: &ImportPlan, report: &mut ImportReport) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let end = match rest[start..].find("]]") {
+            Some(end) => start + end,
+            None => break
+        };
+        let embed = rest[..start].ends_with('!');
+        let before = if embed { &rest[..start - 1] } else { &rest[..start] };
+        let original = &rest[if embed { start - 1 } else { start }..end + 2];
+        let inner = &rest[start + 2..end];
+
+        result.push_str(before);
+        match convert_wikilink(inner, embed, source, dest, plan) {
+            Ok((link, warning)) => {
+                if let Some(warning) = warning {
+                    report.add_issue(source, warning);
+                }
+                result.push_str(&link);
+            }
+            Err(message) => {
+                report.add_issue(source, format!("link `{}` not converted, {}", original, message));
+                result.push_str(original);
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Converts the inside of a wikilink, `target#heading|alias`, to a markdown link
+///
+/// Returns the link and a warning if something was lost in the conversion
+fn convert_wikilink(inner: &str, embed: bool, source: &Path, dest: &Path, plan: &ImportPlan) -> Result<(String, Option<String>), String> {
+    let (target, alias) = match inner.find('|') {
+        Some(index) => (&inner[..index], Some(inner[index + 1..].trim())),
+        None => (inner, None)
+    };
+    let (target, heading) = match target.find('#') {
+        Some(index) => (target[..index].trim(), Some(target[index + 1..].trim())),
+        None => (target.trim(), None)
+    };
+
+    let mut warning = None;
+    let fragment = match heading {
+        Some(heading) if heading.starts_with('^') => {
+            warning = Some(format!("block reference `{}` dropped", heading));
+            String::new()
+        }
+        Some(heading) => format!("#{}", heading_anchor(heading)),
+        None => String::new()
+    };
+
+    // A heading in the same note
+    if target.is_empty() {
+        let text = alias.or(heading).unwrap_or_default();
+        return Ok((format!("[{}]({})", text, fragment), warning));
+    }
+
+    let target_dest = plan.resolve_wikilink(target, source)?;
+    let link = format!("{}{}", percent_encode(&relative_link(dest, &target_dest)), fragment);
+    let is_note = target_dest.extension() == Some(OsStr::new(NOTE_EXTENSION));
+    let name = Path::new(target).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| target.to_string());
+
+    if embed && !is_note {
+        Ok((format!("![{}]({})", name, link), warning))
+    } else {
+        if embed {
+            warning = Some(format!("embedded note `{}` converted to a link", target));
+        }
+        let text = match (alias, heading) {
+            (Some(alias), _) if !embed || !alias.is_empty() => alias.to_string(),
+            (_, Some(heading)) if !heading.starts_with('^') => format!("{} > {}", name, heading),
+            _ => name
+        };
+        Ok((format!("[{}]({})", text, link), warning))
+    }
+}
+
+/// Points the markdown links to imported files to their new path
+fn convert_markdown_links(text: &str, source: &Path, dest: &Path, plan: &ImportPlan, report: &mut ImportReport) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        let link_start = start + 2;
+        let (link_end, link) = if rest[link_start..].starts_with('<') {
+            match rest[link_start..].find('>') {
+                Some(end) => (link_start + end + 1, &rest[link_start + 1..link_start + end]),
+                None => break
+            }
+        } else {
+            let end = rest[link_start..].find(|c: char| c == ')' || c.is_whitespace()).map(|end| link_start + end).unwrap_or_else(|| rest.len());
+            (end, &rest[link_start..end])
+        };
+
+        result.push_str(&rest[..link_start]);
+        match resolve_link(source, link) {
+            Some(target) => {
+                let target_dest = plan.by_source.get(&target)
+                    .or_else(|| plan.by_source.get(&target.with_extension(NOTE_EXTENSION)));
+                match target_dest {
+                    Some(target_dest) => {
+                        let fragment = split_fragment(link).1;
+                        result.push_str(&format!("{}{}", percent_encode(&relative_link(dest, target_dest)), fragment));
+                    }
+                    None => {
+                        report.add_issue(source, format!("link to `{}` not converted, the file is not in the import", link));
+                        result.push_str(&rest[link_start..link_end]);
+                    }
+                }
+            }
+            // External links and anchors
+            None => result.push_str(&rest[link_start..link_end])
+        }
+        rest = &rest[link_end..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// The anchor of a heading as generated by most markdown renderers
+fn heading_anchor(heading: &str) -> String {
+    heading.trim().to_lowercase().chars()
+        .filter_map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { Some(c) } else if c.is_whitespace() { Some('-') } else { None })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, io::ReaderWriter, test_utils::test_dir};
+
+    use super::MarkdownImporter;
+
+    /// An Obsidian vault with two notes linked to each other and an attachment, and the empty notes directory to import it
+    fn test_vault(name: &str) -> Result<(PathBuf, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let vault_path = base_path.join("vault");
+        let notes_path = base_path.join("notes");
+        fs::create_dir_all(vault_path.join("My Projects").join("attachments"))?;
+        fs::create_dir_all(vault_path.join(".obsidian"))?;
+        fs::create_dir_all(&notes_path)?;
+
+        fs::write(vault_path.join(".obsidian").join("app.json"), "{}")?;
+        fs::write(vault_path.join("My Projects.md"), "---\ntags: [work, '#planning']\naliases: [projects]\ncreated: 2021-03-04 10:11\n---\nAll the [[Roadmap 2021|roadmap]] and [[Missing note]].\n")?;
+        fs::write(vault_path.join("My Projects").join("Roadmap 2021.md"), "# Roadmap\nBack to [[My Projects#Current work]], ![[diagram.png]]\n`[[not a link]]`\n[Projects](../My%20Projects.md)\n")?;
+        fs::write(vault_path.join("My Projects").join("attachments").join("diagram.png"), [0u8, 159, 146, 150])?;
+
+        Ok((vault_path, notes_path))
+    }
+
+    #[test]
+    fn converts_the_front_matter() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_front_matter")?;
+
+        let report = MarkdownImporter::new(ReaderWriter::new(notes_path.clone())).import(&vault_path, "vault")?;
+        assert_eq!(2, report.notes.len());
+        let index = ReaderWriter::new(notes_path).read_note("vault/MyProjects/index.md")?;
+        assert_eq!(Some("My Projects".to_string()), index.front_matter.title);
+        assert_eq!(vec!["work".to_string(), "planning".to_string()], index.front_matter.tags);
+        assert_eq!("2021-03-04", index.front_matter.date.format("%Y-%m-%d").to_string());
+        assert!(report.issues.iter().any(|issue| issue.message.contains("`aliases`")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn converts_the_links() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_links")?;
+
+        let report = MarkdownImporter::new(ReaderWriter::new(notes_path.clone())).import(&vault_path, "vault")?;
+        let reader = ReaderWriter::new(notes_path);
+        assert_eq!("All the [roadmap](Roadmap2021.md) and [[Missing note]].", reader.read_note("vault/MyProjects/index.md")?.content);
+        assert_eq!(
+            "# Roadmap\nBack to [My Projects > Current work](index.md#current-work), ![diagram](attachments/diagram.png)\n`[[not a link]]`\n[Projects](index.md)",
+            reader.read_note("vault/MyProjects/Roadmap2021.md")?.content);
+        assert!(report.issues.iter().any(|issue| issue.message.contains("[[Missing note]]")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn copies_the_attachments_but_not_the_hidden_files() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_attachments")?;
+
+        let report = MarkdownImporter::new(ReaderWriter::new(notes_path.clone())).import(&vault_path, "vault")?;
+        assert_eq!(1, report.attachments);
+        assert!(notes_path.join("vault/MyProjects/attachments/diagram.png").exists());
+        assert!(!notes_path.join("vault/.obsidian").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn never_overwrites_the_notes() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_again")?;
+        let importer = MarkdownImporter::new(ReaderWriter::new(notes_path));
+
+        importer.import(&vault_path, "vault")?;
+        let report = importer.import(&vault_path, "vault")?;
+        assert_eq!(0, report.notes.len());
+        assert_eq!(0, report.attachments);
+        assert!(report.issues.iter().any(|issue| issue.message.contains("already exists")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn renames_the_notes_with_the_same_name() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_same_name")?;
+        fs::write(vault_path.join("Todo list.md"), "Milk")?;
+        fs::write(vault_path.join("Todolist.md"), "Bread")?;
+
+        let report = MarkdownImporter::new(ReaderWriter::new(notes_path.clone())).import(&vault_path, "")?;
+        assert_eq!(4, report.notes.len());
+        assert!(notes_path.join("Todolist.md").exists() && notes_path.join("Todolist-2.md").exists());
+        assert!(report.issues.iter().any(|issue| issue.message.contains("already taken")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_invalid_front_matters() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_invalid_front_matter")?;
+        fs::write(vault_path.join("Broken.md"), "---\ntags: [unclosed\n---\nText")?;
+        fs::write(vault_path.join("Dates.md"), "---\ndate: someday\ntime: \"25:99\"\n---\nText")?;
+
+        let report = MarkdownImporter::new(ReaderWriter::new(notes_path.clone())).import(&vault_path, "")?;
+        assert_eq!(4, report.notes.len());
+        let broken = ReaderWriter::new(notes_path).read_note("Broken.md")?;
+        assert_eq!(Some("Broken".to_string()), broken.front_matter.title);
+        let messages = report.issues.iter().map(|issue| issue.message.clone()).collect::<Vec<_>>();
+        assert!(messages.iter().any(|m| m == "the front matter is not valid, ignored"), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("the date `someday`")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("the time `25:99`")), "{:?}", messages);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_without_a_vault() -> Result<(), NottoError> {
+        let (vault_path, notes_path) = test_vault("import_no_vault")?;
+
+        let importer = MarkdownImporter::new(ReaderWriter::new(notes_path));
+        assert!(importer.import(vault_path.join("nothing"), "").is_err());
+        assert!(importer.import(vault_path.join("My Projects.md"), "").is_err());
+
+        Ok(())
+    }
+}
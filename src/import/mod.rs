@@ -0,0 +1,150 @@
+pub mod markdown;
+pub mod archive;
+pub mod joplin;
+pub mod evernote;
+
+use std::{collections::HashSet, ffi::OsStr, fmt, fs, path::{Path, PathBuf}, str::FromStr};
+
+use crate::{errors::NottoError, io::{ReaderWriter, is_note_path}, models::note::Note};
+
+/// The result of importing notes from another application
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// Paths of the imported notes, relative to the notes directory
+    pub notes: Vec<PathBuf>,
+    pub attachments: usize,
+    /// Everything that couldn't be converted
+    pub issues: Vec<ImportIssue>,
+}
+
+impl ImportReport {
+    pub fn add_issue<P, S>(&mut self, source: P, message: S) where P: Into<PathBuf>, S: Into<String> {
+        self.issues.push(ImportIssue { source: source.into(), message: message.into() });
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportIssue {
+    /// The file being imported when the issue happened
+    pub source: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ImportIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source.to_string_lossy(), self.message)
+    }
+}
+
+/// The applications we can import notes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// A directory of markdown files, like an Obsidian vault
+    Markdown,
+    /// A Joplin export directory, in the RAW or JSON format
+    Joplin,
+    /// An Evernote `.enex` file
+    Evernote,
+    /// A `.tar.gz` archive made by `notto export archive`
+    Archive,
+}
+
+impl ImportFormat {
+    /// Guesses the format of the file or directory at `source`
+    pub fn detect<P: AsRef<Path>>(source: P) -> ImportFormat {
+        let source = source.as_ref();
+        if source.is_file() {
+            let file_name = source.file_name().unwrap_or_default().to_string_lossy();
+            return if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+                ImportFormat::Archive
+            } else {
+                ImportFormat::Evernote
+            };
+        }
+
+        let items = fs::read_dir(source).map(|entries| entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && !path.file_name().unwrap_or_default().to_string_lossy().starts_with('.'))
+            .collect::<Vec<_>>())
+            .unwrap_or_default();
+        if !items.is_empty() && items.iter().all(|path| joplin::is_item_file(path)) {
+            ImportFormat::Joplin
+        } else {
+            ImportFormat::Markdown
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = NottoError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "markdown" | "md" | "obsidian" => Ok(ImportFormat::Markdown),
+            "joplin" => Ok(ImportFormat::Joplin),
+            "evernote" | "enex" => Ok(ImportFormat::Evernote),
+            "archive" => Ok(ImportFormat::Archive),
+            _ => Err(NottoError::FileError { message: format!("unknown import format `{}`, expected markdown, joplin, evernote or archive", value) })
+        }
+    }
+}
+
+/// Adds a `-2`, `-3`... suffix to the file name of `path` until it's not taken by another imported file
+fn unique_path(taken: &mut HashSet<PathBuf>, path: PathBuf) -> PathBuf {
+    let mut unique = path.clone();
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut count = 2;
+    while taken.contains(&unique) {
+        unique = path.with_file_name(format!("{}-{}{}", stem, count, extension));
+        count += 1;
+    }
+    taken.insert(unique.clone());
+
+    unique
+}
+
+/// Saves an imported note at `dest`, never overwriting an existing one
+fn save_note(writer: &ReaderWriter, note: Note, dest: &Path, source: &Path, report: &mut ImportReport) {
+    let dest_dir = dest.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = dest.file_name().unwrap_or_else(|| OsStr::new("")).to_string_lossy();
+    match writer.save_note_at(note, dest_dir, file_name, false) {
+        Ok(path) => report.notes.push(path),
+        Err(NottoError::NoteExists { .. }) => report.add_issue(source, format!("a note already exists at `{}`, not imported", dest.to_string_lossy())),
+        Err(e) => report.add_issue(source, format!("can't save the note: {}", e)),
+    }
+}
+
+/// Writes an attachment at `dest`, never overwriting an existing file
+fn save_attachment(writer: &ReaderWriter, contents: &[u8], dest: &Path, source: &Path, report: &mut ImportReport) -> Result<(), NottoError> {
+    if write_new_file(writer, contents, dest, source, report)? {
+        report.attachments += 1;
+    }
+
+    Ok(())
+}
+
+/// Writes a file at `dest` if there's none, returns whether it was written
+///
+/// A `dest` going out of the notes directory, from a crafted file name or type, is reported and not written
+fn write_new_file(writer: &ReaderWriter, contents: &[u8], dest: &Path, source: &Path, report: &mut ImportReport) -> Result<bool, NottoError> {
+    if !is_note_path(dest) {
+        report.add_issue(source, format!("`{}` is out of the notes directory, not copied", dest.to_string_lossy()));
+        return Ok(false);
+    }
+    let full_dest = writer.get_full_path(dest);
+    if full_dest.exists() {
+        report.add_issue(source, format!("a file already exists at `{}`, not copied", dest.to_string_lossy()));
+        return Ok(false);
+    }
+    if let Some(dest_dir) = dest.parent() {
+        writer.create_dir_all(dest_dir)?;
+    }
+    match fs::write(full_dest, contents) {
+        Ok(_) => Ok(true),
+        Err(e) => {
+            report.add_issue(source, format!("can't write the file: {}", e));
+            Ok(false)
+        }
+    }
+}
@@ -1,23 +1,34 @@
 use std::path::{Path, PathBuf};
-use std::fs;
+use std::{fs, str::FromStr, time::SystemTime};
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
 
 use crate::errors::NottoError;
+use crate::io::crypto::is_encrypted;
 use crate::models::note::Note;
 
 const PATH_SEPARATOR: &str = "/";
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct NottoPath {
     path: String,
 }
 
+impl Default for NottoPath {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NottoPath {
     pub fn new() -> Self {
         Self { path: String::new() }
     }
 
     pub fn push<S: AsRef<str>>(&mut self, path: S) {
-        let segments = path.as_ref().split(PATH_SEPARATOR).into_iter().collect::<Vec<_>>();
+        let segments = path.as_ref().split(PATH_SEPARATOR).collect::<Vec<_>>();
         for seg in segments {
             if !self.path.is_empty() {
                 self.path.push_str(PATH_SEPARATOR);
@@ -49,11 +60,11 @@ impl From<&NottoPath> for String {
 
 impl AsRef<Path> for NottoPath {
     fn as_ref(&self) -> &Path {
-        &Path::new(&self.path)
+        Path::new(&self.path)
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Ord)]
+#[derive(Debug, Eq, PartialEq)]
 pub struct PathEntry {
     name: String,
     pub path: NottoPath,
@@ -62,7 +73,7 @@ pub struct PathEntry {
 
 impl PathEntry {
     pub fn string_to_pathbuf(path: &NottoPath) -> PathBuf {
-        let segments = path.path.split(PATH_SEPARATOR).into_iter().collect::<Vec<_>>();
+        let segments = path.path.split(PATH_SEPARATOR).collect::<Vec<_>>();
         let mut path = PathBuf::new();
 
         for seg in segments {
@@ -77,7 +88,7 @@ impl PathEntry {
         let p = path.as_ref();
         for comp in p.components() {
             if let std::path::Component::Normal(name) = comp {
-                result.push(&name.to_string_lossy());
+                result.push(name.to_string_lossy());
             }
         }
 
@@ -93,31 +104,89 @@ impl PathEntry {
     }
 }
 
-impl ToString for PathEntry {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for PathEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.is_dir {
-            format!("[{}]", self.name.clone())
+            write!(f, "[{}]", self.name)
         } else {
-            self.name.clone()
+            write!(f, "{}", self.name)
         }
     }
 }
 
-impl PartialOrd for PathEntry {
+impl Ord for PathEntry {
     // We want directories come first
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         if self.is_dir == other.is_dir {
             if let (Ok(s), Ok(o)) = (self.name.parse::<i32>(), other.name.parse::<i32>()) {
-                s.partial_cmp(&o)
+                s.cmp(&o)
             } else {
-                self.name.partial_cmp(&other.name)
+                self.name.cmp(&other.name)
             }
+        } else if self.is_dir {
+            std::cmp::Ordering::Less
         } else {
-            if self.is_dir { Some(std::cmp::Ordering::Less ) } else { Some(std::cmp::Ordering::Greater ) }
+            std::cmp::Ordering::Greater
         }
     }
 }
 
+impl PartialOrd for PathEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// How the notes are listed, the directories always come first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListOrder {
+    Name,
+    /// Newest first, by the date of the front matter
+    Date,
+    /// Last changed first
+    Updated,
+}
+
+impl FromStr for ListOrder {
+    type Err = NottoError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "name" => Ok(ListOrder::Name),
+            "date" => Ok(ListOrder::Date),
+            "updated" => Ok(ListOrder::Updated),
+            _ => Err(NottoError::FileError { message: format!("unknown sort `{}`, expected name, date or updated", value) })
+        }
+    }
+}
+
+/// A note or a directory as listed by `notto ls`
+#[derive(Debug, Serialize)]
+pub struct NoteListing {
+    /// The file or directory name
+    pub name: String,
+    /// Relative to the notes directory
+    pub path: String,
+    pub is_dir: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// When the file was last changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// `None` for the encrypted notes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<usize>,
+    /// The entries of a directory, when listed as a tree
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NoteListing>,
+}
+
 pub struct NoteBrowser {
     base_path: PathBuf
 }
@@ -147,34 +216,32 @@ impl NoteBrowser {
     pub fn get_selections_for_path(&self, path: &NottoPath) -> Result<Vec<PathEntry>, NottoError> {
         let full_path = self.base_path.join(path);
         let mut result = vec![];
-        for path in fs::read_dir(full_path)? {
-            if let Ok(dir_entry) = path {
-                let path = dir_entry.path();
-                let hidden = if let Some(file_name) = path.file_name() {
-                    file_name.to_string_lossy().starts_with(".")
+        for dir_entry in fs::read_dir(full_path)?.flatten() {
+            let path = dir_entry.path();
+            let hidden = if let Some(file_name) = path.file_name() {
+                file_name.to_string_lossy().starts_with(".")
+            } else {
+                false
+            };
+
+            if !hidden {
+                if path.is_dir() {
+                    if let Some(name) = path.file_name() {
+                        let name = name.to_string_lossy().to_string();
+                        result.push(PathEntry { name, path: self.get_rel_path_string(path), is_dir: true });
+                    }
                 } else {
-                    false
-                };
-
-                if !hidden {
-                    if path.is_dir() {
-                        if let Some(name) = path.file_name() {
-                            let name = name.to_string_lossy().to_string();
-                            result.push(PathEntry { name, path: self.get_rel_path_string(path), is_dir: true });
-                        }
-                    } else {
-                        if let Ok(note_text) = fs::read_to_string(&path) {
-                            let note = Note::from_text(note_text);
-                            let mut name = note.get_title();
-
-                            if let Some(file_name) = path.file_name() {
-                                name = format!("{} [{}]", name, file_name.to_string_lossy());
-                            }
-                            result.push(PathEntry { name, path: self.get_rel_path_string(path), is_dir: false });
+                    if let Ok(note_text) = fs::read_to_string(&path) {
+                        let note = Note::from_text(note_text);
+                        let mut name = note.get_title();
+
+                        if let Some(file_name) = path.file_name() {
+                            name = format!("{} [{}]", name, file_name.to_string_lossy());
                         }
+                        result.push(PathEntry { name, path: self.get_rel_path_string(path), is_dir: false });
                     }
                 }
-            };
+            }
         }
 
         result.sort();
@@ -182,4 +249,152 @@ impl NoteBrowser {
         Ok(result)
     }
 
+    /// Lists the notes and directories under `path`, with the directories listed too if `recursive`
+    pub fn list(&self, path: &NottoPath, recursive: bool, order: ListOrder) -> Result<Vec<NoteListing>, NottoError> {
+        let mut listings = vec![];
+        for entry in self.get_selections_for_path(path)? {
+            let full_path = entry.get_full_path(&self.base_path);
+            let modified = fs::metadata(&full_path).and_then(|metadata| metadata.modified()).ok();
+            let mut listing = NoteListing {
+                name: full_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+                path: String::from(&entry.path),
+                is_dir: entry.is_dir,
+                id: None,
+                title: None,
+                date: None,
+                updated: modified.map(format_time),
+                tags: vec![],
+                words: None,
+                children: vec![],
+            };
+            if entry.is_dir {
+                if recursive {
+                    listing.children = self.list(&entry.path, recursive, order)?;
+                }
+            } else {
+                let note = Note::from_text(fs::read_to_string(&full_path)?);
+                let date = note.front_matter.date.and_time(note.front_matter.time);
+                listing.id = note.front_matter.stored_id().map(String::from);
+                listing.title = Some(note.get_title()).filter(|title| !title.is_empty());
+                listing.date = Some(date.format(DATE_FORMAT).to_string());
+                listing.words = if is_encrypted(&note.content) { None } else { Some(count_words(&note.content)) };
+                listing.tags = note.front_matter.tags;
+            }
+            listings.push((listing, modified));
+        }
+
+        // The dates sort as their text
+        match order {
+            ListOrder::Name => listings.sort_by(|(a, _), (b, _)| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name))),
+            ListOrder::Date => listings.sort_by(|(a, _), (b, _)| b.is_dir.cmp(&a.is_dir).then_with(|| b.date.cmp(&a.date))),
+            ListOrder::Updated => listings.sort_by(|(a, a_modified), (b, b_modified)| b.is_dir.cmp(&a.is_dir).then_with(|| b_modified.cmp(a_modified))),
+        }
+
+        Ok(listings.into_iter().map(|(listing, _)| listing).collect())
+    }
+
+}
+
+/// The words with a letter or a digit, leaving out the markdown like `#` or `-`
+fn count_words(text: &str) -> usize {
+    text.split_whitespace().filter(|word| word.chars().any(char::is_alphanumeric)).count()
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format(DATE_FORMAT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, test_utils::test_dir};
+
+    use super::{ListOrder, NoteBrowser, NottoPath};
+
+    /// An old note and a new one in `test_notes_dir_<name>`, and a plan in `work`
+    fn test_notes(name: &str) -> Result<PathBuf, NottoError> {
+        let base_path = test_dir(name)?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::write(base_path.join("archive.md"), "---\ndate: 2020-01-01\ntags: [a]\n---\n# Old\nthree words here")?;
+        fs::write(base_path.join("new.md"), "---\ndate: 2021-06-01\n---\n# New")?;
+        fs::write(base_path.join("work").join("plan.md"), "# Plan")?;
+
+        Ok(base_path)
+    }
+
+    fn names(browser: &NoteBrowser, path: &NottoPath, recursive: bool, order: ListOrder) -> Result<Vec<String>, NottoError> {
+        Ok(browser.list(path, recursive, order)?.into_iter().map(|listing| listing.name).collect())
+    }
+
+    #[test]
+    fn lists_notes() -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(test_notes("browser_names")?);
+
+        assert_eq!(vec!["work", "archive.md", "new.md"], names(&browser, &NottoPath::new(), false, ListOrder::Name)?);
+        assert!(browser.list(&NottoPath::new(), false, ListOrder::Name)?[0].children.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn describes_the_notes() -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(test_notes("browser_details")?);
+
+        let listings = browser.list(&NottoPath::new(), false, ListOrder::Name)?;
+        assert_eq!(Some("Old".to_string()), listings[1].title);
+        assert_eq!(vec!["a".to_string()], listings[1].tags);
+        assert_eq!(Some(4), listings[1].words);
+        // Without a saved id, rather than a new one at every listing
+        assert_eq!(None, listings[1].id);
+        assert!(listings[1].date.as_ref().unwrap().starts_with("2020-01-01 "));
+        assert!(listings[1].updated.is_some());
+        assert!(!listings[1].is_dir);
+        assert!(listings[0].is_dir);
+        assert_eq!(None, listings[0].title);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_notes_by_date_recursively() -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(test_notes("browser_dates")?);
+
+        let listings = browser.list(&NottoPath::new(), true, ListOrder::Date)?;
+        let names = listings.iter().map(|listing| listing.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["work", "new.md", "archive.md"], names);
+        assert_eq!("work/plan.md", listings[0].children[0].path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_a_directory() -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(test_notes("browser_directory")?);
+
+        assert_eq!(vec!["plan.md"], names(&browser, &NottoPath::from("work".to_string()), false, ListOrder::Name)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_out_the_hidden_files() -> Result<(), NottoError> {
+        let base_path = test_notes("browser_hidden")?;
+        fs::write(base_path.join(".new.md.lock"), "{}")?;
+        fs::create_dir_all(base_path.join(".git"))?;
+        let browser = NoteBrowser::new(base_path);
+
+        assert_eq!(vec!["work", "archive.md", "new.md"], names(&browser, &NottoPath::new(), true, ListOrder::Name)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_the_missing_directories() -> Result<(), NottoError> {
+        let browser = NoteBrowser::new(test_notes("browser_missing")?);
+
+        assert!(browser.list(&NottoPath::from("nowhere".to_string()), false, ListOrder::Name).is_err());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,310 @@
+use std::{collections::HashMap, fs::{self, OpenOptions}, io::{self, Read, Write}, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use argon2::Argon2;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore}};
+use log::warn;
+use zeroize::Zeroizing;
+
+use crate::errors::NottoError;
+
+const ARMOR_BEGIN: &str = "-----BEGIN NOTTO ENCRYPTED NOTE-----";
+const ARMOR_END: &str = "-----END NOTTO ENCRYPTED NOTE-----";
+const ARMOR_VERSION: &str = "1";
+const ARMOR_LINE_LENGTH: usize = 64;
+const SALT_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+type DerivedKey = Zeroizing<[u8; KEY_LENGTH]>;
+
+/// Encrypts the content of the notes with a key derived from a passphrase
+///
+/// The key is derived with Argon2id from the passphrase and a random salt, the content is encrypted with
+/// ChaCha20-Poly1305 and a random nonce. The salt and the nonce are kept in the armor of the note,
+/// the notes encrypted by the same cipher share a salt so the key is only derived once
+#[derive(Clone)]
+pub struct NoteCipher {
+    passphrase: Arc<Zeroizing<String>>,
+    salt: [u8; SALT_LENGTH],
+    /// The keys already derived, by salt
+    keys: Arc<Mutex<HashMap<Vec<u8>, DerivedKey>>>,
+}
+
+impl NoteCipher {
+    pub fn new<S: Into<String>>(passphrase: S) -> Result<Self, NottoError> {
+        let passphrase = Zeroizing::new(passphrase.into());
+        if passphrase.is_empty() {
+            return Err(encryption_error("the passphrase is empty"));
+        }
+        let mut salt = [0; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+
+        Ok(Self { passphrase: Arc::new(passphrase), salt, keys: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    /// The armored ciphertext of `plaintext`
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, NottoError> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher(&self.salt)?
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| encryption_error("can't encrypt the note"))?;
+
+        let mut armor = format!("{}\nVersion: {}\nSalt: {}\nNonce: {}\n\n", ARMOR_BEGIN, ARMOR_VERSION, base64::encode(self.salt), base64::encode(nonce));
+        for line in base64::encode(ciphertext).as_bytes().chunks(ARMOR_LINE_LENGTH) {
+            armor.push_str(&String::from_utf8_lossy(line));
+            armor.push('\n');
+        }
+        armor.push_str(ARMOR_END);
+
+        Ok(armor)
+    }
+
+    /// The plaintext of an armored ciphertext
+    pub fn decrypt(&self, armored: &str) -> Result<String, NottoError> {
+        let armor = Armor::parse(armored).ok_or_else(|| encryption_error("the encrypted content is damaged"))?;
+        let plaintext = self.cipher(&armor.salt)?
+            .decrypt(Nonce::from_slice(&armor.nonce), armor.ciphertext.as_ref())
+            .map_err(|_| encryption_error("wrong passphrase or damaged note"))?;
+
+        String::from_utf8(plaintext).map_err(|_| encryption_error("the decrypted content is not text"))
+    }
+
+    fn cipher(&self, salt: &[u8]) -> Result<ChaCha20Poly1305, NottoError> {
+        let mut keys = self.keys.lock().map_err(|_| encryption_error("the keys are poisoned"))?;
+        if !keys.contains_key(salt) {
+            let mut key = Zeroizing::new([0; KEY_LENGTH]);
+            Argon2::default()
+                .hash_password_into(self.passphrase.as_bytes(), salt, key.as_mut())
+                .map_err(|e| encryption_error(&format!("can't derive the key: {}", e)))?;
+            keys.insert(salt.to_vec(), key);
+        }
+
+        Ok(ChaCha20Poly1305::new(Key::from_slice(keys[salt].as_ref())))
+    }
+}
+
+/// Whether the content of a note is an encrypted armor
+pub fn is_encrypted(content: &str) -> bool {
+    content.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+struct Armor {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+impl Armor {
+    fn parse(text: &str) -> Option<Armor> {
+        let mut lines = text.trim().lines().map(str::trim);
+        if lines.next()? != ARMOR_BEGIN {
+            return None;
+        }
+
+        let (mut version, mut salt, mut nonce) = (None, None, None);
+        for line in &mut lines {
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(':')?;
+            match name.trim() {
+                "Version" => version = Some(value.trim().to_string()),
+                "Salt" => salt = base64::decode(value.trim()).ok(),
+                "Nonce" => nonce = base64::decode(value.trim()).ok(),
+                _ => {}
+            }
+        }
+        if version.as_deref() != Some(ARMOR_VERSION) {
+            return None;
+        }
+
+        let mut body = String::new();
+        for line in lines {
+            if line == ARMOR_END {
+                let nonce = nonce.filter(|nonce| nonce.len() == 12)?;
+                return Some(Armor { salt: salt?, nonce, ciphertext: base64::decode(body).ok()? });
+            }
+            body.push_str(line);
+        }
+
+        None
+    }
+}
+
+/// A file only the user can read with the plaintext of an encrypted note, while it's in the editor
+///
+/// It's overwritten and removed when dropped
+pub struct PlaintextFile {
+    path: PathBuf,
+}
+
+impl PlaintextFile {
+    pub fn create(path: PathBuf, text: &str) -> Result<PlaintextFile, NottoError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        let plaintext_file = PlaintextFile { path };
+        file.write_all(text.as_bytes())?;
+
+        Ok(plaintext_file)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn read(&self) -> Result<String, NottoError> {
+        Ok(fs::read_to_string(&self.path)?)
+    }
+}
+
+impl Drop for PlaintextFile {
+    fn drop(&mut self) {
+        if let Err(e) = secure_remove(&self.path) {
+            warn!("Can't remove the plaintext file {}: {}", self.path.to_string_lossy(), e);
+        }
+    }
+}
+
+/// Overwrites a file with zeros before removing it
+///
+/// It's a best effort, an editor may have written its own copies and the filesystem may keep the old blocks
+pub fn secure_remove(path: &Path) -> Result<(), NottoError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let length = fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    io::copy(&mut io::repeat(0).take(length), &mut file)?;
+    file.sync_all()?;
+    fs::remove_file(path)?;
+
+    Ok(())
+}
+
+fn encryption_error(message: &str) -> NottoError {
+    NottoError::EncryptionError { message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, io::ReaderWriter, models::{front_matter::FrontMatter, note::Note}, test_utils::test_dir};
+
+    use super::{NoteCipher, PlaintextFile, is_encrypted};
+
+    fn bank_front_matter() -> FrontMatter {
+        FrontMatter { title: Some("Bank".to_string()), encrypted: true, ..FrontMatter::default() }
+    }
+
+    /// A reader without passphrase and a writer encrypting the notes marked as encrypted, both on an empty notes directory
+    fn test_writers(name: &str) -> Result<(ReaderWriter, ReaderWriter, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let locked = ReaderWriter::new(base_path.clone());
+        let writer = ReaderWriter::new(base_path.clone()).with_encryption(Some(NoteCipher::new("correct horse")?), false);
+
+        Ok((locked, writer, base_path))
+    }
+
+    #[test]
+    fn encrypts_and_decrypts_text() -> Result<(), NottoError> {
+        let cipher = NoteCipher::new("correct horse")?;
+        let armored = cipher.encrypt("The code is 1234")?;
+        assert!(is_encrypted(&armored));
+        assert!(!armored.contains("1234"));
+        assert_eq!("The code is 1234", cipher.decrypt(&armored)?);
+        // Another session, with its own salt
+        assert_eq!("The code is 1234", NoteCipher::new("correct horse")?.decrypt(&armored)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_wrong_passphrases_and_damaged_armors() -> Result<(), NottoError> {
+        assert!(matches!(NoteCipher::new(""), Err(NottoError::EncryptionError { .. })));
+        let cipher = NoteCipher::new("correct horse")?;
+        let armored = cipher.encrypt("The code is 1234")?;
+        assert!(matches!(NoteCipher::new("wrong horse")?.decrypt(&armored), Err(NottoError::EncryptionError { .. })));
+
+        let salt_line = armored.lines().find(|line| line.starts_with("Salt:")).unwrap();
+        let nonce_line = armored.lines().find(|line| line.starts_with("Nonce:")).unwrap();
+        let damaged = [
+            armored.replace("Version: 1", "Version: 9"),
+            armored.replace(salt_line, "Salt: ***"),
+            armored.replace(salt_line, "Salt: "),
+            armored.replace(nonce_line, "Nonce: AAAA"),
+            armored.replace("-----END NOTTO ENCRYPTED NOTE-----", ""),
+            armored.replacen('\n', "\nGarbage\n", 1),
+            format!("{}!", &armored[..armored.len() - 40]),
+            "-----BEGIN NOTTO ENCRYPTED NOTE-----".to_string(),
+            String::new(),
+        ];
+        for armored in &damaged {
+            assert!(matches!(cipher.decrypt(armored), Err(NottoError::EncryptionError { .. })), "{}", armored);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypts_the_notes_marked_as_encrypted() -> Result<(), NottoError> {
+        let (locked, writer, _) = test_writers("crypto_per_note")?;
+
+        assert!(matches!(locked.save_note_at(Note::new(bank_front_matter(), "The code is 1234"), "", "bank", false), Err(NottoError::NoteLocked { .. })));
+        let path = writer.save_note_at(Note::new(bank_front_matter(), "The code is 1234"), "", "bank", false)?;
+        let text = writer.read_text(&path)?;
+        assert!(text.contains("title: Bank") && text.contains("encrypted: true") && !text.contains("1234"));
+        assert_eq!("The code is 1234", writer.read_note(&path)?.content);
+        assert!(is_encrypted(&locked.read_note(&path)?.content));
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_notes_already_encrypted() -> Result<(), NottoError> {
+        let (locked, writer, _) = test_writers("crypto_kept")?;
+
+        let path = writer.save_note_at(Note::new(bank_front_matter(), "The code is 1234"), "", "bank", false)?;
+        let text = writer.read_text(&path)?;
+        locked.update_note(locked.read_note(&path)?, &path)?;
+        assert_eq!(text, writer.read_text(&path)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn encrypts_every_note_of_an_encrypted_context() -> Result<(), NottoError> {
+        let base_path = test_dir("crypto_per_context")?;
+        let context_writer = ReaderWriter::new(base_path).with_encryption(Some(NoteCipher::new("correct horse")?), true);
+
+        let path = context_writer.save_note_at(Note::new(FrontMatter::default(), "Salary: 100"), "", "hr", false)?;
+        assert!(context_writer.read_note(&path)?.front_matter.encrypted);
+        assert!(!context_writer.read_text(&path)?.contains("100"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_the_plaintext_files() -> Result<(), NottoError> {
+        let plaintext_path = test_dir("crypto_plaintext")?.join("plaintext.md");
+
+        let plaintext = PlaintextFile::create(plaintext_path.clone(), "Salary: 100")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(0o600, fs::metadata(&plaintext_path)?.permissions().mode() & 0o777);
+        }
+        assert_eq!("Salary: 100", plaintext.read()?);
+        // Never over an existing file
+        assert!(PlaintextFile::create(plaintext_path.clone(), "Salary: 200").is_err());
+        drop(plaintext);
+        assert!(!plaintext_path.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use crate::errors::NottoError;
+
+const FILE_PLACEHOLDER: &str = "{file}";
+const LINE_PLACEHOLDER: &str = "{line}";
+const COLUMN_PLACEHOLDER: &str = "{column}";
+
+/// A position in a file, both starting at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The editor setting, parsed like a shell command
+///
+/// The arguments can use `{file}`, `{line}` and `{column}`, like `code --wait --goto {file}:{line}:{column}`.
+/// Without `{file}` the arguments to open a file at a position are added for the known editors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl EditorCommand {
+    pub fn parse<S>(editor: S) -> Result<EditorCommand, NottoError> where S: AsRef<str> {
+        let editor = editor.as_ref();
+        let error = |message: &str| NottoError::EditorError { editor: editor.to_string(), message: message.to_string() };
+
+        let mut words = split_words(editor).map_err(error)?.into_iter();
+        match words.next() {
+            Some(program) => Ok(EditorCommand { program, args: words.collect() }),
+            None => Err(error("the editor is empty"))
+        }
+    }
+
+    /// The arguments to open `file`, at the position if the editor supports it
+    pub fn args_for(&self, file: &Path, position: Option<Position>) -> Vec<String> {
+        let has_file = self.args.iter().any(|arg| arg.contains(FILE_PLACEHOLDER));
+        let mut args = self.args.clone();
+        if !has_file {
+            args.extend(self.default_args(position.is_some()).iter().map(|arg| arg.to_string()));
+        }
+
+        let position = position.unwrap_or(Position { line: 1, column: 1 });
+        let file = file.to_string_lossy();
+        args.iter()
+            .map(|arg| arg
+                .replace(FILE_PLACEHOLDER, &file)
+                .replace(LINE_PLACEHOLDER, &position.line.to_string())
+                .replace(COLUMN_PLACEHOLDER, &position.column.to_string()))
+            .collect()
+    }
+
+    /// How the known editors open a file at a position
+    fn default_args(&self, with_position: bool) -> &'static [&'static str] {
+        if !with_position {
+            return &[FILE_PLACEHOLDER];
+        }
+        let name = Path::new(&self.program).file_stem().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match name.as_str() {
+            "vi" | "vim" | "nvim" | "gvim" | "mvim" => &["+call cursor({line}, {column})", FILE_PLACEHOLDER],
+            "emacs" | "emacsclient" | "nano" => &["+{line}:{column}", FILE_PLACEHOLDER],
+            "hx" | "helix" => &["{file}:{line}:{column}"],
+            "code" | "code-insiders" | "codium" => &["--goto", "{file}:{line}:{column}"],
+            "subl" => &["{file}:{line}:{column}"],
+            _ => &[FILE_PLACEHOLDER]
+        }
+    }
+}
+
+/// Splits a command into words, with `'single'` and `"double"` quotes and `\` escapes
+pub(crate) fn split_words(command: &str) -> Result<Vec<String>, &'static str> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("missing closing quote `'`")
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("missing closing quote `\"`")
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("missing closing quote `\"`")
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("nothing to escape after `\\`")
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{EditorCommand, Position};
+
+    #[test]
+    fn parses_editor_commands() {
+        let editor = EditorCommand::parse(r#"'/Applications/My Editor' --wait "--title=\"notes\"" a\ b"#).unwrap();
+        assert_eq!("/Applications/My Editor", editor.program);
+        assert_eq!(vec!["--wait", "--title=\"notes\"", "a b"], editor.args);
+
+        assert!(EditorCommand::parse("vim 'unclosed").is_err());
+        assert!(EditorCommand::parse("  ").is_err());
+    }
+
+    #[test]
+    fn builds_arguments() {
+        let file = Path::new("/notes/todo list.md");
+        let position = Some(Position { line: 12, column: 3 });
+
+        let code = EditorCommand::parse("code --wait").unwrap();
+        assert_eq!(vec!["--wait", "--goto", "/notes/todo list.md:12:3"], code.args_for(file, position));
+        assert_eq!(vec!["--wait", "/notes/todo list.md"], code.args_for(file, None));
+
+        let vim = EditorCommand::parse("nvim").unwrap();
+        assert_eq!(vec!["+call cursor(12, 3)", "/notes/todo list.md"], vim.args_for(file, position));
+
+        let custom = EditorCommand::parse("myeditor --line={line} {file}").unwrap();
+        assert_eq!(vec!["--line=12", "/notes/todo list.md"], custom.args_for(file, position));
+        assert_eq!(vec!["--line=1", "/notes/todo list.md"], custom.args_for(file, None));
+
+        let unknown = EditorCommand::parse("ed").unwrap();
+        assert_eq!(vec!["/notes/todo list.md"], unknown.args_for(file, position));
+    }
+}
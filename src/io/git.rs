@@ -0,0 +1,236 @@
+use std::{path::{Path, PathBuf}, process::{Command, Output}};
+
+use crate::{errors::NottoError, models::note::Note};
+
+use super::{history::Revision, links::to_link};
+
+/// Separates the fields of a commit in the `git log` output
+const FIELD_SEPARATOR: char = '\u{1f}';
+/// Starts every commit in the `git log` output
+const COMMIT_SEPARATOR: char = '\u{1e}';
+/// Used when git has no identity configured, like on a new machine
+const FALLBACK_NAME: &str = "notto";
+const FALLBACK_EMAIL: &str = "notto@localhost";
+
+/// What happened to a note, to write the commit message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitOperation {
+    Save,
+    Move,
+    Delete,
+}
+
+/// The notes directory as a git repository, every change of a note is committed
+///
+/// It uses the `git` command, everything is local, nothing is pushed
+#[derive(Debug, Clone)]
+pub struct GitRepo {
+    base_path: PathBuf,
+}
+
+impl GitRepo {
+    /// Uses the repository of the notes directory, it's created if the directory isn't in one
+    pub fn open(base_path: PathBuf) -> Result<GitRepo, NottoError> {
+        let repo = GitRepo { base_path };
+        let inside = repo.git(&["rev-parse", "--is-inside-work-tree"]).map(|output| output.status.success()).unwrap_or(false);
+        if inside {
+            Ok(repo)
+        } else {
+            GitRepo::init(repo.base_path)
+        }
+    }
+
+    /// Creates a repository in the notes directory, even if it's already in another one
+    pub fn init(base_path: PathBuf) -> Result<GitRepo, NottoError> {
+        let repo = GitRepo { base_path };
+        repo.run(&["init", "--quiet"])?;
+
+        Ok(repo)
+    }
+
+    /// Commits the changes of the note, and its old path for a move, does nothing if they didn't change
+    pub fn commit(&self, operation: GitOperation, note: &Note, note_path: &Path, old_path: Option<&Path>) -> Result<(), NottoError> {
+        let mut paths = vec![to_link(note_path)];
+        if let Some(old_path) = old_path {
+            paths.push(to_link(old_path));
+        }
+        let mut status_args = vec!["status", "--porcelain", "--"];
+        status_args.extend(paths.iter().map(String::as_str));
+        if self.run(&status_args)?.trim().is_empty() {
+            return Ok(());
+        }
+
+        let mut add_args = vec!["add", "--all", "--"];
+        add_args.extend(paths.iter().map(String::as_str));
+        self.run(&add_args)?;
+
+        let message = match (operation, old_path) {
+            (GitOperation::Move, Some(old_path)) => format!("Move \"{}\" from {} to {}", note.get_title(), to_link(old_path), paths[0]),
+            (GitOperation::Delete, _) => format!("Delete \"{}\" ({})", note.get_title(), paths[0]),
+            _ => format!("Save \"{}\" ({})", note.get_title(), paths[0]),
+        };
+        let mut commit_args = self.identity();
+        commit_args.extend(vec!["commit", "--quiet", "--no-verify", "-m", &message, "--"].into_iter().map(String::from));
+        commit_args.extend(paths);
+        self.run(&commit_args.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+        Ok(())
+    }
+
+    /// The revisions of the note at `note_path`, the latest first, following its moves
+    ///
+    /// Deletions are skipped, the first revision of a deleted note is its last content
+    pub fn history(&self, note_path: &Path) -> Result<Vec<Revision>, NottoError> {
+        let format = format!("--format={}%H{}%aI{}%s", COMMIT_SEPARATOR, FIELD_SEPARATOR, FIELD_SEPARATOR);
+        let path = to_link(note_path);
+        let output = match self.run(&["log", "--follow", "--diff-filter=AMR", "--name-only", "--relative", &format, "--", &path]) {
+            Ok(output) => output,
+            // A repository without commits yet
+            Err(_) if self.run(&["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let revisions = output.split(COMMIT_SEPARATOR)
+            .filter_map(|commit| {
+                let mut lines = commit.lines().filter(|line| !line.trim().is_empty());
+                let mut fields = lines.next()?.split(FIELD_SEPARATOR);
+                let (hash, date, message) = (fields.next()?, fields.next()?, fields.next().unwrap_or_default());
+                let path = lines.next_back().map(PathBuf::from).unwrap_or_else(|| note_path.to_path_buf());
+                Some((hash.to_string(), date.to_string(), message.to_string(), path))
+            })
+            .enumerate()
+            .map(|(index, (id, date, message, location))| Revision { number: index + 1, id, date, message, location })
+            .collect();
+
+        Ok(revisions)
+    }
+
+    /// The text of the note in a revision
+    pub fn read_revision(&self, revision: &Revision) -> Result<String, NottoError> {
+        self.run(&["show", &format!("{}:./{}", revision.id, to_link(&revision.location))])
+    }
+
+    /// `user.name` and `user.email` if git doesn't have them
+    fn identity(&self) -> Vec<String> {
+        let has_identity = self.run(&["config", "user.email"]).map(|email| !email.trim().is_empty()).unwrap_or(false);
+        if has_identity {
+            vec![]
+        } else {
+            vec!["-c".to_string(), format!("user.name={}", FALLBACK_NAME), "-c".to_string(), format!("user.email={}", FALLBACK_EMAIL)]
+        }
+    }
+
+    fn git(&self, args: &[&str]) -> Result<Output, NottoError> {
+        Command::new("git")
+            .args(args)
+            .current_dir(&self.base_path)
+            .output()
+            .map_err(|e| NottoError::GitError { message: format!("can't run git: {}", e) })
+    }
+
+    /// Runs a git command in the notes directory, returns its output
+    fn run(&self, args: &[&str]) -> Result<String, NottoError> {
+        let output = self.git(args)?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            let command = args.iter().find(|arg| !arg.starts_with('-') && !arg.contains('=')).unwrap_or(&"");
+            Err(NottoError::GitError { message: format!("`git {}` failed: {}", command, String::from_utf8_lossy(&output.stderr).trim()) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::{errors::NottoError, io::{ReaderWriter, history::Revision}, models::{front_matter::FrontMatter, note::Note}, test_utils::test_dir};
+
+    use super::GitRepo;
+
+    /// A new repository in an empty notes directory, with a writer committing to it
+    fn test_repo(name: &str) -> Result<(GitRepo, ReaderWriter, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        // The test directory is in the repository of notto
+        let repo = GitRepo::init(base_path.clone())?;
+        let writer = ReaderWriter::new(base_path.clone()).with_git(repo.clone());
+
+        Ok((repo, writer, base_path))
+    }
+
+    /// The note `Plan`, always with the same front matter
+    fn plan(content: &str) -> Note {
+        Note::new(FrontMatter { id: "8f14e45f".to_string(), title: Some("Plan".to_string()), ..FrontMatter::default() }, content)
+    }
+
+    #[test]
+    fn creates_the_repository() -> Result<(), NottoError> {
+        let (repo, _, base_path) = test_repo("git_init")?;
+
+        assert!(base_path.join(".git").is_dir());
+        assert!(repo.history(Path::new("plan.md"))?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_the_changes_of_the_notes() -> Result<(), NottoError> {
+        let (repo, writer, _) = test_repo("git_commits")?;
+
+        writer.save_note_at(plan("Step 1"), "", "plan", false)?;
+        writer.update_note(plan("Step 1\nStep 2"), "plan.md")?;
+        // Nothing changed, no commit
+        writer.update_note(plan("Step 1\nStep 2"), "plan.md")?;
+
+        let history = repo.history(Path::new("plan.md"))?;
+        assert_eq!(2, history.len());
+        assert_eq!("Save \"Plan\" (plan.md)", history[1].message);
+        assert_eq!(2, history[1].number);
+        assert!(repo.read_revision(&history[1])?.ends_with("Step 1"));
+        assert!(repo.read_revision(&history[0])?.ends_with("Step 1\nStep 2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn follows_the_moves() -> Result<(), NottoError> {
+        let (repo, writer, _) = test_repo("git_moves")?;
+
+        writer.save_note_at(plan("Step 1"), "", "plan", false)?;
+        let moved = writer.move_note("plan.md", "projects", "plan")?;
+
+        let history = repo.history(&moved)?;
+        assert_eq!(2, history.len());
+        assert_eq!("Move \"Plan\" from plan.md to projects/plan.md", history[0].message);
+        assert!(repo.read_revision(&history[1])?.ends_with("Step 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_history_of_the_deleted_notes() -> Result<(), NottoError> {
+        let (repo, writer, _) = test_repo("git_deletes")?;
+
+        let path = writer.save_note_at(plan("Step 1"), "", "plan", false)?;
+        writer.delete_note(&path)?;
+
+        let history = repo.history(&path)?;
+        assert_eq!(1, history.len());
+        assert!(repo.read_revision(&history[0])?.ends_with("Step 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn passes_the_paths_as_paths() -> Result<(), NottoError> {
+        let (repo, writer, base_path) = test_repo("git_paths")?;
+
+        writer.save_note_at(plan("Step 1"), "", "plan", false)?;
+        assert!(repo.history(Path::new("--output=leak.md"))?.is_empty());
+        assert!(!base_path.join("leak.md").exists());
+        let unknown = Revision { number: 1, id: "0123456789abcdef".to_string(), date: String::new(), message: String::new(), location: PathBuf::from("plan.md") };
+        assert!(matches!(repo.read_revision(&unknown), Err(NottoError::GitError { .. })));
+
+        Ok(())
+    }
+}
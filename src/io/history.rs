@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use similar::TextDiff;
+
+use crate::errors::NottoError;
+
+use super::{ReaderWriter, git::GitRepo, snapshots::SnapshotStore};
+
+/// A version of a note, in git or in the snapshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    /// 1 is the latest revision, 2 the one before...
+    pub number: usize,
+    /// The commit hash or the snapshot name
+    pub id: String,
+    /// When the revision was made, in RFC 3339
+    pub date: String,
+    pub message: String,
+    /// The path of the note in the git revision or the snapshot file
+    pub(crate) location: PathBuf,
+}
+
+/// Where the versions of the notes of a context are kept
+pub enum NoteHistory {
+    Git(GitRepo),
+    Snapshots(SnapshotStore),
+}
+
+impl NoteHistory {
+    /// The revisions of the note at `note_path`, the latest first
+    pub fn revisions(&self, reader: &ReaderWriter, note_path: &Path) -> Result<Vec<Revision>, NottoError> {
+        match self {
+            NoteHistory::Git(repo) => repo.history(note_path),
+            NoteHistory::Snapshots(store) => {
+                if !reader.get_full_path(note_path).is_file() {
+                    return Err(NottoError::FileError { message: format!("note `{}` not found, the snapshots of deleted notes can't be found by name", note_path.to_string_lossy()) });
+                }
+                let text = reader.read_text(note_path)?;
+                store.history(&SnapshotStore::key(&text, note_path))
+            }
+        }
+    }
+
+    /// The text of the note in a revision
+    pub fn read_revision(&self, revision: &Revision) -> Result<String, NottoError> {
+        match self {
+            NoteHistory::Git(repo) => repo.read_revision(revision),
+            NoteHistory::Snapshots(store) => store.read_revision(revision),
+        }
+    }
+}
+
+/// The changes from `old` to `new` as a unified diff, empty if they're the same
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_name, new_name)
+        .to_string()
+}
@@ -0,0 +1,322 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+use crate::errors::NottoError;
+
+const HOME_ENV: &str = "NOTTO_HOME";
+const APP_DIR: &str = "notto";
+/// The directory of the first versions of notto, in the user home
+const LEGACY_DIR: &str = ".notto";
+const CONFIG_FILE_NAME: &str = "config";
+const NOTES_DIR: &str = "notes";
+const LEGACY_TEMP_DIR: &str = ".temp";
+const TEMP_DIR: &str = "tmp";
+const SNAPSHOTS_DIR: &str = "snapshots";
+
+/// How the directories of notto were chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeLayout {
+    /// Everything in the `NOTTO_HOME` directory
+    Custom,
+    /// Everything in `~/.notto`, from before the XDG directories
+    Legacy,
+    /// The config, the notes and the temp files in the XDG config, data and state directories
+    Xdg,
+}
+
+/// The directories where notto keeps its config, the notes of the default context and its temp files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NottoHome {
+    pub layout: HomeLayout,
+    pub config_dir: PathBuf,
+    pub notes_dir: PathBuf,
+    pub temp_dir: PathBuf,
+    /// The versions of the notes of the contexts without git
+    pub snapshots_dir: PathBuf,
+}
+
+/// What was copied migrating `~/.notto` to the XDG directories
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub from: PathBuf,
+    pub to: Option<NottoHome>,
+    pub files: usize,
+}
+
+impl NottoHome {
+    /// Finds the directories from the environment, nothing is created
+    ///
+    /// `NOTTO_HOME` goes first, then the XDG directories, unless there's only a `~/.notto` directory
+    pub fn locate() -> Result<NottoHome, NottoError> {
+        NottoHome::from_env(|name| env::var_os(name).map(PathBuf::from), dirs::home_dir())
+    }
+
+    /// Finds the directories with the given environment variables and user home
+    pub fn from_env<F>(var: F, user_home: Option<PathBuf>) -> Result<NottoHome, NottoError> where F: Fn(&str) -> Option<PathBuf> {
+        if let Some(home) = var(HOME_ENV).filter(|home| !home.as_os_str().is_empty()) {
+            return Ok(NottoHome::in_dir(HomeLayout::Custom, home));
+        }
+
+        let user_home = user_home.ok_or(NottoError::HomeDirectoryNotFound)?;
+        let xdg = NottoHome::xdg(&var, &user_home);
+        let legacy_dir = user_home.join(LEGACY_DIR);
+        if legacy_dir.is_dir() && !xdg.config_file().exists() {
+            Ok(NottoHome::in_dir(HomeLayout::Legacy, legacy_dir))
+        } else {
+            Ok(xdg)
+        }
+    }
+
+    fn in_dir(layout: HomeLayout, dir: PathBuf) -> NottoHome {
+        NottoHome {
+            layout,
+            notes_dir: dir.join(NOTES_DIR),
+            temp_dir: dir.join(LEGACY_TEMP_DIR),
+            snapshots_dir: dir.join(SNAPSHOTS_DIR),
+            config_dir: dir,
+        }
+    }
+
+    fn xdg<F>(var: &F, user_home: &Path) -> NottoHome where F: Fn(&str) -> Option<PathBuf> {
+        // The spec says relative paths have to be ignored
+        let base_dir = |name: &str, default: &[&str]| var(name)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| default.iter().fold(user_home.to_path_buf(), |dir, part| dir.join(part)))
+            .join(APP_DIR);
+
+        NottoHome {
+            layout: HomeLayout::Xdg,
+            config_dir: base_dir("XDG_CONFIG_HOME", &[".config"]),
+            notes_dir: base_dir("XDG_DATA_HOME", &[".local", "share"]).join(NOTES_DIR),
+            temp_dir: base_dir("XDG_STATE_HOME", &[".local", "state"]).join(TEMP_DIR),
+            snapshots_dir: base_dir("XDG_DATA_HOME", &[".local", "share"]).join(SNAPSHOTS_DIR),
+        }
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE_NAME)
+    }
+
+    /// The config directory, created if needed
+    pub fn ensure_config_dir(&self) -> Result<PathBuf, NottoError> {
+        ensure_dir(&self.config_dir)
+    }
+
+    /// The notes directory of the default context, created if needed
+    pub fn ensure_notes_dir(&self) -> Result<PathBuf, NottoError> {
+        ensure_dir(&self.notes_dir)
+    }
+
+    /// The directory for temporary notes, created if needed
+    pub fn ensure_temp_dir(&self) -> Result<PathBuf, NottoError> {
+        ensure_dir(&self.temp_dir)
+    }
+
+    /// The directory for the snapshots of the notes, created if needed
+    pub fn ensure_snapshots_dir(&self) -> Result<PathBuf, NottoError> {
+        ensure_dir(&self.snapshots_dir)
+    }
+
+    /// Copies the config and the notes of `~/.notto` to the XDG directories
+    ///
+    /// Nothing is removed, once the copy is checked `~/.notto` can be deleted by hand.
+    /// Fails if notto is not using `~/.notto` or if there's already a config in the XDG directories
+    pub fn migrate_legacy(&self) -> Result<MigrationReport, NottoError> {
+        if self.layout != HomeLayout::Legacy {
+            return Err(NottoError::FileError { message: format!("nothing to migrate, the config is in `{}`", self.config_dir.to_string_lossy()) });
+        }
+        let user_home = self.config_dir.parent().map(Path::to_path_buf).ok_or(NottoError::HomeDirectoryNotFound)?;
+        self.copy_to(NottoHome::xdg(&|name: &str| env::var_os(name).map(PathBuf::from), &user_home))
+    }
+
+    fn copy_to(&self, xdg: NottoHome) -> Result<MigrationReport, NottoError> {
+        if xdg.config_file().exists() {
+            return Err(NottoError::FileError { message: format!("there's already a config at `{}`", xdg.config_file().to_string_lossy()) });
+        }
+        if xdg.notes_dir.exists() && fs::read_dir(&xdg.notes_dir)?.next().is_some() {
+            return Err(NottoError::FileError { message: format!("the notes directory `{}` is not empty", xdg.notes_dir.to_string_lossy()) });
+        }
+
+        let mut report = MigrationReport { from: self.config_dir.clone(), ..MigrationReport::default() };
+        if self.notes_dir.is_dir() {
+            report.files += copy_dir(&self.notes_dir, &xdg.ensure_notes_dir()?)?;
+        }
+        if self.snapshots_dir.is_dir() {
+            report.files += copy_dir(&self.snapshots_dir, &xdg.ensure_snapshots_dir()?)?;
+        }
+        // The config goes last, once it's there the XDG directories are used
+        if self.config_file().is_file() {
+            xdg.ensure_config_dir()?;
+            fs::copy(self.config_file(), xdg.config_file())?;
+            report.files += 1;
+        }
+        report.to = Some(xdg);
+
+        Ok(report)
+    }
+}
+
+/// Creates the directory if it doesn't exist, a file in its place is an error
+fn ensure_dir(dir: &Path) -> Result<PathBuf, NottoError> {
+    if dir.exists() && !dir.is_dir() {
+        return Err(NottoError::FileError { message: format!("`{}` should be a directory but it's a file", dir.to_string_lossy()) });
+    }
+    fs::create_dir_all(dir)?;
+
+    Ok(dir.to_path_buf())
+}
+
+/// Copies the contents of a directory, returns the number of files copied
+fn copy_dir(from: &Path, to: &Path) -> Result<usize, NottoError> {
+    let mut files = 0;
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)?.flatten() {
+        let dest = to.join(entry.file_name());
+        if entry.path().is_dir() {
+            files += copy_dir(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+            files += 1;
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, test_utils::test_dir};
+
+    use super::{HomeLayout, NottoHome};
+
+    fn no_vars(_: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// An empty user home
+    fn test_user_home(name: &str) -> Result<PathBuf, NottoError> {
+        let user_home = test_dir(name)?.join("user");
+        fs::create_dir_all(&user_home)?;
+
+        Ok(user_home)
+    }
+
+    /// A user home with a `~/.notto` directory holding a config, a note and a snapshot, and the home using it
+    fn test_legacy_home(name: &str) -> Result<(PathBuf, NottoHome), NottoError> {
+        let user_home = test_user_home(name)?;
+        fs::create_dir_all(user_home.join(".notto/notes"))?;
+        fs::write(user_home.join(".notto/config"), "context: default\n")?;
+        fs::write(user_home.join(".notto/notes/todo.md"), "Buy milk")?;
+        fs::create_dir_all(user_home.join(".notto/snapshots/todo.md"))?;
+        fs::write(user_home.join(".notto/snapshots/todo.md/1620000000"), "Buy bread")?;
+        let home = NottoHome::from_env(no_vars, Some(user_home.clone()))?;
+
+        Ok((user_home, home))
+    }
+
+    #[test]
+    fn uses_the_xdg_directories() -> Result<(), NottoError> {
+        let user_home = test_user_home("home_xdg")?;
+
+        let home = NottoHome::from_env(no_vars, Some(user_home.clone()))?;
+        assert_eq!(HomeLayout::Xdg, home.layout);
+        assert_eq!(user_home.join(".config/notto/config"), home.config_file());
+        assert_eq!(user_home.join(".local/share/notto/notes"), home.notes_dir);
+        assert_eq!(user_home.join(".local/state/notto/tmp"), home.temp_dir);
+        assert_eq!(user_home.join(".local/share/notto/snapshots"), home.snapshots_dir);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_the_relative_xdg_directories() -> Result<(), NottoError> {
+        let user_home = test_user_home("home_xdg_vars")?;
+
+        let xdg_vars = |name: &str| match name {
+            "XDG_CONFIG_HOME" => Some(PathBuf::from("/etc/xdg")),
+            "XDG_DATA_HOME" => Some(PathBuf::from("relative/data")),
+            _ => None
+        };
+        let home = NottoHome::from_env(xdg_vars, Some(user_home.clone()))?;
+        assert_eq!(PathBuf::from("/etc/xdg/notto"), home.config_dir);
+        assert_eq!(user_home.join(".local/share/notto/notes"), home.notes_dir);
+
+        Ok(())
+    }
+
+    #[test]
+    fn uses_notto_home_first() -> Result<(), NottoError> {
+        let custom_vars = |name: &str| if name == "NOTTO_HOME" { Some(PathBuf::from("/srv/notto")) } else { None };
+        let home = NottoHome::from_env(custom_vars, None)?;
+        assert_eq!(HomeLayout::Custom, home.layout);
+        assert_eq!(PathBuf::from("/srv/notto/notes"), home.notes_dir);
+
+        // An empty `NOTTO_HOME` is not set
+        let empty_vars = |name: &str| if name == "NOTTO_HOME" { Some(PathBuf::new()) } else { None };
+        let user_home = test_user_home("home_custom")?;
+        assert_eq!(HomeLayout::Xdg, NottoHome::from_env(empty_vars, Some(user_home))?.layout);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_without_a_user_home() {
+        assert!(matches!(NottoHome::from_env(no_vars, None), Err(NottoError::HomeDirectoryNotFound)));
+    }
+
+    #[test]
+    fn uses_the_legacy_directory_until_migrated() -> Result<(), NottoError> {
+        let (user_home, home) = test_legacy_home("home_legacy")?;
+
+        assert_eq!(HomeLayout::Legacy, home.layout);
+        assert_eq!(user_home.join(".notto/notes"), home.notes_dir);
+        home.copy_to(NottoHome::xdg(&no_vars, &user_home))?;
+        assert_eq!(HomeLayout::Xdg, NottoHome::from_env(no_vars, Some(user_home))?.layout);
+
+        Ok(())
+    }
+
+    #[test]
+    fn copies_the_notes_snapshots_and_config() -> Result<(), NottoError> {
+        let (user_home, home) = test_legacy_home("home_copy")?;
+
+        let report = home.copy_to(NottoHome::xdg(&no_vars, &user_home))?;
+        assert_eq!(3, report.files);
+        assert_eq!("Buy milk", fs::read_to_string(user_home.join(".local/share/notto/notes/todo.md"))?);
+        assert_eq!("Buy bread", fs::read_to_string(user_home.join(".local/share/notto/snapshots/todo.md/1620000000"))?);
+        assert!(user_home.join(".config/notto/config").exists());
+        // Nothing is removed
+        assert!(user_home.join(".notto/notes/todo.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn never_migrates_over_other_notes() -> Result<(), NottoError> {
+        let (user_home, home) = test_legacy_home("home_copy_again")?;
+        let xdg = NottoHome::xdg(&no_vars, &user_home);
+
+        fs::create_dir_all(&xdg.notes_dir)?;
+        fs::write(xdg.notes_dir.join("other.md"), "Other")?;
+        assert!(home.copy_to(xdg.clone()).is_err());
+        fs::remove_file(xdg.notes_dir.join("other.md"))?;
+        home.copy_to(xdg.clone())?;
+        assert!(home.copy_to(xdg.clone()).is_err());
+        assert!(xdg.migrate_legacy().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_files_in_place_of_the_directories() -> Result<(), NottoError> {
+        let user_home = test_user_home("home_files")?;
+        let home = NottoHome::from_env(no_vars, Some(user_home))?;
+
+        fs::create_dir_all(home.notes_dir.parent().unwrap())?;
+        fs::write(&home.notes_dir, "Not a directory")?;
+        assert!(home.ensure_notes_dir().is_err());
+
+        Ok(())
+    }
+}
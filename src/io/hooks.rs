@@ -0,0 +1,283 @@
+use std::{fmt, io::{ErrorKind, Write}, path::{Path, PathBuf}, process::{Command, Stdio}, str::FromStr};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::NottoError, models::{front_matter::FrontMatter, note::Note}};
+
+use super::{editor::split_words, links::to_link};
+
+/// The commands run when notes change, set per context in the config
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_create: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_save: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_delete: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_move: Option<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        HookEvent::ALL.iter().all(|event| self.get(*event).is_none())
+    }
+
+    pub fn get(&self, event: HookEvent) -> Option<&String> {
+        match event {
+            HookEvent::PreCreate => self.pre_create.as_ref(),
+            HookEvent::PostSave => self.post_save.as_ref(),
+            HookEvent::PostDelete => self.post_delete.as_ref(),
+            HookEvent::PostMove => self.post_move.as_ref(),
+        }
+    }
+
+    pub fn set(&mut self, event: HookEvent, command: Option<String>) {
+        let hook = match event {
+            HookEvent::PreCreate => &mut self.pre_create,
+            HookEvent::PostSave => &mut self.post_save,
+            HookEvent::PostDelete => &mut self.post_delete,
+            HookEvent::PostMove => &mut self.post_move,
+        };
+        *hook = command;
+    }
+}
+
+/// When a hook is run
+///
+/// A failing `pre_*` hook aborts the operation, a failing `post_*` hook is only logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    /// Before a new note is written
+    PreCreate,
+    /// After a note is written or edited
+    PostSave,
+    PostDelete,
+    PostMove,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 4] = [HookEvent::PreCreate, HookEvent::PostSave, HookEvent::PostDelete, HookEvent::PostMove];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreCreate => "pre_create",
+            HookEvent::PostSave => "post_save",
+            HookEvent::PostDelete => "post_delete",
+            HookEvent::PostMove => "post_move",
+        }
+    }
+
+    fn can_abort(&self) -> bool {
+        matches!(self, HookEvent::PreCreate)
+    }
+}
+
+impl fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        HookEvent::ALL.iter().find(|event| event.name() == value).copied().ok_or(())
+    }
+}
+
+/// The note sent to the hooks on the standard input, as JSON
+#[derive(Serialize)]
+struct HookNote<'a> {
+    /// Path of the note file, relative to the notes directory
+    path: String,
+    /// The previous path of a moved note
+    old_path: Option<String>,
+    id: &'a str,
+    title: String,
+    front_matter: &'a FrontMatter,
+    content: &'a str,
+}
+
+/// Runs the hooks of a context for the notes of its directory
+///
+/// The hook gets the note as JSON on its standard input and these variables:
+/// `NOTTO_HOOK`, `NOTTO_CONTEXT`, `NOTTO_NOTES_DIR`, `NOTTO_NOTE_PATH`, `NOTTO_NOTE_FILE`, `NOTTO_NOTE_ID`,
+/// and `NOTTO_NOTE_OLD_PATH` for moves. It runs in the notes directory
+#[derive(Debug, Clone)]
+pub struct HookRunner {
+    context: String,
+    hooks: Hooks,
+}
+
+impl HookRunner {
+    pub fn new<S: Into<String>>(context: S, hooks: Hooks) -> Self {
+        Self { context: context.into(), hooks }
+    }
+
+    /// Runs the hook of `event` if there's one, `note_path` is relative to `base_path`
+    pub fn run(&self, event: HookEvent, base_path: &Path, note_path: &Path, old_path: Option<&Path>, note: &Note) -> Result<(), NottoError> {
+        let command = match self.hooks.get(event) {
+            Some(command) => command,
+            None => return Ok(())
+        };
+
+        match self.run_command(event, command, base_path, note_path, old_path, note) {
+            Err(message) if event.can_abort() => Err(NottoError::HookFailed { hook: event.to_string(), message }),
+            Err(message) => {
+                warn!("{} hook for {} failed: {}", event, note_path.to_string_lossy(), message);
+                Ok(())
+            }
+            Ok(()) => Ok(())
+        }
+    }
+
+    fn run_command(&self, event: HookEvent, command: &str, base_path: &Path, note_path: &Path, old_path: Option<&Path>, note: &Note) -> Result<(), String> {
+        let mut words = split_words(command)?.into_iter();
+        let program = words.next().ok_or("the command is empty")?;
+
+        let hook_note = HookNote {
+            path: to_link(note_path),
+            old_path: old_path.map(to_link),
+            id: &note.front_matter.id,
+            title: note.get_title(),
+            front_matter: &note.front_matter,
+            content: &note.content,
+        };
+        let input = serde_json::to_vec(&hook_note).map_err(|e| e.to_string())?;
+
+        let mut process = Command::new(&program);
+        process.args(words)
+            .current_dir(base_path)
+            .stdin(Stdio::piped())
+            .env("NOTTO_HOOK", event.name())
+            .env("NOTTO_CONTEXT", &self.context)
+            .env("NOTTO_NOTES_DIR", base_path)
+            .env("NOTTO_NOTE_PATH", &hook_note.path)
+            .env("NOTTO_NOTE_FILE", full_path(base_path, note_path))
+            .env("NOTTO_NOTE_ID", hook_note.id);
+        if let Some(old_path) = &hook_note.old_path {
+            process.env("NOTTO_NOTE_OLD_PATH", old_path);
+        }
+
+        let mut child = process.spawn().map_err(|e| format!("can't run `{}`: {}", program, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // Hooks don't have to read the note
+            if let Err(e) = stdin.write_all(&input) {
+                if e.kind() != ErrorKind::BrokenPipe {
+                    return Err(e.to_string());
+                }
+            }
+        }
+        let status = child.wait().map_err(|e| e.to_string())?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("`{}` exited with {}", command, status))
+        }
+    }
+}
+
+fn full_path(base_path: &Path, note_path: &Path) -> PathBuf {
+    let path = base_path.join(note_path);
+    path.canonicalize().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::{Path, PathBuf}};
+
+    use crate::{errors::NottoError, io::ReaderWriter, models::{front_matter::FrontMatter, note::Note}, test_utils::test_dir};
+
+    use super::{HookRunner, Hooks};
+
+    /// A hook logging its event, context and paths to `hooks.log` and the note it reads to `stdin.log`, next to the notes directory
+    fn log(name: &str) -> Option<String> {
+        // The hooks run in the notes directory
+        Some(format!("sh -c 'echo {} $NOTTO_CONTEXT $NOTTO_NOTE_PATH $NOTTO_NOTE_OLD_PATH >> ../hooks.log; cat >> ../stdin.log'", name))
+    }
+
+    /// A writer with `hooks` in the context `work`, with the directory of the logs
+    fn test_writer(name: &str, hooks: Hooks) -> Result<(ReaderWriter, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        let notes_path = base_path.join("notes");
+        fs::create_dir_all(&notes_path)?;
+
+        Ok((ReaderWriter::new(notes_path).with_hooks(HookRunner::new("work", hooks)), base_path))
+    }
+
+    fn note(content: &str) -> Note {
+        Note::new(FrontMatter::default(), content)
+    }
+
+    #[test]
+    fn runs_the_hooks_after_the_changes() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("hooks_changes", Hooks { post_save: log("saved"), post_delete: log("deleted"), post_move: log("moved"), ..Hooks::default() })?;
+
+        writer.save_note_at(note("Call the bank"), "", "todo", false)?;
+        let moved = writer.move_note("todo.md", "done", "todo")?;
+        assert_eq!(Path::new("done").join("todo.md"), moved);
+        writer.delete_note(&moved)?;
+        assert!(!base_path.join("notes").join("done").exists());
+        assert_eq!(
+            "saved work todo.md\nmoved work done/todo.md todo.md\ndeleted work done/todo.md\n",
+            fs::read_to_string(base_path.join("hooks.log"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn sends_the_note_to_the_hooks() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("hooks_stdin", Hooks { post_save: log("saved"), post_move: log("moved"), ..Hooks::default() })?;
+
+        writer.save_note_at(note("Call the bank"), "", "todo", false)?;
+        writer.move_note("todo.md", "done", "todo")?;
+        let stdin = fs::read_to_string(base_path.join("stdin.log"))?;
+        assert_eq!(2, stdin.matches("\"content\":\"Call the bank\"").count());
+        assert!(stdin.contains("\"old_path\":\"todo.md\""), "{}", stdin);
+
+        Ok(())
+    }
+
+    #[test]
+    fn aborts_the_creation_when_pre_create_fails() -> Result<(), NottoError> {
+        let pre_create = Some("sh -c 'test \"$NOTTO_NOTE_PATH\" != secret.md'".to_string());
+        let (writer, base_path) = test_writer("hooks_pre_create", Hooks { pre_create, ..Hooks::default() })?;
+
+        writer.save_note_at(note("Call the bank"), "", "todo", false)?;
+        assert!(matches!(writer.save_note_at(note("The code is 1234"), "", "secret", false), Err(NottoError::HookFailed { .. })));
+        assert!(base_path.join("notes").join("todo.md").exists());
+        assert!(!base_path.join("notes").join("secret.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_logs_the_failures_after_the_changes() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("hooks_post_failure", Hooks { post_save: Some("false".to_string()), post_delete: Some("no-such-notto-hook".to_string()), ..Hooks::default() })?;
+
+        let path = writer.save_note_at(note("Call the bank"), "", "todo", false)?;
+        writer.delete_note(&path)?;
+        assert!(!base_path.join("notes").join("todo.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_the_commands_that_cant_run() -> Result<(), NottoError> {
+        for (name, command) in &[("missing", "no-such-notto-hook"), ("quote", "sh -c 'exit 0"), ("empty", "   ")] {
+            let (writer, base_path) = test_writer(&format!("hooks_invalid_{}", name), Hooks { pre_create: Some(command.to_string()), ..Hooks::default() })?;
+            match writer.save_note_at(note("Call the bank"), "", "todo", false) {
+                Err(NottoError::HookFailed { hook, .. }) => assert_eq!("pre_create", hook),
+                result => panic!("expected `{}` to fail, got {:?}", command, result),
+            }
+            assert!(!base_path.join("notes").join("todo.md").exists());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use std::{ops::Range, path::{Component, Path, PathBuf}};
+
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::Serialize;
+
+use super::DIR_ROOT_NOTE_NAME;
+
+const LINK_SEPARATOR: &str = "/";
+const NOTE_EXTENSION: &str = "md";
+/// Characters that can't go as they are in a markdown link destination
+const LINK_ESCAPED_CHARS: [char; 6] = [ ' ', '(', ')', '<', '>', '%' ];
+
+/// A path relative to the notes directory as a link, always separated by `/`
+pub fn to_link(path: &Path) -> String {
+    path.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<_>>().join(LINK_SEPARATOR)
+}
+
+/// The link from the file at `from` to the path `to`, both relative to the same directory
+pub fn relative_link(from: &Path, to: &Path) -> String {
+    let from_dir = from.parent().map(|p| p.components().collect::<Vec<_>>()).unwrap_or_default();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from_dir.iter().zip(to.iter()).take_while(|(a, b)| a == b).count();
+
+    let mut link = "../".repeat(from_dir.len() - common);
+    link.push_str(&to_link(&to[common..].iter().collect::<PathBuf>()));
+
+    link
+}
+
+/// Resolves a link found in the file at `from` to a path relative to the notes directory
+///
+/// Links starting with `/` are relative to the notes directory. Returns `None` for external links,
+/// anchors in the same note or links that go outside the notes directory. The link fragment is removed
+pub fn resolve_link(from: &Path, link: &str) -> Option<PathBuf> {
+    if link.is_empty() || link.starts_with('#') || link.contains("://") || link.starts_with("mailto:") {
+        return None;
+    }
+
+    let link = percent_decode(split_fragment(link).0);
+    match link.strip_prefix(LINK_SEPARATOR) {
+        Some(absolute) => normalize(Path::new(absolute)),
+        None => normalize(&from.parent().unwrap_or_else(|| Path::new("")).join(&link))
+    }
+}
+
+/// A link or an image in the content of a note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteLink {
+    pub url: String,
+    /// Where the whole link is in the content, in bytes
+    pub range: Range<usize>,
+}
+
+/// A link to a note from another note
+#[derive(Debug, Clone, Serialize)]
+pub struct Backlink {
+    /// The note with the link, relative to the notes directory
+    pub path: PathBuf,
+    pub title: String,
+    /// Line of the link in the note content, starting at 0
+    pub line: usize,
+    pub url: String,
+}
+
+/// The links and images of a markdown text
+pub fn find_links(content: &str) -> Vec<NoteLink> {
+    Parser::new(content).into_offset_iter().filter_map(|(event, range)| match event {
+        Event::Start(Tag::Link(_, url, _)) | Event::Start(Tag::Image(_, url, _)) => Some(NoteLink { url: url.to_string(), range }),
+        _ => None
+    }).collect()
+}
+
+/// The files a resolved link may point to, a link without extension points to a note or to the note of a directory
+pub fn link_candidates(path: PathBuf) -> Vec<PathBuf> {
+    if path.extension().map(|extension| extension == NOTE_EXTENSION).unwrap_or(false) {
+        vec![path]
+    } else {
+        vec![path.with_extension(NOTE_EXTENSION), path.join(DIR_ROOT_NOTE_NAME)]
+    }
+}
+
+/// Splits a link into its path and its fragment, the fragment keeps the `#`
+pub fn split_fragment(link: &str) -> (&str, &str) {
+    match link.find('#') {
+        Some(index) => (&link[..index], &link[index..]),
+        None => (link, "")
+    }
+}
+
+/// Resolves `.` and `..`, fails if the path goes above its starting point
+pub fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => if !normalized.pop() { return None; },
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(normalized)
+}
+
+/// Escapes the characters that would break a markdown link
+pub fn percent_encode(link: &str) -> String {
+    let mut encoded = String::with_capacity(link.len());
+    for c in link.chars() {
+        if LINK_ESCAPED_CHARS.contains(&c) {
+            encoded.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded
+}
+
+pub fn percent_decode(link: &str) -> String {
+    let bytes = link.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            if let Some(byte) = link.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{find_links, link_candidates, percent_decode, percent_encode, relative_link, resolve_link};
+
+    #[test]
+    fn calculates_relative_links() {
+        assert_eq!("b.html", relative_link(Path::new("a/index.html"), Path::new("a/b.html")));
+        assert_eq!("../c/d.html", relative_link(Path::new("a/b.html"), Path::new("c/d.html")));
+        assert_eq!("a/b/c.html", relative_link(Path::new("index.html"), Path::new("a/b/c.html")));
+        assert_eq!("../../", relative_link(Path::new("a/b/c.html"), Path::new("")));
+    }
+
+    #[test]
+    fn resolves_links() {
+        assert_eq!(Some(PathBuf::from("a/c.md")), resolve_link(Path::new("a/b.md"), "c.md#title"));
+        assert_eq!(Some(PathBuf::from("c.md")), resolve_link(Path::new("a/b.md"), "../c.md"));
+        assert_eq!(Some(PathBuf::from("c d.md")), resolve_link(Path::new("a/b.md"), "/c%20d.md"));
+        assert_eq!(None, resolve_link(Path::new("a/b.md"), "../../c.md"));
+        assert_eq!(None, resolve_link(Path::new("a/b.md"), "https://example.com/c.md"));
+        assert_eq!(None, resolve_link(Path::new("a/b.md"), "#title"));
+        assert_eq!(vec![PathBuf::from("a/c.md"), PathBuf::from("a/c/index.md")], link_candidates(PathBuf::from("a/c")));
+    }
+
+    #[test]
+    fn finds_links() {
+        let content = "See [the plan](plan.md)\nand ![a diagram](diagram.png)";
+        let links = find_links(content);
+        assert_eq!(vec!["plan.md", "diagram.png"], links.iter().map(|link| link.url.as_str()).collect::<Vec<_>>());
+        assert_eq!("[the plan](plan.md)", &content[links[0].range.clone()]);
+    }
+
+    #[test]
+    fn encodes_links() {
+        let link = "my notes/a (draft) 100%.md";
+        assert_eq!("my%20notes/a%20%28draft%29%20100%25.md", percent_encode(link));
+        assert_eq!(link, percent_decode(&percent_encode(link)));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{convert::TryFrom, fs, io::ErrorKind, path::{Path, PathBuf}, process, sync::atomic::{AtomicUsize, Ordering}};
+
+use chrono::{DateTime, Duration, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::NottoError;
+
+const LOCK_EXTENSION: &str = "lock";
+/// A lock from another host can't be checked, it's stale once it's this old
+const STALE_AFTER_HOURS: i64 = 12;
+/// Tells apart the owner files written by the threads of this process
+static OWNER_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Who holds the lock of a note, written as JSON in the lock file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    /// When the lock was taken, in RFC 3339
+    pub since: String,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self { pid: process::id(), host: host_name(), since: Local::now().to_rfc3339() }
+    }
+
+    /// Whether the process holding the lock is gone, or the lock is too old to tell for another host
+    pub fn is_stale(&self) -> bool {
+        if self.host == host_name() {
+            return !process_exists(self.pid);
+        }
+        DateTime::parse_from_rfc3339(&self.since)
+            .map(|since| Local::now().signed_duration_since(since) > Duration::hours(STALE_AFTER_HOURS))
+            .unwrap_or(true)
+    }
+}
+
+/// An advisory lock on a note while it's open in the editor, removed when dropped
+///
+/// The lock is a hidden file next to the note, `.<note file>.lock`, so every host sharing the notes sees it
+#[derive(Debug)]
+pub struct NoteLock {
+    path: PathBuf,
+    owner: LockOwner,
+}
+
+impl NoteLock {
+    /// Locks the note file at `note_file`, named `note` in the errors
+    ///
+    /// A stale lock is replaced, a lock held by another process too with `force`.
+    /// The owner is written to a file of its own first, linked as the lock once complete: a lock is never seen empty
+    pub fn acquire(note_file: &Path, note: &str, force: bool) -> Result<NoteLock, NottoError> {
+        let path = NoteLock::lock_path(note_file);
+        let owner = LockOwner::current();
+        let contents = serde_json::to_string(&owner).map_err(|e| NottoError::FileError { message: e.to_string() })?;
+
+        let owner_file = path.with_extension(format!("{}.{}.{}", LOCK_EXTENSION, owner.pid, OWNER_FILES.fetch_add(1, Ordering::Relaxed)));
+        fs::write(&owner_file, contents)?;
+        let locked = NoteLock::link_owner(&owner_file, &path, note, force);
+        if let Err(e) = fs::remove_file(&owner_file) {
+            warn!("Can't remove {}: {}", owner_file.to_string_lossy(), e);
+        }
+        locked?;
+
+        Ok(NoteLock { path, owner })
+    }
+
+    fn link_owner(owner_file: &Path, path: &Path, note: &str, force: bool) -> Result<(), NottoError> {
+        loop {
+            match fs::hard_link(owner_file, path) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    match NoteLock::read_owner(path) {
+                        Some(holder) if !holder.is_stale() && !force => {
+                            return Err(NottoError::NoteInUse { note: note.to_string(), pid: holder.pid, host: holder.host });
+                        }
+                        Some(holder) if !holder.is_stale() => warn!("Opening {} while process {} on {} has it open", note, holder.pid, holder.host),
+                        _ => warn!("Removing the stale lock of {}", note),
+                    }
+                    if let Err(e) = fs::remove_file(path) {
+                        if e.kind() != ErrorKind::NotFound {
+                            return Err(e.into());
+                        }
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Who holds the lock of the note at `note_file`, if it's locked and the lock isn't stale
+    pub fn holder(note_file: &Path) -> Option<LockOwner> {
+        NoteLock::read_owner(&NoteLock::lock_path(note_file)).filter(|holder| !holder.is_stale())
+    }
+
+    pub fn lock_path(note_file: &Path) -> PathBuf {
+        let file_name = note_file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        note_file.with_file_name(format!(".{}.{}", file_name, LOCK_EXTENSION))
+    }
+
+    fn read_owner(path: &Path) -> Option<LockOwner> {
+        fs::read_to_string(path).ok().and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+}
+
+impl Drop for NoteLock {
+    fn drop(&mut self) {
+        // Another process may have taken over the lock
+        if NoteLock::read_owner(&self.path).as_ref() == Some(&self.owner) {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Can't remove the lock {}: {}", self.path.to_string_lossy(), e);
+            }
+        }
+    }
+}
+
+fn host_name() -> String {
+    gethostname::gethostname().to_string_lossy().to_string()
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) => pid,
+        Err(_) => return false,
+    };
+    // Signal 0 only checks the process, it may exist but belong to another user
+    let exists = unsafe { libc::kill(pid, 0) == 0 };
+    exists || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, thread};
+
+    use crate::{errors::NottoError, test_utils::test_dir};
+
+    use super::{LockOwner, NoteLock, host_name};
+
+    /// The note file `plan.md` in the empty directory `test_notes_dir_<name>`, and the path of its lock
+    fn test_note(name: &str) -> Result<(PathBuf, PathBuf), NottoError> {
+        let note_file = test_dir(name)?.join("plan.md");
+        let lock_path = NoteLock::lock_path(&note_file);
+
+        Ok((note_file, lock_path))
+    }
+
+    #[test]
+    fn locks_notes() -> Result<(), NottoError> {
+        let (note_file, lock_path) = test_note("lock_acquire")?;
+
+        let lock = NoteLock::acquire(&note_file, "plan.md", false)?;
+        assert_eq!(note_file.with_file_name(".plan.md.lock"), lock_path);
+        assert!(lock_path.is_file());
+        assert_eq!(Some(std::process::id()), NoteLock::holder(&note_file).map(|holder| holder.pid));
+
+        drop(lock);
+        assert!(!lock_path.exists());
+        assert_eq!(None, NoteLock::holder(&note_file));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_notes_in_use() -> Result<(), NottoError> {
+        let (note_file, _) = test_note("lock_in_use")?;
+
+        let _lock = NoteLock::acquire(&note_file, "plan.md", false)?;
+        match NoteLock::acquire(&note_file, "plan.md", false) {
+            Err(NottoError::NoteInUse { note, pid, host }) => {
+                assert_eq!("plan.md", note);
+                assert_eq!(std::process::id(), pid);
+                assert_eq!(host_name(), host);
+            }
+            other => panic!("expected the note to be in use, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn locks_notes_once_for_concurrent_editors() -> Result<(), NottoError> {
+        let (note_file, lock_path) = test_note("lock_concurrent")?;
+
+        for _ in 0..20 {
+            let editors: Vec<_> = (0..8)
+                .map(|_| {
+                    let note_file = note_file.clone();
+                    thread::spawn(move || NoteLock::acquire(&note_file, "plan.md", false))
+                })
+                .collect();
+            let locks: Vec<_> = editors.into_iter().map(|editor| editor.join().expect("the editor panicked")).collect();
+
+            assert_eq!(1, locks.iter().filter(|lock| lock.is_ok()).count());
+            assert!(locks.iter().all(|lock| matches!(lock, Ok(_) | Err(NottoError::NoteInUse { .. }))));
+            drop(locks);
+            assert!(!lock_path.exists());
+        }
+        // Only the note's directory is left, without the owner files
+        assert_eq!(0, fs::read_dir(lock_path.parent().unwrap_or(&lock_path))?.count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn takes_over_the_locks_when_forced() -> Result<(), NottoError> {
+        let (note_file, lock_path) = test_note("lock_forced")?;
+
+        let lock = NoteLock::acquire(&note_file, "plan.md", false)?;
+        let forced = NoteLock::acquire(&note_file, "plan.md", true)?;
+        // Taken over, the first lock doesn't remove it
+        drop(lock);
+        assert!(lock_path.is_file());
+        drop(forced);
+        assert!(!lock_path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tells_the_stale_locks() {
+        let gone = LockOwner { pid: u32::MAX, host: host_name(), since: chrono::Local::now().to_rfc3339() };
+        assert!(gone.is_stale());
+        let running = LockOwner { pid: std::process::id(), host: host_name(), since: "2021-05-01T10:00:00+00:00".to_string() };
+        assert!(!running.is_stale());
+
+        let old = LockOwner { pid: 1, host: "elsewhere".to_string(), since: "2021-05-01T10:00:00+00:00".to_string() };
+        assert!(old.is_stale());
+        let recent = LockOwner { pid: 1, host: "elsewhere".to_string(), since: chrono::Local::now().to_rfc3339() };
+        assert!(!recent.is_stale());
+        let unknown = LockOwner { pid: 1, host: "elsewhere".to_string(), since: "yesterday".to_string() };
+        assert!(unknown.is_stale());
+    }
+
+    #[test]
+    fn replaces_the_stale_locks() -> Result<(), NottoError> {
+        let (note_file, lock_path) = test_note("lock_stale")?;
+
+        let gone = LockOwner { pid: u32::MAX, host: host_name(), since: chrono::Local::now().to_rfc3339() };
+        fs::write(&lock_path, serde_json::to_string(&gone).unwrap_or_default())?;
+        assert_eq!(None, NoteLock::holder(&note_file));
+
+        let _lock = NoteLock::acquire(&note_file, "plan.md", false)?;
+        assert_eq!(Some(std::process::id()), NoteLock::holder(&note_file).map(|holder| holder.pid));
+
+        Ok(())
+    }
+
+    #[test]
+    fn replaces_the_damaged_locks() -> Result<(), NottoError> {
+        let (note_file, lock_path) = test_note("lock_damaged")?;
+
+        fs::write(&lock_path, "{\"pid\": ")?;
+        assert_eq!(None, NoteLock::holder(&note_file));
+
+        let _lock = NoteLock::acquire(&note_file, "plan.md", false)?;
+        assert_eq!(Some(std::process::id()), NoteLock::holder(&note_file).map(|holder| holder.pid));
+
+        Ok(())
+    }
+}
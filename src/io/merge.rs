@@ -0,0 +1,195 @@
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+
+const OURS_MARKER: &str = "<<<<<<< edited";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>> saved meanwhile";
+
+/// What to do when a note changed on disk while it was in the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    /// Resolve the conflicts of the merge in the editor
+    Merge,
+    /// Save the edited version, the changes made meanwhile are lost
+    KeepEdited,
+    /// Keep the version saved meanwhile, the edits are lost
+    KeepSaved,
+}
+
+/// The result of a three-way merge of the lines of a note
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge {
+    /// The merged text, with git-like markers around the conflicts
+    pub text: String,
+    pub conflicts: usize,
+}
+
+/// Lines of the base text replaced by other lines in a version
+struct Change<'a> {
+    start: usize,
+    end: usize,
+    lines: Vec<&'a str>,
+}
+
+/// Merges the changes made from `base` in `ours` and in `theirs`, line by line
+///
+/// Changes touching the same lines are conflicts, unless they're the same
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Merge {
+    let base_lines = base.split_inclusive('\n').collect::<Vec<_>>();
+    let ours_lines = ours.split_inclusive('\n').collect::<Vec<_>>();
+    let theirs_lines = theirs.split_inclusive('\n').collect::<Vec<_>>();
+    let our_changes = changes(&base_lines, &ours_lines);
+    let their_changes = changes(&base_lines, &theirs_lines);
+
+    let mut text = String::new();
+    let mut conflicts = 0;
+    let (mut ours_index, mut theirs_index, mut position) = (0, 0, 0);
+    loop {
+        let ours_first = match (our_changes.get(ours_index), their_changes.get(theirs_index)) {
+            (None, None) => break,
+            (Some(ours), Some(theirs)) => ours.start <= theirs.start,
+            (ours, _) => ours.is_some(),
+        };
+        let (mut ours_end, mut theirs_end) = (ours_index, theirs_index);
+        let first = if ours_first {
+            ours_end += 1;
+            &our_changes[ours_index]
+        } else {
+            theirs_end += 1;
+            &their_changes[theirs_index]
+        };
+        // The changes of both sides touching the region are part of it, the changes of a side never touch each other
+        let (start, mut end) = (first.start, first.end);
+        loop {
+            if let Some(change) = our_changes.get(ours_end).filter(|change| change.start <= end) {
+                end = end.max(change.end);
+                ours_end += 1;
+            } else if let Some(change) = their_changes.get(theirs_end).filter(|change| change.start <= end) {
+                end = end.max(change.end);
+                theirs_end += 1;
+            } else {
+                break;
+            }
+        }
+
+        text.push_str(&base_lines[position..start].concat());
+        let ours_region = apply(&base_lines, start, end, &our_changes[ours_index..ours_end]);
+        let theirs_region = apply(&base_lines, start, end, &their_changes[theirs_index..theirs_end]);
+        if ours_end == ours_index {
+            text.push_str(&theirs_region);
+        } else if theirs_end == theirs_index || ours_region == theirs_region {
+            text.push_str(&ours_region);
+        } else {
+            conflicts += 1;
+            push_line(&mut text, OURS_MARKER);
+            push_lines(&mut text, &ours_region);
+            push_line(&mut text, SEPARATOR_MARKER);
+            push_lines(&mut text, &theirs_region);
+            push_line(&mut text, THEIRS_MARKER);
+        }
+
+        position = end;
+        ours_index = ours_end;
+        theirs_index = theirs_end;
+    }
+    text.push_str(&base_lines[position..].concat());
+
+    Merge { text, conflicts }
+}
+
+/// The number of conflicts left in a merged text
+pub fn count_conflicts(text: &str) -> usize {
+    text.lines().filter(|line| *line == OURS_MARKER).count()
+}
+
+fn changes<'a>(base: &[&str], other: &[&'a str]) -> Vec<Change<'a>> {
+    let mut changes: Vec<Change> = vec![];
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        let (start, end, lines) = match op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete { old_index, old_len, .. } => (old_index, old_index + old_len, vec![]),
+            DiffOp::Insert { old_index, new_index, new_len } => (old_index, old_index, other[new_index..new_index + new_len].to_vec()),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => (old_index, old_index + old_len, other[new_index..new_index + new_len].to_vec()),
+        };
+        match changes.last_mut() {
+            Some(last) if last.end == start => {
+                last.end = end;
+                last.lines.extend(lines);
+            }
+            _ => changes.push(Change { start, end, lines }),
+        }
+    }
+
+    changes
+}
+
+/// The lines of the base from `start` to `end` with the changes in them
+fn apply(base: &[&str], start: usize, end: usize, changes: &[Change]) -> String {
+    let mut text = String::new();
+    let mut position = start;
+    for change in changes {
+        text.push_str(&base[position..change.start].concat());
+        text.push_str(&change.lines.concat());
+        position = change.end;
+    }
+    text.push_str(&base[position..end].concat());
+
+    text
+}
+
+fn push_line(text: &mut String, line: &str) {
+    text.push_str(line);
+    text.push('\n');
+}
+
+/// Adds lines to the text, ending with a new line so a marker can follow
+fn push_lines(text: &mut String, lines: &str) {
+    text.push_str(lines);
+    if !lines.is_empty() && !lines.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_conflicts, merge};
+
+    const BASE: &str = "# Plan\nStep 1\nStep 2\nStep 3\n";
+
+    #[test]
+    fn merges_the_changes_in_different_lines() {
+        let merged = merge(BASE, "# Plan\nStep 1 done\nStep 2\nStep 3\n", "# Plan\nStep 1\nStep 2\nStep 3\nStep 4\n");
+        assert_eq!(0, merged.conflicts);
+        assert_eq!("# Plan\nStep 1 done\nStep 2\nStep 3\nStep 4\n", merged.text);
+    }
+
+    #[test]
+    fn merges_the_same_changes() {
+        let merged = merge(BASE, "# Plan\nStep 2\nStep 3\n", "# Plan\nStep 2\nStep 3\n");
+        assert_eq!(0, merged.conflicts);
+        assert_eq!("# Plan\nStep 2\nStep 3\n", merged.text);
+    }
+
+    #[test]
+    fn marks_the_conflicts() {
+        let merged = merge(BASE, "# Plan\nStep 1\nStep 2 today\nStep 3", "# Plan\nStep 1\nStep 2 tomorrow\nStep 3\n");
+        assert_eq!(1, merged.conflicts);
+        assert_eq!(1, count_conflicts(&merged.text));
+        assert_eq!(
+            "# Plan\nStep 1\n<<<<<<< edited\nStep 2 today\nStep 3\n=======\nStep 2 tomorrow\nStep 3\n>>>>>>> saved meanwhile\n",
+            merged.text
+        );
+    }
+
+    #[test]
+    fn keeps_the_changed_side() {
+        assert_eq!("Rewritten\n", merge(BASE, BASE, "Rewritten\n").text);
+        assert_eq!("Rewritten\n", merge(BASE, "Rewritten\n", BASE).text);
+        assert_eq!("Added\n", merge("", "Added\n", "").text);
+    }
+
+    #[test]
+    fn counts_only_the_conflict_markers() {
+        assert_eq!(0, count_conflicts(BASE));
+        assert_eq!(0, count_conflicts("Text <<<<<<< edited\n=======\n"));
+    }
+}
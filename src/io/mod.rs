@@ -1,15 +1,43 @@
 pub mod browser;
-
-use std::{fs::{self, OpenOptions}, io::Write, path::{Path, PathBuf}};
+pub mod crypto;
+pub mod editor;
+pub mod git;
+pub mod history;
+pub mod home;
+pub mod hooks;
+pub mod links;
+pub mod lock;
+pub mod merge;
+pub mod snapshots;
+pub mod terminal;
+
+use std::{ffi::OsStr, fs::{self, OpenOptions}, io::Write, path::{Component, Path, PathBuf}};
 
 use uuid::Uuid;
 
-use crate::{errors::NottoError, models::note::Note};
+use crate::{errors::NottoError, models::note::{Note, extract_title}};
+
+use crypto::{NoteCipher, is_encrypted};
+use git::{GitOperation, GitRepo};
+use lock::NoteLock;
+use hooks::{HookEvent, HookRunner};
+use links::to_link;
+use snapshots::SnapshotStore;
 
 const FILE_NAME_EXTENSION: &str = "md";
 pub const DIR_ROOT_NOTE_NAME: &str = "index.md";
 const FILE_NAME_LENGTH: usize = 32;
 
+/// Whether `path` names something in the notes directory, only made of names without `.`, `..`, a root or a prefix
+pub fn is_note_path<P>(path: P) -> bool where P: AsRef<Path> {
+    let path = path.as_ref();
+    !path.as_os_str().is_empty() && path.components().all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn strip_note_extension(file_name: &str) -> &str {
+    file_name.strip_suffix(&format!(".{}", FILE_NAME_EXTENSION)).unwrap_or(file_name)
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum NoteFileType {
     File(String),
@@ -17,26 +45,69 @@ pub enum NoteFileType {
 }
 
 pub struct ReaderWriter {
-    base_path: PathBuf
+    base_path: PathBuf,
+    hooks: Option<HookRunner>,
+    git: Option<GitRepo>,
+    snapshots: Option<SnapshotStore>,
+    cipher: Option<NoteCipher>,
+    /// Whether every note of the context is encrypted
+    encrypt_all: bool,
 }
 
 impl ReaderWriter {
-    pub fn new(base_path: PathBuf) -> Self { Self { base_path } }
+    pub fn new(base_path: PathBuf) -> Self { Self { base_path, hooks: None, git: None, snapshots: None, cipher: None, encrypt_all: false } }
+
+    /// Runs the hooks of a context when notes are created, saved, moved or deleted
+    pub fn with_hooks(mut self, hooks: HookRunner) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Commits every change of the notes in the git repository of the notes directory
+    pub fn with_git(mut self, git: GitRepo) -> Self {
+        self.git = Some(git);
+        self
+    }
+
+    /// Keeps a snapshot of the notes before they're overwritten
+    pub fn with_snapshots(mut self, snapshots: SnapshotStore) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
+
+    /// Encrypts the notes with `encrypted: true` in their front matter, or all of them with `encrypt_all`,
+    /// and decrypts them when they're read. Without a cipher they're read as they are and can't be written
+    pub fn with_encryption(mut self, cipher: Option<NoteCipher>, encrypt_all: bool) -> Self {
+        self.cipher = cipher;
+        self.encrypt_all = encrypt_all;
+        self
+    }
+
+    /// The path of the note file `save_note_at` writes for `file_name` in `path`
+    pub fn note_file_path<P, S>(path: P, file_name: S) -> PathBuf where P: AsRef<Path>, S: AsRef<str> {
+        path.as_ref().join(format!("{}.{}", strip_note_extension(file_name.as_ref()), FILE_NAME_EXTENSION))
+    }
 
     pub fn save_note_at<P, S>(&self, note: Note, path: P, file_name: S, overwrite: bool) -> Result<PathBuf, NottoError> where P: AsRef<Path>, S: AsRef<str> {
+        // No extension in the file
+        let file_name = strip_note_extension(file_name.as_ref()).to_string();
+
+        let note_path = ReaderWriter::note_file_path(&path, &file_name);
+        if !is_note_path(&note_path) || Path::new(&file_name).components().count() != 1 {
+            return Err(NottoError::InvalidNoteName { note: to_link(&note_path) });
+        }
+        let note = self.to_stored_note(note, &note_path)?;
+        let existing_note = self.note_file_exists(&path, &file_name);
+        let overwrite_existing = existing_note.is_some();
+        if !overwrite_existing {
+            let new_path = path.as_ref().join(format!("{}.{}", file_name, FILE_NAME_EXTENSION));
+            self.run_hook(HookEvent::PreCreate, &new_path, None, &note)?;
+        }
         if !self.exists(&path) {
             self.create_dir_all(&path)?;
         };
 
-        // No extension in the file
-        let dotted_extension = format!(".{}", FILE_NAME_EXTENSION);
-        let file_name = if file_name.as_ref().ends_with(&dotted_extension) {
-                file_name.as_ref()[..file_name.as_ref().len() - dotted_extension.len()].to_string()
-            } else {
-                file_name.as_ref().to_string()
-            };
-
-        let save_path = match self.note_file_exists(&path, &file_name) {
+        let save_path = match existing_note {
             Some(note_file_type) => {
                 if !overwrite {
                     return Err(NottoError::NoteExists { note_name: file_name } )
@@ -61,57 +132,229 @@ impl ReaderWriter {
             }
         };
 
+        if overwrite_existing {
+            self.snapshot_note(&save_path)?;
+        }
         let mut file = self.get_note_file(&save_path)?;
-        file.write(note.to_text().as_bytes())?;
+        file.write_all(note.to_text().as_bytes())?;
+        self.note_changed(HookEvent::PostSave, &save_path, None, &note)?;
 
         Ok(save_path)
     }
 
+    /// Writes the text of a new note at `note_path` as it is, or encrypted if the note or the context is, like `save_note_at`
+    ///
+    /// For the notes restored from an archive, the text of a plain note is kept byte for byte
+    pub fn save_note_text_at<P>(&self, text: &str, note_path: P) -> Result<PathBuf, NottoError> where P: AsRef<Path> {
+        let note_path = note_path.as_ref();
+        let file_name = note_path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        if !is_note_path(note_path) || note_path.extension() != Some(OsStr::new(FILE_NAME_EXTENSION)) {
+            return Err(NottoError::InvalidNoteName { note: to_link(note_path) });
+        }
+        let dir = note_path.parent().unwrap_or_else(|| Path::new(""));
+        if self.note_file_exists(dir, &file_name).is_some() {
+            return Err(NottoError::NoteExists { note_name: file_name });
+        }
+
+        let note = Note::from_text(text);
+        let encrypt = (note.front_matter.encrypted || self.encrypt_all) && !is_encrypted(&note.content);
+        let note = self.to_stored_note(note, note_path)?;
+        self.run_hook(HookEvent::PreCreate, note_path, None, &note)?;
+        self.create_dir_all(dir)?;
+        let mut file = self.get_note_file(note_path)?;
+        if encrypt {
+            file.write_all(note.to_text().as_bytes())?;
+        } else {
+            file.write_all(text.as_bytes())?;
+        }
+        self.note_changed(HookEvent::PostSave, note_path, None, &note)?;
+
+        Ok(note_path.to_path_buf())
+    }
+
+    /// Commits or snapshots, and runs the `post_save` hook for a note changed outside notto, like in the editor
+    pub fn note_saved<P>(&self, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
+        self.snapshot_note(&note_path)?;
+        if self.hooks.is_some() || self.git.is_some() {
+            let note = self.read_stored_note(&note_path)?;
+            self.note_changed(HookEvent::PostSave, note_path.as_ref(), None, &note)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the note file at `note_path` to `file_name` in the `path` directory, returns the new path
+    ///
+    /// A note with sub notes is moved with its directory
+    pub fn move_note<P, Q, S>(&self, note_path: P, path: Q, file_name: S) -> Result<PathBuf, NottoError> where P: AsRef<Path>, Q: AsRef<Path>, S: AsRef<str> {
+        let note_path = note_path.as_ref();
+        if !is_note_path(note_path) {
+            return Err(NottoError::InvalidNoteName { note: to_link(note_path) });
+        }
+        if !self.is_file(note_path) {
+            return Err(NottoError::FileError{ message: format!("Expected note at `{}` but not found", note_path.to_string_lossy() ) });
+        }
+        let dotted_extension = format!(".{}", FILE_NAME_EXTENSION);
+        let file_name = file_name.as_ref().trim_end_matches(&dotted_extension);
+        if file_name.is_empty() {
+            return Err(NottoError::FileError{ message: "The new name of the note is empty".to_string() });
+        }
+        if !is_note_path(path.as_ref().join(file_name)) || Path::new(file_name).components().count() != 1 {
+            return Err(NottoError::InvalidNoteName { note: to_link(&path.as_ref().join(file_name)) });
+        }
+        if self.note_file_exists(&path, file_name).is_some() {
+            return Err(NottoError::NoteExists { note_name: file_name.to_string() });
+        }
+        self.create_dir_all(&path)?;
+
+        let is_parent_note = note_path.file_name().map(|name| name == DIR_ROOT_NOTE_NAME).unwrap_or(false);
+        let new_path = match note_path.parent() {
+            Some(note_dir) if is_parent_note => {
+                let new_dir = path.as_ref().join(file_name);
+                self.rename_note_file(note_dir, &new_dir)?;
+                new_dir.join(DIR_ROOT_NOTE_NAME)
+            }
+            _ => {
+                let new_path = path.as_ref().join(format!("{}.{}", file_name, FILE_NAME_EXTENSION));
+                self.rename_note_file(note_path, &new_path)?;
+                new_path
+            }
+        };
+        self.note_moved(&new_path, note_path)?;
+
+        Ok(new_path)
+    }
+
+    /// Deletes the note file at `note_path`, the directory of a parent note is removed if nothing else is left in it
+    pub fn delete_note<P>(&self, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
+        let note_path = note_path.as_ref();
+        if !self.is_file(note_path) {
+            return Err(NottoError::FileError{ message: format!("Expected note at `{}` but not found", note_path.to_string_lossy() ) });
+        }
+        let note = self.read_stored_note(note_path)?;
+        fs::remove_file(self.base_path.join(note_path))?;
+
+        let mut dir = note_path.parent();
+        while let Some(parent) = dir.filter(|parent| !parent.as_os_str().is_empty()) {
+            if fs::read_dir(self.base_path.join(parent))?.next().is_some() {
+                break;
+            }
+            fs::remove_dir(self.base_path.join(parent))?;
+            dir = parent.parent();
+        }
+        self.note_changed(HookEvent::PostDelete, note_path, None, &note)
+    }
+
+    /// Reads a note, decrypting its content if it's encrypted and there's a cipher
+    pub fn read_note<P>(&self, note_path: P) -> Result<Note, NottoError> where P: AsRef<Path> {
+        let mut note = self.read_stored_note(note_path)?;
+        if let Some(cipher) = &self.cipher {
+            if is_encrypted(&note.content) {
+                note.content = cipher.decrypt(&note.content)?;
+            }
+        }
+
+        Ok(note)
+    }
+
+    /// The note as it is on disk, the content of encrypted notes stays encrypted
+    fn read_stored_note<P>(&self, note_path: P) -> Result<Note, NottoError> where P: AsRef<Path> {
+        Ok(Note::from_text(self.read_text(note_path)?))
+    }
+
+    /// Writes the text of a note file as it is, without the hooks, git or snapshots
+    pub fn write_text<P>(&self, note_path: P, text: &str) -> Result<(), NottoError> where P: AsRef<Path> {
+        self.get_note_file(note_path)?.write_all(text.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Locks the note at `note_path` while it's edited, see `NoteLock::acquire`
+    pub fn lock_note<P>(&self, note_path: P, force: bool) -> Result<NoteLock, NottoError> where P: AsRef<Path> {
+        NoteLock::acquire(&self.base_path.join(&note_path), &to_link(note_path.as_ref()), force)
+    }
+
+    /// The text of the note file, as it is on disk
+    pub fn read_text<P>(&self, note_path: P) -> Result<String, NottoError> where P: AsRef<Path> {
+        Ok(fs::read_to_string(self.base_path.join(note_path))?)
+    }
+
+    /// Keeps a snapshot of the note as it is now, if snapshots are on and it changed since the last one
+    pub fn snapshot_note<P>(&self, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
+        if self.snapshots.is_some() && self.is_file(&note_path) {
+            self.snapshot_text(&self.read_text(&note_path)?, note_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps a snapshot of `text` as a version of the note, for the text it had before it was changed in place
+    pub fn snapshot_text<P>(&self, text: &str, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
+        if let Some(snapshots) = &self.snapshots {
+            snapshots.snapshot(text, note_path.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites the note file at `note_path` with the given note
+    pub fn update_note<P>(&self, note: Note, note_path: P) -> Result<PathBuf, NottoError> where P: AsRef<Path> {
+        let note_path = note_path.as_ref();
+        match (note_path.parent(), note_path.file_name()) {
+            (Some(parent), Some(file_name)) => self.save_note_at(note, parent, file_name.to_string_lossy(), true),
+            _ => Err(NottoError::FileError{ message: format!("Path {} doesn't contain a file", note_path.to_string_lossy() ) })
+        }
+    }
+
     pub fn convert_note_to_parent_note<P>(&self, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
         let note_path = note_path.as_ref();
-        if !self.exists(&note_path) {
+        if !self.exists(note_path) {
             return Err(NottoError::FileError{ message: format!("Expected note at `{}` but not found", note_path.to_string_lossy() ) });
         }
-        if !self.is_file(&note_path) {
+        if !self.is_file(note_path) {
             return Err(NottoError::FileError{ message: format!("Expected file at `{}` but it's not", note_path.to_string_lossy() ) });
         }
 
         if let Some(dir_name) = note_path.file_stem() {
             // We define the destination directory
-            let dest_directory = note_path.with_file_name(&dir_name);
+            let dest_directory = note_path.with_file_name(dir_name);
             // We create a temp file name
             let temp_file_name = Uuid::new_v4().to_simple().to_string();
             let temp_file_path = note_path.with_file_name(&temp_file_name);
             // We move the file to its new temp file
-            self.rename_note_file(&note_path, &temp_file_path)?;
+            self.rename_note_file(note_path, &temp_file_path)?;
             // We create the directory
             self.create_dir_all(&dest_directory)?;
             // We move the file into the directory with the `index.md` name
             let final_path = dest_directory.join(DIR_ROOT_NOTE_NAME);
             self.rename_note_file(&temp_file_path, &final_path)?;
 
-            Ok(())
+            self.note_moved(&final_path, note_path)
         } else {
             Err(NottoError::FileError{ message: format!("Path {} doesn't contain a file", note_path.to_string_lossy() ) })
         }
     }
 
     pub fn get_file_name_from_note(&self, note: &Note) -> String {
-        let special_chars = [ ' ', ':', '.', ',', '/', '\\', '<', '>', '"', '|', '?', '*', '^', '\'' ];
-        let mut file_name = note.get_title();
-        file_name.retain(|c| !special_chars.contains(&c));
-        file_name = file_name.trim().to_string();
-        if file_name.len() > FILE_NAME_LENGTH {
-            file_name = file_name[..FILE_NAME_LENGTH].to_string();
-        }
-        
+        let mut file_name = ReaderWriter::normalize_file_name(note.get_title());
         if file_name.is_empty() {
-            file_name = note.front_matter.id[..FILE_NAME_LENGTH].to_string();
+            file_name = note.front_matter.id.chars().take(FILE_NAME_LENGTH).collect();
         }
 
         format!("{}.{}", file_name, FILE_NAME_EXTENSION)
     }
 
+    /// Removes the characters we don't want in a note file name and limits its length
+    ///
+    /// The result may be empty
+    pub fn normalize_file_name<S>(name: S) -> String where S: AsRef<str> {
+        let special_chars = [ ' ', ':', '.', ',', '/', '\\', '<', '>', '"', '|', '?', '*', '^', '\'' ];
+        let mut file_name = name.as_ref().to_string();
+        file_name.retain(|c| !special_chars.contains(&c));
+
+        file_name.trim().chars().take(FILE_NAME_LENGTH).collect()
+    }
+
     pub fn note_file_exists<P, S>(&self, path: P, file_name: S) -> Option<NoteFileType> where P: AsRef<Path>, S: AsRef<str> {
         let file_name = String::from(file_name.as_ref());
         // We shouldn't get the file name with extension
@@ -142,16 +385,31 @@ impl ReaderWriter {
         None
     }
 
+    /// All the files under `dir`, relative to the base path and sorted, skipping the hidden ones
+    pub fn list_files<P>(&self, dir: P) -> Result<Vec<PathBuf>, NottoError> where P: AsRef<Path> {
+        let mut files = vec![];
+        for entry in fs::read_dir(self.base_path.join(&dir))?.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = dir.as_ref().join(entry.file_name());
+            if entry.path().is_dir() {
+                files.extend(self.list_files(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        Ok(files)
+    }
+
     pub fn get_full_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         self.base_path.join(path)
     }
 
-    /// Operations in the filesystem
-    /// Using the base_path as a reference
-
-    fn is_dir<P>(&self, path: P) -> bool where P: AsRef<Path> {
-        self.base_path.join(path).is_dir()
-    }
+    // Operations in the filesystem
+    // Using the base_path as a reference
 
     fn is_file<P>(&self, path: P) -> bool where P: AsRef<Path> {
         self.base_path.join(path).is_file()
@@ -166,12 +424,9 @@ impl ReaderWriter {
 
         let mut path_tree = PathBuf::new();
         for comp in components {
-            match comp {
-                std::path::Component::Normal(name) => {
-                    path_tree.push(name);
-                    self.create_dir_if_doesnt_exist(&path_tree)?;
-                }
-                _ => {}
+            if let std::path::Component::Normal(name) = comp {
+                path_tree.push(name);
+                self.create_dir_if_doesnt_exist(&path_tree)?;
             }
         }
 
@@ -203,11 +458,60 @@ impl ReaderWriter {
         Ok(())
     }
 
+    fn run_hook(&self, event: HookEvent, note_path: &Path, old_path: Option<&Path>, note: &Note) -> Result<(), NottoError> {
+        match &self.hooks {
+            Some(hooks) => hooks.run(event, &self.base_path, note_path, old_path, note),
+            None => Ok(())
+        }
+    }
+
+    /// Commits the change to git and runs the `post_*` hook
+    fn note_changed(&self, event: HookEvent, note_path: &Path, old_path: Option<&Path>, note: &Note) -> Result<(), NottoError> {
+        if let Some(git) = &self.git {
+            let operation = match event {
+                HookEvent::PostMove => GitOperation::Move,
+                HookEvent::PostDelete => GitOperation::Delete,
+                _ => GitOperation::Save,
+            };
+            git.commit(operation, note, note_path, old_path)?;
+        }
+
+        self.run_hook(event, note_path, old_path, note)
+    }
+
+    fn note_moved(&self, note_path: &Path, old_path: &Path) -> Result<(), NottoError> {
+        if self.hooks.is_some() || self.git.is_some() {
+            let note = self.read_stored_note(note_path)?;
+            self.note_changed(HookEvent::PostMove, note_path, Some(old_path), &note)?;
+        }
+
+        Ok(())
+    }
+
+    /// The note as it's written on disk, its content encrypted if the note or the context is encrypted
+    ///
+    /// The hooks and git only get this one
+    fn to_stored_note(&self, mut note: Note, note_path: &Path) -> Result<Note, NottoError> {
+        if !(note.front_matter.encrypted || self.encrypt_all) || is_encrypted(&note.content) {
+            return Ok(note);
+        }
+        let cipher = self.cipher.as_ref().ok_or_else(|| NottoError::NoteLocked { note: to_link(note_path) })?;
+        note.front_matter.encrypted = true;
+        // A title taken from the content would leave its first sentence readable
+        if note.front_matter.title.as_deref() == Some(extract_title(&note.content).as_str()) {
+            note.front_matter.title = None;
+        }
+        note.content = cipher.encrypt(&note.content)?;
+
+        Ok(note)
+    }
+
     fn get_note_file<P: AsRef<Path>>(&self, path: P) -> Result<fs::File, NottoError> {
         let file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(&self.base_path.join(&path))?;
+            .truncate(true)
+            .open(self.base_path.join(&path))?;
 
         Ok(file)
     }
@@ -217,12 +521,13 @@ impl ReaderWriter {
 mod test {
     use std::path::PathBuf;
 
-    use crate::{errors::NottoError, models::{front_matter::FrontMatter, note::Note}};
+    use crate::{errors::NottoError, models::{front_matter::FrontMatter, note::Note}, test_utils::test_dir};
 
     use super::ReaderWriter;
     use super::NoteFileType;
     use super::FILE_NAME_EXTENSION;
     use super::DIR_ROOT_NOTE_NAME;
+    use super::is_note_path;
 
     const BASE_PATH: &str = "test_notes_dir"; 
 
@@ -278,4 +583,75 @@ mod test {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// A writer of the empty notes directory `test_notes_dir_<name>`
+    fn test_writer(name: &str) -> Result<(ReaderWriter, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+
+        Ok((ReaderWriter::new(base_path.clone()), base_path))
+    }
+
+    #[test]
+    fn names_the_note_files_as_saved() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("io_file_names")?;
+
+        for file_name in &["my.note", "my.note.md", "plan"] {
+            let saved = writer.save_note_at(Note::new(FrontMatter::default(), "A note"), "work", file_name, true)?;
+            assert_eq!(ReaderWriter::note_file_path("work", file_name), saved);
+        }
+        assert!(base_path.join("work").join("my.note.md").is_file());
+
+        Ok(())
+    }
+
+    #[test]
+    fn overwrites_longer_notes() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("io_overwrite")?;
+
+        let front_matter = FrontMatter::default();
+        writer.save_note_at(Note::new(front_matter.clone(), "A long note, with many more words than the next one"), "", "plan", false)?;
+        writer.save_note_at(Note::new(front_matter.clone(), "Short"), "", "plan", true)?;
+
+        let text = std::fs::read_to_string(base_path.join(format!("plan.{}", FILE_NAME_EXTENSION)))?;
+        assert_eq!(Note::new(front_matter, "Short").to_text(), text);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_saving_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("io_save_paths")?;
+
+        assert!(matches!(writer.save_note_at(Note::new(FrontMatter::default(), "Out"), "..", "escape", false), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(writer.save_note_at(Note::new(FrontMatter::default(), "Out"), "/tmp", "escape", false), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(writer.save_note_at(Note::new(FrontMatter::default(), "Out"), "", "../escape", false), Err(NottoError::InvalidNoteName { .. })));
+        assert!(!base_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_moving_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (writer, base_path) = test_writer("io_move_paths")?;
+
+        writer.save_note_at(Note::new(FrontMatter::default(), "In"), "", "plan", false)?;
+        assert!(matches!(writer.move_note("plan.md", "..", "escape"), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(writer.move_note("plan.md", "", "../escape"), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(writer.move_note("../plan.md", "", "escape"), Err(NottoError::InvalidNoteName { .. })));
+        assert!(base_path.join("plan.md").exists());
+        assert!(!base_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tells_the_note_paths() {
+        assert!(is_note_path("work/plan.md"));
+        assert!(is_note_path("plan.md"));
+        assert!(!is_note_path(""));
+        assert!(!is_note_path("../plan.md"));
+        assert!(!is_note_path("work/../../plan.md"));
+        assert!(!is_note_path("/plan.md"));
+        assert!(!is_note_path("./plan.md"));
+    }
+}
@@ -0,0 +1,270 @@
+use std::{cmp::Reverse, collections::HashSet, fs::{self, File}, io::{Read, Write}, path::{Path, PathBuf}};
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::NottoError, models::note::Note};
+
+use super::{history::Revision, links::to_link};
+
+const SNAPSHOT_EXTENSION: &str = ".md.gz";
+const DEFAULT_KEEP_LAST: usize = 10;
+const DEFAULT_KEEP_DAILY: u32 = 30;
+
+/// Which snapshots are kept, set per context
+///
+/// The last `keep_last` snapshots are always kept, and the last one of each of the last `keep_daily` days
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotPolicy {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_keep_last")]
+    pub keep_last: usize,
+    #[serde(default = "default_keep_daily")]
+    pub keep_daily: u32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+fn default_keep_last() -> usize {
+    DEFAULT_KEEP_LAST
+}
+fn default_keep_daily() -> u32 {
+    DEFAULT_KEEP_DAILY
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self { enabled: true, keep_last: DEFAULT_KEEP_LAST, keep_daily: DEFAULT_KEEP_DAILY }
+    }
+}
+
+impl SnapshotPolicy {
+    pub fn is_default(&self) -> bool {
+        self == &SnapshotPolicy::default()
+    }
+}
+
+/// Compressed copies of the notes, for the contexts without git
+///
+/// They're kept in a directory per note, named after the note id, so they follow the note when it's moved
+pub struct SnapshotStore {
+    dir: PathBuf,
+    policy: SnapshotPolicy,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: PathBuf, policy: SnapshotPolicy) -> Self { Self { dir, policy } }
+
+    /// The name of the snapshots directory of a note, its id or the path of the file if it doesn't have one yet
+    pub fn key(text: &str, note_path: &Path) -> String {
+        let id = Note::split_front_matter(text).0
+            .and_then(|front_matter| serde_yaml::from_str::<serde_yaml::Value>(&front_matter).ok())
+            .and_then(|front_matter| front_matter.get("id").and_then(|id| id.as_str()).map(String::from))
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+
+        id.unwrap_or_else(|| format!("path-{:x}", md5::compute(to_link(note_path))))
+    }
+
+    /// Stores the text of a note unless it's the same as its last snapshot, returns whether it was stored
+    pub fn snapshot(&self, text: &str, note_path: &Path) -> Result<bool, NottoError> {
+        self.snapshot_at(text, note_path, Local::now())
+    }
+
+    fn snapshot_at(&self, text: &str, note_path: &Path, time: DateTime<Local>) -> Result<bool, NottoError> {
+        let key = SnapshotStore::key(text, note_path);
+        if let Some(last) = self.history(&key)?.first() {
+            if self.read_revision(last)? == text {
+                return Ok(false);
+            }
+        }
+
+        let note_dir = self.dir.join(&key);
+        fs::create_dir_all(&note_dir)?;
+        // Two snapshots in the same millisecond get the next one
+        let mut millis = time.timestamp_millis();
+        while note_dir.join(format!("{}{}", millis, SNAPSHOT_EXTENSION)).exists() {
+            millis += 1;
+        }
+        let mut encoder = GzEncoder::new(File::create(note_dir.join(format!("{}{}", millis, SNAPSHOT_EXTENSION)))?, Compression::default());
+        encoder.write_all(text.as_bytes())?;
+        encoder.finish()?;
+
+        self.prune(&key, time)?;
+
+        Ok(true)
+    }
+
+    /// The snapshots of a note, the latest first
+    pub fn history(&self, key: &str) -> Result<Vec<Revision>, NottoError> {
+        let note_dir = self.dir.join(key);
+        if !note_dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut snapshots = fs::read_dir(&note_dir)?.flatten()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                let millis = file_name.strip_suffix(SNAPSHOT_EXTENSION)?.parse::<i64>().ok()?;
+                Some((millis, entry.path()))
+            })
+            .collect::<Vec<_>>();
+        snapshots.sort_by_key(|(millis, _)| Reverse(*millis));
+
+        let mut revisions = vec![];
+        for (index, (millis, location)) in snapshots.into_iter().enumerate() {
+            let date = Local.timestamp_millis_opt(millis).single().map(|date| date.to_rfc3339()).unwrap_or_default();
+            let mut revision = Revision { number: index + 1, id: millis.to_string(), date, message: String::new(), location };
+            let text = self.read_revision(&revision)?;
+            revision.message = format!("\"{}\" ({} lines)", Note::from_text(&text).get_title(), text.lines().count());
+            revisions.push(revision);
+        }
+
+        Ok(revisions)
+    }
+
+    pub fn read_revision(&self, revision: &Revision) -> Result<String, NottoError> {
+        let mut text = String::new();
+        GzDecoder::new(File::open(&revision.location)?).read_to_string(&mut text)?;
+
+        Ok(text)
+    }
+
+    /// Removes the snapshots of a note the policy doesn't keep
+    fn prune(&self, key: &str, now: DateTime<Local>) -> Result<(), NottoError> {
+        let revisions = self.history(key)?;
+        let first_day = now.date().naive_local() - Duration::days(i64::from(self.policy.keep_daily));
+
+        let mut kept_days = HashSet::new();
+        for (index, revision) in revisions.iter().enumerate() {
+            let day = DateTime::parse_from_rfc3339(&revision.date).map(|date| date.with_timezone(&Local).date().naive_local()).ok();
+            // The revisions go from the latest, the first of a day is its last snapshot
+            let last_of_day = day.filter(|day| *day > first_day).map(|day| kept_days.insert(day)).unwrap_or(false);
+            if index >= self.policy.keep_last && !last_of_day {
+                fs::remove_file(&revision.location)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use chrono::{Duration, Local};
+
+    use crate::{errors::NottoError, test_utils::test_dir};
+
+    use super::{SnapshotPolicy, SnapshotStore};
+
+    /// A store keeping the last two snapshots and the last of each of the last three days, in `test_notes_dir_<name>`
+    fn test_store(name: &str) -> Result<SnapshotStore, NottoError> {
+        Ok(SnapshotStore::new(test_dir(name)?, SnapshotPolicy { enabled: true, keep_last: 2, keep_daily: 3 }))
+    }
+
+    fn with_id(content: &str) -> String {
+        format!("---\nid: abc\ntitle: Plan\n---\n{}", content)
+    }
+
+    /// The last line of each snapshot of the note `abc`, the latest first
+    fn last_lines(store: &SnapshotStore) -> Result<Vec<String>, NottoError> {
+        store.history("abc")?.iter()
+            .map(|revision| store.read_revision(revision).map(|text| text.lines().last().unwrap_or_default().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn names_the_snapshots_after_the_note_id() {
+        let note_path = Path::new("plan.md");
+        assert_eq!("abc", SnapshotStore::key(&with_id(""), note_path));
+        assert_eq!(SnapshotStore::key("No front matter", note_path), SnapshotStore::key("Other content", note_path));
+        assert_ne!(SnapshotStore::key("No front matter", note_path), SnapshotStore::key("No front matter", Path::new("home.md")));
+    }
+
+    #[test]
+    fn names_the_snapshots_after_the_path_for_the_invalid_ids() {
+        let note_path = Path::new("plan.md");
+        let path_key = SnapshotStore::key("", note_path);
+        assert!(path_key.starts_with("path-"));
+        assert_eq!(path_key, SnapshotStore::key("---\nid: ../escape\n---\n", note_path));
+        assert_eq!(path_key, SnapshotStore::key("---\nid: ''\n---\n", note_path));
+        assert_eq!(path_key, SnapshotStore::key("---\nid: [abc\n---\n", note_path));
+    }
+
+    #[test]
+    fn skips_the_unchanged_notes() -> Result<(), NottoError> {
+        let store = test_store("snapshots_unchanged")?;
+        let note_path = Path::new("plan.md");
+        let now = Local::now();
+
+        assert!(store.snapshot_at(&with_id("Noon"), note_path, now - Duration::seconds(2))?);
+        assert!(!store.snapshot_at(&with_id("Noon"), note_path, now - Duration::seconds(1))?);
+        assert!(store.snapshot_at(&with_id("Evening"), note_path, now)?);
+        assert_eq!(vec!["Evening", "Noon"], last_lines(&store)?);
+        assert_eq!("\"Plan\" (5 lines)", store.history("abc")?[0].message);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_snapshots_of_the_policy() -> Result<(), NottoError> {
+        let store = test_store("snapshots_policy")?;
+        let note_path = Path::new("plan.md");
+        let now = Local::now();
+
+        // One snapshot a day for a week, then three today
+        for days in (1..=7).rev() {
+            assert!(store.snapshot_at(&with_id(&format!("{} days ago", days)), note_path, now - Duration::days(days))?);
+        }
+        assert!(store.snapshot_at(&with_id("Morning"), note_path, now - Duration::seconds(2))?);
+        assert!(store.snapshot_at(&with_id("Noon"), note_path, now - Duration::seconds(1))?);
+        assert!(store.snapshot_at(&with_id("Evening"), note_path, now)?);
+
+        // The last two, then the last of today and of the two days before
+        assert_eq!(vec!["Evening", "Noon", "1 days ago", "2 days ago"], last_lines(&store)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_snapshots_taken_at_the_same_time() -> Result<(), NottoError> {
+        let store = test_store("snapshots_same_time")?;
+        let note_path = Path::new("plan.md");
+        let now = Local::now();
+
+        assert!(store.snapshot_at(&with_id("Noon"), note_path, now)?);
+        assert!(store.snapshot_at(&with_id("Evening"), note_path, now)?);
+        assert_eq!(vec!["Evening", "Noon"], last_lines(&store)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_the_other_files() -> Result<(), NottoError> {
+        let store = test_store("snapshots_other_files")?;
+        assert!(store.history("abc")?.is_empty());
+
+        assert!(store.snapshot(&with_id("Noon"), Path::new("plan.md"))?);
+        fs::write(store.dir.join("abc").join("notes.txt"), "Not a snapshot")?;
+        fs::write(store.dir.join("abc").join("later.md.gz"), "Not a snapshot")?;
+        assert_eq!(vec!["Noon"], last_lines(&store)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_the_damaged_snapshots() -> Result<(), NottoError> {
+        let store = test_store("snapshots_damaged")?;
+        fs::create_dir_all(store.dir.join("abc"))?;
+        fs::write(store.dir.join("abc").join("1620000000000.md.gz"), "Not gzip")?;
+
+        assert!(store.history("abc").is_err());
+        assert!(store.snapshot(&with_id("Noon"), Path::new("plan.md")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+use console::{Style, measure_text_width};
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Options, Parser, Tag};
+
+const QUOTE_BAR: &str = "│ ";
+const BULLET: &str = "• ";
+const TASK_DONE: &str = "☑";
+const TASK_TODO: &str = "☐";
+/// The narrowest text, whatever the indentation
+const MIN_WIDTH: usize = 20;
+
+/// Renders markdown as styled text for the terminal, word-wrapped to `width` columns
+///
+/// The links are numbered and listed as footnotes after the text
+pub fn render_markdown(markdown: &str, width: usize) -> String {
+    let mut renderer = Renderer::new(width);
+    let options = Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES;
+    for event in Parser::new_ext(markdown, options) {
+        renderer.event(event);
+    }
+
+    renderer.finish()
+}
+
+/// The inline styles in effect
+#[derive(Default)]
+struct Inline {
+    heading: u32,
+    emphasis: bool,
+    strong: bool,
+    strikethrough: bool,
+    link: bool,
+}
+
+/// A word with its styles, and its width on screen
+struct Word {
+    text: String,
+    width: usize,
+}
+
+struct Renderer {
+    width: usize,
+    lines: Vec<String>,
+    inline: Inline,
+    /// The words of the block being read, `None` for a hard break
+    words: Vec<Option<Word>>,
+    /// Whether the next text continues the last word
+    glue: bool,
+    quotes: usize,
+    /// The next number of the ordered lists, `None` for the bullet lists
+    lists: Vec<Option<u64>>,
+    /// The widths of the markers of the items being read, the lines of an item are indented by them
+    items: Vec<usize>,
+    /// The marker starting the first line of the next block
+    marker: Option<String>,
+    code: Option<(String, String)>,
+    /// The urls of the links, numbered from 1
+    footnotes: Vec<String>,
+    table_cells: usize,
+}
+
+impl Renderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width,
+            lines: vec![],
+            inline: Inline::default(),
+            words: vec![],
+            glue: false,
+            quotes: 0,
+            lists: vec![],
+            items: vec![],
+            marker: None,
+            code: None,
+            footnotes: vec![],
+            table_cells: 0,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        if let Some((_, code)) = &mut self.code {
+            match event {
+                Event::Text(text) => return code.push_str(&text),
+                Event::End(Tag::CodeBlock(_)) => {}
+                _ => return,
+            }
+        }
+
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.text(&text, self.style()),
+            Event::Code(code) => self.text(&code, Style::new().yellow()),
+            Event::Html(html) => self.text(&html, Style::new().dim()),
+            Event::FootnoteReference(name) => self.text(&format!("[^{}]", name), Style::new().dim()),
+            Event::SoftBreak => self.glue = false,
+            Event::HardBreak => {
+                self.words.push(None);
+                self.glue = false;
+            }
+            Event::Rule => {
+                self.block_break();
+                let width = self.width.saturating_sub(self.indent_width()).max(MIN_WIDTH);
+                self.push_line(Style::new().dim().apply_to("─".repeat(width)).to_string());
+            }
+            Event::TaskListMarker(done) => {
+                let marker = if done { Style::new().green().apply_to(TASK_DONE) } else { Style::new().apply_to(TASK_TODO) };
+                self.text(&marker.to_string(), Style::new());
+                self.glue = false;
+            }
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.block_break(),
+            Tag::Heading(level) => {
+                self.block_break();
+                self.inline.heading = level;
+            }
+            Tag::BlockQuote => {
+                self.block_break();
+                self.quotes += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.block_break();
+                let language = match kind {
+                    CodeBlockKind::Fenced(language) => language.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                self.code = Some((language, String::new()));
+            }
+            Tag::List(first) => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.block_break();
+                }
+                self.lists.push(first);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => BULLET.to_string(),
+                };
+                self.items.push(measure_text_width(&marker));
+                self.marker = Some(Style::new().blue().apply_to(marker).to_string());
+            }
+            Tag::Emphasis => self.inline.emphasis = true,
+            Tag::Strong => self.inline.strong = true,
+            Tag::Strikethrough => self.inline.strikethrough = true,
+            Tag::Link(..) | Tag::Image(..) => self.inline.link = true,
+            Tag::FootnoteDefinition(name) => {
+                self.block_break();
+                self.text(&format!("[^{}]:", name), Style::new().dim());
+                self.glue = false;
+            }
+            Tag::Table(_) => self.block_break(),
+            Tag::TableHead | Tag::TableRow => self.table_cells = 0,
+            Tag::TableCell => {
+                if self.table_cells > 0 {
+                    self.glue = false;
+                    self.text("│", Style::new().dim());
+                    self.glue = false;
+                }
+                self.table_cells += 1;
+            }
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::TableHead | Tag::TableRow => self.flush(),
+            Tag::Heading(_) => {
+                self.flush();
+                self.inline.heading = 0;
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quotes = self.quotes.saturating_sub(1);
+            }
+            Tag::CodeBlock(_) => {
+                if let Some((language, code)) = self.code.take() {
+                    self.code_block(&language, &code);
+                }
+            }
+            Tag::List(_) => {
+                self.flush();
+                self.lists.pop();
+            }
+            Tag::Item => {
+                self.flush();
+                self.items.pop();
+            }
+            Tag::Emphasis => self.inline.emphasis = false,
+            Tag::Strong => self.inline.strong = false,
+            Tag::Strikethrough => self.inline.strikethrough = false,
+            Tag::Link(link_type, url, _) | Tag::Image(link_type, url, _) => {
+                self.inline.link = false;
+                let is_autolink = matches!(link_type, LinkType::Autolink | LinkType::Email);
+                if !url.is_empty() && !is_autolink {
+                    self.footnotes.push(url.to_string());
+                    self.text(&format!("[{}]", self.footnotes.len()), Style::new().dim());
+                }
+            }
+            Tag::FootnoteDefinition(_) => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn style(&self) -> Style {
+        let mut style = Style::new();
+        if self.inline.heading == 1 {
+            style = style.bold().cyan().underlined();
+        } else if self.inline.heading > 1 {
+            style = style.bold().cyan();
+        }
+        if self.inline.strong {
+            style = style.bold();
+        }
+        if self.inline.emphasis {
+            style = style.italic();
+        }
+        if self.inline.strikethrough {
+            style = style.dim();
+        }
+        if self.inline.link {
+            style = style.blue().underlined();
+        }
+
+        style
+    }
+
+    /// Adds the words of a text, a word without space before continues the last one
+    fn text(&mut self, text: &str, style: Style) {
+        for (index, part) in text.split([' ', '\n']).enumerate() {
+            if index > 0 {
+                self.glue = false;
+            }
+            if part.is_empty() {
+                continue;
+            }
+            let styled = style.apply_to(part).to_string();
+            let width = measure_text_width(part);
+            match self.words.last_mut() {
+                Some(Some(word)) if self.glue => {
+                    word.text.push_str(&styled);
+                    word.width += width;
+                }
+                _ => self.words.push(Some(Word { text: styled, width })),
+            }
+            self.glue = true;
+        }
+    }
+
+    /// Wraps the words read into lines
+    fn flush(&mut self) {
+        if self.words.is_empty() {
+            return;
+        }
+        let available = self.width.saturating_sub(self.indent_width()).max(MIN_WIDTH);
+        let (mut line, mut line_width) = (String::new(), 0);
+        for word in std::mem::take(&mut self.words) {
+            match word {
+                Some(word) => {
+                    if line_width > 0 && line_width + 1 + word.width > available {
+                        self.push_line(std::mem::take(&mut line));
+                        line_width = 0;
+                    }
+                    if line_width > 0 {
+                        line.push(' ');
+                        line_width += 1;
+                    }
+                    line.push_str(&word.text);
+                    line_width += word.width;
+                }
+                None => {
+                    self.push_line(std::mem::take(&mut line));
+                    line_width = 0;
+                }
+            }
+        }
+        if line_width > 0 {
+            self.push_line(line);
+        }
+        self.glue = false;
+    }
+
+    fn code_block(&mut self, language: &str, code: &str) {
+        let border = Style::new().dim();
+        let code_width = code.lines().map(measure_text_width).max().unwrap_or(0);
+        let width = (code_width + 2).max(measure_text_width(language) + 4).min(self.width.saturating_sub(self.indent_width()).max(MIN_WIDTH));
+        let top = if language.is_empty() {
+            format!("┌{}", "─".repeat(width))
+        } else {
+            format!("┌─ {} {}", language, "─".repeat(width.saturating_sub(measure_text_width(language) + 3)))
+        };
+        self.push_line(border.apply_to(top).to_string());
+        for line in code.lines() {
+            self.push_line(format!("{} {}", border.apply_to("│"), Style::new().yellow().apply_to(line)));
+        }
+        self.push_line(border.apply_to(format!("└{}", "─".repeat(width))).to_string());
+    }
+
+    /// Adds a line with the quote bars and the indentation of the list items, or the marker of a new item
+    fn push_line(&mut self, text: String) {
+        let mut line = Style::new().dim().apply_to(QUOTE_BAR.repeat(self.quotes)).to_string();
+        let indent = self.items.iter().sum::<usize>();
+        match self.marker.take() {
+            Some(marker) => {
+                line.push_str(&" ".repeat(indent - self.items.last().copied().unwrap_or(0)));
+                line.push_str(&marker);
+            }
+            None => line.push_str(&" ".repeat(indent)),
+        }
+        line.push_str(&text);
+        self.lines.push(line);
+    }
+
+    fn indent_width(&self) -> usize {
+        self.quotes * measure_text_width(QUOTE_BAR) + self.items.iter().sum::<usize>()
+    }
+
+    /// Separates a block from the one before with an empty line, lists are kept tight
+    fn block_break(&mut self) {
+        self.flush();
+        let in_list = !self.lists.is_empty();
+        if !in_list && self.lines.last().map(|line| !line.is_empty()).unwrap_or(false) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        if !self.footnotes.is_empty() {
+            self.lines.push(String::new());
+            for (index, url) in self.footnotes.iter().enumerate() {
+                self.lines.push(format!("{} {}", Style::new().dim().apply_to(format!("[{}]:", index + 1)), url));
+            }
+        }
+
+        self.lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn renders_markdown() {
+        console::set_colors_enabled(false);
+        let markdown = "# Plan\n\nSome *text* with a [link](https://example.com) and `code` to wrap.\n\n- one\n- [x] two\n  1. first\n  2. second\n\n> quoted\n\n```rust\nlet a = 1;\n```\n";
+        assert_eq!(
+            "Plan\n\nSome text with a link[1]\nand code to wrap.\n\n• one\n• ☑ two\n  1. first\n  2. second\n\n│ quoted\n\n┌─ rust ─────\n│ let a = 1;\n└────────────\n\n[1]: https://example.com",
+            render_markdown(markdown, 24)
+        );
+    }
+}
@@ -1,71 +1,321 @@
-use std::{fs::{self}, io::{Read}, path::{Path, PathBuf}, process::{self, ExitStatus}};
+use std::{cell::RefCell, convert::TryFrom, env, ffi::OsStr, fs::{self}, io::{Read, Write}, path::{Path, PathBuf}, process::{self, ExitStatus}, rc::Rc};
 
-use chrono::{Datelike, Utc};
+use chrono::{Datelike, Duration, Local, NaiveDate, Utc};
 use crossbeam_channel::Receiver;
 use errors::NottoError;
-use finder::{FindCondition, Finder, NoteFindMessage};
-use models::{config::{Config}, front_matter::FrontMatter, note::Note};
+use export::{ExportSummary, archive::ArchiveExporter, html::HtmlExporter, json::JsonExporter};
+use import::{ImportFormat, ImportReport, archive::ArchiveImporter, evernote::EvernoteImporter, joplin::JoplinImporter, markdown::MarkdownImporter};
+use finder::{FindCondition, Finder, NoteFindMessage, resolver::NoteResolver};
+use models::{config::{Config}, front_matter::FrontMatter, note::{Note, roll_recurring_tasks_in_text}, task::{NoteTask, TaskReminder}};
 use uuid::Uuid;
-use io::{ReaderWriter, browser::{NoteBrowser, NottoPath, PathEntry}};
+use io::{ReaderWriter, browser::{ListOrder, NoteBrowser, NoteListing, NottoPath, PathEntry}, crypto::{NoteCipher, PlaintextFile, is_encrypted}, editor::{EditorCommand, Position}, git::GitRepo, history::{NoteHistory, Revision, unified_diff}, home::NottoHome, hooks::HookRunner, links::{Backlink, find_links, link_candidates, resolve_link, to_link}, lock::NoteLock, merge::{ConflictChoice, count_conflicts, merge}, snapshots::SnapshotStore};
+use log::warn;
 
 pub mod models;
 pub mod io;
 pub mod finder;
 pub mod errors;
+pub mod export;
+pub mod import;
+pub mod lsp;
+pub mod mcp;
+pub mod rpc;
+pub mod server;
+#[cfg(test)]
+mod test_utils;
 
-const BASE_CONFIG_DIR: &str = ".notto";
 const PATH_SEPARATOR: &str = "/";
+const NOTE_EXTENSION: &str = "md";
+/// The passphrase of the encrypted notes, instead of asking for it
+const PASSPHRASE_VARIABLE: &str = "NOTTO_PASSPHRASE";
+
+type PassphrasePrompt = Rc<dyn Fn() -> Result<String, NottoError>>;
+type InUsePrompt = Rc<dyn Fn(&str, u32, &str) -> Result<bool, NottoError>>;
+type ConflictPrompt = Rc<dyn Fn(&str, usize) -> Result<ConflictChoice, NottoError>>;
+
+/// The questions notto asks the user through its frontend, without them it fails or takes the safe way
+#[derive(Clone, Default)]
+struct Prompts {
+    passphrase: Option<PassphrasePrompt>,
+    in_use: Option<InUsePrompt>,
+    conflict: Option<ConflictPrompt>,
+}
 
 pub struct Notto {
     pub config: Config,
+    /// Set once notto is unlocked with the passphrase of the encrypted notes
+    cipher: RefCell<Option<NoteCipher>>,
+    prompts: Prompts,
 }
 
 impl Notto {
     pub fn new() -> Result<Self, NottoError> {
+        Notto::with_context::<&str>(None)
+    }
+
+    /// Loads notto using the given context instead of the one in the env variable or the config file
+    pub fn with_context<S: AsRef<str>>(context: Option<S>) -> Result<Self, NottoError> {
         env_logger::init();
 
-        let config = Config::load_config(Notto::get_home_dir()?)?;
+        let mut config = Config::load_config(Notto::get_home()?.ensure_config_dir()?)?;
+        if let Some(context) = context {
+            config.override_context(context)?;
+        }
+
+        let notto = Self { config, cipher: RefCell::new(None), prompts: Prompts::default() };
+        if let Some(passphrase) = env::var(PASSPHRASE_VARIABLE).ok().filter(|passphrase| !passphrase.is_empty()) {
+            notto.unlock(passphrase)?;
+        }
+
+        Ok(notto)
+    }
+
+    /// Uses the given config instead of the config file, like the servers and the tests do
+    pub fn with_config(config: Config) -> Self {
+        Self { config, cipher: RefCell::new(None), prompts: Prompts::default() }
+    }
+
+    /// Uses `passphrase` to encrypt and decrypt the notes
+    ///
+    /// A wrong passphrase is only found when a note can't be decrypted
+    pub fn unlock<S: Into<String>>(&self, passphrase: S) -> Result<(), NottoError> {
+        self.cipher.replace(Some(NoteCipher::new(passphrase)?));
+
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.cipher.borrow().is_some()
+    }
+
+    /// Asks for the passphrase if the context in use is encrypted and notto isn't unlocked yet
+    pub fn unlock_context(&self) -> Result<(), NottoError> {
+        if self.config.is_encrypted()? {
+            self.require_cipher(&self.config.get_context()?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Asks for the passphrase the first time an encrypted note is needed, instead of failing with `NoteLocked`
+    pub fn set_passphrase_prompt<F>(&mut self, prompt: F) where F: Fn() -> Result<String, NottoError> + 'static {
+        self.prompts.passphrase = Some(Rc::new(prompt));
+    }
+
+    /// Asks whether to open a note another process has open, with the note, the process id and its host
+    ///
+    /// Without it opening the note fails with `NoteInUse`
+    pub fn set_in_use_prompt<F>(&mut self, prompt: F) where F: Fn(&str, u32, &str) -> Result<bool, NottoError> + 'static {
+        self.prompts.in_use = Some(Rc::new(prompt));
+    }
+
+    /// Asks what to do when a note changed on disk while it was in the editor and the changes conflict,
+    /// with the note and the number of conflicts
+    ///
+    /// Without it the merge is saved with its conflicts and the edit fails with `EditConflict`
+    pub fn set_conflict_prompt<F>(&mut self, prompt: F) where F: Fn(&str, usize) -> Result<ConflictChoice, NottoError> + 'static {
+        self.prompts.conflict = Some(Rc::new(prompt));
+    }
 
-        Ok(Self { config })
+    /// Saves the changes to the config, like new contexts
+    pub fn save_config(&self) -> Result<(), NottoError> {
+        self.config.save_config_file(&Config::config_file_path(Notto::get_home()?.ensure_config_dir()?))
+    }
+
+    /// The path of the note `note_name` found by its path, its id or the start of it, the end of its path or its title
+    ///
+    /// Fails with `AmbiguousNote` listing the candidates when several notes match the same way
+    pub fn resolve<S: AsRef<str>>(&self, note_name: S) -> Result<PathBuf, NottoError> {
+        Notto::resolve_note(&self.get_writer()?, note_name.as_ref())
+    }
+
+    /// The path of the note `note_name` found by its path, its id or the start of it, for the commands changing a note
+    pub fn resolve_exactly<S: AsRef<str>>(&self, note_name: S) -> Result<PathBuf, NottoError> {
+        Notto::resolve_note_exactly(&self.get_writer()?, note_name.as_ref())
+    }
+
+    /// Opens the note `note_name`, found like `resolve`
+    pub fn open_note<S: AsRef<str>>(&self, note_name: S) -> Result<PathBuf, NottoError> {
+        let note_path = self.resolve(note_name)?;
+        self.edit_note(&note_path, None)?;
+
+        Ok(note_path)
     }
 
     pub fn open_by_path<P: Into<NottoPath>>(&self, note_path: P) -> Result<(), NottoError> {
-        let notto_path: NottoPath = note_path.into();
-        let path = self.config.get_notes_dir()?.join(notto_path);
+        self.open_by_path_at(note_path, None)
+    }
 
-        self.open_editor_with_path(&path)?;
+    /// Opens the note with the cursor at `position`, when the editor supports it
+    pub fn open_by_path_at<P: Into<NottoPath>>(&self, note_path: P, position: Option<Position>) -> Result<(), NottoError> {
+        let notto_path: NottoPath = note_path.into();
+        self.edit_note(notto_path.as_ref(), position)?;
 
         Ok(())
     }
 
     /// Returns a receiver with the find results
+    ///
+    /// The encrypted notes are searched once notto is unlocked, the passphrase is asked for in an encrypted context
     pub fn find(&self, conditions: Vec<FindCondition>) -> Result<Receiver<NoteFindMessage>, NottoError> {
-        let finder = Finder::new(self.config.get_notes_dir()?);
+        let rx = self.finder()?.find(PathBuf::new(), conditions)?;
+
+        Ok(rx)
+    }
+
+    /// The finder of the notes of the context in use, to search from another thread
+    ///
+    /// Like `find`, the passphrase is asked for in an encrypted context
+    pub fn finder(&self) -> Result<Finder, NottoError> {
+        self.unlock_context()?;
+
+        Ok(Finder::new(self.config.get_notes_dir()?).with_cipher(self.cipher.borrow().clone()))
+    }
+
+    /// Returns a receiver with the find results of every context, tagged with their context
+    pub fn find_in_all_contexts(&self, conditions: Vec<FindCondition>) -> Result<Receiver<NoteFindMessage>, NottoError> {
+        let contexts = self.config.get_context_names().into_iter()
+            .map(|context| self.config.get_notes_dir_from(&context).map(|notes_dir| (context, notes_dir)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let finder = Finder::with_contexts(contexts).with_cipher(self.cipher.borrow().clone());
         let rx = finder.find(PathBuf::new(), conditions)?;
 
         Ok(rx)
     }
 
+    /// The same notto using another context, to work with the notes of several contexts
+    pub fn in_context<S: AsRef<str>>(&self, context: S) -> Result<Notto, NottoError> {
+        let mut config = self.config.clone();
+        config.override_context(context)?;
+
+        Ok(Notto { config, cipher: self.cipher.clone(), prompts: self.prompts.clone() })
+    }
+
+    /// The note at `note_path` in the notes directory, its content is decrypted once notto is unlocked
+    pub fn read_note<P: AsRef<Path>>(&self, note_path: P) -> Result<Note, NottoError> {
+        self.get_writer()?.read_note(note_path)
+    }
+
+    /// The note `note_name`, found by its path, its id or its title like `open`, with its path
+    ///
+    /// An encrypted note is decrypted, the passphrase is asked for if notto isn't unlocked
+    pub fn get_note<S: AsRef<str>>(&self, note_name: S) -> Result<(PathBuf, Note), NottoError> {
+        let writer = self.get_writer()?;
+        let note_path = Notto::resolve_note(&writer, note_name.as_ref())?;
+        let mut note = writer.read_note(&note_path)?;
+        if is_encrypted(&note.content) {
+            note.content = self.require_cipher(&to_link(&note_path))?.decrypt(&note.content)?;
+        }
+
+        Ok((note_path, note))
+    }
+
+    /// The text of the note `note_name` as the editor gets it, decrypted, with its path
+    pub fn get_note_text<S: AsRef<str>>(&self, note_name: S) -> Result<(PathBuf, String), NottoError> {
+        let writer = self.get_writer()?;
+        let note_path = Notto::resolve_note(&writer, note_name.as_ref())?;
+        let text = self.decrypt_text(writer.read_text(&note_path)?, &note_path)?;
+
+        Ok((note_path, text))
+    }
+
+    /// Saves `text` as the note `note_name` unless the note changed since its text with `hash` was read
+    ///
+    /// Fails with `NoteChanged` then, and with `NoteInUse` while the note is open in an editor
+    pub fn update_note_text<S: AsRef<str>>(&self, note_name: S, text: &str, hash: &str) -> Result<PathBuf, NottoError> {
+        let writer = self.get_writer()?;
+        let note_path = Notto::resolve_note(&writer, note_name.as_ref())?;
+        let _lock = writer.lock_note(&note_path, false)?;
+
+        let stored_text = writer.read_text(&note_path)?;
+        let encrypted = is_encrypted(&Note::split_front_matter(&stored_text).1);
+        let current_hash = Notto::text_hash(&self.decrypt_text(stored_text, &note_path)?);
+        if current_hash != hash {
+            return Err(NottoError::NoteChanged { note: to_link(&note_path), hash: current_hash });
+        }
+
+        let writer = self.get_writer()?;
+        writer.snapshot_note(&note_path)?;
+        if encrypted {
+            let mut note = Note::from_text(text);
+            note.front_matter.encrypted = true;
+            note.roll_recurring_tasks(Notto::today());
+            writer.update_note(note, &note_path)?;
+        } else {
+            writer.write_text(&note_path, text)?;
+            self.note_edited(&writer, &note_path)?;
+        }
+
+        Ok(note_path)
+    }
+
+    /// The hash of the text of a note, to tell whether it changed
+    pub fn text_hash(text: &str) -> String {
+        format!("{:x}", md5::compute(text))
+    }
+
     pub fn browse(&self, path: &NottoPath) -> Result<Vec<PathEntry>, NottoError> {
+        Notto::check_dir_path(path)?;
         let browser = NoteBrowser::new(self.config.get_notes_dir()?);
         browser.get_selections_for_path(path)
     }
 
+    /// Lists the notes and directories under `path`, the notes directory by default, and under the directories too if `recursive`
+    pub fn list<S: AsRef<str>>(&self, path: Option<S>, recursive: bool, order: ListOrder) -> Result<Vec<NoteListing>, NottoError> {
+        let browser = NoteBrowser::new(self.config.get_notes_dir()?);
+        let path = path.map(|path| NottoPath::from(path.as_ref().trim_matches('/').to_string())).unwrap_or_default();
+        Notto::check_dir_path(&path)?;
+        if !self.config.get_notes_dir()?.join(&path).is_dir() {
+            return Err(NottoError::FileError { message: format!("`{}` is not a directory of the notes", String::from(path)) });
+        }
+
+        browser.list(&path, recursive, order)
+    }
+
+    /// Exports the notes of the current context as a static HTML site
+    pub fn export_html<P: AsRef<Path>>(&self, out_dir: P) -> Result<ExportSummary, NottoError> {
+        self.unlock_context()?;
+        let exporter = HtmlExporter::new(self.get_writer()?);
+        exporter.export(out_dir)
+    }
+
+    /// Writes every note of the current context as a line of JSON
+    pub fn export_json<W: Write>(&self, out: W) -> Result<ExportSummary, NottoError> {
+        self.unlock_context()?;
+        let exporter = JsonExporter::new(self.get_writer()?);
+        exporter.export(out)
+    }
+
+    /// Bundles the notes of the current context in a `.tar.gz` archive
+    pub fn export_archive<P: AsRef<Path>>(&self, archive_path: P) -> Result<ExportSummary, NottoError> {
+        let exporter = ArchiveExporter::new(self.get_writer()?);
+        exporter.export(archive_path, self.config.get_context()?)
+    }
+
+    /// Imports notes from another application, the format is detected from the source when not given
+    pub fn import<P: AsRef<Path>, S: AsRef<str>>(&self, source: P, format: Option<ImportFormat>, into: Option<S>) -> Result<ImportReport, NottoError> {
+        self.unlock_context()?;
+        let writer = self.get_writer()?;
+        let into = into.map(|into| NottoPath::from(into.as_ref().to_string())).unwrap_or_default();
+        Notto::check_dir_path(&into)?;
+        let into = PathEntry::string_to_pathbuf(&into);
+        match format.unwrap_or_else(|| ImportFormat::detect(&source)) {
+            ImportFormat::Markdown => MarkdownImporter::new(writer).import(source, into),
+            ImportFormat::Joplin => JoplinImporter::new(writer).import(source, into),
+            ImportFormat::Evernote => EvernoteImporter::new(writer).import(source, into),
+            ImportFormat::Archive => ArchiveImporter::new(writer).import(source, into),
+        }
+    }
+
     pub fn create_or_open_note_at<S: AsRef<str>>(&self, dest_path: Option<S>) -> Result<PathBuf, NottoError> {
-        let writer = ReaderWriter::new(self.config.get_notes_dir()?);
+        self.unlock_context()?;
+        let writer = self.get_writer()?;
 
         if let Some(dest_path) = dest_path {
-            let path_segments = dest_path.as_ref().split(PATH_SEPARATOR).into_iter().collect::<Vec<_>>();
-            let mut path = PathBuf::new();
-            let segments = path_segments.len();
-
-            if segments > 1 {
-                for i in 0..(segments - 1) {
-                    path.push(path_segments[i]);
-                }
+            let (path, file_name) = Notto::split_note_name(dest_path.as_ref())?;
+            if !path.as_os_str().is_empty() {
                 writer.create_dir_all(&path)?;
             }
-            let file_name = path_segments[segments - 1].to_string();
             let result_path = match writer.note_file_exists(&path, &file_name) {
                 Some(note_type) => match note_type {
                     io::NoteFileType::File(file_name) => path.join(file_name),
@@ -78,7 +328,7 @@ impl Notto {
                 }
             };
 
-            let status = self.open_editor_with_path(writer.get_full_path(&result_path))?;
+            let status = self.edit_note(&result_path, None)?;
 
             if status.success() {
                 Ok(result_path)
@@ -90,16 +340,418 @@ impl Notto {
             // A note without name
             let note_text = self.get_text_from_editor()?;
             if !note_text.is_empty() {
-                let note = Note::from_text(&note_text);
+                let mut note = Note::from_text(&note_text);
+                note.roll_recurring_tasks(Notto::today());
                 let file_name = writer.get_file_name_from_note(&note);
                 writer.save_note_at(note, PathBuf::new(), file_name, false)
             } else {
-                Err(NottoError::CreateNoteError { message: format!("No content in the note, not saving") })
+                Err(NottoError::CreateNoteError { message: "No content in the note, not saving".to_string() })
             }
         }
     }
 
+    /// Saves a new note with `text` without opening the editor, at `note_name` like `work/plan` or named after its title
+    ///
+    /// The text may start with a front matter, fails if the note already exists
+    pub fn create_note<S: AsRef<str>>(&self, note_name: Option<S>, text: &str) -> Result<PathBuf, NottoError> {
+        self.unlock_context()?;
+        let writer = self.get_writer()?;
+
+        let mut note = Note::from_text(text);
+        note.roll_recurring_tasks(Notto::today());
+        match note_name.as_ref().map(|note_name| note_name.as_ref().trim()).filter(|note_name| !note_name.is_empty()) {
+            Some(note_name) => {
+                let (dir, file_name) = Notto::split_note_name(note_name)?;
+                writer.save_note_at(note, dir, file_name, false)
+            }
+            None => {
+                let file_name = writer.get_file_name_from_note(&note);
+                writer.save_note_at(note, PathBuf::new(), file_name, false)
+            }
+        }
+    }
+
+    /// Moves the note `note_name`, like `work/plan`, found like `resolve_exactly`, to `dest_name`, returns its new path
+    pub fn move_note<S: AsRef<str>, T: AsRef<str>>(&self, note_name: S, dest_name: T) -> Result<PathBuf, NottoError> {
+        let writer = self.get_writer()?;
+        let note_path = Notto::resolve_note_exactly(&writer, note_name.as_ref())?;
+        let (dest_dir, dest_file_name) = Notto::split_note_name(dest_name.as_ref())?;
+
+        writer.move_note(note_path, dest_dir, dest_file_name)
+    }
+
+    /// Deletes the note `note_name`, like `work/plan`, found like `resolve_exactly`, returns the path of the deleted file
+    pub fn delete_note<S: AsRef<str>>(&self, note_name: S) -> Result<PathBuf, NottoError> {
+        let writer = self.get_writer()?;
+        let note_path = Notto::resolve_note_exactly(&writer, note_name.as_ref())?;
+        writer.delete_note(&note_path)?;
+
+        Ok(note_path)
+    }
+
+    /// The revisions of the note `note_name` in git or in the snapshots, the latest first
+    pub fn history<S: AsRef<str>>(&self, note_name: S) -> Result<Vec<Revision>, NottoError> {
+        let (history, note_path) = self.get_note_history(note_name.as_ref())?;
+        history.revisions(&self.get_writer()?, &note_path)
+    }
+
+    /// Brings back the note as it was in the revision `number` of its history, with git a deleted note too
+    pub fn restore<S: AsRef<str>>(&self, note_name: S, number: usize) -> Result<PathBuf, NottoError> {
+        let (history, note_path) = self.get_note_history(note_name.as_ref())?;
+        let writer = self.get_writer()?;
+        let revision = Notto::find_revision(history.revisions(&writer, &note_path)?, number, note_name.as_ref())?;
+        let note = Note::from_text(history.read_revision(&revision)?);
+
+        writer.update_note(note, note_path)
+    }
+
+    /// The changes from the revision `number`, the latest by default, to the note as it is now, as a unified diff
+    pub fn diff<S: AsRef<str>>(&self, note_name: S, number: Option<usize>) -> Result<String, NottoError> {
+        let (history, note_path) = self.get_note_history(note_name.as_ref())?;
+        let writer = self.get_writer()?;
+        let number = number.unwrap_or(1);
+        let revision = Notto::find_revision(history.revisions(&writer, &note_path)?, number, note_name.as_ref())?;
+        let old_text = self.decrypt_text(history.read_revision(&revision)?, &note_path)?;
+        // A deleted note is empty now
+        let new_text = self.decrypt_text(writer.read_text(&note_path).unwrap_or_default(), &note_path)?;
+        let path = to_link(&note_path);
+
+        Ok(unified_diff(&old_text, &new_text, &format!("{} (revision {})", path, number), &path))
+    }
+
     pub fn create_journal_entry<S: AsRef<str>>(&self, name: Option<S>) -> Result<PathBuf, NottoError> {
+        self.create_or_open_note_at(Some(Notto::journal_entry_name(name)))
+    }
+
+    /// Adds `text` as a paragraph at the end of today's journal entry, which is created with it if there's none yet
+    pub fn append_to_journal(&self, text: &str) -> Result<PathBuf, NottoError> {
+        let note_name = Notto::journal_entry_name::<&str>(None);
+        let note_path = match Notto::existing_note_path(&self.get_writer()?, &note_name) {
+            Ok(note_path) => note_path,
+            Err(_) => return self.create_note(Some(note_name), text),
+        };
+
+        let (_, current_text) = self.get_note_text(to_link(&note_path))?;
+        let text = format!("{}\n\n{}\n", current_text.trim_end(), text.trim_end());
+        self.update_note_text(to_link(&note_path), &text, &Notto::text_hash(&current_text))
+    }
+
+    /// Returns the pending tasks with a date up to `days` from today, including the overdue ones
+    ///
+    /// Sorted by date
+    pub fn remind(&self, days: i64) -> Result<Vec<TaskReminder>, NottoError> {
+        let today = Notto::today();
+        let until = i32::try_from(days).ok().and_then(|days| today.checked_add_signed(Duration::days(i64::from(days))))
+            .ok_or_else(|| NottoError::InvalidArgument { argument: "days".to_string(), message: format!("{} days from today is out of range", days) })?;
+        let notes_dir = self.config.get_notes_dir()?;
+
+        let mut reminders = vec![];
+        for message in self.find(vec![])? {
+            match message {
+                NoteFindMessage::Result(result) => {
+                    let path = result.path.strip_prefix(&notes_dir).map(PathBuf::from).unwrap_or(result.path);
+                    for task in result.note.get_tasks() {
+                        let pending_date = task.pending_date(today).unwrap_or_else(|e| {
+                            warn!("{} in task `{}`", e, task.text);
+                            None
+                        });
+                        if let Some(date) = pending_date {
+                            if date <= until {
+                                let note_title = result.note.get_title();
+                                reminders.push(TaskReminder { task, date, note_title, path: path.clone() });
+                            }
+                        }
+                    }
+                }
+                NoteFindMessage::Finish { .. } => break
+            }
+        }
+        reminders.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.path.cmp(&b.path)));
+
+        Ok(reminders)
+    }
+
+    /// Returns the tasks of every note, the done ones too if `done`
+    ///
+    /// Sorted by note and line
+    pub fn tasks(&self, done: bool) -> Result<Vec<NoteTask>, NottoError> {
+        let notes_dir = self.config.get_notes_dir()?;
+
+        let mut tasks = vec![];
+        for message in self.find(vec![])? {
+            match message {
+                NoteFindMessage::Result(result) => {
+                    let path = result.path.strip_prefix(&notes_dir).map(PathBuf::from).unwrap_or(result.path);
+                    let note_title = result.note.get_title();
+                    for task in result.note.get_tasks().into_iter().filter(|task| done || !task.done) {
+                        tasks.push(NoteTask { task, note_title: note_title.clone(), path: path.clone() });
+                    }
+                }
+                NoteFindMessage::Finish { .. } => break
+            }
+        }
+        tasks.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.task.line.cmp(&b.task.line)));
+
+        Ok(tasks)
+    }
+
+    /// Returns the links to the note `note_name`, found like `resolve`, from the other notes
+    pub fn backlinks<S: AsRef<str>>(&self, note_name: S) -> Result<Vec<Backlink>, NottoError> {
+        let writer = self.get_writer()?;
+        let target = Notto::resolve_note(&writer, note_name.as_ref())?;
+
+        let mut backlinks = vec![];
+        for path in writer.list_files("")? {
+            if path.extension() != Some(OsStr::new(NOTE_EXTENSION)) {
+                continue;
+            }
+            let note = writer.read_note(&path)?;
+            for link in find_links(&note.content) {
+                let linked = resolve_link(&path, &link.url).map(|linked| link_candidates(linked).contains(&target)).unwrap_or(false);
+                if linked {
+                    let line = note.content[..link.range.start].matches('\n').count();
+                    backlinks.push(Backlink { path: path.clone(), title: note.get_title(), line, url: link.url });
+                }
+            }
+        }
+
+        Ok(backlinks)
+    }
+
+    pub fn today() -> NaiveDate {
+        Local::now().naive_local().date()
+    }
+
+    /// Opens the note in the editor and saves it once edited
+    ///
+    /// The note is locked while it's edited. A plain note is edited in place, an encrypted note in a private file
+    /// with its plaintext: when the note changed on disk meanwhile the edits are merged with the changes.
+    /// Nothing is saved, snapshot or committed when the note is left unchanged
+    fn edit_note(&self, note_path: &Path, position: Option<Position>) -> Result<ExitStatus, NottoError> {
+        let writer = self.get_writer()?;
+        let _lock = self.lock_note(&writer, note_path)?;
+
+        let stored_text = writer.read_text(note_path)?;
+        if !is_encrypted(&Note::split_front_matter(&stored_text).1) {
+            let status = self.open_editor_at(writer.get_full_path(note_path), position)?;
+            if status.success() && writer.read_text(note_path)? != stored_text {
+                writer.snapshot_text(&stored_text, note_path)?;
+                self.note_edited(&writer, note_path)?;
+            }
+            return Ok(status);
+        }
+
+        let text = self.decrypt_text(stored_text.clone(), note_path)?;
+        let writer = self.get_writer()?;
+        let working_copy = PlaintextFile::create(Notto::get_temp_path()?, &text)?;
+        let status = self.open_editor_at(working_copy.path(), position)?;
+        if !status.success() {
+            return Ok(status);
+        }
+
+        let mut edited_text = working_copy.read()?;
+        let mut conflicts = 0;
+        let saved_text = writer.read_text(note_path).unwrap_or_default();
+        if md5::compute(&saved_text) != md5::compute(&stored_text) {
+            let saved_text = self.decrypt_text(saved_text, note_path)?;
+            match self.merge_edits(note_path, &text, &edited_text, &saved_text, &working_copy)? {
+                Some((merged_text, left)) => {
+                    edited_text = merged_text;
+                    conflicts = left;
+                }
+                None => return Ok(status)
+            }
+        } else if edited_text == text {
+            return Ok(status);
+        }
+
+        writer.snapshot_note(note_path)?;
+        let mut note = Note::from_text(edited_text);
+        // Removing the front matter in the editor doesn't decrypt the note
+        note.front_matter.encrypted = true;
+        note.roll_recurring_tasks(Notto::today());
+        writer.update_note(note, note_path)?;
+
+        if conflicts > 0 {
+            return Err(NottoError::EditConflict { note: to_link(note_path), conflicts });
+        }
+
+        Ok(status)
+    }
+
+    /// Locks the note to edit it, asking whether to open it anyway if another process has it open
+    fn lock_note(&self, writer: &ReaderWriter, note_path: &Path) -> Result<NoteLock, NottoError> {
+        match writer.lock_note(note_path, false) {
+            Err(NottoError::NoteInUse { note, pid, host }) => match &self.prompts.in_use {
+                Some(prompt) if prompt(&note, pid, &host)? => writer.lock_note(note_path, true),
+                _ => Err(NottoError::NoteInUse { note, pid, host })
+            },
+            result => result
+        }
+    }
+
+    /// The text to save, with the number of conflicts left in it, when the note was saved by someone else while it was edited
+    ///
+    /// `base` is the text the editor got, `None` keeps the saved note
+    fn merge_edits(&self, note_path: &Path, base: &str, edited: &str, saved: &str, working_copy: &PlaintextFile) -> Result<Option<(String, usize)>, NottoError> {
+        let note = to_link(note_path);
+        let merged = merge(base, edited, saved);
+        if merged.conflicts == 0 {
+            warn!("{} changed while it was edited, the edits are merged with the changes", note);
+            return Ok(Some((merged.text, 0)));
+        }
+
+        match &self.prompts.conflict {
+            Some(prompt) => match prompt(&note, merged.conflicts)? {
+                ConflictChoice::Merge => {
+                    fs::write(working_copy.path(), &merged.text)?;
+                    self.open_editor_at(working_copy.path(), None)?;
+                    let text = working_copy.read()?;
+                    let left = count_conflicts(&text);
+                    Ok(Some((text, left)))
+                }
+                ConflictChoice::KeepEdited => Ok(Some((edited.to_string(), 0))),
+                ConflictChoice::KeepSaved => Ok(None),
+            },
+            None => Ok(Some((merged.text, merged.conflicts)))
+        }
+    }
+
+    /// Adds the next occurrence of the recurring tasks completed in the note once it's edited, the note is saved with its hooks
+    ///
+    /// A note given `encrypted: true` in the editor is encrypted
+    fn note_edited<P>(&self, writer: &ReaderWriter, note_path: P) -> Result<(), NottoError> where P: AsRef<Path> {
+        let mut note = writer.read_note(&note_path)?;
+        let to_encrypt = (note.front_matter.encrypted || self.config.is_encrypted()?) && !is_encrypted(&note.content);
+        if to_encrypt {
+            note.roll_recurring_tasks(Notto::today());
+            self.require_cipher(&to_link(note_path.as_ref()))?;
+            self.get_writer()?.update_note(note, note_path)?;
+        } else {
+            // Only the lines of the rolled tasks are rewritten, the rest of the text stays as it was edited
+            if let Some(text) = roll_recurring_tasks_in_text(&writer.read_text(&note_path)?, Notto::today()) {
+                writer.write_text(&note_path, &text)?;
+            }
+            writer.note_saved(note_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Unlocks notto with the passphrase prompt if it's not yet, `name` is what needs it
+    fn require_cipher(&self, name: &str) -> Result<NoteCipher, NottoError> {
+        if let Some(cipher) = self.cipher.borrow().as_ref() {
+            return Ok(cipher.clone());
+        }
+        match &self.prompts.passphrase {
+            Some(prompt) => {
+                let cipher = NoteCipher::new(prompt()?)?;
+                self.cipher.replace(Some(cipher.clone()));
+                Ok(cipher)
+            }
+            None => Err(NottoError::NoteLocked { note: name.to_string() })
+        }
+    }
+
+    /// The text of a note with its content decrypted if it's encrypted
+    fn decrypt_text(&self, text: String, note_path: &Path) -> Result<String, NottoError> {
+        let mut note = Note::from_text(&text);
+        if !is_encrypted(&note.content) {
+            return Ok(text);
+        }
+        note.content = self.require_cipher(&to_link(note_path))?.decrypt(&note.content)?;
+
+        Ok(note.to_text())
+    }
+
+    /// The reader writer of the notes of the context in use, running its hooks and committing to git or keeping snapshots
+    ///
+    /// It encrypts and decrypts the notes once notto is unlocked
+    fn get_writer(&self) -> Result<ReaderWriter, NottoError> {
+        let notes_dir = self.config.get_notes_dir()?;
+        let hooks = HookRunner::new(self.config.get_context()?, self.config.get_hooks()?);
+        let writer = ReaderWriter::new(notes_dir.clone())
+            .with_hooks(hooks)
+            .with_encryption(self.cipher.borrow().clone(), self.config.is_encrypted()?);
+        if self.config.is_git_enabled()? {
+            Ok(writer.with_git(GitRepo::open(notes_dir)?))
+        } else if let Some(store) = self.get_snapshot_store()? {
+            Ok(writer.with_snapshots(store))
+        } else {
+            Ok(writer)
+        }
+    }
+
+    /// Where the versions of the notes are kept, and the path of a note which may have been deleted
+    fn get_note_history(&self, note_name: &str) -> Result<(NoteHistory, PathBuf), NottoError> {
+        let notes_dir = self.config.get_notes_dir()?;
+        let history = if self.config.is_git_enabled()? {
+            NoteHistory::Git(GitRepo::open(notes_dir.clone())?)
+        } else if let Some(store) = self.get_snapshot_store()? {
+            NoteHistory::Snapshots(store)
+        } else {
+            return Err(NottoError::HistoryError { message: format!("no history for context {}, turn on `git` or `snapshots` with `notto config set`", self.config.get_context()?) });
+        };
+        // A deleted note is only found by its path
+        let note_path = match Notto::resolve_note(&ReaderWriter::new(notes_dir), note_name) {
+            Err(NottoError::NoteNotFound { .. }) => {
+                let (dir, file_name) = Notto::split_note_name(note_name)?;
+                let dotted_extension = format!(".{}", NOTE_EXTENSION);
+                dir.join(format!("{}{}", file_name.trim_end_matches(&dotted_extension), dotted_extension))
+            }
+            result => result?
+        };
+
+        Ok((history, note_path))
+    }
+
+    /// The snapshots of the notes when they're on and git is off
+    fn get_snapshot_store(&self) -> Result<Option<SnapshotStore>, NottoError> {
+        let policy = self.config.get_snapshot_policy()?;
+        if policy.enabled && !self.config.is_git_enabled()? {
+            Ok(Some(SnapshotStore::new(Notto::get_home()?.ensure_snapshots_dir()?, policy)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn find_revision(revisions: Vec<Revision>, number: usize, note_name: &str) -> Result<Revision, NottoError> {
+        revisions.into_iter()
+            .find(|revision| revision.number == number)
+            .ok_or_else(|| NottoError::HistoryError { message: format!("no revision {} for note `{}`", number, note_name) })
+    }
+
+    /// The path of a note by its path, or by its id, the end of its path or its title with the `NoteResolver`
+    ///
+    /// A name going out of the notes directory is rejected before looking for it
+    fn resolve_note(writer: &ReaderWriter, note_name: &str) -> Result<PathBuf, NottoError> {
+        match Notto::existing_note_path(writer, note_name) {
+            Ok(note_path) => Ok(note_path),
+            Err(e @ NottoError::InvalidNoteName { .. }) => Err(e),
+            Err(_) => NoteResolver::load(writer)?.resolve(note_name)
+        }
+    }
+
+    /// The path of a note by its path, or by its id or the start of it with the `NoteResolver`
+    fn resolve_note_exactly(writer: &ReaderWriter, note_name: &str) -> Result<PathBuf, NottoError> {
+        match Notto::existing_note_path(writer, note_name) {
+            Ok(note_path) => Ok(note_path),
+            Err(e @ NottoError::InvalidNoteName { .. }) => Err(e),
+            Err(_) => NoteResolver::load(writer)?.resolve_exactly(note_name)
+        }
+    }
+
+    fn existing_note_path(writer: &ReaderWriter, note_name: &str) -> Result<PathBuf, NottoError> {
+        let (dir, file_name) = Notto::split_note_name(note_name)?;
+        match writer.note_file_exists(&dir, &file_name) {
+            Some(io::NoteFileType::File(file_name)) => Ok(dir.join(file_name)),
+            Some(io::NoteFileType::Directory(dir_name)) => Ok(dir.join(dir_name).join(io::DIR_ROOT_NOTE_NAME)),
+            None => Err(NottoError::FileError { message: format!("note `{}` not found", note_name) })
+        }
+    }
+
+    /// The path of today's journal entry `name`, like `2021/3/14/index.md`
+    fn journal_entry_name<S: AsRef<str>>(name: Option<S>) -> String {
         let date = Utc::now().naive_local().date();
         let note_name = match name {
             Some(n) => {
@@ -111,12 +763,43 @@ impl Notto {
             }
             None => String::from(io::DIR_ROOT_NOTE_NAME)
         };
-        let path = format!("{}/{}/{}/{}", date.year().to_string(), date.month().to_string(), date.day().to_string(), note_name);
-        self.create_or_open_note_at(Some(path))
+        format!("{}/{}/{}/{}", date.year(), date.month(), date.day(), note_name)
+    }
+
+    /// Fails for a directory going out of the notes directory, like `..`, the notes directory itself is empty
+    fn check_dir_path(path: &NottoPath) -> Result<(), NottoError> {
+        let dir = PathEntry::string_to_pathbuf(path);
+        if dir.as_os_str().is_empty() || io::is_note_path(&dir) {
+            Ok(())
+        } else {
+            Err(NottoError::InvalidNoteName { note: to_link(&dir) })
+        }
+    }
+
+    /// Splits a note name like `work/plan` into its directory and its file name
+    ///
+    /// Every name is checked here so none goes out of the notes directory, fails on `.`, `..`, a root or a prefix
+    fn split_note_name(note_name: &str) -> Result<(PathBuf, String), NottoError> {
+        let mut segments = note_name.split(PATH_SEPARATOR).filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+        if note_name.starts_with(PATH_SEPARATOR) || !segments.iter().all(io::is_note_path) {
+            return Err(NottoError::InvalidNoteName { note: note_name.to_string() });
+        }
+        let file_name = segments.pop().unwrap_or_default().to_string();
+
+        Ok((segments.iter().collect(), file_name))
     }
 
     pub fn get_text_from_editor(&self) -> Result<String, NottoError> {
-        let file_path = Notto::get_temp_file()?;
+        // The text of an encrypted context isn't left in the temp directory
+        let plaintext_file = if self.config.is_encrypted()? {
+            Some(PlaintextFile::create(Notto::get_temp_path()?, "")?)
+        } else {
+            None
+        };
+        let file_path = match &plaintext_file {
+            Some(plaintext_file) => plaintext_file.path().to_path_buf(),
+            None => Notto::get_temp_file()?
+        };
 
         let status = self.open_editor_with_path(&file_path)?;
 
@@ -133,51 +816,243 @@ impl Notto {
     }
 
     fn open_editor_with_path<P>(&self, path: P) -> Result<ExitStatus, NottoError> where P: AsRef<Path> {
+        self.open_editor_at(path, None)
+    }
+
+    fn open_editor_at<P>(&self, path: P, position: Option<Position>) -> Result<ExitStatus, NottoError> where P: AsRef<Path> {
         let editor = self.config.get_editor()?;
-        let status = process::Command::new(editor)
-            .arg(path.as_ref())
-            .status()?;
+        let command = EditorCommand::parse(&editor)?;
+        let status = process::Command::new(&command.program)
+            .args(command.args_for(path.as_ref(), position))
+            .status()
+            .map_err(|e| NottoError::EditorError { editor, message: e.to_string() })?;
 
         Ok(status)
     }
 
     fn get_temp_file() -> Result<PathBuf, NottoError> {
+        let file_path = Notto::get_temp_path()?;
+        fs::File::create(&file_path)?;
+        Ok(file_path)
+    }
+
+    /// A new file name in the temp directory
+    fn get_temp_path() -> Result<PathBuf, NottoError> {
         let mut file_path = Notto::get_temp_dir()?;
         let uuid = Uuid::new_v4().to_simple();
-        let file_name = format!("{}.md", uuid.to_string());
+        let file_name = format!("{}.md", uuid);
         file_path.push(file_name);
-        fs::File::create(&file_path)?;
         Ok(file_path)
     }
 
-    pub fn get_home_dir() -> Result<PathBuf, NottoError> {
-        let mut home = dirs::home_dir();
-        match home {
-            Some(ref mut home_path) => {
-                home_path.push(BASE_CONFIG_DIR);
-                if !home_path.exists() {
-                    fs::create_dir(&home_path)?;
-                } else {
-                    if !home_path.is_dir() {
-                        fs::remove_file(&home_path)?;
-                        fs::create_dir(&home_path)?
-                    }
-                }
-                Ok(home_path.clone())
-            }
-            None => {
-                Err(NottoError::HomeDirectoryNotFound)
-            }
-        }
-    }   
+    /// The directories of notto, see `NottoHome::locate`
+    pub fn get_home() -> Result<NottoHome, NottoError> {
+        NottoHome::locate()
+    }
 
     pub fn get_temp_dir() -> Result<PathBuf, NottoError> {
-        let mut temp = Notto::get_home_dir()?;
-        temp.push(".temp");
-        if !temp.exists() {
-            fs::create_dir(&temp)?;
+        Notto::get_home()?.ensure_temp_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{Notto, errors::NottoError, import::ImportFormat, io::{ReaderWriter, crypto::{NoteCipher, is_encrypted}}, models::{front_matter::FrontMatter, note::Note}, test_utils::{test_dir, test_notto}};
+
+    #[test]
+    fn rejects_reminders_out_of_range() -> Result<(), NottoError> {
+        let (notto, _) = test_notto("remind_range")?;
+
+        assert!(matches!(notto.remind(i64::MAX), Err(NottoError::InvalidArgument { .. })));
+        assert!(matches!(notto.remind(999_999_999_999), Err(NottoError::InvalidArgument { .. })));
+        assert!(matches!(notto.remind(i64::MIN), Err(NottoError::InvalidArgument { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_names_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("invalid_names")?;
+        notto.create_note(Some("work/plan"), "# Weekly plan")?;
+
+        for name in &["../escape", "work/../../escape", "/tmp/escape", "./escape", "work/./escape", ".."] {
+            assert!(matches!(notto.create_note(Some(name), "# Escape"), Err(NottoError::InvalidNoteName { .. })), "{}", name);
+            assert!(matches!(notto.move_note("work/plan", name), Err(NottoError::InvalidNoteName { .. })), "{}", name);
         }
+        assert!(!base_path.join("..").join("escape.md").exists());
+        assert!(base_path.join("work").join("plan.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_names_before_resolving() -> Result<(), NottoError> {
+        let (notto, _) = test_notto("resolve_names")?;
+        // Found by its title otherwise
+        notto.create_note(Some("escape"), "# ..")?;
+
+        assert!(matches!(notto.get_note_text(".."), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(notto.delete_note("../escape"), Err(NottoError::InvalidNoteName { .. })));
+        assert!(matches!(notto.get_note_text("/escape"), Err(NottoError::InvalidNoteName { .. })));
+        assert!(notto.get_note_text("escape").is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_changes_the_notes_named_exactly() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("exact_names")?;
+        notto.create_note(Some("budget"), "# Quarterly budget")?;
+
+        for name in &["bug", "qb", "quarterly"] {
+            assert!(matches!(notto.delete_note(name), Err(NottoError::NoteNotFound { .. })), "{}", name);
+            assert!(matches!(notto.move_note(name, "archive/old"), Err(NottoError::NoteNotFound { .. })), "{}", name);
+        }
+        assert!(base_path.join("budget.md").exists());
+        assert_eq!(PathBuf::from("budget.md"), notto.resolve("qb")?);
+
+        notto.delete_note("budget")?;
+        assert!(!base_path.join("budget.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn edits_plain_notes_in_place() -> Result<(), NottoError> {
+        let (mut notto, base_path) = test_notto("edit_in_place")?;
+        notto.create_note(Some("plan"), "# Plan")?;
+        let opened = base_path.join("opened.txt");
+        let editor = format!("sh -c 'echo \"$0\" > {}; echo Edited >> \"$0\"'", opened.display());
+        notto.config.set_value("editor", &editor)?;
+
+        notto.open_note("plan")?;
+        assert_eq!(fs::canonicalize(base_path.join("plan.md"))?, PathBuf::from(fs::read_to_string(&opened)?.trim()));
+        assert!(fs::read_to_string(base_path.join("plan.md"))?.ends_with("Edited\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn saves_nothing_when_the_note_is_left_unchanged() -> Result<(), NottoError> {
+        let (mut notto, base_path) = test_notto("edit_unchanged")?;
+        notto.create_note(Some("plan"), "# Plan")?;
+        let saved = fs::canonicalize(&base_path)?.join("saved.txt");
+        notto.config.set_value("hooks.post_save", format!("sh -c 'echo saved >> {}'", saved.display()))?;
+
+        notto.config.set_value("editor", "true")?;
+        notto.open_note("plan")?;
+        assert!(!saved.exists());
+
+        notto.config.set_value("editor", "sh -c 'echo Edited >> \"$0\"'")?;
+        notto.open_note("plan")?;
+        assert_eq!("saved\n", fs::read_to_string(&saved)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rolls_edited_tasks_keeping_the_text() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("roll_text")?;
+        let text = "---\nreviewed_by: ana\n---\n# Chores\n- [ ] Water the plants due: 2021-05-01 every: 3 days\n";
+        fs::write(base_path.join("chores.md"), text)?;
+
+        let done = text.replace("- [ ]", "- [x]");
+        notto.update_note_text("chores", &done, &Notto::text_hash(text))?;
+        assert_eq!(
+            "---\nreviewed_by: ana\n---\n# Chores\n- [x] Water the plants due: 2021-05-01\n- [ ] Water the plants due: 2021-05-04 every: 3 days\n",
+            fs::read_to_string(base_path.join("chores.md"))?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_leave_out_the_locked_notes() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("export_locked")?;
+        let writer = ReaderWriter::new(base_path.clone()).with_encryption(Some(NoteCipher::new("correct horse")?), false);
+        let front_matter = FrontMatter { title: Some("Bank".to_string()), encrypted: true, ..FrontMatter::default() };
+        writer.save_note_at(Note::new(front_matter, "The code is 1234"), "", "bank", false)?;
+        notto.create_note(Some("todo"), "Buy milk")?;
+
+        let mut out = vec![];
+        let summary = notto.export_json(&mut out)?;
+        assert_eq!(1, summary.exported);
+        assert_eq!(vec![PathBuf::from("bank.md")], summary.encrypted);
+        assert!(!String::from_utf8_lossy(&out).contains("Bank"));
+
+        let site_path = base_path.join("site");
+        let summary = notto.export_html(&site_path)?;
+        assert_eq!(vec![PathBuf::from("bank.md")], summary.encrypted);
+        assert!(!site_path.join("bank.html").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn exports_decrypt_the_notes_once_unlocked() -> Result<(), NottoError> {
+        let (mut notto, base_path) = test_notto("export_unlocked")?;
+        notto.config.set_value("encrypted", "true")?;
+        notto.set_passphrase_prompt(|| Ok("correct horse".to_string()));
+        notto.create_note(Some("bank"), "The code is 1234")?;
+        assert!(!fs::read_to_string(base_path.join("bank.md"))?.contains("1234"));
 
-        Ok(temp)
+        let mut out = vec![];
+        let summary = notto.export_json(&mut out)?;
+        assert_eq!(1, summary.exported);
+        assert!(summary.encrypted.is_empty());
+        assert!(String::from_utf8_lossy(&out).contains("The code is 1234"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn imports_encrypt_the_notes_of_an_encrypted_context() -> Result<(), NottoError> {
+        let (mut notto, base_path) = test_notto("import_encrypted")?;
+        notto.config.set_value("encrypted", "true")?;
+        notto.set_passphrase_prompt(|| Ok("correct horse".to_string()));
+        let vault_path = test_dir("import_encrypted_vault")?;
+        fs::write(vault_path.join("Bank.md"), "The code is 1234")?;
+
+        let report = notto.import(&vault_path, Some(ImportFormat::Markdown), None::<&str>)?;
+        assert_eq!(1, report.notes.len());
+        let text = fs::read_to_string(base_path.join(&report.notes[0]))?;
+        assert!(is_encrypted(&Note::from_text(&text).content) && !text.contains("1234"));
+
+        assert!(matches!(notto.import(&vault_path, Some(ImportFormat::Markdown), Some("../escape")), Err(NottoError::InvalidNoteName { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn restores_archives_encrypted_in_an_encrypted_context() -> Result<(), NottoError> {
+        let (plain, plain_path) = test_notto("archive_plain")?;
+        fs::write(plain_path.join("bank.md"), "# Bank\nThe code is 1234\n")?;
+        let archive_path = test_dir("archive_plain_backup")?.join("backup.tar.gz");
+        plain.export_archive(&archive_path)?;
+
+        let (mut notto, base_path) = test_notto("archive_encrypted")?;
+        notto.config.set_value("encrypted", "true")?;
+        notto.set_passphrase_prompt(|| Ok("correct horse".to_string()));
+        let report = notto.import(&archive_path, Some(ImportFormat::Archive), None::<&str>)?;
+        assert_eq!(vec![PathBuf::from("bank.md")], report.notes);
+        let text = fs::read_to_string(base_path.join("bank.md"))?;
+        assert!(is_encrypted(&Note::from_text(&text).content) && !text.contains("1234"));
+        assert!(notto.get_note_text("bank")?.1.contains("The code is 1234"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_recurrences_out_of_range() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("remind_recurrence")?;
+        fs::write(base_path.join("chores.md"), "# Chores\n- [x] Renew the passport due: 2021-05-01 every: 999999999 years\n- [ ] Call mum due: 2021-05-02\n")?;
+
+        let reminders = notto.remind(7)?;
+        assert_eq!(1, reminders.len());
+        assert_eq!("Call mum", reminders[0].task.text);
+
+        Ok(())
     }
 }
@@ -0,0 +1,700 @@
+use std::{collections::{BTreeSet, HashMap}, ffi::OsStr, fs, io::{BufRead, Write}, ops::Range, path::{Path, PathBuf}};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{Notto, errors::NottoError, finder::resolver::NoteResolver, io::{ReaderWriter, links::{find_links, link_candidates, percent_decode, percent_encode, relative_link, resolve_link, to_link}}, models::{front_matter::FrontMatter, note::Note}, rpc::{self, Framing, METHOD_NOT_FOUND, RpcError, read_message, write_message}};
+
+const NOTE_EXTENSION: &str = "md";
+const FILE_URI_PREFIX: &str = "file://";
+const FRONT_MATTER_DELIMITER: &str = "---";
+const WIKILINK_START: &str = "[[";
+const WIKILINK_END: &str = "]]";
+const TAG_START: char = '#';
+/// The lines of the target note shown when hovering a link
+const HOVER_LINES: usize = 5;
+/// The documents are sent whole on every change
+const TEXT_DOCUMENT_SYNC_FULL: u8 = 1;
+const SEVERITY_ERROR: u8 = 1;
+const SEVERITY_WARNING: u8 = 2;
+const COMPLETION_KIND_FILE: u8 = 17;
+const COMPLETION_KIND_KEYWORD: u8 = 14;
+const SYMBOL_KIND_FILE: u8 = 1;
+
+#[derive(Deserialize)]
+struct TextDocumentItem {
+    uri: String,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidOpenParams {
+    text_document: TextDocumentItem,
+}
+
+#[derive(Deserialize)]
+struct ContentChange {
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidChangeParams {
+    text_document: TextDocumentIdentifier,
+    content_changes: Vec<ContentChange>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocumentParams {
+    text_document: TextDocumentIdentifier,
+}
+
+#[derive(Deserialize)]
+struct Position {
+    line: usize,
+    character: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PositionParams {
+    text_document: TextDocumentIdentifier,
+    position: Position,
+}
+
+#[derive(Deserialize)]
+struct SymbolParams {
+    #[serde(default)]
+    query: String,
+}
+
+/// A note of the notes directory, or the text of the editor if it's open
+struct VaultNote {
+    /// Relative to the notes directory
+    path: PathBuf,
+    text: String,
+    note: Note,
+}
+
+/// The notes read for a request
+struct Vault {
+    notes: Vec<VaultNote>,
+    resolver: NoteResolver,
+}
+
+impl Vault {
+    fn get(&self, path: &Path) -> Option<&VaultNote> {
+        self.notes.iter().find(|note| note.path == path)
+    }
+}
+
+/// A link in a note, a markdown link or a `[[wikilink]]`
+struct DocumentLink {
+    range: Range<usize>,
+    url: String,
+    wikilink: bool,
+}
+
+/// A language server for the notes of a context, over stdio
+///
+/// It completes `[[` with markdown links to the notes and `#` with the tags, goes to the notes linked,
+/// finds the links to a note, shows the start of the note linked on hover, reports the broken links and the invalid
+/// front matters, and finds the notes by title as workspace symbols
+pub struct LspServer {
+    notes_dir: PathBuf,
+    /// The text of the notes open in the editor, by their path relative to the notes directory
+    documents: HashMap<PathBuf, String>,
+}
+
+impl LspServer {
+    /// The server of the notes of the context in use, the encrypted notes are left as they're stored
+    pub fn new(notto: &Notto) -> Result<Self, NottoError> {
+        let notes_dir = notto.config.get_notes_dir()?;
+        let notes_dir = notes_dir.canonicalize().unwrap_or(notes_dir);
+
+        Ok(Self { notes_dir, documents: HashMap::new() })
+    }
+
+    /// Answers the client until it sends `exit` or closes the input
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> Result<(), NottoError> {
+        while let Some((message, _)) = read_message(&mut input)? {
+            let message = match rpc::parse_message(message) {
+                Ok(message) => message,
+                Err(e) => {
+                    write_message(&mut output, &rpc::response(Value::Null, Err(e)), Framing::Headers)?;
+                    continue;
+                }
+            };
+            let params = message.get("params").cloned();
+            match (message.get("method").and_then(Value::as_str), message.get("id").cloned()) {
+                (Some("exit"), _) => break,
+                (Some(method), Some(id)) => {
+                    let result = self.request(method, params);
+                    write_message(&mut output, &rpc::response(id, result), Framing::Headers)?;
+                }
+                (Some(method), None) => {
+                    // A notification can't be answered, not even with an error
+                    if let Ok(Some(diagnostics)) = self.notification(method, params) {
+                        write_message(&mut output, &json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": diagnostics }), Framing::Headers)?;
+                    }
+                }
+                // The responses to the requests of the server, it doesn't send any
+                (None, _) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(&mut self, method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+                    "completionProvider": { "triggerCharacters": ["[", "#"] },
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "workspaceSymbolProvider": true,
+                },
+                "serverInfo": { "name": "notto", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/completion" => self.completion(rpc::params(params)?),
+            "textDocument/definition" => self.definition(rpc::params(params)?),
+            "textDocument/references" => self.references(rpc::params(params)?),
+            "textDocument/hover" => self.hover(rpc::params(params)?),
+            "workspace/symbol" => self.symbols(rpc::params(params)?),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    /// Keeps the text of the open notes, returns the diagnostics of the note changed
+    fn notification(&mut self, method: &str, params: Option<Value>) -> Result<Option<Value>, RpcError> {
+        let uri = match method {
+            "textDocument/didOpen" => {
+                let params: DidOpenParams = rpc::params(params)?;
+                if let Some(path) = self.note_path(&params.text_document.uri) {
+                    self.documents.insert(path, params.text_document.text);
+                }
+                params.text_document.uri
+            }
+            "textDocument/didChange" => {
+                let params: DidChangeParams = rpc::params(params)?;
+                if let (Some(path), Some(change)) = (self.note_path(&params.text_document.uri), params.content_changes.into_iter().last()) {
+                    self.documents.insert(path, change.text);
+                }
+                params.text_document.uri
+            }
+            "textDocument/didSave" => rpc::params::<DocumentParams>(params)?.text_document.uri,
+            "textDocument/didClose" => {
+                let params: DocumentParams = rpc::params(params)?;
+                if let Some(path) = self.note_path(&params.text_document.uri) {
+                    self.documents.remove(&path);
+                }
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(self.diagnostics(&uri)?.map(|diagnostics| json!({ "uri": uri, "diagnostics": diagnostics })))
+    }
+
+    /// The broken links and the invalid front matter of a note, `None` if it isn't in the notes directory
+    fn diagnostics(&self, uri: &str) -> Result<Option<Vec<Value>>, NottoError> {
+        let path = match self.note_path(uri) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let text = match self.documents.get(&path) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let vault = self.vault()?;
+
+        let mut diagnostics = vec![];
+        if let Some((yaml_range, yaml)) = front_matter(&text) {
+            if let Err(e) = serde_yaml::from_str::<FrontMatter>(yaml) {
+                diagnostics.push(json!({ "range": range(&text, yaml_range), "severity": SEVERITY_ERROR, "source": "notto", "message": format!("invalid front matter: {}", e) }));
+            }
+        }
+        for link in document_links(&text) {
+            let external = !link.wikilink && resolve_link(&path, &link.url).is_none();
+            if !external && self.link_target(&vault, &path, &link).is_none() {
+                diagnostics.push(json!({ "range": range(&text, link.range), "severity": SEVERITY_WARNING, "source": "notto", "message": format!("broken link to `{}`", link.url) }));
+            }
+        }
+
+        Ok(Some(diagnostics))
+    }
+
+    /// The tags after `#`, the links to the notes after `[[`
+    fn completion(&self, params: PositionParams) -> Result<Value, RpcError> {
+        let (path, text, offset) = match self.document_at(&params) {
+            Some(document) => document,
+            None => return Ok(json!([])),
+        };
+        let line_start = text[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+        let before = &text[line_start..offset];
+        let vault = self.vault()?;
+
+        let word_start = before.rfind(char::is_whitespace).map(|index| index + 1).unwrap_or(0);
+        if before[word_start..].starts_with(TAG_START) {
+            let replaced = range(&text, line_start + word_start..offset);
+            let tags = vault.notes.iter().flat_map(|note| note.note.front_matter.tags.iter()).collect::<BTreeSet<_>>();
+            let items = tags.into_iter().map(|tag| json!({
+                "label": format!("{}{}", TAG_START, tag),
+                "kind": COMPLETION_KIND_KEYWORD,
+                "textEdit": { "range": replaced, "newText": format!("{}{}", TAG_START, tag) },
+            })).collect::<Vec<_>>();
+            return Ok(json!(items));
+        }
+
+        if let Some(start) = before.rfind(WIKILINK_START).filter(|start| !before[*start..].contains(WIKILINK_END)) {
+            let replaced = range(&text, line_start + start..offset);
+            let items = vault.notes.iter().filter(|note| note.path != path).map(|note| {
+                let title = note.note.get_title();
+                let link = format!("[{}]({})", title, percent_encode(&relative_link(&path, &note.path)));
+                json!({
+                    "label": title,
+                    "kind": COMPLETION_KIND_FILE,
+                    "detail": to_link(&note.path),
+                    "filterText": format!("{}{}", WIKILINK_START, title),
+                    "textEdit": { "range": replaced, "newText": link },
+                })
+            }).collect::<Vec<_>>();
+            return Ok(json!(items));
+        }
+
+        Ok(json!([]))
+    }
+
+    fn definition(&self, params: PositionParams) -> Result<Value, RpcError> {
+        let vault = self.vault()?;
+        let target = self.link_at(&params).and_then(|(path, link)| self.link_target(&vault, &path, &link));
+
+        Ok(target.map(|target| json!({ "uri": self.uri(&target), "range": range("", 0..0) })).unwrap_or(Value::Null))
+    }
+
+    /// The links to the note from the other notes
+    fn references(&self, params: PositionParams) -> Result<Value, RpcError> {
+        let target = match self.note_path(&params.text_document.uri) {
+            Some(target) => target,
+            None => return Ok(json!([])),
+        };
+        let vault = self.vault()?;
+
+        let mut locations = vec![];
+        for note in &vault.notes {
+            for link in document_links(&note.text) {
+                if self.link_target(&vault, &note.path, &link).as_ref() == Some(&target) {
+                    locations.push(json!({ "uri": self.uri(&note.path), "range": range(&note.text, link.range) }));
+                }
+            }
+        }
+
+        Ok(json!(locations))
+    }
+
+    /// The title and the first lines of the note linked
+    fn hover(&self, params: PositionParams) -> Result<Value, RpcError> {
+        let vault = self.vault()?;
+        let (path, link) = match self.link_at(&params) {
+            Some(found) => found,
+            None => return Ok(Value::Null),
+        };
+        let note = match self.link_target(&vault, &path, &link).and_then(|target| vault.get(&target)) {
+            Some(note) => note,
+            None => return Ok(Value::Null),
+        };
+
+        let title = note.note.get_title();
+        let lines = note.note.content.lines()
+            .filter(|line| !line.trim().is_empty() && line.trim_start_matches('#').trim() != title)
+            .take(HOVER_LINES)
+            .collect::<Vec<_>>();
+        let markdown = format!("**{}** `{}`\n\n{}", title, to_link(&note.path), lines.join("\n"));
+
+        Ok(json!({ "contents": { "kind": "markdown", "value": markdown }, "range": range(&self.documents[&path], link.range) }))
+    }
+
+    /// The notes with the query in their title
+    fn symbols(&self, params: SymbolParams) -> Result<Value, RpcError> {
+        let query = params.query.to_lowercase();
+        let vault = self.vault()?;
+        let symbols = vault.notes.iter()
+            .map(|note| (note, note.note.get_title()))
+            .filter(|(_, title)| title.to_lowercase().contains(&query))
+            .map(|(note, title)| json!({
+                "name": title,
+                "kind": SYMBOL_KIND_FILE,
+                "location": { "uri": self.uri(&note.path), "range": range("", 0..0) },
+                "containerName": note.path.parent().map(to_link).unwrap_or_default(),
+            }))
+            .collect::<Vec<_>>();
+
+        Ok(json!(symbols))
+    }
+
+    /// Reads the notes, the open ones as they are in the editor
+    fn vault(&self) -> Result<Vault, NottoError> {
+        let mut notes = vec![];
+        let mut resolver = NoteResolver::new();
+        for path in ReaderWriter::new(self.notes_dir.clone()).list_files("")? {
+            if path.extension() != Some(OsStr::new(NOTE_EXTENSION)) {
+                continue;
+            }
+            let text = match self.documents.get(&path) {
+                Some(text) => text.clone(),
+                None => match fs::read_to_string(self.notes_dir.join(&path)) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                },
+            };
+            let note = Note::from_text(&text);
+            resolver.add(path.clone(), &note.front_matter.id, note.get_title());
+            notes.push(VaultNote { path, text, note });
+        }
+
+        Ok(Vault { notes, resolver })
+    }
+
+    /// The note linked, a file that isn't a note counts too
+    fn link_target(&self, vault: &Vault, source: &Path, link: &DocumentLink) -> Option<PathBuf> {
+        if link.wikilink {
+            return vault.resolver.resolve(&link.url).ok();
+        }
+
+        let path = resolve_link(source, &link.url)?;
+        link_candidates(path.clone()).into_iter()
+            .find(|candidate| vault.get(candidate).is_some())
+            .or_else(|| Some(path).filter(|path| self.notes_dir.join(path).is_file()))
+    }
+
+    /// The link under the cursor, with the path of the note
+    fn link_at(&self, params: &PositionParams) -> Option<(PathBuf, DocumentLink)> {
+        let (path, text, offset) = self.document_at(params)?;
+        let link = document_links(&text).into_iter().find(|link| link.range.start <= offset && offset < link.range.end)?;
+
+        Some((path, link))
+    }
+
+    /// The path and the text of the open note, with the offset of the position
+    fn document_at(&self, params: &PositionParams) -> Option<(PathBuf, String, usize)> {
+        let path = self.note_path(&params.text_document.uri)?;
+        let text = self.documents.get(&path)?.clone();
+        let offset = offset(&text, &params.position);
+
+        Some((path, text, offset))
+    }
+
+    /// The path of a note relative to the notes directory, `None` for the files out of it
+    fn note_path(&self, uri: &str) -> Option<PathBuf> {
+        let path = PathBuf::from(percent_decode(uri.strip_prefix(FILE_URI_PREFIX)?));
+        let path = path.canonicalize().unwrap_or(path);
+
+        path.strip_prefix(&self.notes_dir).ok().map(PathBuf::from)
+    }
+
+    fn uri(&self, path: &Path) -> String {
+        let mut uri = String::from(FILE_URI_PREFIX);
+        for byte in self.notes_dir.join(path).to_string_lossy().bytes() {
+            if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+                uri.push(byte as char);
+            } else {
+                uri.push_str(&format!("%{:02X}", byte));
+            }
+        }
+
+        uri
+    }
+}
+
+/// The yaml of the front matter, with where it is in the text
+fn front_matter(text: &str) -> Option<(Range<usize>, &str)> {
+    let first_line = text.lines().next()?;
+    if first_line.trim() != FRONT_MATTER_DELIMITER {
+        return None;
+    }
+    let start = text.find('\n')? + 1;
+    let mut line_start = start;
+    for line in text[start..].split('\n') {
+        if line.trim() == FRONT_MATTER_DELIMITER {
+            return Some((start..line_start, &text[start..line_start]));
+        }
+        line_start += line.len() + 1;
+    }
+
+    None
+}
+
+/// The markdown links after the front matter and the `[[wikilinks]]`, the target of a wikilink is before its `#` or `|`
+fn document_links(text: &str) -> Vec<DocumentLink> {
+    let content_start = front_matter(text).map(|(yaml, _)| yaml.end + text[yaml.end..].find('\n').map(|index| index + 1).unwrap_or(0)).unwrap_or(0);
+    let content = &text[content_start.min(text.len())..];
+
+    let mut links = find_links(content).into_iter()
+        .map(|link| DocumentLink { range: content_start + link.range.start..content_start + link.range.end, url: link.url, wikilink: false })
+        .collect::<Vec<_>>();
+    let mut rest = 0;
+    while let Some(start) = content[rest..].find(WIKILINK_START).map(|index| rest + index) {
+        let end = match content[start..].find(WIKILINK_END) {
+            Some(end) => start + end + WIKILINK_END.len(),
+            None => break,
+        };
+        let inner = &content[start + WIKILINK_START.len()..end - WIKILINK_END.len()];
+        let target = inner.split(['|', '#']).next().unwrap_or_default().trim();
+        if !target.is_empty() && !inner.contains('\n') {
+            links.push(DocumentLink { range: content_start + start..content_start + end, url: target.to_string(), wikilink: true });
+        }
+        rest = end;
+    }
+
+    links
+}
+
+/// The LSP position of a byte offset, the characters of a line are counted in UTF-16 code units
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+
+    json!({ "line": before.matches('\n').count(), "character": before[line_start..].encode_utf16().count() })
+}
+
+fn range(text: &str, range: Range<usize>) -> Value {
+    json!({ "start": position(text, range.start), "end": position(text, range.end) })
+}
+
+/// The byte offset of an LSP position, the end of the line or of the text if it's beyond
+fn offset(text: &str, position: &Position) -> usize {
+    let line_start = match position.line {
+        0 => 0,
+        line => match text.match_indices('\n').nth(line - 1) {
+            Some((index, _)) => index + 1,
+            None => return text.len(),
+        },
+    };
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= position.character {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+
+    line_start + line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use serde_json::{Value, json};
+
+    use crate::{Notto, errors::NottoError, rpc::{INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR, read_message}, test_utils::test_notto};
+
+    use super::LspServer;
+
+    const HOME: &str = "---\ntitle: [unclosed\n---\nSee [the plan](work/plan.md), [[Weekly plan]] and [gone](gone.md)\n[[We #pl";
+
+    fn frame(message: &Value) -> String {
+        let text = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", text.len(), text)
+    }
+
+    /// A notto with the notes `work/plan.md` and `home.md` linking to it, with the URI of `home.md`
+    fn test_lsp_notto(context: &str) -> Result<(Notto, String), NottoError> {
+        let (notto, base_path) = test_notto(context)?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::write(base_path.join("work").join("plan.md"), "---\ntags: [work, planning]\n---\n# Weekly plan\nCall Ana\n")?;
+        fs::write(base_path.join("home.md"), HOME)?;
+        let home_uri = format!("file://{}", base_path.canonicalize()?.join("home.md").to_string_lossy());
+
+        Ok((notto, home_uri))
+    }
+
+    /// Sends the framed messages to the server and reads its messages
+    fn exchange_text(notto: &Notto, input: String) -> Result<Vec<Value>, NottoError> {
+        let mut output = vec![];
+        LspServer::new(notto)?.serve(Cursor::new(input), &mut output)?;
+
+        let mut output = Cursor::new(output);
+        let mut messages = vec![];
+        while let Some((text, _)) = read_message(&mut output)? {
+            messages.push(serde_json::from_str::<Value>(&text?).unwrap());
+        }
+
+        Ok(messages)
+    }
+
+    /// Opens `home.md` as it's stored, then sends the messages, the diagnostics of the opening are left out
+    fn exchange(notto: &Notto, home_uri: &str, messages: &[Value]) -> Result<Vec<Value>, NottoError> {
+        let open = json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": home_uri, "languageId": "markdown", "version": 1, "text": HOME } } });
+        let input = std::iter::once(&open).chain(messages).map(frame).collect::<String>();
+
+        Ok(exchange_text(notto, input)?.into_iter().skip(1).collect())
+    }
+
+    fn position_params(uri: &str, line: usize, character: usize) -> Value {
+        json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn initializes_and_exits() -> Result<(), NottoError> {
+        let (notto, _) = test_lsp_notto("lsp_initialize")?;
+
+        let input = [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "initialize", "params": {} }),
+        ].iter().map(frame).collect::<String>();
+        let messages = exchange_text(&notto, input)?;
+        assert_eq!(2, messages.len());
+        assert_eq!(true, messages[0]["result"]["capabilities"]["definitionProvider"]);
+        assert_eq!(Value::Null, messages[1]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_broken_links_and_front_matter() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_diagnostics")?;
+
+        let open = json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": home_uri, "languageId": "markdown", "version": 1, "text": HOME } } });
+        let change = json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": { "textDocument": { "uri": home_uri, "version": 2 }, "contentChanges": [{ "text": "# Home\nSee [the plan](work/plan.md)" }] } });
+        let close = json!({ "jsonrpc": "2.0", "method": "textDocument/didClose", "params": { "textDocument": { "uri": home_uri } } });
+        let messages = exchange_text(&notto, [open, change, close].iter().map(frame).collect())?;
+        assert_eq!(2, messages.len());
+        let diagnostics = messages[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(2, diagnostics.len());
+        assert!(diagnostics[0]["message"].as_str().unwrap().starts_with("invalid front matter"));
+        assert_eq!("broken link to `gone.md`", diagnostics[1]["message"]);
+        assert_eq!(0, messages[1]["params"]["diagnostics"].as_array().unwrap().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn goes_to_the_notes_linked() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_definition")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/definition", "params": position_params(&home_uri, 3, 8) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": position_params(&home_uri, 3, 36) }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/definition", "params": position_params(&home_uri, 3, 60) }),
+        ])?;
+        assert!(messages[0]["result"]["uri"].as_str().unwrap().ends_with("/work/plan.md"));
+        assert!(messages[1]["result"]["uri"].as_str().unwrap().ends_with("/work/plan.md"));
+        assert_eq!(Value::Null, messages[2]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn shows_the_note_linked_on_hover() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_hover")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": position_params(&home_uri, 3, 36) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": position_params(&home_uri, 3, 1) }),
+        ])?;
+        let hover = messages[0]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(hover.starts_with("**Weekly plan** `work/plan.md`") && hover.contains("Call Ana"), "{}", hover);
+        assert_eq!(Value::Null, messages[1]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn completes_links_and_tags() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_completion")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/completion", "params": position_params(&home_uri, 4, 4) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/completion", "params": position_params(&home_uri, 4, 8) }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/completion", "params": position_params(&home_uri, 3, 3) }),
+        ])?;
+        assert_eq!("[Weekly plan](work/plan.md)", messages[0]["result"][0]["textEdit"]["newText"]);
+        assert_eq!(vec!["#planning", "#work"], messages[1]["result"].as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap()).collect::<Vec<_>>());
+        assert_eq!(json!([]), messages[2]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_and_symbols() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_references")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/references", "params": position_params(&home_uri.replace("home.md", "work/plan.md"), 0, 0) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "workspace/symbol", "params": { "query": "weekly" } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "workspace/symbol", "params": { "query": "nothing like it" } }),
+        ])?;
+        assert_eq!(2, messages[0]["result"].as_array().unwrap().len());
+        assert!(messages[1]["result"].as_array().unwrap().iter().any(|symbol| symbol["name"] == "Weekly plan" && symbol["containerName"] == "work"));
+        assert_eq!(json!([]), messages[2]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers_requests_out_of_the_notes() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_out_of_notes")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": "file:///etc/hosts", "languageId": "markdown", "version": 1, "text": "[[Weekly plan]]" } } }),
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/definition", "params": position_params("file:///etc/hosts", 0, 3) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/references", "params": position_params("https://example.com/plan.md", 0, 0) }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/hover", "params": position_params(&home_uri, usize::MAX, usize::MAX) }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/completion", "params": position_params(&home_uri, 3, usize::MAX) }),
+        ])?;
+        assert_eq!(4, messages.len());
+        assert_eq!(Value::Null, messages[0]["result"]);
+        assert_eq!(json!([]), messages[1]["result"]);
+        assert_eq!(Value::Null, messages[2]["result"]);
+        assert_eq!(json!([]), messages[3]["result"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_requests() -> Result<(), NottoError> {
+        let (notto, home_uri) = test_lsp_notto("lsp_invalid")?;
+
+        let messages = exchange(&notto, &home_uri, &[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/rename", "params": position_params(&home_uri, 3, 8) }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": { "textDocument": { "uri": home_uri } } }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {} }),
+        ])?;
+        assert_eq!(2, messages.len());
+        assert_eq!(METHOD_NOT_FOUND, messages[0]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, messages[1]["error"]["code"]);
+
+        let messages = exchange_text(&notto, "Content-Length: 8\r\n\r\n{\"id\": 1".to_string())?;
+        assert_eq!(PARSE_ERROR, messages[0]["error"]["code"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers_the_invalid_headers_and_goes_on() -> Result<(), NottoError> {
+        let (notto, _) = test_lsp_notto("lsp_invalid_headers")?;
+
+        let shutdown = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+        let messages = exchange_text(&notto, format!("Content-Length: -1\r\n\r\n{}", frame(&shutdown)))?;
+        assert_eq!(2, messages.len());
+        assert_eq!(PARSE_ERROR, messages[0]["error"]["code"]);
+        assert_eq!(1, messages[1]["id"]);
+
+        Ok(())
+    }
+}
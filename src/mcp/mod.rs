@@ -0,0 +1,468 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{Notto, errors::NottoError, finder::{FindCondition, NoteFindMessage}, io::{browser::{ListOrder, NoteListing, NottoPath}, is_note_path, links::{percent_decode, percent_encode, to_link}}, rpc::{self, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, RpcError, read_message, write_message}};
+
+/// The version of the Model Context Protocol spoken
+const PROTOCOL_VERSION: &str = "2024-11-05";
+const RESOURCE_SCHEME: &str = "notto://";
+const MARKDOWN_TYPE: &str = "text/markdown";
+pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+#[derive(Deserialize)]
+struct ReadResourceParams {
+    uri: String,
+}
+
+#[derive(Deserialize)]
+struct ToolCallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchArguments {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ReadArguments {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct CreateArguments {
+    name: Option<String>,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct AppendArguments {
+    text: String,
+}
+
+/// The URI of a note as a resource, like `notto://work/projects/plan.md` in the context `work`
+pub fn resource_uri(context: &str, path: &NottoPath) -> String {
+    format!("{}{}/{}", RESOURCE_SCHEME, context, percent_encode(&String::from(path.clone())))
+}
+
+/// The context and the path of the note of a resource URI, `None` for a path going out of the notes directory
+pub fn resource_path(uri: &str) -> Option<(String, NottoPath)> {
+    let (context, path) = uri.strip_prefix(RESOURCE_SCHEME)?.split_once('/')?;
+    let path = percent_decode(path);
+    if context.is_empty() || path.is_empty() || !is_note_path(&path) {
+        return None;
+    }
+
+    Some((context.to_string(), NottoPath::from(path)))
+}
+
+/// Serves the notes of the context in use to the AI assistants speaking the Model Context Protocol, a JSON-RPC message per line
+///
+/// The notes are resources with a URI per path. The tools `search_notes` and `read_note` are always there,
+/// `create_note` and `append_to_journal` only when `mcp_write` is on for the context
+pub struct McpServer<'a> {
+    notto: &'a Notto,
+    context: String,
+    writable: bool,
+}
+
+impl<'a> McpServer<'a> {
+    pub fn new(notto: &'a Notto) -> Result<Self, NottoError> {
+        Ok(Self { notto, context: notto.config.get_context()?, writable: notto.config.is_mcp_write_enabled()? })
+    }
+
+    /// Answers the requests until the end of the input
+    pub fn serve<R: BufRead, W: Write>(&self, mut input: R, mut output: W) -> Result<(), NottoError> {
+        while let Some((message, framing)) = read_message(&mut input)? {
+            let answer = match rpc::parse_message(message) {
+                Ok(message) => self.answer(message),
+                Err(e) => Some(rpc::response(Value::Null, Err(e))),
+            };
+            if let Some(answer) = answer {
+                write_message(&mut output, &answer, framing)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The response to a message, `None` for a notification
+    fn answer(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(Value::as_str), id) {
+            (Some(method), Some(id)) => Some(rpc::response(id, self.call(method, message.get("params").cloned()))),
+            // `notifications/initialized` and `notifications/cancelled` need no answer
+            (Some(_), None) => None,
+            (None, id) => Some(rpc::response(id.unwrap_or(Value::Null), Err(RpcError::new(INVALID_REQUEST, "expected a request with a method")))),
+        }
+    }
+
+    fn call(&self, method: &str, params: Option<Value>) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "resources": {}, "tools": {} },
+                "serverInfo": { "name": "notto", "version": env!("CARGO_PKG_VERSION") },
+                "instructions": format!("The markdown notes of the notto context {}. Notes are found by their path like `work/plan.md`, their id or their title.", self.context),
+            })),
+            "ping" => Ok(json!({})),
+            "resources/list" => self.resources(),
+            "resources/templates/list" => Ok(json!({ "resourceTemplates": [{
+                "uriTemplate": format!("{}{}/{{path}}", RESOURCE_SCHEME, self.context),
+                "name": "note",
+                "description": "A note by its path in the notes directory",
+                "mimeType": MARKDOWN_TYPE,
+            }] })),
+            "resources/read" => {
+                let params: ReadResourceParams = rpc::params(params)?;
+                self.read_resource(&params.uri)
+            }
+            "tools/list" => Ok(json!({ "tools": self.tools() })),
+            "tools/call" => self.call_tool(rpc::params(params)?),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    fn resources(&self) -> Result<Value, RpcError> {
+        fn add_notes(server: &McpServer, listings: Vec<NoteListing>, resources: &mut Vec<Value>) {
+            for listing in listings {
+                if listing.is_dir {
+                    add_notes(server, listing.children, resources);
+                } else {
+                    resources.push(json!({
+                        "uri": resource_uri(&server.context, &NottoPath::from(listing.path)),
+                        "name": listing.title.unwrap_or(listing.name),
+                        "mimeType": MARKDOWN_TYPE,
+                    }));
+                }
+            }
+        }
+
+        let mut resources = vec![];
+        add_notes(self, self.notto.list::<&str>(None, true, ListOrder::Name)?, &mut resources);
+
+        Ok(json!({ "resources": resources }))
+    }
+
+    fn read_resource(&self, uri: &str) -> Result<Value, RpcError> {
+        let path = match resource_path(uri) {
+            Some((context, path)) if context == self.context => path,
+            _ => return Err(RpcError::new(RESOURCE_NOT_FOUND, format!("`{}` isn't a note of the context {}", uri, self.context))),
+        };
+        let (_, text) = self.notto.get_note_text(String::from(path)).map_err(|e| match e {
+            NottoError::NoteNotFound { .. } | NottoError::InvalidNoteName { .. } => RpcError::new(RESOURCE_NOT_FOUND, e.to_string()),
+            e => RpcError::from(e),
+        })?;
+
+        Ok(json!({ "contents": [{ "uri": uri, "mimeType": MARKDOWN_TYPE, "text": text }] }))
+    }
+
+    fn tools(&self) -> Vec<Value> {
+        let mut tools = vec![
+            json!({
+                "name": "search_notes",
+                "description": "Finds the notes containing a text, with their path, title and the line of the first match",
+                "inputSchema": { "type": "object", "properties": { "text": { "type": "string", "description": "Text to find" } }, "required": ["text"] },
+            }),
+            json!({
+                "name": "read_note",
+                "description": "Reads a note as markdown with its front matter",
+                "inputSchema": { "type": "object", "properties": { "name": { "type": "string", "description": "Path of the note like `work/plan`, its id or its title" } }, "required": ["name"] },
+            }),
+        ];
+        if self.writable {
+            tools.push(json!({
+                "name": "create_note",
+                "description": "Creates a note, it fails if the note exists",
+                "inputSchema": { "type": "object", "properties": {
+                    "name": { "type": "string", "description": "Path of the note like `work/plan`, the note is named after its title without it" },
+                    "content": { "type": "string", "description": "Markdown of the note, it may start with a front matter" },
+                }, "required": ["content"] },
+            }));
+            tools.push(json!({
+                "name": "append_to_journal",
+                "description": "Adds a paragraph at the end of today's journal entry",
+                "inputSchema": { "type": "object", "properties": { "text": { "type": "string", "description": "Markdown to add" } }, "required": ["text"] },
+            }));
+        }
+
+        tools
+    }
+
+    /// The errors of notto are results for the assistant to read, the unknown tools and invalid arguments errors of the request
+    fn call_tool(&self, params: ToolCallParams) -> Result<Value, RpcError> {
+        let result = match params.name.as_str() {
+            "search_notes" => self.search(arguments(params.arguments)?),
+            "read_note" => {
+                let arguments: ReadArguments = arguments(params.arguments)?;
+                self.notto.get_note_text(&arguments.name).map(|(_, text)| text)
+            }
+            "create_note" | "append_to_journal" if !self.writable => {
+                return Ok(tool_result(format!("notes can't be written in context {}, turn on `mcp_write` with `notto config set`", self.context), true));
+            }
+            "create_note" => {
+                let arguments: CreateArguments = arguments(params.arguments)?;
+                self.notto.create_note(arguments.name, &arguments.content).map(|path| format!("Created {}", to_link(&path)))
+            }
+            "append_to_journal" => {
+                let arguments: AppendArguments = arguments(params.arguments)?;
+                self.notto.append_to_journal(&arguments.text).map(|path| format!("Added to {}", to_link(&path)))
+            }
+            name => return Err(RpcError::new(INVALID_PARAMS, format!("unknown tool `{}`", name))),
+        };
+
+        Ok(match result {
+            Ok(text) => tool_result(text, false),
+            Err(e) => tool_result(e.to_string(), true),
+        })
+    }
+
+    /// The notes found as a JSON array
+    fn search(&self, arguments: SearchArguments) -> Result<String, NottoError> {
+        let notes_dir = self.notto.config.get_notes_dir()?;
+        let mut found = vec![];
+        for message in self.notto.find(vec![FindCondition::Text(arguments.text)])? {
+            match message {
+                NoteFindMessage::Result(result) => found.push(json!({
+                    "path": to_link(result.path.strip_prefix(&notes_dir).unwrap_or(&result.path)),
+                    "title": result.note.get_title(),
+                    "line": result.position.map(|position| position.line),
+                })),
+                NoteFindMessage::Finish { .. } => break,
+            }
+        }
+
+        Ok(Value::Array(found).to_string())
+    }
+}
+
+fn arguments<T: DeserializeOwned>(arguments: Value) -> Result<T, RpcError> {
+    rpc::params(Some(arguments).filter(|arguments| !arguments.is_null()))
+}
+
+fn tool_result(text: String, is_error: bool) -> Value {
+    json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    use serde_json::Value;
+
+    use crate::{Notto, errors::NottoError, io::browser::NottoPath, rpc::{INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR}, test_utils::test_notto};
+
+    use super::{McpServer, RESOURCE_NOT_FOUND, resource_path, resource_uri};
+
+    fn exchange(notto: &Notto, requests: &[&str]) -> Result<Vec<Value>, NottoError> {
+        let mut output = vec![];
+        McpServer::new(notto)?.serve(Cursor::new(requests.join("\n")), &mut output)?;
+
+        Ok(String::from_utf8_lossy(&output).lines().map(|line| serde_json::from_str(line).unwrap()).collect())
+    }
+
+    /// A notto with the notes `work/weekly plan.md`, known by its id and title, and `home.md` linking to it
+    fn test_mcp_notto(context: &str, writable: bool) -> Result<(Notto, PathBuf), NottoError> {
+        let (mut notto, base_path) = test_notto(context)?;
+        notto.config.set_value("mcp_write", if writable { "true" } else { "false" })?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::write(base_path.join("work").join("weekly plan.md"), "---\nid: 8f14e45f\n---\n# Weekly plan\n- [ ] Call Ana")?;
+        fs::write(base_path.join("home.md"), "# Home\nSee [the plan](work/weekly%20plan.md)")?;
+
+        Ok((notto, base_path))
+    }
+
+    /// The text of the first content of a tool result
+    fn tool_text(message: &Value) -> &str {
+        message["result"]["content"][0]["text"].as_str().unwrap()
+    }
+
+    #[test]
+    fn converts_resource_uris() {
+        let uri = resource_uri("mcp", &NottoPath::from("work/weekly plan.md".to_string()));
+        assert_eq!("notto://mcp/work/weekly%20plan.md", uri);
+        assert_eq!(Some(("mcp".to_string(), NottoPath::from("work/weekly plan.md".to_string()))), resource_path(&uri));
+
+        assert_eq!(None, resource_path("https://mcp/home.md"));
+        assert_eq!(None, resource_path("notto://mcp"));
+        assert_eq!(None, resource_path("notto:///home.md"));
+        assert_eq!(None, resource_path("notto://mcp/"));
+    }
+
+    #[test]
+    fn initializes_and_ignores_notifications() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_initialize", false)?;
+
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "test", "version": "1"}}}"#,
+            r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "ping"}"#,
+        ])?;
+        assert_eq!(2, messages.len());
+        assert_eq!("notto", messages[0]["result"]["serverInfo"]["name"]);
+        assert_eq!(2, messages[1]["id"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_notes_as_resources() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_resources", false)?;
+
+        let messages = exchange(&notto, &[r#"{"jsonrpc": "2.0", "id": 1, "method": "resources/list"}"#])?;
+        let resources = messages[0]["result"]["resources"].as_array().unwrap();
+        assert_eq!(2, resources.len());
+        assert!(resources.iter().any(|resource| resource["uri"] == "notto://mcp_resources/work/weekly%20plan.md" && resource["name"] == "Weekly plan"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reads_the_resources_of_the_context() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_read_resource", false)?;
+
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resources/read", "params": {"uri": "notto://mcp_read_resource/work/weekly%20plan.md"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {"uri": "notto://work/home.md"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {"uri": "notto://mcp_read_resource/nothing.md"}}"#,
+        ])?;
+        assert!(messages[0]["result"]["contents"][0]["text"].as_str().unwrap().contains("- [ ] Call Ana"));
+        assert_eq!(RESOURCE_NOT_FOUND, messages[1]["error"]["code"]);
+        assert_eq!(RESOURCE_NOT_FOUND, messages[2]["error"]["code"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn offers_the_writing_tools_with_mcp_write() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_read_only_tools", false)?;
+        let messages = exchange(&notto, &[r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}"#])?;
+        assert_eq!(2, messages[0]["result"]["tools"].as_array().unwrap().len());
+
+        let (notto, _) = test_mcp_notto("mcp_write_tools", true)?;
+        let messages = exchange(&notto, &[r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/list"}"#])?;
+        assert_eq!(4, messages[0]["result"]["tools"].as_array().unwrap().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn searches_and_reads_notes() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_search", false)?;
+
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "search_notes", "arguments": {"text": "plan"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "read_note", "arguments": {"name": "8f14"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "read_note", "arguments": {"name": "nothing like it"}}}"#,
+        ])?;
+        let found: Value = serde_json::from_str(tool_text(&messages[0])).unwrap();
+        assert_eq!(2, found.as_array().unwrap().len());
+        assert!(tool_text(&messages[1]).starts_with("---\nid: 8f14e45f"));
+        assert_eq!(true, messages[2]["result"]["isError"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_writes_without_mcp_write() -> Result<(), NottoError> {
+        let (notto, base_path) = test_mcp_notto("mcp_read_only", false)?;
+
+        let messages = exchange(&notto, &[
+            r##"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "create_note", "arguments": {"name": "work/ideas", "content": "# Ideas"}}}"##,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "append_to_journal", "arguments": {"text": "Called Ana"}}}"#,
+        ])?;
+        for message in &messages {
+            assert_eq!(true, message["result"]["isError"]);
+        }
+        assert!(!base_path.join("work").join("ideas.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn creates_notes() -> Result<(), NottoError> {
+        let (notto, base_path) = test_mcp_notto("mcp_create", true)?;
+
+        let messages = exchange(&notto, &[
+            r##"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "create_note", "arguments": {"name": "work/ideas", "content": "# Ideas"}}}"##,
+            r##"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "create_note", "arguments": {"name": "work/ideas", "content": "# Ideas"}}}"##,
+        ])?;
+        assert_eq!("Created work/ideas.md", tool_text(&messages[0]));
+        assert!(base_path.join("work").join("ideas.md").exists());
+        assert_eq!(true, messages[1]["result"]["isError"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn appends_to_the_journal() -> Result<(), NottoError> {
+        let (notto, base_path) = test_mcp_notto("mcp_journal", true)?;
+
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "append_to_journal", "arguments": {"text": "Called Ana"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "append_to_journal", "arguments": {"text": "Sent the report"}}}"#,
+        ])?;
+        assert_eq!(false, messages[0]["result"]["isError"]);
+        let journal_entry = tool_text(&messages[1]).trim_start_matches("Added to ").to_string();
+        assert!(fs::read_to_string(base_path.join(journal_entry))?.ends_with("Called Ana\n\nSent the report\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_requests() -> Result<(), NottoError> {
+        let (notto, _) = test_mcp_notto("mcp_invalid", true)?;
+
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "drop_notes", "arguments": {}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "search_notes", "arguments": {"txt": "plan"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "resources/read", "params": {}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "notes/drop"}"#,
+            r#"{"jsonrpc": "2.0", "id": 5}"#,
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "#,
+        ])?;
+        assert_eq!(6, messages.len());
+        assert_eq!(INVALID_PARAMS, messages[0]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, messages[1]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, messages[2]["error"]["code"]);
+        assert_eq!(METHOD_NOT_FOUND, messages[3]["error"]["code"]);
+        assert_eq!(INVALID_REQUEST, messages[4]["error"]["code"]);
+        assert_eq!(5, messages[4]["id"]);
+        assert_eq!(PARSE_ERROR, messages[5]["error"]["code"]);
+        assert_eq!(Value::Null, messages[5]["id"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_names_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (mut notto, base_path) = test_notto("mcp_names")?;
+        notto.config.set_value("mcp_write", "true")?;
+        let outside_path = PathBuf::from("test_notes_dir_mcp_names_outside");
+        fs::create_dir_all(&outside_path)?;
+        fs::write(outside_path.join("secret.md"), "The code is 1234")?;
+
+        assert_eq!(None, resource_path("notto://mcp_names/..%2Ftest_notes_dir_mcp_names_outside%2Fsecret.md"));
+        assert_eq!(None, resource_path("notto://mcp_names/%2Fetc%2Fpasswd"));
+        let messages = exchange(&notto, &[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resources/read", "params": {"uri": "notto://mcp_names/..%2Ftest_notes_dir_mcp_names_outside%2Fsecret.md"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "resources/read", "params": {"uri": "notto://mcp_names/work/.%2F..%2F..%2Ftest_notes_dir_mcp_names_outside%2Fsecret.md"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "read_note", "arguments": {"name": "../test_notes_dir_mcp_names_outside/secret"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "create_note", "arguments": {"name": "../escape", "content": "Out"}}}"#,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "tools/call", "params": {"name": "create_note", "arguments": {"name": "/tmp/escape", "content": "Out"}}}"#,
+        ])?;
+        assert_eq!(RESOURCE_NOT_FOUND, messages[0]["error"]["code"]);
+        assert_eq!(RESOURCE_NOT_FOUND, messages[1]["error"]["code"]);
+        for message in &messages[2..] {
+            assert_eq!(true, message["result"]["isError"]);
+            assert!(message["result"]["content"][0]["text"].as_str().unwrap().starts_with("invalid note name"));
+        }
+        assert!(!base_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+}
@@ -32,10 +32,14 @@ impl Bucket {
                         Ok(text) => {
                             let writer = ReaderWriter::new(base_path);
                             let note = Note::from_text(text);
-                            let file_name = item.file_name.as_ref().map(|f| f.clone()).unwrap_or_else(|| writer.get_file_name_from_note(&note));
-                            writer.save_note_at(note, &item.dest_path, file_name, false).and_then(|_| {
-                                Ok(std::fs::remove_file(&temp_note_path)?)
-                            })
+                            let file_name = item.file_name.clone().unwrap_or_else(|| writer.get_file_name_from_note(&note));
+                            // Not while the note is open, the temp file is kept for the next time
+                            writer.create_dir_all(&item.dest_path)
+                                .and_then(|_| writer.lock_note(ReaderWriter::note_file_path(&item.dest_path, &file_name), false))
+                                .and_then(|_lock| writer.save_note_at(note, &item.dest_path, file_name, false))
+                                .and_then(|_| {
+                                    Ok(std::fs::remove_file(&temp_note_path)?)
+                                })
                         }
                         Err(e) => {
                             Err(e.into())
@@ -1,15 +1,35 @@
 use std::{collections::HashMap, env, fs::{self, File}, io::{BufReader, BufWriter}, path::{Path, PathBuf}};
 
-use crate::{Notto, errors::NottoError};
+use uuid::Uuid;
+
+use crate::{Notto, errors::NottoError, io::{editor::{EditorCommand, split_words}, hooks::{HookEvent, Hooks}, snapshots::SnapshotPolicy}};
 
 const DEFAULT_CONTEXT: &str = "default";
+const CONFIG_FILE_NAME: &str = "config";
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Config {
     #[serde(default)]
     context: String,
     #[serde(default = "default_contexts")]
-    contexts: HashMap<String, ConfigContext>
+    contexts: HashMap<String, ConfigContext>,
+    /// Context chosen for this run only, it's never saved
+    #[serde(skip)]
+    context_override: Option<String>,
+}
+
+/// The settings of a context, with the defaults resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextInfo {
+    pub name: String,
+    pub base_dir: PathBuf,
+    pub editor: Option<String>,
+    /// Whether the changes are committed to git
+    pub git: bool,
+    /// Whether all its notes are encrypted
+    pub encrypted: bool,
+    /// Whether it's the context in use
+    pub active: bool,
 }
 
 impl Config {
@@ -17,10 +37,10 @@ impl Config {
     ///
     /// If it doesn't find any file there, creates a new one
     pub fn load_config<P>(base_path: P) -> Result<Config, NottoError> where P: AsRef<Path> {
-        let mut config_file_path = PathBuf::from(base_path.as_ref());
-        config_file_path.push("config");
+        let config_file_path = Config::config_file_path(base_path);
+        let path = config_file_path.to_string_lossy().to_string();
         if config_file_path.exists() && !config_file_path.is_file() {
-            fs::remove_dir_all(&config_file_path)?;
+            return Err(NottoError::InvalidConfig { path, location: String::new(), message: "it's not a file".to_string() });
         }
 
         if !config_file_path.exists() {
@@ -28,17 +48,26 @@ impl Config {
             Config::save_config_file(&config, &config_file_path)?;
             Ok(config)
         } else {
-            let config_file = File::open(config_file_path)?;
+            let config_file = File::open(&config_file_path)?;
             let reader = BufReader::new(config_file);
             match serde_yaml::from_reader(reader) {
                 Ok(conf) => Ok(conf),
                 Err(e) => {
-                    Err(NottoError::LoadConfigError { message: format!("{}", e) } )
+                    let location = e.location().map(|location| format!(" at line {}, column {}", location.line(), location.column())).unwrap_or_default();
+                    // The message of the error may have the location too
+                    let message = e.to_string();
+                    let message = message.find(" at line ").map(|index| message[..index].to_string()).unwrap_or(message);
+                    Err(NottoError::InvalidConfig { path, location, message })
                 }
             }
         }
     }
 
+    /// The path of the config file inside notto's home directory
+    pub fn config_file_path<P>(base_path: P) -> PathBuf where P: AsRef<Path> {
+        base_path.as_ref().join(CONFIG_FILE_NAME)
+    }
+
     /// Saves the config file to disk
     ///
     /// Fails on IO operations
@@ -66,7 +95,7 @@ impl Config {
             Some(editor) => Ok(editor),
             None => {
                 if context.as_ref() == DEFAULT_CONTEXT {
-                    Config::default_editor()   
+                    Config::default_editor().map_err(|_| NottoError::EditorNotSet { context: self.get_context().unwrap_or_else(|_| DEFAULT_CONTEXT.to_string()) })
                 } else {
                     self.get_editor_from(DEFAULT_CONTEXT)
                 }
@@ -74,6 +103,31 @@ impl Config {
         }
     }
 
+    /// Gets the hooks of the current context, they aren't inherited from the default context
+    pub fn get_hooks(&self) -> Result<Hooks, NottoError> {
+        Ok(self.get_config_context(self.get_context()?)?.hooks)
+    }
+
+    /// Whether the changes of the notes of the current context are committed to git
+    pub fn is_git_enabled(&self) -> Result<bool, NottoError> {
+        Ok(self.get_config_context(self.get_context()?)?.git)
+    }
+
+    /// Whether every note of the current context is encrypted, not only the ones with `encrypted: true`
+    pub fn is_encrypted(&self) -> Result<bool, NottoError> {
+        Ok(self.get_config_context(self.get_context()?)?.encrypted)
+    }
+
+    /// Whether the assistants served by `notto mcp` may create and change the notes of the current context
+    pub fn is_mcp_write_enabled(&self) -> Result<bool, NottoError> {
+        Ok(self.get_config_context(self.get_context()?)?.mcp_write)
+    }
+
+    /// The snapshots kept for the notes of the current context, when git is off
+    pub fn get_snapshot_policy(&self) -> Result<SnapshotPolicy, NottoError> {
+        Ok(self.get_config_context(self.get_context()?)?.snapshots)
+    }
+
     /// Gets the notes directory for the current context
     ///
     /// Defaults to the default directory
@@ -97,8 +151,11 @@ impl Config {
 
     /// Gets the current context
     ///
-    /// Tries to get it first form the env vatiable, then in the config file, finally defaults to `default`
-    fn get_context(&self) -> Result<String, NottoError> {
+    /// Tries to get it first from the override, then the env variable, then in the config file, finally defaults to `default`
+    pub fn get_context(&self) -> Result<String, NottoError> {
+        if let Some(context) = &self.context_override {
+            return Ok(context.clone());
+        }
         match env::var("NOTTO_CONFIG") {
             Ok(context) => Ok(context),
             Err(e) => {
@@ -112,6 +169,264 @@ impl Config {
         }
     }
 
+    /// Uses the context for this run, over the env variable and the config file
+    pub fn override_context<S>(&mut self, context: S) -> Result<(), NottoError> where S: AsRef<str> {
+        self.get_config_context(&context)?;
+        self.context_override = Some(context.as_ref().to_string());
+
+        Ok(())
+    }
+
+    /// Names of all the contexts, the default one first and then sorted
+    pub fn get_context_names(&self) -> Vec<String> {
+        let mut names = self.contexts.keys().cloned().collect::<Vec<_>>();
+        names.sort_by(|a, b| (b == DEFAULT_CONTEXT).cmp(&(a == DEFAULT_CONTEXT)).then_with(|| a.cmp(b)));
+
+        names
+    }
+
+    pub fn get_context_info<S>(&self, context: S) -> Result<ContextInfo, NottoError> where S: AsRef<str> {
+        let name = context.as_ref().to_string();
+        self.get_config_context(&name)?;
+
+        Ok(ContextInfo {
+            base_dir: self.get_notes_dir_from(&name)?,
+            editor: self.get_editor_from(&name).ok(),
+            git: self.get_config_context(&name)?.git,
+            encrypted: self.get_config_context(&name)?.encrypted,
+            active: self.get_context()? == name,
+            name,
+        })
+    }
+
+    /// Adds a new context, its directory has to exist
+    pub fn add_context<S>(&mut self, context: S, base_dir: PathBuf, editor: Option<String>) -> Result<(), NottoError> where S: AsRef<str> {
+        let context = context.as_ref();
+        if context.trim().is_empty() || context.chars().any(char::is_whitespace) {
+            return Err(NottoError::InvalidContext { context: context.to_string(), message: "the name can't be empty or have spaces".to_string() });
+        }
+        if self.contexts.contains_key(context) {
+            return Err(NottoError::ContextExists { context: context.to_string() });
+        }
+        if !base_dir.is_dir() {
+            return Err(NottoError::InvalidContext { context: context.to_string(), message: format!("`{}` is not a directory", base_dir.to_string_lossy()) });
+        }
+        let base_dir = base_dir.canonicalize()?;
+
+        self.contexts.insert(context.to_string(), ConfigContext { editor, base_dir: Some(base_dir), ..ConfigContext::default() });
+
+        Ok(())
+    }
+
+    /// Removes a context, the notes in its directory are left untouched
+    ///
+    /// The default context and the one in use can't be removed
+    pub fn remove_context<S>(&mut self, context: S) -> Result<(), NottoError> where S: AsRef<str> {
+        let context = context.as_ref();
+        self.get_config_context(context)?;
+        if context == DEFAULT_CONTEXT {
+            return Err(NottoError::InvalidContext { context: context.to_string(), message: "the default context can't be removed".to_string() });
+        }
+        if self.get_context()? == context || self.context == context {
+            return Err(NottoError::InvalidContext { context: context.to_string(), message: "the context is in use, switch to another one first".to_string() });
+        }
+        self.contexts.remove(context);
+
+        Ok(())
+    }
+
+    /// Sets the context used when there's no override or env variable
+    pub fn use_context<S>(&mut self, context: S) -> Result<(), NottoError> where S: AsRef<str> {
+        self.get_config_context(&context)?;
+        self.context = context.as_ref().to_string();
+
+        Ok(())
+    }
+
+    /// Checks that every context can be used: its directory is writable, its editor can be found
+    /// and it doesn't share its directory with another context
+    pub fn check(&self) -> Vec<ConfigIssue> {
+        let mut issues = vec![];
+        let current = self.get_context().unwrap_or_else(|_| self.context.clone());
+        if !self.contexts.contains_key(&current) {
+            issues.push(ConfigIssue { context: current.clone(), message: "the context in use doesn't exist".to_string() });
+        }
+
+        let mut base_dirs: Vec<(String, PathBuf)> = vec![];
+        for name in self.get_context_names() {
+            let context = &self.contexts[&name];
+            let mut add_issue = |message: String| issues.push(ConfigIssue { context: name.clone(), message });
+
+            // The other contexts without a directory use the one of the default context
+            let base_dir = match &context.base_dir {
+                Some(base_dir) => Some(base_dir.clone()),
+                None if name == DEFAULT_CONTEXT => Config::default_directory().ok(),
+                None => None
+            };
+            match &base_dir {
+                Some(base_dir) if !base_dir.is_dir() => add_issue(format!("base_dir `{}` is not a directory", base_dir.to_string_lossy())),
+                Some(base_dir) => {
+                    if let Err(e) = check_writable(base_dir) {
+                        add_issue(format!("base_dir `{}` is not writable: {}", base_dir.to_string_lossy(), e));
+                    }
+                    let canonical = base_dir.canonicalize().unwrap_or_else(|_| base_dir.clone());
+                    if let Some((other, _)) = base_dirs.iter().find(|(_, dir)| dir == &canonical) {
+                        add_issue(format!("base_dir `{}` is also the directory of context {}", base_dir.to_string_lossy(), other));
+                    }
+                    base_dirs.push((name.clone(), canonical));
+                }
+                None => {}
+            }
+
+            match self.get_editor_from(&name) {
+                Ok(editor) => match EditorCommand::parse(&editor) {
+                    Ok(command) => if find_program(&command.program).is_none() {
+                        add_issue(format!("editor `{}` not found", command.program));
+                    }
+                    Err(e) => add_issue(e.to_string()),
+                }
+                Err(e) => add_issue(e.to_string()),
+            }
+
+            for event in HookEvent::ALL.iter() {
+                if let Some(hook) = context.hooks.get(*event) {
+                    match split_words(hook) {
+                        Ok(words) => if words.first().map(|program| find_program(program).is_none()).unwrap_or(true) {
+                            add_issue(format!("{} hook `{}` not found", event, words.first().cloned().unwrap_or_default()));
+                        }
+                        Err(e) => add_issue(format!("{} hook `{}`: {}", event, hook, e)),
+                    }
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Gets a value of the config, `None` if it's not set
+    ///
+    /// `editor`, `base_dir`, `git`, `encrypted`, `mcp_write`, `snapshots`, `snapshots.keep_last|keep_daily` and `hooks.<hook>` are the ones of the context in use, `contexts.<name>.editor` the one of another context
+    pub fn get_value<S>(&self, key: S) -> Result<Option<String>, NottoError> where S: AsRef<str> {
+        match self.parse_key(key.as_ref())? {
+            ConfigKey::Context => Ok(Some(self.context.clone()).filter(|context| !context.is_empty())),
+            ConfigKey::Editor(context) => Ok(self.get_config_context(context)?.editor),
+            ConfigKey::BaseDir(context) => Ok(self.get_config_context(context)?.base_dir.map(|base_dir| base_dir.to_string_lossy().to_string())),
+            ConfigKey::Hook(context, event) => Ok(self.get_config_context(context)?.hooks.get(event).cloned()),
+            ConfigKey::Git(context) => Ok(Some(self.get_config_context(context)?.git.to_string())),
+            ConfigKey::Encrypted(context) => Ok(Some(self.get_config_context(context)?.encrypted.to_string())),
+            ConfigKey::McpWrite(context) => Ok(Some(self.get_config_context(context)?.mcp_write.to_string())),
+            ConfigKey::Snapshots(context, setting) => {
+                let policy = self.get_config_context(context)?.snapshots;
+                Ok(Some(match setting {
+                    SnapshotSetting::Enabled => policy.enabled.to_string(),
+                    SnapshotSetting::KeepLast => policy.keep_last.to_string(),
+                    SnapshotSetting::KeepDaily => policy.keep_daily.to_string(),
+                }))
+            }
+        }
+    }
+
+    /// Sets a value of the config, an empty value unsets it, going back to the default
+    pub fn set_value<S, V>(&mut self, key: S, value: V) -> Result<(), NottoError> where S: AsRef<str>, V: AsRef<str> {
+        let value = value.as_ref().trim();
+        match self.parse_key(key.as_ref())? {
+            ConfigKey::Context => self.use_context(if value.is_empty() { DEFAULT_CONTEXT } else { value })?,
+            ConfigKey::Editor(context) => {
+                self.get_config_context(&context)?;
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.editor = Some(value.to_string()).filter(|editor| !editor.is_empty());
+                }
+            }
+            ConfigKey::BaseDir(context) => {
+                self.get_config_context(&context)?;
+                let base_dir = if value.is_empty() {
+                    None
+                } else if Path::new(value).is_dir() {
+                    Some(Path::new(value).canonicalize()?)
+                } else {
+                    return Err(NottoError::InvalidContext { context, message: format!("`{}` is not a directory", value) });
+                };
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.base_dir = base_dir;
+                }
+            }
+            ConfigKey::Hook(context, event) => {
+                self.get_config_context(&context)?;
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.hooks.set(event, Some(value.to_string()).filter(|hook| !hook.is_empty()));
+                }
+            }
+            ConfigKey::Git(context) => {
+                self.get_config_context(&context)?;
+                let git = parse_bool(&context, "git", value, false)?;
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.git = git;
+                }
+            }
+            ConfigKey::Encrypted(context) => {
+                self.get_config_context(&context)?;
+                let encrypted = parse_bool(&context, "encrypted", value, false)?;
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.encrypted = encrypted;
+                }
+            }
+            ConfigKey::McpWrite(context) => {
+                self.get_config_context(&context)?;
+                let mcp_write = parse_bool(&context, "mcp_write", value, false)?;
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.mcp_write = mcp_write;
+                }
+            }
+            ConfigKey::Snapshots(context, setting) => {
+                let mut policy = self.get_config_context(&context)?.snapshots;
+                let default = SnapshotPolicy::default();
+                let invalid_number = |key: &str| NottoError::InvalidContext { context: context.clone(), message: format!("`{}` is not a valid value for `{}`, use a number", value, key) };
+                match setting {
+                    SnapshotSetting::Enabled => policy.enabled = parse_bool(&context, "snapshots", value, default.enabled)?,
+                    SnapshotSetting::KeepLast if value.is_empty() => policy.keep_last = default.keep_last,
+                    SnapshotSetting::KeepLast => policy.keep_last = value.parse().map_err(|_| invalid_number("snapshots.keep_last"))?,
+                    SnapshotSetting::KeepDaily if value.is_empty() => policy.keep_daily = default.keep_daily,
+                    SnapshotSetting::KeepDaily => policy.keep_daily = value.parse().map_err(|_| invalid_number("snapshots.keep_daily"))?,
+                }
+                if let Some(config_context) = self.contexts.get_mut(&context) {
+                    config_context.snapshots = policy;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_key(&self, key: &str) -> Result<ConfigKey, NottoError> {
+        let hook_event = |event: &str| event.parse::<HookEvent>().map_err(|_| NottoError::UnknownConfigKey { key: key.to_string() });
+        let snapshot_setting = |setting: &str| match setting {
+            "keep_last" => Ok(SnapshotSetting::KeepLast),
+            "keep_daily" => Ok(SnapshotSetting::KeepDaily),
+            _ => Err(NottoError::UnknownConfigKey { key: key.to_string() })
+        };
+        let parts = key.split('.').collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["context"] => Ok(ConfigKey::Context),
+            ["editor"] => Ok(ConfigKey::Editor(self.get_context()?)),
+            ["base_dir"] => Ok(ConfigKey::BaseDir(self.get_context()?)),
+            ["contexts", context, "editor"] => Ok(ConfigKey::Editor(context.to_string())),
+            ["contexts", context, "base_dir"] => Ok(ConfigKey::BaseDir(context.to_string())),
+            ["git"] => Ok(ConfigKey::Git(self.get_context()?)),
+            ["contexts", context, "git"] => Ok(ConfigKey::Git(context.to_string())),
+            ["encrypted"] => Ok(ConfigKey::Encrypted(self.get_context()?)),
+            ["contexts", context, "encrypted"] => Ok(ConfigKey::Encrypted(context.to_string())),
+            ["mcp_write"] => Ok(ConfigKey::McpWrite(self.get_context()?)),
+            ["contexts", context, "mcp_write"] => Ok(ConfigKey::McpWrite(context.to_string())),
+            ["snapshots"] => Ok(ConfigKey::Snapshots(self.get_context()?, SnapshotSetting::Enabled)),
+            ["snapshots", setting] => Ok(ConfigKey::Snapshots(self.get_context()?, snapshot_setting(setting)?)),
+            ["contexts", context, "snapshots"] => Ok(ConfigKey::Snapshots(context.to_string(), SnapshotSetting::Enabled)),
+            ["contexts", context, "snapshots", setting] => Ok(ConfigKey::Snapshots(context.to_string(), snapshot_setting(setting)?)),
+            ["hooks", event] => Ok(ConfigKey::Hook(self.get_context()?, hook_event(event)?)),
+            ["contexts", context, "hooks", event] => Ok(ConfigKey::Hook(context.to_string(), hook_event(event)?)),
+            _ => Err(NottoError::UnknownConfigKey { key: key.to_string() })
+        }
+    }
+
     fn get_config_context<S>(&self, context: S) -> Result<ConfigContext, NottoError> where S: AsRef<str> {
         if let Some(context) = self.contexts.get(context.as_ref()) {
             Ok(context.clone())
@@ -126,18 +441,7 @@ impl Config {
     }
 
     fn default_directory() -> Result<PathBuf, NottoError> {
-        let mut home_dir = Notto::get_home_dir()?;
-        home_dir.push("notes");
-        let home_path = home_dir.as_path();
-        if !home_path.exists() {
-            fs::create_dir_all(home_path)?;
-        } else {
-            if !home_path.is_dir() {
-                fs::remove_file(home_path)?;
-            }
-        }
-
-        Ok(home_dir.to_path_buf())
+        Notto::get_home()?.ensure_notes_dir()
     }
 }
 
@@ -146,7 +450,8 @@ impl Default for Config {
         let contexts = default_contexts();
         Self {
             context: DEFAULT_CONTEXT.to_string(),
-            contexts: contexts
+            contexts,
+            context_override: None,
         }
     }
 }
@@ -159,17 +464,301 @@ fn default_contexts() -> HashMap<String, ConfigContext> {
     contexts
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+/// A problem found checking the config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub context: String,
+    pub message: String,
+}
+
+/// The values of the config that can be read and changed one by one, with their context
+enum ConfigKey {
+    Context,
+    Editor(String),
+    BaseDir(String),
+    Hook(String, HookEvent),
+    Git(String),
+    Encrypted(String),
+    McpWrite(String),
+    Snapshots(String, SnapshotSetting),
+}
+
+enum SnapshotSetting {
+    Enabled,
+    KeepLast,
+    KeepDaily,
+}
+
+/// Parses a yes or no setting, an empty value is the default
+fn parse_bool(context: &str, key: &str, value: &str, default: bool) -> Result<bool, NottoError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        "" => Ok(default),
+        _ => Err(NottoError::InvalidContext { context: context.to_string(), message: format!("`{}` is not a valid value for `{}`, use true or false", value, key) })
+    }
+}
+
+/// Writes and removes a file to check we can create notes in the directory
+fn check_writable(dir: &Path) -> Result<(), std::io::Error> {
+    let check_file = dir.join(format!(".notto-check-{}", Uuid::new_v4().to_simple()));
+    File::create(&check_file)?;
+    fs::remove_file(&check_file)
+}
+
+/// Finds a program like the shell does, looking in the `PATH` if it's not a path
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.is_empty() {
+        return None;
+    }
+    if program.contains(std::path::MAIN_SEPARATOR) {
+        let path = PathBuf::from(program);
+        return if path.is_file() { Some(path) } else { None };
+    }
+
+    env::var_os("PATH").and_then(|paths| env::split_paths(&paths).map(|dir| dir.join(program)).find(|path| path.is_file()))
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Default)]
 struct ConfigContext {
     editor: Option<String>,
-    base_dir: Option<PathBuf>
+    base_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    hooks: Hooks,
+    /// Whether every change of the notes is committed to git
+    #[serde(default, skip_serializing_if = "is_false")]
+    git: bool,
+    /// Whether every note is encrypted, not only the ones with `encrypted: true`
+    #[serde(default, skip_serializing_if = "is_false")]
+    encrypted: bool,
+    /// Whether the assistants served by `notto mcp` may create and change notes
+    #[serde(default, skip_serializing_if = "is_false")]
+    mcp_write: bool,
+    /// The snapshots kept when git is off
+    #[serde(default, skip_serializing_if = "SnapshotPolicy::is_default")]
+    snapshots: SnapshotPolicy,
 }
 
-impl Default for ConfigContext {
-    fn default() -> Self {
-        Self {
-            editor: None, 
-            base_dir: None
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use crate::{errors::NottoError, test_utils::test_dir};
+
+    use super::{Config, DEFAULT_CONTEXT};
+
+    /// A config with the contexts `work` and `personal`, in `test_notes_dir_<name>`, with `work` in use
+    fn test_config(name: &str) -> Result<(Config, PathBuf), NottoError> {
+        let base_path = test_dir(name)?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::create_dir_all(base_path.join("personal"))?;
+
+        let mut config = Config::default();
+        config.add_context("work", base_path.join("work"), Some("vi".to_string()))?;
+        config.add_context("personal", base_path.join("personal"), None)?;
+        config.override_context("work")?;
+
+        Ok((config, base_path))
+    }
+
+    #[test]
+    fn adds_contexts() -> Result<(), NottoError> {
+        let (config, base_path) = test_config("config_add")?;
+
+        assert_eq!(vec![DEFAULT_CONTEXT.to_string(), "personal".to_string(), "work".to_string()], config.get_context_names());
+        let info = config.get_context_info("work")?;
+        assert!(info.active);
+        assert_eq!(Some("vi".to_string()), info.editor);
+        assert_eq!(base_path.join("work").canonicalize()?, info.base_dir);
+        assert!(!config.get_context_info("personal")?.active);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_existing_contexts() -> Result<(), NottoError> {
+        let (mut config, base_path) = test_config("config_existing")?;
+
+        assert!(matches!(config.add_context("work", base_path.join("personal"), None), Err(NottoError::ContextExists { .. })));
+        assert!(matches!(config.add_context(DEFAULT_CONTEXT, base_path.join("work"), None), Err(NottoError::ContextExists { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_missing_directories() -> Result<(), NottoError> {
+        let (mut config, base_path) = test_config("config_missing_dir")?;
+
+        assert!(matches!(config.add_context("other", base_path.join("missing"), None), Err(NottoError::InvalidContext { .. })));
+        assert!(!config.get_context_names().contains(&"other".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_unknown_contexts() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_unknown")?;
+
+        assert!(config.override_context("missing").is_err());
+        assert!(config.use_context("missing").is_err());
+        assert!(config.get_context_info("missing").is_err());
+        assert!(config.remove_context("missing").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn removes_contexts() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_remove")?;
+
+        // Not the one in use, nor the default one
+        assert!(config.remove_context("work").is_err());
+        config.context_override = None;
+        config.use_context(DEFAULT_CONTEXT)?;
+        config.remove_context("work")?;
+        assert!(config.remove_context(DEFAULT_CONTEXT).is_err());
+        assert_eq!(vec![DEFAULT_CONTEXT.to_string(), "personal".to_string()], config.get_context_names());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sets_the_editors() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_editors")?;
+
+        config.set_value("editor", "notto-missing-editor -w")?;
+        assert_eq!(Some("notto-missing-editor -w".to_string()), config.get_value("contexts.work.editor")?);
+        config.set_value("contexts.personal.editor", "vi")?;
+        assert_eq!(Some("vi".to_string()), config.get_value("contexts.personal.editor")?);
+        config.set_value("contexts.personal.editor", "")?;
+        assert_eq!(None, config.get_value("contexts.personal.editor")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_unknown_keys() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_unknown_keys")?;
+
+        assert!(matches!(config.get_value("contexts.work"), Err(NottoError::UnknownConfigKey { .. })));
+        assert!(matches!(config.get_value("colour"), Err(NottoError::UnknownConfigKey { .. })));
+        assert!(matches!(config.set_value("hooks.on_open", "true"), Err(NottoError::UnknownConfigKey { .. })));
+        assert!(matches!(config.set_value("snapshots.keep_all", "true"), Err(NottoError::UnknownConfigKey { .. })));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_missing_base_dirs() -> Result<(), NottoError> {
+        let (mut config, base_path) = test_config("config_base_dir")?;
+
+        assert!(matches!(config.set_value("base_dir", "missing_dir"), Err(NottoError::InvalidContext { .. })));
+        assert_eq!(base_path.join("work").canonicalize()?, config.get_context_info("work")?.base_dir);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sets_the_hooks() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_hooks")?;
+
+        config.set_value("hooks.post_save", "notify-send saved")?;
+        assert_eq!(Some("notify-send saved".to_string()), config.get_hooks()?.post_save);
+        assert_eq!(Some("notify-send saved".to_string()), config.get_value("hooks.post_save")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sets_the_switches() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_switches")?;
+
+        config.set_value("git", "yes")?;
+        assert!(config.is_git_enabled()?);
+        config.set_value("contexts.work.encrypted", "on")?;
+        assert!(config.is_encrypted()?);
+        assert_eq!(Some("false".to_string()), config.get_value("contexts.personal.encrypted")?);
+        assert!(!config.is_mcp_write_enabled()?);
+        config.set_value("mcp_write", "true")?;
+        assert!(config.is_mcp_write_enabled()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_invalid_switches() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_invalid_switches")?;
+
+        assert!(config.set_value("git", "maybe").is_err());
+        assert!(!config.is_git_enabled()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sets_the_snapshot_policy() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_snapshots")?;
+
+        config.set_value("snapshots.keep_last", "3")?;
+        assert_eq!(Some("3".to_string()), config.get_value("snapshots.keep_last")?);
+        assert_eq!(3, config.get_snapshot_policy()?.keep_last);
+        config.set_value("contexts.work.snapshots", "off")?;
+        assert!(!config.get_snapshot_policy()?.enabled);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_the_invalid_numbers() -> Result<(), NottoError> {
+        let (mut config, _) = test_config("config_numbers")?;
+
+        assert!(matches!(config.set_value("snapshots.keep_daily", "many"), Err(NottoError::InvalidContext { .. })));
+        assert!(matches!(config.set_value("snapshots.keep_daily", "-1"), Err(NottoError::InvalidContext { .. })));
+        assert!(matches!(config.set_value("snapshots.keep_last", "99999999999999999999999"), Err(NottoError::InvalidContext { .. })));
+        assert!(config.get_snapshot_policy()?.is_default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn checks_the_contexts() -> Result<(), NottoError> {
+        let (mut config, base_path) = test_config("config_check")?;
+
+        config.set_value("editor", "notto-missing-editor -w")?;
+        // Both contexts in the same directory
+        config.set_value("contexts.personal.base_dir", base_path.join("work").to_string_lossy())?;
+        let issues = config.check();
+        assert!(issues.iter().any(|issue| issue.context == "work" && issue.message.contains("context personal")));
+        assert!(issues.iter().any(|issue| issue.context == "work" && issue.message == "editor `notto-missing-editor` not found"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_config_errors_location() -> Result<(), NottoError> {
+        let base_path = test_dir("config_invalid")?;
+
+        fs::write(base_path.join("config"), "context: default\ncontexts: [\n")?;
+        match Config::load_config(&base_path) {
+            Err(NottoError::InvalidConfig { location, .. }) => assert!(location.starts_with(" at line ")),
+            other => panic!("expected an invalid config error, got {:?}", other),
         }
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_a_config_directory() -> Result<(), NottoError> {
+        let base_path = test_dir("config_directory")?;
+
+        fs::create_dir(base_path.join("config"))?;
+        assert!(matches!(Config::load_config(&base_path), Err(NottoError::InvalidConfig { .. })));
+        assert!(base_path.join("config").is_dir());
+
+        Ok(())
     }
 }
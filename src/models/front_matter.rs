@@ -13,6 +13,17 @@ pub struct FrontMatter {
     pub date: NaiveDate,
     #[serde(deserialize_with = "from_time_string", serialize_with = "to_time_string", default = "default_time")]
     pub time: NaiveTime,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Private notes are left out of exports
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub private: bool,
+    /// The content of encrypted notes is stored as an armored ciphertext, the front matter stays readable
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub encrypted: bool,
+    /// The id was made up when the note was read: it has none saved and gets a new one every time
+    #[serde(skip)]
+    pub new_id: bool,
 }
 
 fn default_id() -> String {
@@ -26,6 +37,9 @@ fn default_time() -> NaiveTime {
     let utc = Utc::now();
     utc.naive_local().time()
 }
+fn is_false(value: &bool) -> bool {
+    !value
+}
 
 impl Default for FrontMatter {
     fn default() -> Self {
@@ -33,11 +47,22 @@ impl Default for FrontMatter {
             id: default_id(),
             title: None,
             date: default_date(),
-            time: default_time()
+            time: default_time(),
+            tags: vec![],
+            private: false,
+            encrypted: false,
+            new_id: false
         }
     }
 }
 
+impl FrontMatter {
+    /// The id saved with the note, none when it was made up as the note has no id yet
+    pub fn stored_id(&self) -> Option<&str> {
+        Some(self.id.as_str()).filter(|_| !self.new_id)
+    }
+}
+
 struct DateVisitor;
 impl<'de> Visitor<'de> for DateVisitor {
     type Value = NaiveDate;
@@ -103,12 +128,16 @@ mod tests {
         let id = "123e4567-e89b-12d3-a456-426614174000".to_string();
         let title = Some("test_note".to_string());
         let date = NaiveDate::from_ymd(2021, 4, 7);
-        let time = NaiveTime::from_hms(23, 08, 15);
+        let time = NaiveTime::from_hms(23, 8, 15);
         let front_matter = FrontMatter {
             id,
             title,
             date,
-            time
+            time,
+            tags: vec![],
+            private: false,
+            encrypted: false,
+            new_id: false
         };
 
         let serialized = serde_yaml::to_string(&front_matter).unwrap();
@@ -126,4 +155,20 @@ date: 2021-05-01"#;
         assert_eq!(Some("serialized note".to_string()), front_matter.title);
         assert_eq!(dt.clone(), front_matter.date);
     }
+
+    #[test]
+    fn deserialize_tags_and_private() {
+        let fm = r#"title: private note
+tags: [work, meetings]
+private: true"#;
+
+        let front_matter: FrontMatter = serde_yaml::from_str(fm).unwrap();
+
+        assert_eq!(vec!["work".to_string(), "meetings".to_string()], front_matter.tags);
+        assert!(front_matter.private);
+
+        let serialized = serde_yaml::to_string(&FrontMatter::default()).unwrap();
+        assert!(!serialized.contains("tags"));
+        assert!(!serialized.contains("private"));
+    }
 }
\ No newline at end of file
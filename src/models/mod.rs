@@ -1,4 +1,5 @@
 pub mod config;
 pub mod note;
 pub mod front_matter;
-mod bucket;
\ No newline at end of file
+pub mod task;
+pub mod bucket;
\ No newline at end of file
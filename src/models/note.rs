@@ -1,64 +1,68 @@
+use chrono::NaiveDate;
 use pulldown_cmark::{Options, Parser};
-use super::front_matter::FrontMatter;
+use log::warn;
+use crate::io::crypto::is_encrypted;
 
-#[derive(Debug, Clone)]
+use super::{front_matter::FrontMatter, task::Task};
+
+#[derive(Debug, Clone, Default)]
 pub struct Note {
     pub front_matter: FrontMatter,
     pub content: String
 }
 
-impl Default for Note {
-    fn default() -> Self {
-        Self {
-            front_matter: FrontMatter::default(),
-            content: String::new()
-        }
-    }
-}
-
 impl Note {
     pub fn new<S: Into<String>>(front_matter: FrontMatter, content: S) -> Self {
         Self { front_matter, content: content.into() }
     }
 
     pub fn from_text<S>(text: S) -> Note where S: AsRef<str> {
+        let (front_matter, cont) = Note::split_front_matter(text);
+
+        // Front Matter
+        let values = front_matter.and_then(|f| serde_yaml::from_str::<serde_yaml::Value>(&f).ok());
+        let has_id = values.as_ref().and_then(|values| values.get("id")).is_some();
+        let mut fm = match values.and_then(|values| serde_yaml::from_value::<FrontMatter>(values).ok()) {
+            Some(fm) => FrontMatter { new_id: !has_id, ..fm },
+            None => FrontMatter { new_id: true, ..FrontMatter::default() },
+        };
+        if fm.title.is_none() && !is_encrypted(&cont) {
+            let title = extract_title(&cont);
+            fm.title = Some(title);
+        }
+
+        Note::new(fm, cont)
+    }
+
+    /// Splits the text of a note into its front matter, if there's any, and its content
+    pub fn split_front_matter<S>(text: S) -> (Option<String>, String) where S: AsRef<str> {
         let lines = text.as_ref().lines();
         let mut front_matter = vec![];
         let mut content = vec![];
         let mut in_front_matter = false;
+        let mut has_front_matter = false;
         lines.enumerate().for_each(|(pos, line)| {
             content.push(line);
             if pos != 0 && line.trim() == "---" && in_front_matter {
                 in_front_matter = false;
+                has_front_matter = true;
                 content.clear();
             }
             if in_front_matter { front_matter.push(line); }
             if pos == 0 && line.trim() == "---" { in_front_matter = true; }
         });
-        // The front matter section hasn't been closed
-        if in_front_matter == true { front_matter.clear(); }
 
-        let cont = content.join("\n");
-
-        // Front Matter
-        let mut fm = match serde_yaml::from_str(front_matter.join("\n").as_str()) {
-            Ok(f) => f,
-            Err(_) => FrontMatter::default()
-        };
-        if fm.title == None {
-            let title = extract_title(&cont);
-            fm.title = Some(title);
+        // The front matter section hasn't been closed
+        if has_front_matter {
+            (Some(front_matter.join("\n")), content.join("\n"))
+        } else {
+            (None, content.join("\n"))
         }
-
-        Note::new(fm, cont)
     }
 
     pub fn to_text(&self) -> String {
         let mut text = String::new();
-        let front_matter_text = match serde_yaml::to_string(&self.front_matter) {
-            Ok(fm_text) => fm_text,
-            Err(_e) => String::new()
-        };
+        let front_matter_text = serde_yaml::to_string(&self.front_matter).unwrap_or_default();
         text.push_str(&front_matter_text);
         text.push_str("---\n");
         text.push_str(&self.content);
@@ -72,9 +76,77 @@ impl Note {
             None => extract_title(&self.content)
         }
     }
+
+    pub fn get_tasks(&self) -> Vec<Task> {
+        self.content.lines().enumerate().filter_map(|(line_number, line)| Task::parse(line_number, line)).collect()
+    }
+
+    /// Adds the next occurrence of every completed recurring task right after it
+    ///
+    /// The completed task loses its recurrence so it's only rolled once, the task
+    /// whose next date is out of range is left as it is.
+    /// Returns `true` if the content of the note changed
+    pub fn roll_recurring_tasks(&mut self, today: NaiveDate) -> bool {
+        match roll_task_lines(&self.content, 0, today) {
+            Some(content) => {
+                self.content = content;
+                true
+            }
+            None => false
+        }
+    }
 }
 
-fn extract_title<S>(note_text: S) -> String where S: AsRef<str> {
+/// Rolls the recurring tasks in the text of a note like `Note::roll_recurring_tasks`
+///
+/// Only the lines of the rolled tasks change, the front matter and the rest of the text are kept as they are.
+/// `None` if no task was rolled
+pub fn roll_recurring_tasks_in_text(text: &str, today: NaiveDate) -> Option<String> {
+    let first_line = match Note::split_front_matter(text) {
+        (Some(_), content) => text.lines().count() - content.lines().count(),
+        (None, _) => 0
+    };
+
+    roll_task_lines(text, first_line, today)
+}
+
+/// Rolls the recurring tasks found from `first_line` of the text, `None` if no task was rolled
+fn roll_task_lines(text: &str, first_line: usize, today: NaiveDate) -> Option<String> {
+    let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+    let tasks = lines.iter().enumerate().skip(first_line).filter_map(|(line_number, line)| Task::parse(line_number, line)).collect::<Vec<_>>();
+    let mut rolled = false;
+    // Backwards, so inserting lines doesn't move the tasks still to process
+    for task in tasks.into_iter().rev() {
+        let next = task.next_occurrence(today).unwrap_or_else(|e| {
+            warn!("{} in task `{}`", e, task.text);
+            None
+        });
+        if let Some(next) = next {
+            let mut completed = task;
+            completed.every = None;
+            lines[completed.line] = completed.to_line();
+            lines.insert(next.line, next.to_line());
+            rolled = true;
+        }
+    }
+    if !rolled {
+        return None;
+    }
+
+    let line_break = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut rolled_text = lines.join(line_break);
+    if text.ends_with('\n') {
+        rolled_text.push_str(line_break);
+    }
+
+    Some(rolled_text)
+}
+
+/// The first sentence of the text, an encrypted content has no title
+pub(crate) fn extract_title<S>(note_text: S) -> String where S: AsRef<str> {
+    if is_encrypted(note_text.as_ref()) {
+        return String::new();
+    }
     let mut options = Options::empty();
     options.insert(Options::ENABLE_STRIKETHROUGH);
 
@@ -126,7 +198,7 @@ fn get_first_sentence<S>(text: S) -> String where S: AsRef<str> {
 mod test {
     use chrono::{NaiveDate, NaiveTime};
 
-    use super::Note;
+    use super::{Note, roll_recurring_tasks_in_text};
 
     #[test]
     fn detects_front_matter() {
@@ -146,11 +218,23 @@ And an extra line break.
 "#;
         let note = Note::from_text(text);
         assert_eq!(Some("test note".to_string()), note.front_matter.title);
-        assert_eq!(NaiveDate::from_ymd(2021, 03, 28), note.front_matter.date);
-        assert_eq!(NaiveTime::from_hms(17, 08, 13), note.front_matter.time);
+        assert_eq!(NaiveDate::from_ymd(2021, 3, 28), note.front_matter.date);
+        assert_eq!(NaiveTime::from_hms(17, 8, 13), note.front_matter.time);
         println!("{}", &note.content);
     }
 
+    #[test]
+    fn tells_the_stored_ids() {
+        let note = Note::from_text("---\nid: 8f14e45f\ntitle: Plan\n---\n# Plan");
+        assert_eq!(Some("8f14e45f"), note.front_matter.stored_id());
+
+        for text in &["# Plan", "---\ntitle: Plan\n---\n# Plan", "---\nid: [damaged\n---\n# Plan"] {
+            let note = Note::from_text(text);
+            assert_eq!(None, note.front_matter.stored_id(), "{}", text);
+            assert!(!note.front_matter.id.is_empty());
+        }
+    }
+
     #[test]
     fn detects_title() {
         let text =
@@ -211,4 +295,56 @@ And an extra line break.
         let note = Note::from_text(text);
         assert_eq!(Some("This is a demo note".to_string()), note.front_matter.title);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rolls_recurring_tasks() {
+        let text =
+r#"# Chores
+- [x] Pay the rent due: 2021-05-01 every: monthly
+- [ ] Clean the kitchen every: weekly
+- [x] Call mum due: 2021-05-02
+"#;
+        let mut note = Note::from_text(text);
+        assert!(note.roll_recurring_tasks(NaiveDate::from_ymd(2021, 5, 3)));
+        assert_eq!(
+r#"# Chores
+- [x] Pay the rent due: 2021-05-01
+- [ ] Pay the rent due: 2021-06-01 every: monthly
+- [ ] Clean the kitchen every: weekly
+- [x] Call mum due: 2021-05-02"#, note.content);
+        assert_eq!(4, note.get_tasks().len());
+
+        // Already rolled
+        assert!(!note.roll_recurring_tasks(NaiveDate::from_ymd(2021, 5, 3)));
+    }
+
+    #[test]
+    fn rolls_recurring_tasks_in_text() {
+        let text = "---\nid: 8f14e45f\ntags: [home]\nreviewed_by: ana\n---\n- [x] Pay the rent due: 2021-05-01 every: monthly\n\nNotes  \n";
+        let rolled = roll_recurring_tasks_in_text(text, NaiveDate::from_ymd(2021, 5, 3));
+        assert_eq!(
+            Some("---\nid: 8f14e45f\ntags: [home]\nreviewed_by: ana\n---\n- [x] Pay the rent due: 2021-05-01\n- [ ] Pay the rent due: 2021-06-01 every: monthly\n\nNotes  \n"),
+            rolled.as_deref()
+        );
+
+        assert_eq!(None, roll_recurring_tasks_in_text("- [ ] Pay the rent every: monthly\n", NaiveDate::from_ymd(2021, 5, 3)));
+    }
+
+    #[test]
+    fn keeps_invalid_front_matter_when_rolling() {
+        let text = "---\r\ntitle: [unclosed\r\n---\r\n- [x] Water the plants due: 2021-05-01 every: 3 days\r\n";
+        let rolled = roll_recurring_tasks_in_text(text, NaiveDate::from_ymd(2021, 5, 3));
+        assert_eq!(
+            Some("---\r\ntitle: [unclosed\r\n---\r\n- [x] Water the plants due: 2021-05-01\r\n- [ ] Water the plants due: 2021-05-04 every: 3 days\r\n"),
+            rolled.as_deref()
+        );
+    }
+
+    #[test]
+    fn leaves_recurring_tasks_out_of_range() {
+        let text = "- [x] Renew the passport due: 2021-05-01 every: 999999999 years\n";
+        let mut note = Note::from_text(text);
+        assert!(!note.roll_recurring_tasks(NaiveDate::from_ymd(2021, 5, 3)));
+        assert_eq!(text.trim_end(), note.content);
+    }
+}
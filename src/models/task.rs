@@ -0,0 +1,428 @@
+use std::{convert::TryFrom, fmt, path::PathBuf, str::FromStr};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use log::warn;
+
+use crate::errors::NottoError;
+
+const DUE_ATTRIBUTE: &str = "due:";
+const EVERY_ATTRIBUTE: &str = "every:";
+const TASK_ATTRIBUTES: [&str; 2] = [ DUE_ATTRIBUTE, EVERY_ATTRIBUTE ];
+const BULLETS: [char; 3] = [ '-', '*', '+' ];
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A task item inside a note, written as a markdown checkbox
+///
+/// `- [ ] Pay the rent due: 2021-05-01 every: monthly`
+///
+/// The `due:` and `every:` attributes go after the task description
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Task {
+    /// Line of the task in the note content, starting at 0
+    pub line: usize,
+    pub done: bool,
+    pub text: String,
+    pub due: Option<NaiveDate>,
+    pub every: Option<Recurrence>,
+    indent: String,
+    bullet: char,
+}
+
+impl Task {
+    /// Parses a line of text, returns `None` if the line is not a task
+    pub fn parse<S>(line_number: usize, line: S) -> Option<Task> where S: AsRef<str> {
+        let line = line.as_ref();
+        let trimmed = line.trim_start();
+        let indent = line[..line.len() - trimmed.len()].to_string();
+
+        let mut chars = trimmed.chars();
+        let bullet = chars.next().filter(|c| BULLETS.contains(c))?;
+        let rest = chars.as_str();
+        let done = if rest.starts_with(" [ ]") {
+            false
+        } else if rest.starts_with(" [x]") || rest.starts_with(" [X]") {
+            true
+        } else {
+            return None;
+        };
+        let body = &rest[4..];
+        if !body.is_empty() && !body.starts_with(' ') {
+            return None;
+        }
+
+        let (text, attributes) = split_attributes(body);
+        let mut due = None;
+        let mut every = None;
+        for (attribute, value) in attributes {
+            match attribute {
+                DUE_ATTRIBUTE => match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+                    Ok(date) => due = Some(date),
+                    Err(_) => warn!("Invalid due date `{}` in task `{}`", value, text),
+                },
+                EVERY_ATTRIBUTE => match value.parse() {
+                    Ok(recurrence) => every = Some(recurrence),
+                    Err(e) => warn!("{} in task `{}`", e, text),
+                },
+                _ => {}
+            }
+        }
+
+        Some(Task { line: line_number, done, text, due, every, indent, bullet })
+    }
+
+    /// Returns the task as a line of markdown text
+    pub fn to_line(&self) -> String {
+        let mut line = format!("{}{} [{}] {}", self.indent, self.bullet, if self.done { "x" } else { " " }, self.text);
+        if let Some(due) = self.due {
+            line.push_str(&format!(" {} {}", DUE_ATTRIBUTE, due.format(DATE_FORMAT)));
+        }
+        if let Some(every) = &self.every {
+            line.push_str(&format!(" {} {}", EVERY_ATTRIBUTE, every));
+        }
+
+        line
+    }
+
+    /// The date this task is pending for
+    ///
+    /// For a completed recurring task this is the date of its next occurrence,
+    /// `today` is used as the completion date when the task had no due date
+    pub fn pending_date(&self, today: NaiveDate) -> Result<Option<NaiveDate>, NottoError> {
+        if !self.done {
+            Ok(self.due)
+        } else {
+            Ok(self.next_occurrence(today)?.and_then(|next| next.due))
+        }
+    }
+
+    /// Generates the next occurrence of a completed recurring task
+    pub fn next_occurrence(&self, today: NaiveDate) -> Result<Option<Task>, NottoError> {
+        match (&self.every, self.done) {
+            (Some(every), true) => {
+                let due = every.next_after(self.due.unwrap_or(today))?;
+                Ok(Some(Task {
+                    line: self.line + 1,
+                    done: false,
+                    text: self.text.clone(),
+                    due: Some(due),
+                    every: Some(every.clone()),
+                    indent: self.indent.clone(),
+                    bullet: self.bullet,
+                }))
+            }
+            _ => Ok(None)
+        }
+    }
+}
+
+/// A task found in the note at `path`
+#[derive(Debug, Clone)]
+pub struct NoteTask {
+    pub task: Task,
+    pub note_title: String,
+    pub path: PathBuf,
+}
+
+/// A pending task with its date, found in the note at `path`
+#[derive(Debug, Clone)]
+pub struct TaskReminder {
+    pub task: Task,
+    pub date: NaiveDate,
+    pub note_title: String,
+    pub path: PathBuf,
+}
+
+/// Splits the body of a task into its description and `(attribute, value)` pairs
+fn split_attributes(body: &str) -> (String, Vec<(&'static str, &str)>) {
+    let mut positions = vec![];
+    for attribute in TASK_ATTRIBUTES.iter() {
+        let mut start = 0;
+        while let Some(index) = body[start..].find(attribute) {
+            let position = start + index;
+            // Attributes must start a word
+            if position == 0 || body[..position].ends_with(char::is_whitespace) {
+                positions.push((position, *attribute));
+            }
+            start = position + attribute.len();
+        }
+    }
+    positions.sort_unstable();
+
+    let text_end = positions.first().map(|(position, _)| *position).unwrap_or_else(|| body.len());
+    let attributes = positions.iter().enumerate().map(|(index, (position, attribute))| {
+        let value_end = positions.get(index + 1).map(|(next, _)| *next).unwrap_or_else(|| body.len());
+        (*attribute, body[position + attribute.len()..value_end].trim())
+    }).collect();
+
+    (body[..text_end].trim().to_string(), attributes)
+}
+
+/// How often a task repeats, the value of the `every:` attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+    /// Every week on the given day
+    Weekday(Weekday),
+    /// The nth weekday of every month, like `2nd monday`
+    NthWeekday(u8, Weekday),
+    /// The last weekday of every month, like `last friday`
+    LastWeekday(Weekday),
+}
+
+impl Recurrence {
+    /// The first date of the recurrence strictly after `date`
+    ///
+    /// Fails when that date is out of the range of the dates
+    pub fn next_after(&self, date: NaiveDate) -> Result<NaiveDate, NottoError> {
+        let next = match self {
+            Recurrence::Days(days) => date.checked_add_signed(Duration::days(i64::from(*days))),
+            Recurrence::Weeks(weeks) => date.checked_add_signed(Duration::weeks(i64::from(*weeks))),
+            Recurrence::Months(months) => add_months(date, *months),
+            Recurrence::Years(years) => years.checked_mul(12).and_then(|months| add_months(date, months)),
+            Recurrence::Weekday(weekday) => {
+                let days_ahead = (7 + weekday.num_days_from_monday() - date.weekday().num_days_from_monday()) % 7;
+                date.checked_add_signed(Duration::days(if days_ahead == 0 { 7 } else { i64::from(days_ahead) }))
+            }
+            Recurrence::NthWeekday(nth, weekday) => {
+                next_in_months(date, |year, month| NaiveDate::from_weekday_of_month_opt(year, month, *weekday, *nth))
+            }
+            Recurrence::LastWeekday(weekday) => {
+                next_in_months(date, |year, month| {
+                    let mut day = last_day_of_month(year, month)?;
+                    while day.weekday() != *weekday {
+                        day = day.pred_opt()?;
+                    }
+                    Some(day)
+                })
+            }
+        };
+
+        next.ok_or_else(|| NottoError::InvalidRecurrence { value: format!("{}, the next date is out of range", self) })
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = NottoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim().to_lowercase();
+        let words = value.split_whitespace().collect::<Vec<_>>();
+        let error = || NottoError::InvalidRecurrence { value: s.trim().to_string() };
+
+        match words.as_slice() {
+            ["daily"] | ["day"] => Ok(Recurrence::Days(1)),
+            ["weekly"] | ["week"] => Ok(Recurrence::Weeks(1)),
+            ["monthly"] | ["month"] => Ok(Recurrence::Months(1)),
+            ["yearly"] | ["year"] | ["annually"] => Ok(Recurrence::Years(1)),
+            [weekday] => weekday.parse().map(Recurrence::Weekday).map_err(|_| error()),
+            [count, unit] => {
+                if let Ok(count) = count.parse::<u32>() {
+                    if count == 0 {
+                        return Err(error());
+                    }
+                    match unit.trim_end_matches('s') {
+                        "day" => Ok(Recurrence::Days(count)),
+                        "week" => Ok(Recurrence::Weeks(count)),
+                        "month" => Ok(Recurrence::Months(count)),
+                        "year" => Ok(Recurrence::Years(count)),
+                        _ => Err(error())
+                    }
+                } else {
+                    let weekday = unit.parse::<Weekday>().map_err(|_| error())?;
+                    match *count {
+                        "last" => Ok(Recurrence::LastWeekday(weekday)),
+                        nth => parse_ordinal(nth).map(|nth| Recurrence::NthWeekday(nth, weekday)).ok_or_else(error)
+                    }
+                }
+            }
+            _ => Err(error())
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Days(1) => write!(f, "daily"),
+            Recurrence::Weeks(1) => write!(f, "weekly"),
+            Recurrence::Months(1) => write!(f, "monthly"),
+            Recurrence::Years(1) => write!(f, "yearly"),
+            Recurrence::Days(days) => write!(f, "{} days", days),
+            Recurrence::Weeks(weeks) => write!(f, "{} weeks", weeks),
+            Recurrence::Months(months) => write!(f, "{} months", months),
+            Recurrence::Years(years) => write!(f, "{} years", years),
+            Recurrence::Weekday(weekday) => write!(f, "{}", weekday_name(weekday)),
+            Recurrence::NthWeekday(nth, weekday) => {
+                let suffix = match nth {
+                    1 => "st",
+                    2 => "nd",
+                    3 => "rd",
+                    _ => "th"
+                };
+                write!(f, "{}{} {}", nth, suffix, weekday_name(weekday))
+            }
+            Recurrence::LastWeekday(weekday) => write!(f, "last {}", weekday_name(weekday)),
+        }
+    }
+}
+
+fn parse_ordinal(nth: &str) -> Option<u8> {
+    let nth = match nth {
+        "first" => 1,
+        "second" => 2,
+        "third" => 3,
+        "fourth" => 4,
+        "fifth" => 5,
+        _ => {
+            let number = nth.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+            if !["st", "nd", "rd", "th"].contains(&&nth[number.len()..]) {
+                return None;
+            }
+            number.parse().ok()?
+        }
+    };
+
+    if (1..=5).contains(&nth) { Some(nth) } else { None }
+}
+
+fn weekday_name(weekday: &Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "monday",
+        Weekday::Tue => "tuesday",
+        Weekday::Wed => "wednesday",
+        Weekday::Thu => "thursday",
+        Weekday::Fri => "friday",
+        Weekday::Sat => "saturday",
+        Weekday::Sun => "sunday",
+    }
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<NaiveDate> {
+    let (next_year, next_month) = if month == 12 { (year.checked_add(1)?, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt()
+}
+
+/// Adds months to a date, using the last day of the month when the day doesn't exist
+///
+/// `None` when the date is out of range
+fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    let month0 = date.month0().checked_add(months)?;
+    let year = date.year().checked_add(i32::try_from(month0 / 12).ok()?)?;
+    let month = month0 % 12 + 1;
+    let day = date.day().min(last_day_of_month(year, month)?.day());
+
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Finds the first date after `date` produced by `day_in_month`, looking month by month
+fn next_in_months<F>(date: NaiveDate, day_in_month: F) -> Option<NaiveDate> where F: Fn(i32, u32) -> Option<NaiveDate> {
+    let mut month = NaiveDate::from_ymd_opt(date.year(), date.month(), 1)?;
+    loop {
+        if let Some(day) = day_in_month(month.year(), month.month()) {
+            if day > date {
+                return Some(day);
+            }
+        }
+        month = add_months(month, 1)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+
+    use crate::errors::NottoError;
+
+    use super::{Recurrence, Task};
+
+    #[test]
+    fn parses_tasks() {
+        let task = Task::parse(3, "  - [ ] Pay the rent due: 2021-05-01 every: monthly").unwrap();
+        assert_eq!(3, task.line);
+        assert!(!task.done);
+        assert_eq!("Pay the rent", task.text);
+        assert_eq!(Some(NaiveDate::from_ymd(2021, 5, 1)), task.due);
+        assert_eq!(Some(Recurrence::Months(1)), task.every);
+        assert_eq!("  - [ ] Pay the rent due: 2021-05-01 every: monthly", task.to_line());
+
+        let task = Task::parse(0, "* [x] Water the plants every: 2nd monday").unwrap();
+        assert!(task.done);
+        assert_eq!(None, task.due);
+        assert_eq!(Some(Recurrence::NthWeekday(2, Weekday::Mon)), task.every);
+
+        assert!(Task::parse(0, "- a list item").is_none());
+        assert!(Task::parse(0, "- [x]done").is_none());
+        assert!(Task::parse(0, "[ ] not a list item").is_none());
+    }
+
+    #[test]
+    fn parses_recurrences() {
+        assert_eq!(Recurrence::Days(1), "daily".parse().unwrap());
+        assert_eq!(Recurrence::Weeks(1), "Weekly".parse().unwrap());
+        assert_eq!(Recurrence::Weeks(2), "2 weeks".parse().unwrap());
+        assert_eq!(Recurrence::Years(1), "1 year".parse().unwrap());
+        assert_eq!(Recurrence::Weekday(Weekday::Fri), "friday".parse().unwrap());
+        assert_eq!(Recurrence::NthWeekday(3, Weekday::Tue), "third tuesday".parse().unwrap());
+        assert_eq!(Recurrence::NthWeekday(1, Weekday::Sun), "1st sun".parse().unwrap());
+        assert_eq!(Recurrence::LastWeekday(Weekday::Fri), "last friday".parse().unwrap());
+        assert!("6th monday".parse::<Recurrence>().is_err());
+        assert!("0 days".parse::<Recurrence>().is_err());
+        assert!("sometimes".parse::<Recurrence>().is_err());
+        assert!("99999999999 days".parse::<Recurrence>().is_err());
+        assert!("-2 weeks".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn calculates_next_dates() -> Result<(), NottoError> {
+        // 2021-01-31 is a Sunday
+        let date = NaiveDate::from_ymd(2021, 1, 31);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 3), Recurrence::Days(3).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 7), Recurrence::Weeks(1).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 28), Recurrence::Months(1).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2022, 1, 31), Recurrence::Years(1).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 1), Recurrence::Weekday(Weekday::Mon).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 7), Recurrence::Weekday(Weekday::Sun).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 8), Recurrence::NthWeekday(2, Weekday::Mon).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 3, 29), Recurrence::NthWeekday(5, Weekday::Mon).next_after(date)?);
+        assert_eq!(NaiveDate::from_ymd(2021, 2, 26), Recurrence::LastWeekday(Weekday::Fri).next_after(date)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fails_on_dates_out_of_range() {
+        let date = NaiveDate::from_ymd(2021, 1, 31);
+        assert!(matches!(Recurrence::Years(999_999_999).next_after(date), Err(NottoError::InvalidRecurrence { .. })));
+        assert!(matches!(Recurrence::Years(u32::MAX).next_after(date), Err(NottoError::InvalidRecurrence { .. })));
+        assert!(matches!(Recurrence::Months(u32::MAX).next_after(date), Err(NottoError::InvalidRecurrence { .. })));
+        assert!(matches!(Recurrence::Weeks(u32::MAX).next_after(date), Err(NottoError::InvalidRecurrence { .. })));
+        assert!(matches!(Recurrence::Days(u32::MAX).next_after(date), Err(NottoError::InvalidRecurrence { .. })));
+
+        let last_day = chrono::naive::MAX_DATE;
+        assert!(Recurrence::LastWeekday(Weekday::Fri).next_after(last_day).is_err());
+        assert!(Recurrence::NthWeekday(1, Weekday::Mon).next_after(last_day).is_err());
+
+        let task = Task::parse(1, "- [x] Renew the passport every: 999999999 years").unwrap();
+        assert!(task.next_occurrence(date).is_err());
+    }
+
+    #[test]
+    fn generates_next_occurrence() -> Result<(), NottoError> {
+        let today = NaiveDate::from_ymd(2021, 5, 10);
+        let task = Task::parse(1, "- [x] Team retro due: 2021-05-07 every: 2 weeks").unwrap();
+        let next = task.next_occurrence(today)?.unwrap();
+        assert_eq!("- [ ] Team retro due: 2021-05-21 every: 2 weeks", next.to_line());
+        assert_eq!(Some(NaiveDate::from_ymd(2021, 5, 21)), task.pending_date(today)?);
+
+        let task = Task::parse(1, "- [x] Stretch every: daily").unwrap();
+        assert_eq!(Some(NaiveDate::from_ymd(2021, 5, 11)), task.pending_date(today)?);
+
+        let task = Task::parse(1, "- [ ] Stretch every: daily").unwrap();
+        assert!(task.next_occurrence(today)?.is_none());
+
+        Ok(())
+    }
+}
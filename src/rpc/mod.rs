@@ -0,0 +1,583 @@
+use std::io::{self, BufRead, Read, Write};
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{Notto, errors::NottoError, finder::{FindCondition, NoteFindMessage}, io::{browser::ListOrder, links::to_link}};
+
+const JSONRPC_VERSION: &str = "2.0";
+const CONTENT_LENGTH_HEADER: &str = "content-length:";
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+/// The notification sent for every note found by a `find` request
+const FIND_RESULT_METHOD: &str = "find/result";
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The errors of notto, with their code and exit code in the data
+pub const APPLICATION_ERROR: i64 = -32000;
+
+/// How the messages are delimited, a message is answered the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// A JSON document per line
+    Lines,
+    /// `Content-Length` headers before every JSON document, like a language server
+    Headers,
+}
+
+/// A message read, or the `ProtocolError` it's answered with, in the framing it came in
+pub type Message = (Result<String, NottoError>, Framing);
+
+/// Reads the next message, `None` at the end of the input
+///
+/// The JSON is returned as it is, so a message that can't be parsed is still answered in its framing.
+/// A message that can't be read, like one with an invalid length, is skipped and is a `ProtocolError`
+pub fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Message>, NottoError> {
+    read_message_within(input, MAX_MESSAGE_LENGTH)
+}
+
+fn read_message_within<R: BufRead>(input: &mut R, max_length: usize) -> Result<Option<Message>, NottoError> {
+    loop {
+        let line = match read_line(input, max_length)? {
+            None => return Ok(None),
+            Some(Ok(line)) => line,
+            Some(Err(e)) => return Ok(Some((Err(e), Framing::Lines))),
+        };
+        let header = line.trim();
+        if header.is_empty() {
+            continue;
+        }
+        if !header.to_lowercase().starts_with(CONTENT_LENGTH_HEADER) {
+            return Ok(Some((Ok(header.to_string()), Framing::Lines)));
+        }
+
+        let length = header[CONTENT_LENGTH_HEADER.len()..].trim().parse::<usize>()
+            .map_err(|_| NottoError::ProtocolError { message: format!("invalid header `{}`", header) });
+        // The other headers, up to the empty line
+        loop {
+            match read_line(input, max_length)? {
+                None => return Ok(None),
+                Some(Ok(line)) if line.trim().is_empty() => break,
+                Some(_) => {}
+            }
+        }
+        let length = match length {
+            Ok(length) => length,
+            // Without a length the body is read as lines, and answered as such
+            Err(e) => return Ok(Some((Err(e), Framing::Headers))),
+        };
+        if length > max_length {
+            // Skipped without keeping it
+            io::copy(&mut input.by_ref().take(length as u64), &mut io::sink())?;
+            return Ok(Some((Err(too_long(max_length)), Framing::Headers)));
+        }
+        let mut body = vec![0; length];
+        input.read_exact(&mut body)?;
+
+        return Ok(Some((Ok(String::from_utf8_lossy(&body).to_string()), Framing::Headers)));
+    }
+}
+
+/// Reads a line of at most `max_length` bytes, a longer one is skipped up to its end and is a `ProtocolError`
+fn read_line<R: BufRead>(input: &mut R, max_length: usize) -> Result<Option<Result<String, NottoError>>, NottoError> {
+    let limit = max_length as u64 + 1;
+    let mut line = vec![];
+    if input.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() > max_length && !line.ends_with(b"\n") {
+        loop {
+            line.clear();
+            if input.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 || line.ends_with(b"\n") {
+                return Ok(Some(Err(too_long(max_length))));
+            }
+        }
+    }
+
+    Ok(Some(Ok(String::from_utf8_lossy(&line).to_string())))
+}
+
+fn too_long(max_length: usize) -> NottoError {
+    NottoError::ProtocolError { message: format!("the message is longer than {} bytes", max_length) }
+}
+
+/// The JSON of a message read, the error answered with `PARSE_ERROR` otherwise
+pub fn parse_message(message: Result<String, NottoError>) -> Result<Value, RpcError> {
+    message
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| NottoError::ProtocolError { message: e.to_string() }))
+        .map_err(|e| RpcError::new(PARSE_ERROR, e.to_string()))
+}
+
+pub fn write_message<W: Write>(output: &mut W, message: &Value, framing: Framing) -> Result<(), NottoError> {
+    let text = message.to_string();
+    match framing {
+        Framing::Lines => writeln!(output, "{}", text)?,
+        Framing::Headers => write!(output, "Content-Length: {}\r\n\r\n{}", text.len(), text)?,
+    }
+    output.flush()?;
+
+    Ok(())
+}
+
+/// The error answered to a request
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new<S: Into<String>>(code: i64, message: S) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+
+        error
+    }
+}
+
+impl From<NottoError> for RpcError {
+    /// The invalid names and arguments given in the params are `INVALID_PARAMS`, the other errors `APPLICATION_ERROR`
+    fn from(e: NottoError) -> Self {
+        let code = match e {
+            NottoError::InvalidNoteName { .. } | NottoError::InvalidArgument { .. } => INVALID_PARAMS,
+            _ => APPLICATION_ERROR,
+        };
+        Self { code, message: e.to_string(), data: Some(e.to_json()) }
+    }
+}
+
+/// The response to the request `id`
+pub fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": JSONRPC_VERSION, "id": id, "result": result }),
+        Err(e) => json!({ "jsonrpc": JSONRPC_VERSION, "id": id, "error": e.to_json() }),
+    }
+}
+
+/// Where the notifications sent while a request runs go, before its response
+pub struct RpcOutput<'a, W: Write> {
+    writer: &'a mut W,
+    framing: Framing,
+}
+
+impl<'a, W: Write> RpcOutput<'a, W> {
+    pub fn new(writer: &'a mut W, framing: Framing) -> Self {
+        Self { writer, framing }
+    }
+
+    pub fn send(&mut self, message: &Value) -> Result<(), NottoError> {
+        write_message(self.writer, message, self.framing)
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) -> Result<(), NottoError> {
+        self.send(&json!({ "jsonrpc": JSONRPC_VERSION, "method": method, "params": params }))
+    }
+}
+
+/// The params of a request, a request without params gets the defaults
+pub fn params<T: DeserializeOwned>(params: Option<Value>) -> Result<T, RpcError> {
+    serde_json::from_value(params.unwrap_or_else(|| json!({}))).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+#[derive(Deserialize)]
+struct FindParams {
+    text: String,
+    #[serde(default)]
+    all_contexts: bool,
+}
+
+#[derive(Deserialize)]
+struct BrowseParams {
+    path: Option<String>,
+    #[serde(default)]
+    tree: bool,
+    sort: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CreateParams {
+    /// Like `work/plan`, the note is named after its title without it
+    name: Option<String>,
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct NameParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct TasksParams {
+    #[serde(default)]
+    done: bool,
+}
+
+/// Answers JSON-RPC 2.0 requests about the notes of a context, for the editor plugins
+///
+/// Its methods are `find`, `browse`, `create`, `resolve`, `backlinks`, `tasks` and `config.contexts`.
+/// The paths are relative to the notes directory, the encrypted notes need the `NOTTO_PASSPHRASE` variable
+pub struct RpcServer<'a> {
+    notto: &'a Notto,
+}
+
+impl<'a> RpcServer<'a> {
+    pub fn new(notto: &'a Notto) -> Self {
+        Self { notto }
+    }
+
+    /// Answers the requests until the end of the input, batches included
+    pub fn serve<R: BufRead, W: Write>(&self, mut input: R, mut output: W) -> Result<(), NottoError> {
+        while let Some((message, framing)) = read_message(&mut input)? {
+            let mut out = RpcOutput::new(&mut output, framing);
+            let answer = match parse_message(message) {
+                Ok(Value::Array(batch)) if !batch.is_empty() => {
+                    let answers = batch.into_iter().filter_map(|message| self.answer(message, &mut out)).collect::<Vec<_>>();
+                    if answers.is_empty() { None } else { Some(Value::Array(answers)) }
+                }
+                Ok(message) => self.answer(message, &mut out),
+                Err(e) => Some(response(Value::Null, Err(e))),
+            };
+            if let Some(answer) = answer {
+                out.send(&answer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The response to a message, `None` for a notification
+    fn answer<W: Write>(&self, message: Value, out: &mut RpcOutput<W>) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let method = message.get("method").and_then(Value::as_str).filter(|_| message.get("jsonrpc") == Some(&json!(JSONRPC_VERSION)));
+        match (method, id) {
+            (Some(method), Some(id)) => {
+                let result = self.call(method, &id, message.get("params").cloned(), out);
+                Some(response(id, result))
+            }
+            (Some(method), None) => {
+                let _ = self.call(method, &Value::Null, message.get("params").cloned(), out);
+                None
+            }
+            (None, id) => Some(response(id.unwrap_or(Value::Null), Err(RpcError::new(INVALID_REQUEST, "expected a JSON-RPC 2.0 request with a method")))),
+        }
+    }
+
+    fn call<W: Write>(&self, method: &str, id: &Value, params: Option<Value>, out: &mut RpcOutput<W>) -> Result<Value, RpcError> {
+        match method {
+            "find" => self.find(id, self::params(params)?, out),
+            "browse" => self.browse(self::params(params)?),
+            "create" => {
+                let params: CreateParams = self::params(params)?;
+                let path = self.notto.create_note(params.name, &params.content)?;
+                Ok(json!({ "path": to_link(&path) }))
+            }
+            "resolve" => {
+                let params: NameParams = self::params(params)?;
+                let path = self.notto.resolve(&params.name)?;
+                Ok(json!({ "path": to_link(&path) }))
+            }
+            "backlinks" => {
+                let params: NameParams = self::params(params)?;
+                let backlinks = self.notto.backlinks(&params.name)?.into_iter()
+                    .map(|backlink| json!({ "path": to_link(&backlink.path), "title": backlink.title, "line": backlink.line, "url": backlink.url }))
+                    .collect::<Vec<_>>();
+                Ok(json!(backlinks))
+            }
+            "tasks" => self.tasks(self::params(params)?),
+            "config.contexts" => self.contexts(),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    /// Sends the notes found as `find/result` notifications, then answers with the number of notes read and found
+    fn find<W: Write>(&self, id: &Value, params: FindParams, out: &mut RpcOutput<W>) -> Result<Value, RpcError> {
+        let conditions = vec![FindCondition::Text(params.text)];
+        let rx = if params.all_contexts { self.notto.find_in_all_contexts(conditions)? } else { self.notto.find(conditions)? };
+
+        let notes_dir = self.notto.config.get_notes_dir()?;
+        for message in rx {
+            match message {
+                NoteFindMessage::Result(result) => {
+                    let notes_dir = match &result.context {
+                        Some(context) => self.notto.config.get_notes_dir_from(context)?,
+                        None => notes_dir.clone(),
+                    };
+                    let path = result.path.strip_prefix(&notes_dir).unwrap_or(&result.path);
+                    out.notify(FIND_RESULT_METHOD, json!({
+                        "id": id,
+                        "path": to_link(path),
+                        "note_id": result.note.front_matter.stored_id(),
+                        "title": result.note.get_title(),
+                        "context": result.context,
+                        "line": result.position.map(|position| position.line),
+                        "column": result.position.map(|position| position.column),
+                    }))?;
+                }
+                NoteFindMessage::Finish { scanned, matched } => return Ok(json!({ "scanned": scanned, "matched": matched })),
+            }
+        }
+
+        Ok(json!({ "scanned": 0, "matched": 0 }))
+    }
+
+    fn browse(&self, params: BrowseParams) -> Result<Value, RpcError> {
+        let order = params.sort.map(|sort| sort.parse::<ListOrder>()).transpose()?.unwrap_or(ListOrder::Name);
+        let listings = self.notto.list(params.path, params.tree, order)?;
+
+        Ok(json!(listings))
+    }
+
+    fn tasks(&self, params: TasksParams) -> Result<Value, RpcError> {
+        let tasks = self.notto.tasks(params.done)?.into_iter()
+            .map(|note_task| json!({
+                "path": to_link(&note_task.path),
+                "title": note_task.note_title,
+                "line": note_task.task.line,
+                "done": note_task.task.done,
+                "text": note_task.task.text,
+                "due": note_task.task.due.map(|due| due.format("%Y-%m-%d").to_string()),
+                "every": note_task.task.every.map(|every| every.to_string()),
+            }))
+            .collect::<Vec<_>>();
+
+        Ok(json!(tasks))
+    }
+
+    fn contexts(&self) -> Result<Value, RpcError> {
+        let config = &self.notto.config;
+        let contexts = config.get_context_names().into_iter()
+            .map(|context| config.get_context_info(context).map(|info| json!({
+                "name": info.name,
+                "base_dir": info.base_dir,
+                "editor": info.editor,
+                "git": info.git,
+                "encrypted": info.encrypted,
+                "active": info.active,
+            })))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(json!({ "current": config.get_context()?, "contexts": contexts }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    use serde_json::Value;
+
+    use crate::{Notto, errors::NottoError, test_utils::test_notto};
+
+    use super::{APPLICATION_ERROR, Framing, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR, RpcServer, read_message, read_message_within};
+
+    /// Sends the requests to the server and reads its messages in any framing, both through in-memory pipes
+    fn exchange(notto: &Notto, requests: &str) -> Result<Vec<Value>, NottoError> {
+        let mut output = vec![];
+        RpcServer::new(notto).serve(Cursor::new(requests.to_string()), &mut output)?;
+
+        let mut output = Cursor::new(output);
+        let mut messages = vec![];
+        while let Some((message, _)) = read_message(&mut output)? {
+            messages.push(serde_json::from_str(&message?).unwrap());
+        }
+
+        Ok(messages)
+    }
+
+    /// A notto with the notes `work/plan.md`, known by its id and title, and `home.md` linking to it
+    fn test_rpc_notto(context: &str) -> Result<(Notto, PathBuf), NottoError> {
+        let (notto, base_path) = test_notto(context)?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::write(base_path.join("work").join("plan.md"), "---\nid: 8f14e45f\n---\n# Weekly plan\n- [ ] Call Ana\n- [x] Send the report")?;
+        fs::write(base_path.join("home.md"), "# Home\nSee [the plan](work/plan.md)")?;
+
+        Ok((notto, base_path))
+    }
+
+    #[test]
+    fn finds_notes_with_notifications() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_find")?;
+
+        let messages = exchange(&notto, r#"{"jsonrpc": "2.0", "id": 1, "method": "find", "params": {"text": "plan"}}"#)?;
+        assert_eq!(3, messages.len());
+        assert!(messages[..2].iter().all(|message| message["method"] == "find/result" && message["params"]["id"] == 1));
+        assert_eq!(2, messages[2]["result"]["matched"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_names() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_resolve")?;
+
+        let messages = exchange(&notto, concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resolve", "params": {"name": "8f14"}}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "resolve", "params": {"name": "weekly plan"}}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "resolve", "params": {"name": "nothing like it"}}"#, "\n",
+        ))?;
+        assert_eq!("work/plan.md", messages[0]["result"]["path"]);
+        assert_eq!("work/plan.md", messages[1]["result"]["path"]);
+        assert_eq!(APPLICATION_ERROR, messages[2]["error"]["code"]);
+        assert_eq!("note_not_found", messages[2]["error"]["data"]["code"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_backlinks_and_tasks() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_backlinks")?;
+
+        let messages = exchange(&notto, concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "backlinks", "params": {"name": "weekly plan"}}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tasks"}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "tasks", "params": {"done": true}}"#, "\n",
+        ))?;
+        assert_eq!("home.md", messages[0]["result"][0]["path"]);
+        assert_eq!(1, messages[0]["result"][0]["line"]);
+        assert_eq!(1, messages[1]["result"].as_array().unwrap().len());
+        assert_eq!("Call Ana", messages[1]["result"][0]["text"]);
+        assert_eq!(2, messages[2]["result"].as_array().unwrap().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn creates_and_browses_notes() -> Result<(), NottoError> {
+        let (notto, base_path) = test_rpc_notto("rpc_create")?;
+
+        let messages = exchange(&notto, concat!(
+            r##"{"jsonrpc": "2.0", "id": 1, "method": "create", "params": {"name": "work/ideas", "content": "# Ideas"}}"##, "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "browse", "params": {"path": "work"}}"#, "\n",
+        ))?;
+        assert_eq!("work/ideas.md", messages[0]["result"]["path"]);
+        assert!(base_path.join("work").join("ideas.md").exists());
+        assert_eq!(2, messages[1]["result"].as_array().unwrap().len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers_batches_and_ignores_notifications() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_batch")?;
+
+        let messages = exchange(&notto, concat!(
+            r#"[{"jsonrpc": "2.0", "id": 1, "method": "resolve", "params": {"name": "home"}}, {"jsonrpc": "2.0", "method": "tasks"}, {"jsonrpc": "2.0", "id": 2, "method": "unknown"}]"#, "\n",
+            r#"{"jsonrpc": "2.0", "method": "tasks"}"#, "\n",
+            r#"[{"jsonrpc": "2.0", "method": "tasks"}]"#, "\n",
+        ))?;
+        assert_eq!(1, messages.len());
+        let answers = messages[0].as_array().unwrap();
+        assert_eq!(2, answers.len());
+        assert_eq!("home.md", answers[0]["result"]["path"]);
+        assert_eq!(METHOD_NOT_FOUND, answers[1]["error"]["code"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers_in_the_framing_of_the_request() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_framing")?;
+
+        let body = r#"{"jsonrpc": "2.0", "id": 1, "method": "resolve", "params": {"name": "home"}}"#;
+        let mut output = vec![];
+        RpcServer::new(&notto).serve(Cursor::new(format!("Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{}", body.len(), body)), &mut output)?;
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.starts_with("Content-Length: "));
+        assert!(output.ends_with(r#""result":{"path":"home.md"}}"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_requests() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_invalid")?;
+
+        let messages = exchange(&notto, concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resolve"}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "find", "params": {"text": 12}}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "unknown"}"#, "\n",
+            r#"{"jsonrpc": "1.0", "id": 4, "method": "tasks"}"#, "\n",
+            r#"[]"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "#, "\n",
+        ))?;
+        assert_eq!(6, messages.len());
+        assert_eq!(INVALID_PARAMS, messages[0]["error"]["code"]);
+        assert_eq!(INVALID_PARAMS, messages[1]["error"]["code"]);
+        assert_eq!(METHOD_NOT_FOUND, messages[2]["error"]["code"]);
+        assert_eq!(INVALID_REQUEST, messages[3]["error"]["code"]);
+        assert_eq!(4, messages[3]["id"]);
+        assert_eq!(INVALID_REQUEST, messages[4]["error"]["code"]);
+        assert_eq!(PARSE_ERROR, messages[5]["error"]["code"]);
+        assert_eq!(Value::Null, messages[5]["id"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn answers_the_invalid_lengths_and_goes_on() -> Result<(), NottoError> {
+        let (notto, _) = test_rpc_notto("rpc_invalid_lengths")?;
+
+        let messages = exchange(&notto, concat!(
+            "Content-Length: many\r\n\r\n",
+            "Content-Length: 99999999999999999999999\r\n\r\n",
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "resolve", "params": {"name": "home"}}"#, "\n",
+        ))?;
+        assert_eq!(3, messages.len());
+        assert_eq!(PARSE_ERROR, messages[0]["error"]["code"]);
+        assert!(messages[0]["error"]["message"].as_str().unwrap_or_default().contains("invalid header"));
+        assert_eq!(PARSE_ERROR, messages[1]["error"]["code"]);
+        assert_eq!("home.md", messages[2]["result"]["path"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skips_the_messages_too_long() -> Result<(), NottoError> {
+        let request = r#"{"jsonrpc": "2.0", "id": 1, "method": "tasks"}"#;
+        let long = "x".repeat(64);
+        let mut input = Cursor::new(format!("Content-Length: {}\r\n\r\n{}{}\n{}\n{}\n", long.len(), long, long, request, request));
+
+        let (message, framing) = read_message_within(&mut input, 32)?.unwrap();
+        assert!(matches!(message, Err(NottoError::ProtocolError { .. })));
+        assert_eq!(Framing::Headers, framing);
+        let (message, framing) = read_message_within(&mut input, 32)?.unwrap();
+        assert!(matches!(message, Err(NottoError::ProtocolError { .. })));
+        assert_eq!(Framing::Lines, framing);
+        // The messages after them are read again
+        let (message, _) = read_message_within(&mut input, 64)?.unwrap();
+        assert_eq!(request, message?);
+        assert!(matches!(read_message_within(&mut input, 32)?, Some((Err(NottoError::ProtocolError { .. }), Framing::Lines))));
+        assert!(read_message_within(&mut input, 32)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_names_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("rpc_names")?;
+
+        let messages = exchange(&notto, concat!(
+            r##"{"jsonrpc": "2.0", "id": 1, "method": "create", "params": {"name": "../escape", "content": "# Escape"}}"##, "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "resolve", "params": {"name": "../escape"}}"#, "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "browse", "params": {"path": ".."}}"#, "\n",
+        ))?;
+        assert_eq!(3, messages.len());
+        for message in &messages {
+            assert_eq!(INVALID_PARAMS, message["error"]["code"]);
+            assert_eq!("invalid_note_name", message["error"]["data"]["code"]);
+        }
+        assert!(!base_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,851 @@
+use std::{collections::HashMap, io::{BufRead, BufReader, Read, Write}, net::{TcpListener, TcpStream}, time::Duration};
+
+use log::warn;
+use pulldown_cmark::{CowStr, Event, Parser, Tag, html::push_html};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{Notto, errors::{EXIT_AMBIGUOUS, EXIT_EXISTS, EXIT_LOCKED, EXIT_NOT_FOUND, EXIT_USAGE, NottoError}, export::html::markdown_options, finder::{FindCondition, NoteFindMessage}, io::{browser::ListOrder, links::{percent_decode, to_link}}, models::note::Note};
+
+const NOTES_PATH: &str = "/api/notes";
+const SEARCH_PATH: &str = "/api/search";
+/// The largest request body read, bigger notes are refused
+const MAX_BODY_LENGTH: usize = 16 * 1024 * 1024;
+/// The longest request line read, with the method, the target and the version
+const MAX_REQUEST_LINE_LENGTH: usize = 8 * 1024;
+/// The most bytes read for all the headers of a request
+const MAX_HEADERS_LENGTH: usize = 64 * 1024;
+const MAX_HEADERS: usize = 100;
+/// How long a connection may wait for the client, so a silent one doesn't block the others
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// The hosts the server answers to without a token, the other names could point to it through DNS rebinding
+const LOOPBACK_HOSTS: [&str; 3] = ["localhost", "127.0.0.1", "[::1]"];
+
+const JSON_TYPE: &str = "application/json";
+const MARKDOWN_TYPE: &str = "text/markdown; charset=utf-8";
+const HTML_TYPE: &str = "text/html; charset=utf-8";
+
+/// The page served at `/` to read the notes from a browser
+const READER_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>notto</title>
+<style>
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; line-height: 1.6; margin: 0; color: #222; display: flex; }
+nav { width: 18em; flex-shrink: 0; padding: 1em; border-right: 1px solid #ddd; height: 100vh; overflow: auto; box-sizing: border-box; }
+nav ul { list-style: none; padding-left: 1em; margin: 0; }
+nav > ul { padding-left: 0; }
+article { padding: 1em 2em; max-width: 48em; overflow: auto; height: 100vh; box-sizing: border-box; }
+a { color: #0366d6; text-decoration: none; }
+pre { background: #f6f8fa; padding: 0.8em; overflow: auto; }
+code { background: #f6f8fa; padding: 0.1em 0.3em; }
+input#search { width: 100%; font-size: 1.1em; padding: 0.3em; margin-bottom: 0.5em; box-sizing: border-box; }
+.error { color: #b00; }
+</style>
+</head>
+<body>
+<nav><input id="search" type="search" placeholder="Search"><div id="notes"></div></nav>
+<article id="note"></article>
+<script>
+const notes = document.getElementById('notes');
+const note = document.getElementById('note');
+const search = document.getElementById('search');
+let current = '';
+// Given in the address of the page, like `/?token=...`
+const token = new URLSearchParams(location.search).get('token');
+
+function api(url) {
+    return fetch(url, { headers: token ? { Authorization: 'Bearer ' + token } : {} }).then(response => response.ok ? response : response.json().then(body => Promise.reject(body.error.message)));
+}
+
+function addItem(list, item) {
+    const entry = document.createElement('li');
+    if (item.is_dir) {
+        entry.textContent = item.name + '/';
+        const children = document.createElement('ul');
+        (item.children || []).forEach(child => addItem(children, child));
+        entry.appendChild(children);
+    } else {
+        const link = document.createElement('a');
+        link.href = '#' + item.path;
+        link.textContent = item.title || item.name;
+        entry.appendChild(link);
+    }
+    list.appendChild(entry);
+}
+
+function showList(items) {
+    const list = document.createElement('ul');
+    items.forEach(item => addItem(list, item));
+    notes.replaceChildren(list);
+}
+
+function showError(element, error) {
+    element.replaceChildren();
+    element.className = 'error';
+    element.textContent = error;
+}
+
+function listNotes() {
+    api('/api/notes?tree=true').then(response => response.json()).then(showList).catch(error => showError(notes, error));
+}
+
+function openNote() {
+    current = decodeURIComponent(location.hash.slice(1));
+    if (!current) {
+        return;
+    }
+    api('/api/notes/' + encodeURIComponent(current) + '?format=html').then(response => response.text()).then(html => {
+        note.className = '';
+        note.innerHTML = html;
+        note.scrollTop = 0;
+    }).catch(error => showError(note, error));
+}
+
+note.addEventListener('click', event => {
+    const link = event.target.closest('a');
+    const href = link && link.getAttribute('href');
+    if (!href || href.startsWith('#') || /^[a-z]+:/i.test(href)) {
+        return;
+    }
+    event.preventDefault();
+    location.hash = decodeURIComponent(new URL(href, 'http://notes/' + current).pathname.slice(1));
+});
+
+let searching;
+search.addEventListener('input', () => {
+    clearTimeout(searching);
+    searching = setTimeout(() => {
+        const text = search.value.trim();
+        if (!text) {
+            return listNotes();
+        }
+        api('/api/search?q=' + encodeURIComponent(text)).then(response => response.json())
+            .then(found => showList(found.map(result => ({ path: result.path, name: result.path, title: result.title }))))
+            .catch(error => showError(notes, error));
+    }, 300);
+});
+
+window.addEventListener('hashchange', openNote);
+listNotes();
+openNote();
+</script>
+</body>
+</html>
+"#;
+
+/// A request read from a connection, its path and query decoded
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// With their names in lowercase
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// Reads the next request, `None` when the connection is closed before it
+    ///
+    /// A request that can't be read is an `HttpError` with the status to answer it
+    pub fn read<R: BufRead>(input: &mut R) -> Result<Option<Self>, NottoError> {
+        let mut line = String::new();
+        match read_line_within(input, &mut line, MAX_REQUEST_LINE_LENGTH)? {
+            Some(0) => return Ok(None),
+            Some(_) => {}
+            None => return Err(invalid_request(400, format!("the request line is longer than {} bytes", MAX_REQUEST_LINE_LENGTH))),
+        }
+        let mut request_line = line.split_whitespace();
+        let (method, target) = match (request_line.next(), request_line.next()) {
+            (Some(method), Some(target)) => (method.to_uppercase(), target.to_string()),
+            _ => return Err(invalid_request(400, format!("invalid request line `{}`", line.trim()))),
+        };
+
+        let mut headers = HashMap::new();
+        let mut headers_length = 0;
+        loop {
+            let read = read_line_within(input, &mut line, MAX_HEADERS_LENGTH - headers_length)?
+                .ok_or_else(|| invalid_request(431, format!("the headers are longer than {} bytes", MAX_HEADERS_LENGTH)))?;
+            if read == 0 || line.trim().is_empty() {
+                break;
+            }
+            headers_length += read;
+            if let Some((name, value)) = line.split_once(':') {
+                if headers.len() == MAX_HEADERS {
+                    return Err(invalid_request(431, format!("more than {} headers", MAX_HEADERS)));
+                }
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let length = headers.get("content-length").map(|length| length.parse::<usize>()).transpose()
+            .map_err(|_| invalid_request(400, "invalid Content-Length header"))?
+            .unwrap_or(0);
+        if length > MAX_BODY_LENGTH {
+            return Err(invalid_request(400, format!("the body is longer than {} bytes", MAX_BODY_LENGTH)));
+        }
+        let mut body = vec![0; length];
+        input.read_exact(&mut body)?;
+
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let query = query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_query(name), decode_query(value))
+            })
+            .collect();
+
+        Ok(Some(Self { method, path: percent_decode(path), query, headers, body }))
+    }
+
+    /// Whether the query parameter is set like `tree=true`
+    fn flag(&self, name: &str) -> bool {
+        matches!(self.query.get(name).map(String::as_str), Some("true") | Some("1") | Some(""))
+    }
+}
+
+/// Reads a line of at most `max_length` bytes into `line`, its length, `None` when it's longer
+fn read_line_within<R: BufRead>(input: &mut R, line: &mut String, max_length: usize) -> Result<Option<usize>, NottoError> {
+    line.clear();
+    let read = input.by_ref().take(max_length as u64 + 1).read_line(line)?;
+    Ok(Some(read).filter(|_| read <= max_length))
+}
+
+fn invalid_request<S: Into<String>>(status: u16, message: S) -> NottoError {
+    NottoError::HttpError { status, message: message.into() }
+}
+
+fn decode_query(value: &str) -> String {
+    percent_decode(&value.replace('+', " "))
+}
+
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new<B: Into<Vec<u8>>>(status: u16, content_type: &'static str, body: B) -> Self {
+        Self { status, content_type, headers: vec![], body: body.into() }
+    }
+
+    pub fn json(status: u16, body: &Value) -> Self {
+        Self::new(status, JSON_TYPE, body.to_string())
+    }
+
+    /// An error of the request itself, like `{"error": {"code": "bad_request", "message": ...}}`
+    pub fn client_error<S: AsRef<str>>(status: u16, message: S) -> Self {
+        let code = reason(status).to_lowercase().replace(' ', "_");
+        Self::json(status, &json!({ "error": { "code": code, "message": message.as_ref() } }))
+    }
+
+    /// The error of notto in the body, its status follows its exit code
+    pub fn error(e: &NottoError) -> Self {
+        let status = match e.exit_code() {
+            EXIT_USAGE => 400,
+            EXIT_NOT_FOUND => 404,
+            EXIT_AMBIGUOUS | EXIT_EXISTS | EXIT_LOCKED => 409,
+            _ => 500,
+        };
+        Self::json(status, &json!({ "error": e.to_json() }))
+    }
+
+    pub fn with_header<S: Into<String>>(mut self, name: &str, value: S) -> Self {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Writes the response, the connection is closed after it
+    pub fn write_to<W: Write>(&self, output: &mut W) -> Result<(), NottoError> {
+        write!(output, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n", self.status, reason(self.status), self.content_type, self.body.len())?;
+        for (name, value) in &self.headers {
+            write!(output, "{}: {}\r\n", name, value)?;
+        }
+        write!(output, "\r\n")?;
+        output.write_all(&self.body)?;
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+/// The host of the `Host` header without its port, like `localhost` or `[::1]`
+fn host_name(host: &str) -> &str {
+    match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    }
+}
+
+/// Compares the tokens in a time that doesn't depend on where they differ
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The schemes of the links kept in the HTML of a note, the relative links are kept too
+const SAFE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Renders the markdown of a note with its raw HTML escaped and the links with another scheme, like `javascript:`, left empty,
+/// the page puts it in the document as it is
+fn render_html(content: &str) -> String {
+    fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+        // Browsers skip the whitespace and the control characters of a scheme
+        let cleaned = url.chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect::<String>().to_lowercase();
+        let scheme = cleaned.split_once(':').map(|(scheme, _)| scheme).filter(|scheme| !scheme.contains(['/', '?', '#']));
+        match scheme {
+            Some(scheme) if !SAFE_SCHEMES.contains(&scheme) => CowStr::Borrowed(""),
+            _ => url,
+        }
+    }
+    let events = Parser::new_ext(content, markdown_options()).map(|event| match event {
+        Event::Html(html) => Event::Text(html),
+        Event::Start(Tag::Link(link_type, url, title)) => Event::Start(Tag::Link(link_type, safe_url(url), title)),
+        Event::Start(Tag::Image(link_type, url, title)) => Event::Start(Tag::Image(link_type, safe_url(url), title)),
+        event => event,
+    });
+    let mut html = String::new();
+    push_html(&mut html, events);
+
+    html
+}
+
+/// The JSON body of a request, or the response refusing it
+fn json_body<T: DeserializeOwned>(request: &HttpRequest) -> Result<T, HttpResponse> {
+    serde_json::from_slice(&request.body).map_err(|e| HttpResponse::client_error(400, format!("invalid JSON body: {}", e)))
+}
+
+#[derive(Deserialize)]
+struct CreateBody {
+    /// Like `work/plan`, the note is named after its title without it
+    name: Option<String>,
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateBody {
+    content: String,
+    /// The hash of the text the content replaces, or the `If-Match` header
+    hash: Option<String>,
+}
+
+/// Serves the notes of a context over HTTP, with a page at `/` to read them from a browser
+///
+/// `GET /api/notes?path=&tree=&sort=` lists the notes, `GET /api/notes/<note>?format=json|markdown|html` gets a note,
+/// `GET /api/search?q=` finds notes, `POST /api/notes` creates a note and `PUT /api/notes/<note>` saves a note given the
+/// `hash` of the text it replaces. The notes are found by their path, id or title, and the answers carry their paths
+/// relative to the notes directory
+///
+/// Without a token only the requests to a loopback host are answered, with one the API needs it as a bearer token.
+/// The requests of a page from another origin are refused
+pub struct NoteServer<'a> {
+    notto: &'a Notto,
+    read_only: bool,
+    token: Option<String>,
+}
+
+impl<'a> NoteServer<'a> {
+    pub fn new(notto: &'a Notto) -> Self {
+        Self { notto, read_only: false, token: None }
+    }
+
+    /// Requires `Authorization: Bearer <token>` on the API, and answers to any host
+    pub fn token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Refuses to create and save notes
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Answers the connections one after the other, a failed connection is logged
+    pub fn serve(&self, listener: &TcpListener) -> Result<(), NottoError> {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.map_err(NottoError::from).and_then(|stream| self.handle_connection(stream)) {
+                warn!("connection failed: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Answers the request of a connection and closes it
+    pub fn handle_connection(&self, mut stream: TcpStream) -> Result<(), NottoError> {
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let response = match HttpRequest::read(&mut input) {
+            Ok(Some(request)) => self.handle(&request),
+            Ok(None) => return Ok(()),
+            Err(NottoError::HttpError { status, message }) => HttpResponse::client_error(status, message),
+            Err(e) => HttpResponse::client_error(400, e.to_string()),
+        };
+
+        response.write_to(&mut stream)
+    }
+
+    pub fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        if let Some(refused) = self.check_access(request) {
+            return refused;
+        }
+        let note_name = request.path.strip_prefix(NOTES_PATH).and_then(|name| name.strip_prefix('/')).filter(|name| !name.is_empty());
+        let result = match (request.method.as_str(), request.path.as_str(), note_name) {
+            ("GET", "/", _) => Ok(HttpResponse::new(200, HTML_TYPE, READER_PAGE)),
+            ("GET", NOTES_PATH, _) => self.list(request),
+            ("GET", SEARCH_PATH, _) => self.search(request),
+            ("POST", NOTES_PATH, _) => self.create(request),
+            ("GET", _, Some(note_name)) => self.get(note_name, request),
+            ("PUT", _, Some(note_name)) => self.update(note_name, request),
+            (_, "/", _) | (_, NOTES_PATH, _) | (_, SEARCH_PATH, _) | (_, _, Some(_)) => Ok(HttpResponse::client_error(405, format!("{} isn't allowed on {}", request.method, request.path))),
+            _ => Ok(HttpResponse::client_error(404, format!("nothing at {}", request.path))),
+        };
+
+        result.unwrap_or_else(|e| HttpResponse::error(&e))
+    }
+
+    /// The response refusing a request from an unexpected host or origin, or to the API without the token
+    fn check_access(&self, request: &HttpRequest) -> Option<HttpResponse> {
+        let host = match request.headers.get("host") {
+            Some(host) => host,
+            None => return Some(HttpResponse::client_error(400, "expected a Host header")),
+        };
+        if self.token.is_none() && !LOOPBACK_HOSTS.contains(&host_name(host).to_lowercase().as_str()) {
+            return Some(HttpResponse::client_error(403, format!("unexpected host `{}`, start the server with a token to answer to it", host)));
+        }
+        if let Some(origin) = request.headers.get("origin") {
+            if origin.split_once("://").map(|(_, origin_host)| origin_host) != Some(host.as_str()) {
+                return Some(HttpResponse::client_error(403, format!("requests from `{}` aren't allowed", origin)));
+            }
+        }
+
+        let token = match &self.token {
+            Some(token) if request.path.starts_with("/api/") => token,
+            _ => return None,
+        };
+        let given = request.headers.get("authorization").and_then(|authorization| authorization.strip_prefix("Bearer "));
+        if given.map(|given| same_token(given.trim(), token)).unwrap_or(false) {
+            None
+        } else {
+            Some(HttpResponse::client_error(401, "expected the token as `Authorization: Bearer <token>`").with_header("WWW-Authenticate", "Bearer"))
+        }
+    }
+
+    fn list(&self, request: &HttpRequest) -> Result<HttpResponse, NottoError> {
+        let order = request.query.get("sort").map(|sort| sort.parse::<ListOrder>()).transpose()?.unwrap_or(ListOrder::Name);
+        let listings = self.notto.list(request.query.get("path"), request.flag("tree"), order)?;
+
+        Ok(HttpResponse::json(200, &json!(listings)))
+    }
+
+    /// The note as JSON, markdown or HTML, with the hash of its text as ETag
+    fn get(&self, note_name: &str, request: &HttpRequest) -> Result<HttpResponse, NottoError> {
+        let (note_path, text) = self.notto.get_note_text(note_name)?;
+        let hash = Notto::text_hash(&text);
+        let response = match request.query.get("format").map(String::as_str).unwrap_or("json") {
+            "json" => {
+                let note = Note::from_text(&text);
+                HttpResponse::json(200, &json!({
+                    "path": to_link(&note_path),
+                    "id": note.front_matter.id,
+                    "title": note.get_title(),
+                    "front_matter": note.front_matter,
+                    "content": note.content,
+                    "hash": hash,
+                }))
+            }
+            "markdown" => HttpResponse::new(200, MARKDOWN_TYPE, text),
+            "html" => HttpResponse::new(200, HTML_TYPE, render_html(&Note::from_text(&text).content)),
+            format => return Ok(HttpResponse::client_error(400, format!("unknown format `{}`, expected `json`, `markdown` or `html`", format))),
+        };
+
+        Ok(response.with_header("ETag", format!("\"{}\"", hash)))
+    }
+
+    fn search(&self, request: &HttpRequest) -> Result<HttpResponse, NottoError> {
+        let text = match request.query.get("q").filter(|text| !text.trim().is_empty()) {
+            Some(text) => text,
+            None => return Ok(HttpResponse::client_error(400, "expected the text to find in `q`")),
+        };
+
+        let notes_dir = self.notto.config.get_notes_dir()?;
+        let mut found = vec![];
+        for message in self.notto.find(vec![FindCondition::Text(text.clone())])? {
+            match message {
+                NoteFindMessage::Result(result) => found.push(json!({
+                    "path": to_link(result.path.strip_prefix(&notes_dir).unwrap_or(&result.path)),
+                    "id": result.note.front_matter.id,
+                    "title": result.note.get_title(),
+                    "line": result.position.map(|position| position.line),
+                })),
+                NoteFindMessage::Finish { .. } => break,
+            }
+        }
+
+        Ok(HttpResponse::json(200, &json!(found)))
+    }
+
+    fn create(&self, request: &HttpRequest) -> Result<HttpResponse, NottoError> {
+        if self.read_only {
+            return Ok(HttpResponse::client_error(403, "the notes are served read only"));
+        }
+        let body: CreateBody = match json_body(request) {
+            Ok(body) => body,
+            Err(response) => return Ok(response),
+        };
+
+        let note_path = self.notto.create_note(body.name, &body.content)?;
+        let (_, text) = self.notto.get_note_text(to_link(&note_path))?;
+
+        Ok(HttpResponse::json(201, &json!({ "path": to_link(&note_path), "hash": Notto::text_hash(&text) })))
+    }
+
+    /// Saves the note unless it changed since it was read, a conflict gives the hash of the note saved meanwhile
+    fn update(&self, note_name: &str, request: &HttpRequest) -> Result<HttpResponse, NottoError> {
+        if self.read_only {
+            return Ok(HttpResponse::client_error(403, "the notes are served read only"));
+        }
+        let body: UpdateBody = match json_body(request) {
+            Ok(body) => body,
+            Err(response) => return Ok(response),
+        };
+        let hash = match body.hash.or_else(|| request.headers.get("if-match").map(|hash| hash.trim_matches('"').to_string())) {
+            Some(hash) => hash,
+            None => return Ok(HttpResponse::client_error(400, "expected the `hash` of the note the content replaces")),
+        };
+
+        let note_path = self.notto.update_note_text(note_name, &body.content, &hash)?;
+        let (_, text) = self.notto.get_note_text(to_link(&note_path))?;
+
+        Ok(HttpResponse::json(200, &json!({ "path": to_link(&note_path), "hash": Notto::text_hash(&text) })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::{Cursor, Read, Write}, net::{TcpListener, TcpStream}, path::PathBuf, thread};
+
+    use serde_json::Value;
+
+    use crate::{Notto, errors::NottoError, test_utils::test_notto};
+
+    use super::{HttpRequest, HttpResponse, NoteServer};
+
+    /// Sends a request on a new connection, returns the status and the body of the response
+    fn request(address: &str, method: &str, target: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n{}", method, target, address, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.split_whitespace().nth(1).unwrap().parse().unwrap(), body.to_string())
+    }
+
+    fn json(body: &str) -> Value {
+        serde_json::from_str(body).unwrap()
+    }
+
+    /// Answers a request like `GET /api/notes` from `host` without a connection, `headers` end with `\r\n`
+    fn handle_from(server: &NoteServer, request_line: &str, host: &str, headers: &str, body: &str) -> HttpResponse {
+        let host = if host.is_empty() { String::new() } else { format!("Host: {}\r\n", host) };
+        let raw = format!("{} HTTP/1.1\r\n{}{}Content-Length: {}\r\n\r\n{}", request_line, host, headers, body.len(), body);
+        server.handle(&HttpRequest::read(&mut Cursor::new(raw)).unwrap().unwrap())
+    }
+
+    /// Answers a request to the server on `127.0.0.1:8080`
+    fn handle(server: &NoteServer, request_line: &str, headers: &str, body: &str) -> HttpResponse {
+        handle_from(server, request_line, "127.0.0.1:8080", headers, body)
+    }
+
+    fn body(response: &HttpResponse) -> Value {
+        serde_json::from_slice(&response.body).unwrap()
+    }
+
+    /// A notto with the notes `work/plan.md`, known by its id and title, and `home.md` linking to it
+    fn test_server_notto(context: &str) -> Result<(Notto, PathBuf), NottoError> {
+        let (notto, base_path) = test_notto(context)?;
+        fs::create_dir_all(base_path.join("work"))?;
+        fs::write(base_path.join("work").join("plan.md"), "---\nid: 8f14e45f\n---\n# Weekly plan\n- [ ] Call Ana")?;
+        fs::write(base_path.join("home.md"), "# Home\nSee [the plan](work/plan.md)")?;
+
+        Ok((notto, base_path))
+    }
+
+    #[test]
+    fn serves_requests_over_connections() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_connections")?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+        // The client asks while the server answers, one connection after the other
+        let client = thread::spawn(move || vec![request(&address, "GET", "/api/notes/home", ""), request(&address, "GET", "/", "")]);
+        let server = NoteServer::new(&notto);
+        for stream in listener.incoming().take(2) {
+            server.handle_connection(stream?)?;
+        }
+        let responses = client.join().unwrap();
+
+        assert_eq!(200, responses[0].0);
+        assert_eq!("home.md", json(&responses[0].1)["path"]);
+        assert_eq!(200, responses[1].0);
+        assert!(responses[1].1.starts_with("<!DOCTYPE html>"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_notes_as_a_tree() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_tree")?;
+
+        let response = handle(&NoteServer::new(&notto), "GET /api/notes?tree=true", "", "");
+        assert_eq!(200, response.status);
+        let listings = body(&response);
+        assert_eq!("work", listings[0]["name"]);
+        assert_eq!("work/plan.md", listings[0]["children"][0]["path"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn gets_notes_by_id_and_title() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_get")?;
+        let server = NoteServer::new(&notto);
+
+        for target in &["/api/notes/8f14", "/api/notes/Weekly%20plan", "/api/notes/work/plan.md"] {
+            let response = handle(&server, &format!("GET {}", target), "", "");
+            assert_eq!(200, response.status, "{}", target);
+            assert_eq!("work/plan.md", body(&response)["path"]);
+            assert_eq!("Weekly plan", body(&response)["title"]);
+        }
+        assert_eq!(404, handle(&server, "GET /api/notes/nothing%20like%20it", "", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn renders_the_notes_as_html() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_render")?;
+        let server = NoteServer::new(&notto);
+
+        let response = handle(&server, "GET /api/notes/Weekly%20plan?format=html", "", "");
+        assert_eq!(200, response.status);
+        assert!(String::from_utf8_lossy(&response.body).contains("<h1>Weekly plan</h1>"));
+        assert_eq!(400, handle(&server, "GET /api/notes/home?format=pdf", "", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn searches_the_notes() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_search")?;
+        let server = NoteServer::new(&notto);
+
+        let response = handle(&server, "GET /api/search?q=plan", "", "");
+        assert_eq!(200, response.status);
+        assert_eq!(2, body(&response).as_array().unwrap().len());
+        assert_eq!(400, handle(&server, "GET /api/search", "", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn updates_the_notes_of_the_same_hash() -> Result<(), NottoError> {
+        let (notto, base_path) = test_server_notto("server_update")?;
+        let server = NoteServer::new(&notto);
+        let hash = body(&handle(&server, "GET /api/notes/8f14", "", ""))["hash"].clone();
+
+        let response = handle(&server, "PUT /api/notes/work/plan.md", "", r##"{"content": "# Weekly plan\n- [x] Call Ana", "hash": "0123"}"##);
+        assert_eq!(409, response.status);
+        assert_eq!("note_changed", body(&response)["error"]["code"]);
+        assert_eq!(hash, body(&response)["error"]["hash"]);
+
+        let response = handle(&server, "PUT /api/notes/work/plan.md", "", &format!(r##"{{"content": "# Weekly plan\n- [x] Call Ana", "hash": {}}}"##, hash));
+        assert_eq!(200, response.status);
+        assert_ne!(hash, body(&response)["hash"]);
+        assert_eq!("# Weekly plan\n- [x] Call Ana", fs::read_to_string(base_path.join("work").join("plan.md"))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn creates_the_new_notes() -> Result<(), NottoError> {
+        let (notto, base_path) = test_server_notto("server_create")?;
+        let server = NoteServer::new(&notto);
+
+        assert_eq!(201, handle(&server, "POST /api/notes", "", r##"{"name": "work/ideas", "content": "# Ideas"}"##).status);
+        assert!(base_path.join("work").join("ideas.md").exists());
+        assert_eq!(409, handle(&server, "POST /api/notes", "", r##"{"name": "work/ideas", "content": "# Ideas"}"##).status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_writes_when_read_only() -> Result<(), NottoError> {
+        let (notto, base_path) = test_server_notto("server_read_only")?;
+        let server = NoteServer::new(&notto).read_only(true);
+
+        assert_eq!(403, handle(&server, "POST /api/notes", "", r##"{"name": "nope", "content": "# Nope"}"##).status);
+        assert_eq!(403, handle(&server, "PUT /api/notes/home", "", r##"{"content": "# Nope", "hash": "0123"}"##).status);
+        assert!(!base_path.join("nope.md").exists());
+        assert_eq!(200, handle(&server, "GET /api/notes/home", "", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_bodies() -> Result<(), NottoError> {
+        let (notto, _) = test_server_notto("server_bodies")?;
+        let server = NoteServer::new(&notto);
+
+        let response = handle(&server, "POST /api/notes", "", "{\"name\": ");
+        assert_eq!(400, response.status);
+        assert!(body(&response)["error"]["message"].as_str().unwrap().starts_with("invalid JSON body"));
+        assert_eq!(400, handle(&server, "POST /api/notes", "", "[1, 2]").status);
+        assert_eq!(400, handle(&server, "PUT /api/notes/home", "", r##"{"content": "# Home"}"##).status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_invalid_requests() {
+        let read = |raw: &str| HttpRequest::read(&mut Cursor::new(raw.to_string()));
+
+        assert!(read("GET /api/notes HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: twelve\r\n\r\n").is_err());
+        assert!(read("POST /api/notes HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: 99999999999999999999999\r\n\r\n").is_err());
+        assert!(read(&format!("POST /api/notes HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: {}\r\n\r\n", super::MAX_BODY_LENGTH + 1)).is_err());
+        assert!(read("GARBAGE\r\n\r\n").is_err());
+        assert!(read("").unwrap().is_none());
+        // The malformed escapes are kept as they are
+        let request = read("GET /api/notes/50%25%zz%2 HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n").unwrap().unwrap();
+        assert_eq!("/api/notes/50%%zz%2", request.path);
+    }
+
+    #[test]
+    fn limits_the_request_line_and_the_headers() {
+        let status = |raw: String| match HttpRequest::read(&mut Cursor::new(raw)) {
+            Err(NottoError::HttpError { status, .. }) => status,
+            other => panic!("expected the request to be refused, got {:?}", other),
+        };
+
+        let target = "a".repeat(super::MAX_REQUEST_LINE_LENGTH);
+        assert_eq!(400, status(format!("GET /{} HTTP/1.1\r\n\r\n", target)));
+        let value = "a".repeat(super::MAX_HEADERS_LENGTH);
+        assert_eq!(431, status(format!("GET / HTTP/1.1\r\nHost: 127.0.0.1\r\nCookie: {}\r\n\r\n", value)));
+        let headers: String = (0..=super::MAX_HEADERS).map(|n| format!("X-Header-{}: {}\r\n", n, n)).collect();
+        assert_eq!(431, status(format!("GET / HTTP/1.1\r\n{}\r\n", headers)));
+        assert_eq!(400, status("GET / HTTP/1.1\r\nContent-Length: twelve\r\n\r\n".to_string()));
+
+        let headers: String = (1..super::MAX_HEADERS).map(|n| format!("X-Header-{}: {}\r\n", n, n)).collect();
+        assert!(HttpRequest::read(&mut Cursor::new(format!("GET / HTTP/1.1\r\nHost: 127.0.0.1\r\n{}\r\n", headers))).is_ok());
+    }
+
+    #[test]
+    fn rejects_names_out_of_the_notes_dir() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("server_names")?;
+        let outside_path = PathBuf::from("test_notes_dir_server_names_outside");
+        fs::create_dir_all(&outside_path)?;
+        fs::write(outside_path.join("secret.md"), "The code is 1234")?;
+        let server = NoteServer::new(&notto);
+
+        for target in &["/api/notes/..%2Ftest_notes_dir_server_names_outside%2Fsecret", "/api/notes/../test_notes_dir_server_names_outside/secret.md", "/api/notes/%2Fetc%2Fpasswd"] {
+            let response = handle(&server, &format!("GET {}", target), "", "");
+            assert_eq!(400, response.status, "{}", target);
+            assert_eq!("invalid_note_name", body(&response)["error"]["code"]);
+        }
+        let response = handle(&server, "PUT /api/notes/..%2Fescape", "", r#"{"content": "Out", "hash": "0123"}"#);
+        assert_eq!(400, response.status);
+        let response = handle(&server, "POST /api/notes", "", r#"{"name": "../escape", "content": "Out"}"#);
+        assert_eq!(400, response.status);
+        let response = handle(&server, "GET /api/notes?path=..", "", "");
+        assert_eq!(400, response.status);
+        assert!(!base_path.join("..").join("escape.md").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn requires_the_token() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("server_token")?;
+        fs::write(base_path.join("home.md"), "# Home")?;
+        let server = NoteServer::new(&notto).token(Some("s3cret".to_string()));
+
+        assert_eq!(401, handle(&server, "GET /api/notes", "", "").status);
+        assert_eq!(401, handle(&server, "GET /api/notes/home", "Authorization: Bearer s3cre\r\n", "").status);
+        assert_eq!(401, handle(&server, "POST /api/notes", "Authorization: Basic s3cret\r\n", r#"{"content": "Out"}"#).status);
+        assert_eq!(200, handle(&server, "GET /api/notes/home", "Authorization: Bearer s3cret\r\n", "").status);
+        // The page itself has no notes, it reads the token from its address
+        assert_eq!(200, handle(&server, "GET /?token=s3cret", "", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_other_hosts_and_origins() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("server_hosts")?;
+        fs::write(base_path.join("home.md"), "# Home")?;
+        let server = NoteServer::new(&notto);
+
+        for host in &["localhost:8080", "127.0.0.1", "[::1]:8080"] {
+            assert_eq!(200, handle_from(&server, "GET /api/notes/home", host, "", "").status, "{}", host);
+        }
+        assert_eq!(403, handle_from(&server, "GET /api/notes/home", "notes.example.com:8080", "", "").status);
+        assert_eq!(403, handle_from(&server, "GET /", "192.168.1.10:8080", "", "").status);
+        assert_eq!(400, handle_from(&server, "GET /api/notes/home", "", "", "").status);
+
+        assert_eq!(200, handle(&server, "GET /api/notes/home", "Origin: http://127.0.0.1:8080\r\n", "").status);
+        let response = handle(&server, "POST /api/notes", "Origin: http://evil.example.com\r\n", r#"{"name": "planted", "content": "Out"}"#);
+        assert_eq!(403, response.status);
+        assert!(!base_path.join("planted.md").exists());
+
+        // With a token any host is fine, the origin is still checked
+        let server = NoteServer::new(&notto).token(Some("s3cret".to_string()));
+        assert_eq!(200, handle_from(&server, "GET /api/notes/home", "192.168.1.10:8080", "Authorization: Bearer s3cret\r\n", "").status);
+        assert_eq!(403, handle_from(&server, "GET /api/notes/home", "192.168.1.10:8080", "Authorization: Bearer s3cret\r\nOrigin: http://evil.example.com\r\n", "").status);
+
+        Ok(())
+    }
+
+    #[test]
+    fn escapes_the_html_of_the_notes() -> Result<(), NottoError> {
+        let (notto, base_path) = test_notto("server_html")?;
+        fs::write(base_path.join("page.md"), concat!(
+            "# Page\n<script>alert(1)</script>\n\n<img src=x onerror=alert(1)>\n\n",
+            "[click](javascript:alert(1)) [tab](<java\tscript:alert(1)>) [data](DATA:text/html,hi) ",
+            "[web](https://example.com) [plan](work/plan.md) [mail](mailto:ana@example.com)\n"
+        ))?;
+
+        let response = handle(&NoteServer::new(&notto), "GET /api/notes/page?format=html", "", "");
+        assert_eq!(200, response.status);
+        let html = String::from_utf8_lossy(&response.body);
+        assert!(html.contains("&lt;script&gt;") && html.contains("&lt;img"), "{}", html);
+        assert!(!html.contains("<script") && !html.contains("<img"), "{}", html);
+        assert_eq!(3, html.matches(r#"href="""#).count(), "{}", html);
+        assert!(html.contains(r#"href="https://example.com""#) && html.contains(r#"href="work/plan.md""#) && html.contains(r#"href="mailto:ana@example.com""#), "{}", html);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+//! Fixtures shared by the tests
+
+use std::{fs, path::PathBuf};
+
+use crate::{Notto, errors::NottoError, models::config::Config};
+
+/// The empty directory `test_notes_dir_<name>`, removed first if a previous run left it
+pub(crate) fn test_dir(name: &str) -> Result<PathBuf, NottoError> {
+    let path = PathBuf::from(format!("test_notes_dir_{}", name));
+    if path.exists() {
+        fs::remove_dir_all(&path)?;
+    }
+    fs::create_dir_all(&path)?;
+
+    Ok(path)
+}
+
+/// A notto using `context` as its only context, with the empty notes directory `test_notes_dir_<context>`
+///
+/// The snapshots are off so nothing is written outside of the notes directory
+pub(crate) fn test_notto(context: &str) -> Result<(Notto, PathBuf), NottoError> {
+    let base_path = test_dir(context)?;
+    let mut config = Config::default();
+    config.add_context(context, base_path.clone(), None)?;
+    config.override_context(context)?;
+    config.set_value("snapshots", "false")?;
+
+    Ok((Notto::with_config(config), base_path))
+}